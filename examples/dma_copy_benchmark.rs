@@ -0,0 +1,61 @@
+// This example measures how long copies of different sizes take with the CPU and with the DMA,
+// and prints the results over UART0. The thresholds `dma::copy_best_effort()` uses to pick one
+// are based on these measurements.
+
+#![no_std]
+#![no_main]
+
+use bl602_hal as hal;
+use core::fmt::Write;
+use hal::{
+    clock::{Strict, SysclkFreq, UART_PLL_FREQ},
+    dma::{self, DmaExt},
+    pac,
+    prelude::*,
+    profile::measure_cycles,
+    serial::*,
+};
+use panic_halt as _;
+
+static mut SRC: [u8; 4096] = [0x55; 4096];
+static mut DST: [u8; 4096] = [0; 4096];
+
+#[riscv_rt::entry]
+fn main() -> ! {
+    let dp = pac::Peripherals::take().unwrap();
+    let mut parts = dp.GLB.split();
+
+    // Set up all the clocks we need
+    let clocks = Strict::new()
+        .use_pll(40_000_000u32.Hz())
+        .sys_clk(SysclkFreq::Pll160Mhz)
+        .uart_clk(UART_PLL_FREQ.Hz())
+        .freeze(&mut parts.clk_cfg);
+
+    // Set up uart output
+    let pin16 = parts.pin16.into_uart_sig0();
+    let pin7 = parts.pin7.into_uart_sig7();
+    let mux0 = parts.uart_mux0.into_uart0_tx();
+    let mux7 = parts.uart_mux7.into_uart0_rx();
+    let mut serial = Serial::new(
+        dp.UART0,
+        Config::default().baudrate(115_200.Bd()),
+        ((pin16, mux0), (pin7, mux7)),
+        clocks,
+    );
+
+    let mut channels = dp.DMA.split();
+    let (src, dst) = unsafe { (&SRC, &mut DST) };
+
+    writeln!(serial, "bytes\tcpu\tdma (cycles)\r").ok();
+    for len in [16, 64, 256, 512, 1024, 2048, 4096] {
+        let (_, cpu) = measure_cycles(|| dst[..len].copy_from_slice(&src[..len]));
+        let (_, dma) =
+            measure_cycles(|| dma::mem_copy(&mut channels.channel0, &src[..len], &mut dst[..len]));
+        writeln!(serial, "{}\t{}\t{}\r", len, cpu, dma).ok();
+    }
+
+    loop {
+        core::hint::spin_loop();
+    }
+}
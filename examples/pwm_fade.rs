@@ -0,0 +1,45 @@
+// This example fades the blue channel of the RGB LED on the Pinecone eval board in and out, using
+// PWM channel 1 on GPIO11.
+
+#![no_std]
+#![no_main]
+
+use bl602_hal as hal;
+use embedded_hal::delay::DelayNs;
+use embedded_hal::pwm::SetDutyCycle;
+use hal::{
+    clock::{Strict, SysclkFreq},
+    pac,
+    prelude::*,
+    pwm::PwmExt,
+};
+use panic_halt as _;
+
+#[riscv_rt::entry]
+fn main() -> ! {
+    let dp = pac::Peripherals::take().unwrap();
+    let mut parts = dp.GLB.split();
+
+    // Set up all the clocks we need
+    let clocks = Strict::new()
+        .use_pll(40_000_000u32.Hz())
+        .sys_clk(SysclkFreq::Pll160Mhz)
+        .freeze(&mut parts.clk_cfg);
+
+    // GPIO11 can only be driven by PWM channel 1 (11 % 5 == 1)
+    let blue_led = parts.pin11.into_pwm_ch1();
+    let channels = dp.PWM.split();
+    let mut blue = channels
+        .channel1
+        .set_frequency(blue_led, 1_000u32.Hz(), &clocks);
+
+    // Create a blocking delay function based on the current cpu frequency
+    let mut d = bl602_hal::delay::McycleDelay::new(clocks.sysclk().0);
+
+    loop {
+        for percent in (0..=100).chain((0..100).rev()) {
+            blue.set_duty_cycle_percent(percent).unwrap();
+            d.delay_ms(10);
+        }
+    }
+}
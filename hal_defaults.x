@@ -1,4 +1,10 @@
+PROVIDE(Bor = DefaultHandler);
+PROVIDE(Dma = DefaultHandler);
+PROVIDE(Gpadc = DefaultHandler);
 PROVIDE(Gpio = DefaultHandler);
+PROVIDE(HbnOut0 = DefaultHandler);
+PROVIDE(HbnOut1 = DefaultHandler);
+PROVIDE(Pwm = DefaultHandler);
 PROVIDE(TimerCh0 = DefaultHandler);
 PROVIDE(TimerCh1 = DefaultHandler);
 PROVIDE(Watchdog = DefaultHandler);
@@ -0,0 +1,628 @@
+/*!
+  # Analog Comparators
+  The two analog comparators (ACOMP) in the always on (AON) domain compare a positive with a
+  negative input. Either input is an analog pin, a DAC channel, the internal 1.2V reference, a
+  fraction of VIO from the reference ladder or ground. Pins used as inputs are put into analog
+  mode with `into_analog()` first.
+
+  Hysteresis keeps the output from toggling while the inputs are close, it's added to both
+  inputs in 10mV steps.
+
+  ## Example
+  ```rust
+    let _pin = parts.pin4.into_analog();
+    let mut acomp = Acomp::new(
+        Id::Acomp0,
+        Config::default()
+            .positive(Input::Pin4)
+            .negative(Input::Vio(Scale::Half))
+            .hysteresis(Hysteresis::Mv20),
+    );
+    acomp.enable();
+
+    if acomp.is_high() {
+        // GPIO4 is above half of VIO
+    }
+  ```
+
+  ## Interrupt example
+  A comparator raises the `HbnOut1` interrupt when its output changes on the edges it listens
+  to. With a callback registered the HAL clears the interrupt and passes the edge, e.g. to start
+  an ADC capture with [adc::start_capture](crate::adc::start_capture) when a current sense
+  signal crosses its limit.
+  ```rust
+    fn on_crossing(_acomp: Id, edge: Edge) {
+        if edge == Edge::Rising {
+            adc::start_capture();
+        }
+    }
+
+    acomp.set_edge_callback(on_crossing);
+    acomp.listen(Edge::Both);
+    enable_interrupt(Interrupt::HbnOut1);
+  ```
+
+  ## Wakeup example
+  The comparators keep running while the chip sleeps in the power down sleep (PDS) and
+  hibernate (HBN) states, and their interrupt wakes it up, e.g. once a supercap drops below a
+  setpoint. With a callback the interrupt is handled once the chip is awake, after HBN it boots
+  again and [Acomp::is_interrupt_pending] tells it was the comparator.
+  ```rust
+    let _pin = parts.pin5.into_analog();
+    let mut acomp = Acomp::new(
+        Id::Acomp1,
+        Config::default()
+            .positive(Input::Pin5)
+            .negative(Input::Vref)
+            .hysteresis(Hysteresis::Mv50),
+    );
+    acomp.enable();
+    acomp.wake_on(Edge::Falling);
+    // enter PDS or HBN ...
+  ```
+
+  ## Output pin example
+  The output of a comparator can't be muxed onto a pin directly, the HAL mirrors it onto an
+  output pin from the `HbnOut1` interrupt instead, e.g. to gate the enable line of a driver.
+  The pin follows the comparator within the interrupt latency, without any application code.
+
+  This is not a hardware interlock: the pin only changes once the interrupt is handled, so it
+  lags the comparator by the interrupt latency, and stays put while interrupts are disabled or a
+  higher priority handler runs. Protection that has to act within a bounded time needs an
+  external circuit.
+  ```rust
+    let mirror = acomp.mirror_to(parts.pin17.into_floating_output());
+    enable_interrupt(Interrupt::HbnOut1);
+    // ...
+    let pin17 = mirror.free();
+  ```
+*/
+
+use crate::{
+    gpio, pac,
+    reg::{aon, modify_reg, read_reg},
+};
+use core::cell::Cell;
+use critical_section::Mutex;
+
+// see components\bl602\bl602_std\bl602_std\StdDriver\Inc\bl602_aon_reg.h
+const ACOMP0_CTRL: usize = 0x900;
+const ACOMP1_CTRL: usize = 0x904;
+const ACOMP_CTRL: usize = 0x908;
+
+const XCTRL_EN: u32 = 1 << 0;
+const XCTRL_HYST_SELN_SHIFT: u32 = 4;
+const XCTRL_HYST_SELP_SHIFT: u32 = 7;
+const XCTRL_HYST_MASK: u32 = 0x7;
+const XCTRL_NEG_SEL_SHIFT: u32 = 18;
+const XCTRL_POS_SEL_SHIFT: u32 = 22;
+const XCTRL_SEL_MASK: u32 = 0xf;
+const XCTRL_MUXEN: u32 = 1 << 26;
+
+const CTRL_ACOMP1_RSTN_ANA: u32 = 1 << 0;
+const CTRL_ACOMP0_RSTN_ANA: u32 = 1 << 1;
+const CTRL_ACOMP1_OUT_RAW: u32 = 1 << 17;
+const CTRL_ACOMP0_OUT_RAW: u32 = 1 << 19;
+
+// see components\bl602\bl602_std\bl602_std\StdDriver\Inc\hbn_reg.h
+const IRQ_MODE_ACOMP0_EN_SHIFT: u32 = 20;
+const IRQ_MODE_ACOMP1_EN_SHIFT: u32 = 22;
+const IRQ_MODE_ACOMP_EN_MASK: u32 = 0x3;
+
+const IRQ_ACOMP0: u32 = 1 << 20;
+const IRQ_ACOMP1: u32 = 1 << 22;
+
+// see components\bl602\bl602_std\bl602_std\StdDriver\Inc\pds_reg.h
+const INT_WAKEUP_SRC_HBN_IRQ_OUT1: u32 = 1 << 18;
+
+/// One of the comparators
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Id {
+    /// Comparator 0
+    Acomp0,
+    /// Comparator 1
+    Acomp1,
+}
+
+impl Id {
+    fn control(&self) -> usize {
+        match self {
+            Id::Acomp0 => ACOMP0_CTRL,
+            Id::Acomp1 => ACOMP1_CTRL,
+        }
+    }
+
+    fn reset(&self) -> u32 {
+        match self {
+            Id::Acomp0 => CTRL_ACOMP0_RSTN_ANA,
+            Id::Acomp1 => CTRL_ACOMP1_RSTN_ANA,
+        }
+    }
+
+    fn output(&self) -> u32 {
+        match self {
+            Id::Acomp0 => CTRL_ACOMP0_OUT_RAW,
+            Id::Acomp1 => CTRL_ACOMP1_OUT_RAW,
+        }
+    }
+
+    fn index(&self) -> usize {
+        match self {
+            Id::Acomp0 => 0,
+            Id::Acomp1 => 1,
+        }
+    }
+
+    fn from_index(index: usize) -> Id {
+        match index {
+            0 => Id::Acomp0,
+            _ => Id::Acomp1,
+        }
+    }
+
+    fn irq_shift(&self) -> u32 {
+        match self {
+            Id::Acomp0 => IRQ_MODE_ACOMP0_EN_SHIFT,
+            Id::Acomp1 => IRQ_MODE_ACOMP1_EN_SHIFT,
+        }
+    }
+
+    fn irq(&self) -> u32 {
+        match self {
+            Id::Acomp0 => IRQ_ACOMP0,
+            Id::Acomp1 => IRQ_ACOMP1,
+        }
+    }
+
+    fn is_interrupt_pending(&self) -> bool {
+        unsafe { &*pac::HBN::ptr() }.hbn_irq_stat.read().bits() & self.irq() != 0
+    }
+
+    fn clear_interrupt(&self) {
+        let hbn = unsafe { &*pac::HBN::ptr() };
+        hbn.hbn_irq_clr
+            .modify(|r, w| unsafe { w.bits(r.bits() | self.irq()) });
+        hbn.hbn_irq_clr
+            .modify(|r, w| unsafe { w.bits(r.bits() & !self.irq()) });
+    }
+
+    fn is_high(&self) -> bool {
+        read_reg(aon(ACOMP_CTRL)) & self.output() != 0
+    }
+
+    fn set_callback(&self, callback: Option<fn(Id, Edge)>) {
+        critical_section::with(|cs| {
+            let cell = CALLBACKS.borrow(cs);
+            let mut callbacks = cell.get();
+            callbacks[self.index()] = callback;
+            cell.set(callbacks);
+        });
+    }
+
+    fn set_mirror(&self, pin: Option<u32>) {
+        critical_section::with(|cs| {
+            let cell = MIRRORS.borrow(cs);
+            let mut mirrors = cell.get();
+            mirrors[self.index()] = pin;
+            cell.set(mirrors);
+        });
+    }
+}
+
+/// The edges of the output a comparator interrupts on
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Edge {
+    /// The positive input rises above the negative input
+    Rising = 1,
+    /// The positive input falls below the negative input
+    Falling = 2,
+    /// Either edge
+    Both = 3,
+}
+
+/// The edge callbacks of the comparators, called from the `HbnOut1` interrupt
+static CALLBACKS: Mutex<Cell<[Option<fn(Id, Edge)>; 2]>> = Mutex::new(Cell::new([None; 2]));
+
+/// The numbers of the pins the comparators are mirrored to
+static MIRRORS: Mutex<Cell<[Option<u32>; 2]>> = Mutex::new(Cell::new([None; 2]));
+
+/// A fraction of VIO, taken from the reference ladder
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Scale {
+    /// 1/4 of VIO
+    Quarter,
+    /// 1/2 of VIO
+    Half,
+    /// 3/4 of VIO
+    ThreeQuarters,
+    /// VIO
+    Full,
+}
+
+/// An input of a comparator
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Input {
+    /// GPIO12
+    Pin12,
+    /// GPIO4
+    Pin4,
+    /// GPIO14
+    Pin14,
+    /// GPIO13
+    Pin13,
+    /// GPIO5
+    Pin5,
+    /// GPIO6
+    Pin6,
+    /// GPIO7
+    Pin7,
+    /// GPIO9
+    Pin9,
+    /// The output of DAC channel A
+    DacA,
+    /// The output of DAC channel B
+    DacB,
+    /// The internal 1.2V reference
+    Vref,
+    /// A fraction of VIO from the reference ladder
+    Vio(Scale),
+    /// Ground
+    Gnd,
+}
+
+impl Input {
+    fn bits(&self) -> u32 {
+        match self {
+            Input::Pin12 => 0,
+            Input::Pin4 => 1,
+            Input::Pin14 => 2,
+            Input::Pin13 => 3,
+            Input::Pin5 => 4,
+            Input::Pin6 => 5,
+            Input::Pin7 => 6,
+            Input::Pin9 => 7,
+            Input::DacA => 8,
+            Input::DacB => 9,
+            Input::Vref => 10,
+            Input::Vio(Scale::Quarter) => 11,
+            Input::Vio(Scale::Half) => 12,
+            Input::Vio(Scale::ThreeQuarters) => 13,
+            Input::Vio(Scale::Full) => 14,
+            Input::Gnd => 15,
+        }
+    }
+}
+
+/// The hysteresis added to the inputs
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Hysteresis {
+    /// No hysteresis
+    None = 0,
+    /// 10mV
+    Mv10,
+    /// 20mV
+    Mv20,
+    /// 30mV
+    Mv30,
+    /// 40mV
+    Mv40,
+    /// 50mV
+    Mv50,
+    /// 60mV
+    Mv60,
+    /// 70mV
+    Mv70,
+}
+
+/// Comparator configuration
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Config {
+    /// The positive input
+    pub positive: Input,
+    /// The negative input
+    pub negative: Input,
+    /// The hysteresis added to both inputs
+    pub hysteresis: Hysteresis,
+}
+
+impl Config {
+    /// Sets the positive input
+    pub fn positive(mut self, positive: Input) -> Self {
+        self.positive = positive;
+
+        self
+    }
+
+    /// Sets the negative input
+    pub fn negative(mut self, negative: Input) -> Self {
+        self.negative = negative;
+
+        self
+    }
+
+    /// Sets the hysteresis added to both inputs
+    pub fn hysteresis(mut self, hysteresis: Hysteresis) -> Self {
+        self.hysteresis = hysteresis;
+
+        self
+    }
+}
+
+impl Default for Config {
+    /// The internal 1.2V reference against half of VIO, without hysteresis
+    fn default() -> Self {
+        Config {
+            positive: Input::Vref,
+            negative: Input::Vio(Scale::Half),
+            hysteresis: Hysteresis::None,
+        }
+    }
+}
+
+/// An analog comparator
+pub struct Acomp {
+    id: Id,
+    config: Config,
+}
+
+impl Acomp {
+    /// Configures the comparator `id`, which stays disabled until [Acomp::enable]
+    pub fn new(id: Id, config: Config) -> Self {
+        let mut acomp = Acomp { id, config };
+        acomp.disable();
+        acomp.set_config(config);
+        acomp
+    }
+
+    /// Selects the inputs and the hysteresis, while the comparator keeps running if it's enabled
+    pub fn set_config(&mut self, config: Config) {
+        let hysteresis = config.hysteresis as u32;
+        modify_reg(aon(self.id.control()), |r| {
+            r & !(XCTRL_HYST_MASK << XCTRL_HYST_SELN_SHIFT
+                | XCTRL_HYST_MASK << XCTRL_HYST_SELP_SHIFT
+                | XCTRL_SEL_MASK << XCTRL_NEG_SEL_SHIFT
+                | XCTRL_SEL_MASK << XCTRL_POS_SEL_SHIFT)
+                | hysteresis << XCTRL_HYST_SELN_SHIFT
+                | hysteresis << XCTRL_HYST_SELP_SHIFT
+                | config.negative.bits() << XCTRL_NEG_SEL_SHIFT
+                | config.positive.bits() << XCTRL_POS_SEL_SHIFT
+                | XCTRL_MUXEN
+        });
+        self.config = config;
+    }
+
+    /// The configuration of the comparator
+    pub fn config(&self) -> Config {
+        self.config
+    }
+
+    /// The comparator this is
+    pub fn id(&self) -> Id {
+        self.id
+    }
+
+    /// Takes the comparator out of reset and starts comparing
+    pub fn enable(&mut self) {
+        modify_reg(aon(ACOMP_CTRL), |r| r | self.id.reset());
+        modify_reg(aon(self.id.control()), |r| r | XCTRL_EN);
+    }
+
+    /// Stops comparing and puts the comparator back into reset
+    pub fn disable(&mut self) {
+        modify_reg(aon(self.id.control()), |r| r & !XCTRL_EN);
+        modify_reg(aon(ACOMP_CTRL), |r| r & !self.id.reset());
+    }
+
+    /// Whether the comparator is enabled
+    pub fn is_enabled(&self) -> bool {
+        read_reg(aon(self.id.control())) & XCTRL_EN != 0
+    }
+
+    /// Whether the positive input is above the negative input
+    pub fn is_high(&self) -> bool {
+        self.id.is_high()
+    }
+
+    /// Raises the `HbnOut1` interrupt on `edge` of the output
+    pub fn listen(&mut self, edge: Edge) {
+        let shift = self.id.irq_shift();
+        unsafe { &*pac::HBN::ptr() }
+            .hbn_irq_mode
+            .modify(|r, w| unsafe {
+                w.bits(r.bits() & !(IRQ_MODE_ACOMP_EN_MASK << shift) | (edge as u32) << shift)
+            });
+    }
+
+    /// Stops raising the interrupt
+    pub fn unlisten(&mut self) {
+        let shift = self.id.irq_shift();
+        unsafe { &*pac::HBN::ptr() }
+            .hbn_irq_mode
+            .modify(|r, w| unsafe { w.bits(r.bits() & !(IRQ_MODE_ACOMP_EN_MASK << shift)) });
+        self.id.clear_interrupt();
+    }
+
+    /// Will only become true on the edges set with [Acomp::listen]
+    pub fn is_interrupt_pending(&self) -> bool {
+        self.id.is_interrupt_pending()
+    }
+
+    /// Clears the interrupt of the comparator
+    pub fn clear_interrupt(&self) {
+        self.id.clear_interrupt();
+    }
+
+    /// Registers `callback` to be called from the `HbnOut1` interrupt on the edges set with
+    /// [Acomp::listen], with [Edge::Rising] or [Edge::Falling] depending on the output. The HAL
+    /// clears the interrupt before calling it, and the `HbnOut1()` handler won't be called for
+    /// this comparator anymore.
+    pub fn set_edge_callback(&mut self, callback: fn(Id, Edge)) {
+        self.id.set_callback(Some(callback));
+    }
+
+    /// Removes a callback registered by [Acomp::set_edge_callback]
+    pub fn clear_edge_callback(&mut self) {
+        self.id.set_callback(None);
+    }
+
+    /// Wakes the chip from PDS or HBN on `edge` of the output, through the interrupt of the
+    /// comparator, see [Acomp::listen]. The comparator has to be enabled while the chip sleeps.
+    pub fn wake_on(&mut self, edge: Edge) {
+        self.id.clear_interrupt();
+        self.listen(edge);
+        unsafe { &*pac::PDS::ptr() }
+            .pds_int
+            .modify(|r, w| unsafe { w.bits(r.bits() | INT_WAKEUP_SRC_HBN_IRQ_OUT1) });
+    }
+
+    /// Stops waking the chip, and stops raising the interrupt
+    pub fn disable_wakeup(&mut self) {
+        self.unlisten();
+        let listening = IRQ_MODE_ACOMP_EN_MASK << IRQ_MODE_ACOMP0_EN_SHIFT
+            | IRQ_MODE_ACOMP_EN_MASK << IRQ_MODE_ACOMP1_EN_SHIFT;
+        if unsafe { &*pac::HBN::ptr() }.hbn_irq_mode.read().bits() & listening == 0 {
+            unsafe { &*pac::PDS::ptr() }
+                .pds_int
+                .modify(|r, w| unsafe { w.bits(r.bits() & !INT_WAKEUP_SRC_HBN_IRQ_OUT1) });
+        }
+    }
+
+    /// Mirrors the output of the comparator onto `pin`, which is driven high while the positive
+    /// input is above the negative input. This listens to both edges, the `HbnOut1` interrupt has
+    /// to be enabled for the pin to follow.
+    pub fn mirror_to<PIN: MirrorPin>(&mut self, pin: PIN) -> Mirror<PIN> {
+        self.id.set_mirror(Some(PIN::NUMBER));
+        self.id.clear_interrupt();
+        self.listen(Edge::Both);
+        drive(PIN::NUMBER, self.id.is_high());
+        Mirror { id: self.id, pin }
+    }
+
+    /// Disables the comparator and its interrupt
+    pub fn free(mut self) {
+        self.disable_wakeup();
+        self.clear_edge_callback();
+        self.id.set_mirror(None);
+        self.disable();
+    }
+}
+
+/// The output of a comparator mirrored onto a pin, see [Acomp::mirror_to]
+pub struct Mirror<PIN> {
+    id: Id,
+    pin: PIN,
+}
+
+impl<PIN: MirrorPin> Mirror<PIN> {
+    /// Stops mirroring and the interrupt, and releases the pin, which keeps its level
+    pub fn free(self) -> PIN {
+        self.id.set_mirror(None);
+        let shift = self.id.irq_shift();
+        unsafe { &*pac::HBN::ptr() }
+            .hbn_irq_mode
+            .modify(|r, w| unsafe { w.bits(r.bits() & !(IRQ_MODE_ACOMP_EN_MASK << shift)) });
+        self.id.clear_interrupt();
+        self.pin
+    }
+}
+
+fn drive(pin: u32, high: bool) {
+    // The register holds the outputs of all pins, which the application drives as well
+    critical_section::with(|_| {
+        unsafe { &*pac::GLB::ptr() }
+            .gpio_cfgctl32
+            .modify(|r, w| unsafe {
+                if high {
+                    w.bits(r.bits() | 1 << pin)
+                } else {
+                    w.bits(r.bits() & !(1 << pin))
+                }
+            })
+    });
+}
+
+/// Mirrors the outputs and calls the edge callbacks of the comparators with a pending interrupt.
+/// Returns false if the `HbnOut1()` handler still needs to be called, for a comparator that's
+/// neither mirrored nor has a callback.
+pub(crate) fn dispatch_interrupt() -> bool {
+    let (callbacks, mirrors) =
+        critical_section::with(|cs| (CALLBACKS.borrow(cs).get(), MIRRORS.borrow(cs).get()));
+    let mut handled = true;
+
+    for (index, (callback, mirror)) in callbacks.iter().zip(mirrors.iter()).enumerate() {
+        let id = Id::from_index(index);
+        if !id.is_interrupt_pending() {
+            continue;
+        }
+        if callback.is_none() && mirror.is_none() {
+            handled = false;
+            continue;
+        }
+
+        id.clear_interrupt();
+        let high = id.is_high();
+        if let Some(pin) = mirror {
+            drive(*pin, high);
+        }
+        if let Some(callback) = callback {
+            let edge = if high { Edge::Rising } else { Edge::Falling };
+            callback(id, edge);
+        }
+    }
+
+    handled
+}
+
+/// Output pins the comparators can be mirrored to - DO NOT IMPLEMENT THIS TRAIT
+pub trait MirrorPin: private::Sealed {}
+
+macro_rules! impl_mirror_pin {
+    ($($pin: ident: $number: literal,)+) => {
+        $(
+            impl<MODE> MirrorPin for gpio::$pin<gpio::Output<MODE>> {}
+
+            impl<MODE> private::Sealed for gpio::$pin<gpio::Output<MODE>> {
+                const NUMBER: u32 = $number;
+            }
+        )+
+    };
+}
+
+impl_mirror_pin! {
+    Pin0: 0,
+    Pin1: 1,
+    Pin2: 2,
+    Pin3: 3,
+    Pin4: 4,
+    Pin5: 5,
+    Pin6: 6,
+    Pin7: 7,
+    Pin8: 8,
+    Pin9: 9,
+    Pin10: 10,
+    Pin11: 11,
+    Pin12: 12,
+    Pin13: 13,
+    Pin14: 14,
+    Pin15: 15,
+    Pin16: 16,
+    Pin17: 17,
+    Pin18: 18,
+    Pin19: 19,
+    Pin20: 20,
+    Pin21: 21,
+    Pin22: 22,
+}
+
+mod private {
+    pub trait Sealed {
+        /// The GPIO number of the pin
+        const NUMBER: u32;
+    }
+}
@@ -0,0 +1,1439 @@
+/*!
+  # Analog to Digital Converter
+  The general purpose ADC (GPADC) converts the voltage on one of twelve analog capable pins, or
+  on an internal source, into a 12 to 16 bit value. Its analog part is configured in the always-on
+  (AON) domain, the conversion results are read from the FIFO in the GPIP block.
+
+  | ADC channel | 0  | 1 | 2  | 3  | 4 | 5 | 6 | 7 | 8  | 9  | 10 | 11 |
+  |-------------|----|---|----|----|---|---|---|---|----|----|----|----|
+  | GPIO        | 12 | 4 | 14 | 13 | 5 | 6 | 7 | 9 | 18 | 19 | 20 | 21 |
+
+  The pins are put into analog mode with `into_analog()` and handed to [Adc::new], which owns
+  them until [Adc::free], so they can't be used digitally while they're being converted. The
+  conversions select a pin by its type.
+
+  Conversions are single ended against ground. By default the internal 3.2V reference is used
+  without gain, which [Config] changes together with the conversion clock. Conversions are 12 bit
+  by default, the higher resolutions average a number of conversions in hardware, see
+  [Resolution].
+
+  Values are converted into millivolts with [Adc::to_millivolts]. Noise is reduced by averaging a
+  number of conversions with [Adc::blocking_read_average], or by taking their median with
+  [Adc::blocking_read_median], which also rejects outliers.
+
+  The temperature of the chip is measured with [Adc::read_temperature], in centi-degrees Celsius.
+  The supply voltage is measured with [Adc::read_vbat], through an internal divider, and the other
+  internal sources with [Adc::blocking_read_internal].
+
+  ## Example
+  ```rust
+    let pins = (parts.pin4.into_analog(), parts.pin5.into_analog(), parts.pin6.into_analog());
+    let mut adc = Adc::new(dp.GPIP, pins, Config::default(), &clocks);
+
+    // Blocking
+    let value: u16 = adc.blocking_read::<Pin4<Analog>>();
+
+    // Or polled
+    let value: u16 = nb::block!(adc.read::<Pin4<Analog>>()).unwrap();
+  ```
+
+  ## Scan example
+  A scan converts a list of up to 12 channels in sequence, and tags every result in the FIFO with
+  the channel it was converted from.
+  ```rust
+    let list = ScanList::new()
+        .add::<Pin4<Analog>>()
+        .add::<Pin5<Analog>>()
+        .add::<Pin6<Analog>>();
+    let mut samples = [Sample::default(); 3];
+    adc.blocking_scan(&list, &mut samples).unwrap();
+
+    // Or keep scanning, and read the results as they come in
+    adc.start_scan(&list, true);
+    let sample = nb::block!(adc.read_sample()).unwrap();
+  ```
+
+  ## DMA example
+  A continuous scan can be streamed into a buffer by the DMA, without an interrupt per sample.
+  ```rust
+    let buffer = dma_buffer!([u32; 512] = [0; 512]).unwrap();
+    let mut stream = adc.stream_circular(
+        &list,
+        channels.channel0,
+        buffer,
+        unsafe { &mut DESCRIPTORS },
+        None,
+    );
+
+    stream.peek(|raw, _half| {
+        for sample in raw.iter().map(|raw| adc.sample(*raw)) {
+            // ...
+        }
+    });
+  ```
+
+  ## Timed example
+  A continuous scan runs at [Adc::sample_rate], timed by the ADC clock. Any other rate is set by
+  a timer channel, which kicks a pass of the scan from its interrupt.
+  ```rust
+    let mut stream = adc.stream_triggered(
+        &list,
+        channels.channel0,
+        buffer,
+        unsafe { &mut DESCRIPTORS },
+        Some(on_half_done),
+    );
+    // 8kHz
+    ch1.start_trigger::<ScanTrigger>(125u32.microseconds());
+  ```
+
+  ## Capture example
+  A capture is armed up front, and started from an event such as a comparator crossing a
+  threshold, capturing what follows it.
+  ```rust
+    fn on_threshold(_acomp: acomp::Id, _edge: acomp::Edge) {
+        adc::start_capture();
+    }
+
+    acomp.set_edge_callback(on_threshold);
+
+    let capture = adc.arm_capture(&list, channels.channel0, buffer);
+    // ...
+    let (channel0, _, buffer) = capture.wait().ok().unwrap();
+    adc.stop();
+  ```
+
+  ## Interrupt example
+  The `Gpadc` interrupt is raised when the FIFO holds a number of results. With a callback
+  registered the HAL reads them, at a rate that doesn't need a DMA channel.
+  ```rust
+    fn on_sample(sample: Sample) {
+        // ...
+    }
+
+    adc.set_sample_callback(on_sample);
+    adc.listen(FifoThreshold::Four);
+    enable_interrupt(Interrupt::Gpadc);
+    adc.start_scan(&list, true);
+  ```
+
+  ## Async example
+  With the `async` feature conversions and scans can be awaited, the task is woken from the
+  `Gpadc` interrupt.
+  ```rust
+    let value = adc.read_async::<Pin4<Analog>>().await;
+    adc.scan_async(&list, &mut samples).await.unwrap();
+  ```
+*/
+
+#[cfg(feature = "async")]
+use crate::interrupts::{enable_interrupt, Interrupt};
+use crate::{
+    clock::Clocks,
+    dma::{self, fifo, Circular, Descriptor, DmaChannel, Half, Transfer, WriteBuffer},
+    gpio::Analog,
+    pac,
+    power::suspend::{Resume, SavedRegisters, Suspend},
+    reg::{aon, glb, gpip, modify_reg, read_reg, write_reg},
+    timer::Trigger,
+};
+use core::{cell::Cell, convert::Infallible};
+#[cfg(feature = "async")]
+use core::{
+    cell::RefCell,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+use critical_section::Mutex;
+use embedded_time::{duration::Nanoseconds, rate::Hertz};
+
+// see components\bl602\bl602_std\bl602_std\StdDriver\Inc\bl602_aon_reg.h
+const GPADC_CMD: usize = 0x90c;
+const GPADC_CONFIG1: usize = 0x910;
+const GPADC_CONFIG2: usize = 0x914;
+const GPADC_SCN_POS1: usize = 0x918;
+const GPADC_SCN_POS2: usize = 0x91c;
+const GPADC_SCN_NEG1: usize = 0x920;
+const GPADC_SCN_NEG2: usize = 0x924;
+
+const CMD_GLOBAL_EN: u32 = 1 << 0;
+const CMD_CONV_START: u32 = 1 << 1;
+const CMD_SOFT_RST: u32 = 1 << 2;
+const CMD_NEG_SEL_SHIFT: u32 = 3;
+const CMD_POS_SEL_SHIFT: u32 = 8;
+const CMD_SEL_MASK: u32 = 0x1f;
+const CMD_NEG_GND: u32 = 1 << 13;
+
+const CONFIG1_CONT_CONV_EN: u32 = 1 << 1;
+const CONFIG1_RES_SEL_SHIFT: u32 = 2;
+const CONFIG1_RES_SEL_MASK: u32 = 0x7;
+const CONFIG1_CLK_DIV_SHIFT: u32 = 18;
+const CLOCK_DIVIDER_MASK: u32 = 0x7;
+const CONFIG1_SCAN_LENGTH_SHIFT: u32 = 21;
+const CONFIG1_SCAN_LENGTH_MASK: u32 = 0xf;
+const CONFIG1_SCAN_EN: u32 = 1 << 25;
+const CONFIG1_V11_SEL_SHIFT: u32 = 27;
+const CONFIG1_V18_SEL_SHIFT: u32 = 29;
+
+const CONFIG2_DIFF_MODE: u32 = 1 << 2;
+const CONFIG2_VREF_SEL: u32 = 1 << 3;
+const CONFIG2_VBAT_EN: u32 = 1 << 4;
+const CONFIG2_TSEXT_SEL: u32 = 1 << 5;
+const CONFIG2_TS_EN: u32 = 1 << 6;
+const CONFIG2_PGA_VCM_SHIFT: u32 = 7;
+const CONFIG2_PGA_OS_CAL_SHIFT: u32 = 9;
+const CONFIG2_PGA_EN: u32 = 1 << 13;
+const CONFIG2_CHOP_MODE_SHIFT: u32 = 15;
+const CONFIG2_PGA2_GAIN_SHIFT: u32 = 22;
+const CONFIG2_PGA1_GAIN_SHIFT: u32 = 25;
+const CONFIG2_PGA_GAIN_MASK: u32 = 0x7;
+const CONFIG2_DLY_SEL_SHIFT: u32 = 28;
+const CONFIG2_DLY_SEL_MASK: u32 = 0x7;
+const CONFIG2_TSVBE_LOW: u32 = 1 << 31;
+
+// see components\bl602\bl602_std\bl602_std\StdDriver\Inc\bl602_gpip_reg.h
+const GPADC_CONFIG: usize = 0x0;
+const GPADC_DMA_RDATA: usize = 0x4;
+
+const CONFIG_DMA_EN: u32 = 1 << 0;
+const CONFIG_FIFO_CLR: u32 = 1 << 1;
+const CONFIG_RDY: u32 = 1 << 4;
+const CONFIG_OVERRUN: u32 = 1 << 5;
+const CONFIG_RDY_CLR: u32 = 1 << 8;
+const CONFIG_OVERRUN_CLR: u32 = 1 << 9;
+const CONFIG_RDY_MASK: u32 = 1 << 12;
+const CONFIG_OVERRUN_MASK: u32 = 1 << 13;
+const CONFIG_UNDERRUN_MASK: u32 = 1 << 14;
+const CONFIG_FIFO_DATA_COUNT_SHIFT: u32 = 16;
+const CONFIG_FIFO_DATA_COUNT_MASK: u32 = 0x3f;
+const CONFIG_FIFO_THL_SHIFT: u32 = 22;
+const CONFIG_FIFO_THL_MASK: u32 = 0x3;
+
+const RESULT_VALUE_MASK: u32 = 0xffff;
+const RESULT_POS_SHIFT: u32 = 21;
+
+// see components\bl602\bl602_std\bl602_std\StdDriver\Src\bl602_ef_ctrl.c
+const EF_DATA_BASE: usize = 0x4000_7000;
+const EF_TSEN_TRIM: usize = 0x78;
+const EF_TSEN_REFCODE_MASK: u32 = 0xfff;
+const EF_TSEN_PARITY: u32 = 1 << 12;
+const EF_TSEN_EN: u32 = 1 << 13;
+const EF_ADC_GAIN_TRIM: usize = 0x78;
+const EF_ADC_GAIN_SHIFT: u32 = 14;
+const EF_ADC_GAIN_MASK: u32 = 0xfff;
+const EF_ADC_GAIN_PARITY: u32 = 1 << 26;
+const EF_ADC_GAIN_EN: u32 = 1 << 27;
+
+// see components\bl602\bl602_std\bl602_std\StdDriver\Inc\bl602_glb_reg.h
+const GLB_GPADC_32M_SRC_CTRL: usize = 0xa4;
+const GPADC_32M_CLK_SEL_XCLK: u32 = 1 << 7;
+const GPADC_32M_DIV_EN: u32 = 1 << 8;
+
+/// The negative input of single ended conversions
+const CHANNEL_GND: u8 = 23;
+/// The internal temperature sensor diode
+const CHANNEL_TSEN_P: u8 = 14;
+
+/// Conversions averaged for each of the two temperature sensor measurements
+const TSEN_SAMPLES: u32 = 8;
+/// The difference of the two temperature sensor measurements at 0°C, for chips without a
+/// calibration in efuse
+const TSEN_DEFAULT_OFFSET: i32 = 2042;
+/// The change of the difference of the two measurements, in 1/1000 per °C
+const TSEN_SLOPE_MILLI: i32 = 7753;
+
+/// Channels in a scan list, 6 in each of the two scan registers
+pub const MAX_SCAN_CHANNELS: usize = 12;
+
+/// A gain of 1 in the calibration
+const CALIBRATION_UNITY: u32 = 2048;
+
+/// The longest settling delay, in conversion clock cycles
+pub const MAX_SETTLING: u8 = 7;
+
+/// Conversion clock cycles of a single conversion, before averaging and without settling delay
+const CONVERSION_CYCLES: u32 = 20;
+
+/// ADC error
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Error {
+    /// The FIFO was full and results were lost
+    Overrun,
+}
+
+/// How a callback registered with [Adc::set_sample_callback] parses the results
+#[derive(Copy, Clone)]
+struct SampleCallback {
+    callback: fn(Sample),
+    calibration: Calibration,
+    resolution: Resolution,
+}
+
+static CALLBACK: Mutex<Cell<Option<SampleCallback>>> = Mutex::new(Cell::new(None));
+
+/// The task waiting for a result in the FIFO
+#[cfg(feature = "async")]
+static WAKER: Mutex<RefCell<Option<Waker>>> = Mutex::new(RefCell::new(None));
+
+/// The general purpose ADC, owning the analog `PINS` it converts
+pub struct Adc<PINS> {
+    gpip: pac::GPIP,
+    pins: PINS,
+    clock: Hertz,
+    config: Config,
+    calibration: Calibration,
+    resolution: Resolution,
+    /// The channel of the conversion started by `read()`, if any
+    converting: Option<u8>,
+}
+
+impl<PINS: Pins> Adc<PINS> {
+    /// Powers up and resets the ADC, clocked at 32MHz from the PLL if it's enabled, or from the
+    /// crystal or internal oscillator otherwise, to convert `pins`. Panics if `config` is invalid.
+    pub fn new(gpip: pac::GPIP, pins: PINS, config: Config, clocks: &Clocks) -> Self {
+        let clock = setup_clock(clocks);
+
+        modify_cmd(|r| r | CMD_GLOBAL_EN);
+        modify_cmd(|r| r | CMD_SOFT_RST);
+        for _ in 0..8 {
+            core::hint::spin_loop();
+        }
+        modify_cmd(|r| r & !(CMD_SOFT_RST | CMD_CONV_START));
+
+        // 12 bit results, 1.1V and 1.8V regulators at their nominal voltages
+        write_reg(
+            aon(GPADC_CONFIG1),
+            1 << CONFIG1_V11_SEL_SHIFT | 2 << CONFIG1_V18_SEL_SHIFT,
+        );
+        // Single ended, default chopper and PGA trim as in the SDK
+        modify_reg(aon(GPADC_CONFIG2), |r| {
+            r & !CONFIG2_DIFF_MODE
+                | 1 << CONFIG2_PGA_VCM_SHIFT
+                | 8 << CONFIG2_PGA_OS_CAL_SHIFT
+                | 2 << CONFIG2_CHOP_MODE_SHIFT
+        });
+        modify_cmd(|r| {
+            r & !(CMD_SEL_MASK << CMD_NEG_SEL_SHIFT)
+                | (CHANNEL_GND as u32) << CMD_NEG_SEL_SHIFT
+                | CMD_NEG_GND
+        });
+
+        let mut adc = Adc {
+            gpip,
+            pins,
+            clock,
+            config,
+            calibration: Calibration::NONE,
+            resolution: Resolution::Bits12,
+            converting: None,
+        };
+        adc.set_config(config);
+        adc
+    }
+
+    /// Applies `config` to the following conversions, stopping a running scan or conversion.
+    /// Panics if `config` is invalid.
+    pub fn set_config(&mut self, config: Config) {
+        if config.settling > MAX_SETTLING {
+            panic!("Invalid ADC settling time");
+        }
+        let divider = clock_divider_bits(config.clock_divider);
+        let (pga1, pga2) = config.gain.stages();
+
+        self.stop();
+        modify_reg(aon(GPADC_CONFIG1), |r| {
+            r & !(CLOCK_DIVIDER_MASK << CONFIG1_CLK_DIV_SHIFT) | divider << CONFIG1_CLK_DIV_SHIFT
+        });
+        modify_reg(aon(GPADC_CONFIG2), |r| {
+            let r = r & !(CONFIG2_VREF_SEL
+                | CONFIG2_PGA_EN
+                | CONFIG2_PGA_GAIN_MASK << CONFIG2_PGA1_GAIN_SHIFT
+                | CONFIG2_PGA_GAIN_MASK << CONFIG2_PGA2_GAIN_SHIFT
+                | CONFIG2_DLY_SEL_MASK << CONFIG2_DLY_SEL_SHIFT)
+                | pga1 << CONFIG2_PGA1_GAIN_SHIFT
+                | pga2 << CONFIG2_PGA2_GAIN_SHIFT
+                | (config.settling as u32) << CONFIG2_DLY_SEL_SHIFT;
+            let r = match config.reference {
+                Reference::Internal3V2 => r,
+                Reference::Internal2V0 => r | CONFIG2_VREF_SEL,
+            };
+            if config.gain == Gain::X1 {
+                r
+            } else {
+                r | CONFIG2_PGA_EN
+            }
+        });
+        self.config = config;
+
+        // The offset depends on the gain and reference, so it's measured again
+        self.calibration = if config.calibrate {
+            Calibration {
+                gain: efuse_gain(),
+                offset: self.blocking_convert(CHANNEL_GND) & RESULT_VALUE_MASK,
+            }
+        } else {
+            Calibration::NONE
+        };
+    }
+
+    /// The configuration of the conversions
+    pub fn config(&self) -> Config {
+        self.config
+    }
+
+    /// Powers down the ADC and releases the GPIP peripheral and the pins
+    pub fn free(self) -> (pac::GPIP, PINS) {
+        modify_cmd(|r| r & !(CMD_CONV_START | CMD_GLOBAL_EN));
+        (self.gpip, self.pins)
+    }
+
+    /// The clock the ADC runs from, before the conversion clock divider
+    pub fn clock(&self) -> Hertz {
+        self.clock
+    }
+
+    /// The rate of the results at the configured clock divider, settling delay and resolution,
+    /// e.g. of a continuous scan
+    pub fn sample_rate(&self) -> Hertz {
+        Hertz(self.clock.0 / self.config.clock_divider / self.cycles_per_result())
+    }
+
+    /// Sets the resolution of the following conversions, stopping a running scan or conversion
+    pub fn set_resolution(&mut self, resolution: Resolution) {
+        self.stop();
+        modify_reg(aon(GPADC_CONFIG1), |r| {
+            r & !(CONFIG1_RES_SEL_MASK << CONFIG1_RES_SEL_SHIFT)
+                | (resolution as u32) << CONFIG1_RES_SEL_SHIFT
+        });
+        self.resolution = resolution;
+    }
+
+    /// The resolution of the conversions
+    pub fn resolution(&self) -> Resolution {
+        self.resolution
+    }
+
+    /// The time a conversion takes at the configured resolution, including the conversions that
+    /// are averaged in hardware
+    pub fn conversion_time(&self) -> Nanoseconds<u64> {
+        let cycles = self.cycles_per_result() as u64 * self.config.clock_divider as u64;
+        Nanoseconds(cycles * 1_000_000_000 / self.clock.0 as u64)
+    }
+
+    /// Conversion clock cycles of a result, including the conversions averaged in hardware
+    fn cycles_per_result(&self) -> u32 {
+        (CONVERSION_CYCLES + self.config.settling as u32) * self.resolution.averaged()
+    }
+
+    /// Converts the voltage on `PIN`, blocking until the conversion is done. Panics if `PIN`
+    /// isn't one of the pins of the ADC.
+    pub fn blocking_read<PIN: AdcPin>(&mut self) -> u16 {
+        let result = self.blocking_convert(self.channel_of::<PIN>());
+        self.sample(result).value
+    }
+
+    /// Starts a conversion of `PIN` on the first call, and returns the value once it's done. A
+    /// conversion of another pin that is still running is restarted on `PIN`. Panics if `PIN`
+    /// isn't one of the pins of the ADC.
+    pub fn read<PIN: AdcPin>(&mut self) -> nb::Result<u16, Infallible> {
+        let channel = self.channel_of::<PIN>();
+        if self.converting != Some(channel) {
+            self.start(channel);
+        }
+        match self.finish() {
+            Some(result) => Ok(self.sample(result).value),
+            None => Err(nb::Error::WouldBlock),
+        }
+    }
+
+    /// Converts the internal `source`, blocking until the conversion is done
+    pub fn blocking_read_internal(&mut self, source: InternalSource) -> u16 {
+        let result = self.blocking_convert(source as u8);
+        self.sample(result).value
+    }
+
+    /// Measures the supply voltage (VBAT) in millivolts, through the internal divider by two,
+    /// blocking until the conversion is done
+    pub fn read_vbat(&mut self) -> u32 {
+        modify_reg(aon(GPADC_CONFIG2), |r| r | CONFIG2_VBAT_EN);
+        let value = self.blocking_read_internal(InternalSource::VbatHalf);
+        modify_reg(aon(GPADC_CONFIG2), |r| r & !CONFIG2_VBAT_EN);
+
+        self.to_millivolts(value) * 2
+    }
+
+    /// Converts a value at the configured resolution into the millivolts at the input, taking
+    /// the reference and gain into account
+    pub fn to_millivolts(&self, value: u16) -> u32 {
+        ((value as u32 * self.config.reference.millivolts()) >> self.resolution.bits())
+            / self.config.gain.factor()
+    }
+
+    /// Converts the voltage on `PIN` `count` times, blocking until the conversions are done, and
+    /// returns the average. Panics if `count` is 0.
+    pub fn blocking_read_average<PIN: AdcPin>(&mut self, count: u32) -> u16 {
+        if count == 0 {
+            panic!("No ADC conversions to average");
+        }
+        let sum: u64 = (0..count).map(|_| self.blocking_read::<PIN>() as u64).sum();
+        (sum / count as u64) as u16
+    }
+
+    /// Converts the voltage on `PIN` once for every entry of `values`, blocking until the
+    /// conversions are done, and returns the median. Unlike the average it ignores single
+    /// outliers, e.g. from switching noise. Panics if `values` is empty.
+    pub fn blocking_read_median<PIN: AdcPin>(&mut self, values: &mut [u16]) -> u16 {
+        for value in values.iter_mut() {
+            *value = self.blocking_read::<PIN>();
+        }
+        median(values)
+    }
+
+    /// Measures the temperature of the chip in centi-degrees Celsius, blocking for the duration
+    /// of the conversions
+    ///
+    /// The internal sensor diode is measured at two bias currents, both averaged in hardware and
+    /// over a number of conversions. The difference is offset by the factory calibration in
+    /// efuse, if the chip has one.
+    pub fn read_temperature(&mut self) -> i32 {
+        let config1 = read_reg(aon(GPADC_CONFIG1));
+        let config2 = read_reg(aon(GPADC_CONFIG2));
+        self.stop();
+
+        modify_reg(aon(GPADC_CONFIG1), |r| {
+            r & !(CONFIG1_RES_SEL_MASK << CONFIG1_RES_SEL_SHIFT)
+                | (Resolution::Bits16Avg256 as u32) << CONFIG1_RES_SEL_SHIFT
+        });
+        modify_reg(aon(GPADC_CONFIG2), |r| {
+            r & !(CONFIG2_DIFF_MODE | CONFIG2_TSEXT_SEL | CONFIG2_PGA_EN) | CONFIG2_TS_EN
+        });
+
+        let mut measure = |tsvbe_low: bool| {
+            modify_reg(aon(GPADC_CONFIG2), |r| {
+                if tsvbe_low {
+                    r | CONFIG2_TSVBE_LOW
+                } else {
+                    r & !CONFIG2_TSVBE_LOW
+                }
+            });
+            // The first conversion after switching the bias current is discarded
+            self.blocking_convert(CHANNEL_TSEN_P);
+            let sum: u32 = (0..TSEN_SAMPLES)
+                .map(|_| self.blocking_convert(CHANNEL_TSEN_P) & RESULT_VALUE_MASK)
+                .sum();
+            (sum / TSEN_SAMPLES) as i32
+        };
+        let high = measure(false);
+        let low = measure(true);
+
+        write_reg(aon(GPADC_CONFIG1), config1);
+        write_reg(aon(GPADC_CONFIG2), config2);
+
+        (high - low - tsen_offset()) * 100_000 / TSEN_SLOPE_MILLI
+    }
+
+    /// Converts the channels of `list` once, in order, blocking until all results are read into
+    /// `samples`. Panics if `samples` is shorter than the list.
+    pub fn blocking_scan(&mut self, list: &ScanList, samples: &mut [Sample]) -> Result<(), Error> {
+        let samples = &mut samples[..list.len];
+        self.start_scan(list, false);
+        for sample in samples.iter_mut() {
+            match nb::block!(self.read_sample()) {
+                Ok(value) => *sample = value,
+                Err(error) => {
+                    self.stop();
+                    return Err(error);
+                }
+            }
+        }
+        self.stop();
+        Ok(())
+    }
+
+    /// Starts converting the channels of `list` in order, after clearing the FIFO. With
+    /// `continuous` set the scan repeats until it's stopped, otherwise it stops after one pass.
+    /// The results are read with [Adc::read_sample].
+    pub fn start_scan(&mut self, list: &ScanList, continuous: bool) {
+        self.setup_scan(list, continuous);
+        modify_cmd(|r| r | CMD_CONV_START);
+    }
+
+    /// Sets up `list` for a pass per [ScanTrigger] kick, e.g. from a timer channel with
+    /// `start_trigger::<ScanTrigger>()`, and streams the results into the two halves of `buffer`
+    /// in turn using the DMA, see [Circular]. `on_done` is called from the DMA interrupt with
+    /// every half that was filled.
+    ///
+    /// The samples are taken at the period of the timer, with the interrupt latency as jitter.
+    /// For jitter free timing at one of the rates of the ADC clock use
+    /// [Adc::stream_circular], which scans continuously at [Adc::sample_rate].
+    pub fn stream_triggered<CH, B>(
+        &mut self,
+        list: &ScanList,
+        channel: CH,
+        buffer: B,
+        descriptors: &'static mut [Descriptor; 2],
+        on_done: Option<fn(Half)>,
+    ) -> Circular<CH, B>
+    where
+        CH: DmaChannel,
+        B: WriteBuffer<Word = u32>,
+    {
+        self.setup_scan(list, false);
+        modify_reg(gpip(GPADC_CONFIG), |r| r | CONFIG_DMA_EN);
+        Circular::peripheral_to_memory(channel, fifo::Gpadc, buffer, descriptors, on_done)
+    }
+
+    /// Sets up a continuous scan of `list` that fills `buffer` using the DMA, like
+    /// [Adc::stream], but doesn't start converting until [start_capture] is called, e.g. from
+    /// the interrupt of a comparator crossing a threshold. This captures what follows the event,
+    /// like the trigger of an oscilloscope. The transfer is done once `buffer` is full, the ADC
+    /// keeps converting until it's stopped or [stop_capture] is called.
+    pub fn arm_capture<CH, B>(
+        &mut self,
+        list: &ScanList,
+        channel: CH,
+        buffer: B,
+    ) -> Transfer<CH, dma::Peripheral, B>
+    where
+        CH: DmaChannel,
+        B: WriteBuffer<Word = u32>,
+    {
+        self.setup_scan(list, true);
+        modify_reg(gpip(GPADC_CONFIG), |r| r | CONFIG_DMA_EN);
+        Transfer::peripheral_to_memory(channel, fifo::Gpadc, buffer)
+    }
+
+    /// Sets up a scan of `list` after clearing the FIFO, without starting it
+    fn setup_scan(&mut self, list: &ScanList, continuous: bool) {
+        if list.len == 0 {
+            panic!("Empty ADC scan list");
+        }
+        if list.channels[..list.len]
+            .iter()
+            .any(|&channel| PINS::CHANNELS & 1 << channel == 0)
+        {
+            panic!("ADC scan list has a pin the ADC doesn't own");
+        }
+
+        modify_cmd(|r| r & !CMD_CONV_START);
+        let mut pos = [0u32; 2];
+        let mut neg = [0u32; 2];
+        for (index, channel) in list.channels[..list.len].iter().enumerate() {
+            let shift = (index % 6) as u32 * 5;
+            pos[index / 6] |= (*channel as u32) << shift;
+            neg[index / 6] |= (CHANNEL_GND as u32) << shift;
+        }
+        write_reg(aon(GPADC_SCN_POS1), pos[0]);
+        write_reg(aon(GPADC_SCN_POS2), pos[1]);
+        write_reg(aon(GPADC_SCN_NEG1), neg[0]);
+        write_reg(aon(GPADC_SCN_NEG2), neg[1]);
+
+        modify_reg(aon(GPADC_CONFIG1), |r| {
+            let r = r & !(CONFIG1_CONT_CONV_EN
+                | CONFIG1_SCAN_LENGTH_MASK << CONFIG1_SCAN_LENGTH_SHIFT)
+                | CONFIG1_SCAN_EN
+                | ((list.len - 1) as u32) << CONFIG1_SCAN_LENGTH_SHIFT;
+            if continuous {
+                r | CONFIG1_CONT_CONV_EN
+            } else {
+                r
+            }
+        });
+        modify_reg(gpip(GPADC_CONFIG), |r| r | CONFIG_FIFO_CLR);
+        self.converting = None;
+    }
+
+    /// Reads the next result of a scan from the FIFO, tagged with the channel it was converted
+    /// from. Reports an overrun once if results were lost because the FIFO was full.
+    pub fn read_sample(&mut self) -> nb::Result<Sample, Error> {
+        if read_reg(gpip(GPADC_CONFIG)) & CONFIG_OVERRUN != 0 {
+            clear_flags(CONFIG_OVERRUN_CLR);
+            return Err(nb::Error::Other(Error::Overrun));
+        }
+        if fifo_count() == 0 {
+            return Err(nb::Error::WouldBlock);
+        }
+        Ok(self.sample(read_reg(gpip(GPADC_DMA_RDATA))))
+    }
+
+    /// Parses a raw FIFO entry, e.g. one streamed with the DMA, at the configured resolution and
+    /// with the calibration applied
+    pub fn sample(&self, raw: u32) -> Sample {
+        Sample::from_raw(self.calibration.apply(raw), self.resolution)
+    }
+
+    /// Continuously scans `list` and fills `buffer` with the raw FIFO entries using the DMA,
+    /// which are turned into samples with [Adc::sample]. The ADC keeps converting after the
+    /// buffer is full, until it's stopped.
+    pub fn stream<CH, B>(
+        &mut self,
+        list: &ScanList,
+        channel: CH,
+        buffer: B,
+    ) -> Transfer<CH, dma::Peripheral, B>
+    where
+        CH: DmaChannel,
+        B: WriteBuffer<Word = u32>,
+    {
+        self.start_scan(list, true);
+        modify_reg(gpip(GPADC_CONFIG), |r| r | CONFIG_DMA_EN);
+        Transfer::peripheral_to_memory(channel, fifo::Gpadc, buffer)
+    }
+
+    /// Continuously scans `list` into the two halves of `buffer` in turn using the DMA, see
+    /// [Circular]. `on_done` is called from the DMA interrupt with every half that was filled.
+    pub fn stream_circular<CH, B>(
+        &mut self,
+        list: &ScanList,
+        channel: CH,
+        buffer: B,
+        descriptors: &'static mut [Descriptor; 2],
+        on_done: Option<fn(Half)>,
+    ) -> Circular<CH, B>
+    where
+        CH: DmaChannel,
+        B: WriteBuffer<Word = u32>,
+    {
+        self.start_scan(list, true);
+        modify_reg(gpip(GPADC_CONFIG), |r| r | CONFIG_DMA_EN);
+        Circular::peripheral_to_memory(channel, fifo::Gpadc, buffer, descriptors, on_done)
+    }
+
+    /// Converts the voltage on `PIN` without blocking, the task is woken from the `Gpadc`
+    /// interrupt, which is enabled
+    #[cfg(feature = "async")]
+    pub async fn read_async<PIN: AdcPin>(&mut self) -> u16 {
+        self.start(self.channel_of::<PIN>());
+        loop {
+            FifoFuture::new().await;
+            if let Some(result) = self.finish() {
+                return self.sample(result).value;
+            }
+        }
+    }
+
+    /// Converts the channels of `list` once, in order, without blocking until all results are
+    /// read into `samples`. The task is woken from the `Gpadc` interrupt, which is enabled.
+    /// Panics if `samples` is shorter than the list.
+    #[cfg(feature = "async")]
+    pub async fn scan_async(
+        &mut self,
+        list: &ScanList,
+        samples: &mut [Sample],
+    ) -> Result<(), Error> {
+        let samples = &mut samples[..list.len];
+        self.start_scan(list, false);
+        for sample in samples.iter_mut() {
+            loop {
+                FifoFuture::new().await;
+                match self.read_sample() {
+                    Ok(value) => {
+                        *sample = value;
+                        break;
+                    }
+                    Err(nb::Error::WouldBlock) => {}
+                    Err(nb::Error::Other(error)) => {
+                        self.stop();
+                        return Err(error);
+                    }
+                }
+            }
+        }
+        self.stop();
+        Ok(())
+    }
+
+    /// Raises the `Gpadc` interrupt when the FIFO holds at least `threshold` results, or when
+    /// it overruns
+    pub fn listen(&mut self, threshold: FifoThreshold) {
+        clear_flags(CONFIG_RDY_CLR | CONFIG_OVERRUN_CLR);
+        modify_reg(gpip(GPADC_CONFIG), |r| {
+            r & !(CONFIG_FIFO_THL_MASK << CONFIG_FIFO_THL_SHIFT
+                | CONFIG_RDY_MASK
+                | CONFIG_OVERRUN_MASK)
+                | (threshold as u32) << CONFIG_FIFO_THL_SHIFT
+                | CONFIG_UNDERRUN_MASK
+        });
+    }
+
+    /// Stops raising the `Gpadc` interrupt
+    pub fn unlisten(&mut self) {
+        modify_reg(gpip(GPADC_CONFIG), |r| {
+            r | CONFIG_RDY_MASK | CONFIG_OVERRUN_MASK | CONFIG_UNDERRUN_MASK
+        });
+    }
+
+    /// Check if the FIFO holds at least the threshold set by [Adc::listen]
+    pub fn is_ready(&self) -> bool {
+        read_reg(gpip(GPADC_CONFIG)) & CONFIG_RDY != 0
+    }
+
+    /// Registers a callback that the HAL calls from the `Gpadc` interrupt with every result in
+    /// the FIFO, parsed at the current configuration and resolution. The `Gpadc()` handler won't
+    /// be called anymore, and the results can't be read with [Adc::read_sample] meanwhile.
+    /// Overruns are cleared without notice.
+    pub fn set_sample_callback(&mut self, callback: fn(Sample)) {
+        let callback = SampleCallback {
+            callback,
+            calibration: self.calibration,
+            resolution: self.resolution,
+        };
+        critical_section::with(|cs| CALLBACK.borrow(cs).set(Some(callback)));
+    }
+
+    /// Removes a callback registered by `set_sample_callback()`
+    pub fn clear_sample_callback(&mut self) {
+        critical_section::with(|cs| CALLBACK.borrow(cs).set(None));
+    }
+
+    /// Stops a running scan, conversion or stream
+    pub fn stop(&mut self) {
+        modify_reg(gpip(GPADC_CONFIG), |r| r & !CONFIG_DMA_EN);
+        modify_cmd(|r| r & !CMD_CONV_START);
+        modify_reg(aon(GPADC_CONFIG1), |r| {
+            r & !(CONFIG1_CONT_CONV_EN | CONFIG1_SCAN_EN)
+        });
+        self.converting = None;
+    }
+
+    /// The channel of `PIN`, which has to be one of the pins of the ADC
+    fn channel_of<PIN: AdcPin>(&self) -> u8 {
+        if PINS::CHANNELS & 1 << PIN::CHANNEL == 0 {
+            panic!("ADC pin the ADC doesn't own");
+        }
+        PIN::CHANNEL
+    }
+
+    /// Starts a single conversion of `channel`, after clearing the FIFO
+    fn start(&mut self, channel: u8) {
+        modify_reg(aon(GPADC_CONFIG1), |r| {
+            r & !(CONFIG1_CONT_CONV_EN | CONFIG1_SCAN_EN)
+        });
+        modify_cmd(|r| {
+            r & !(CMD_CONV_START | CMD_SEL_MASK << CMD_POS_SEL_SHIFT)
+                | (channel as u32) << CMD_POS_SEL_SHIFT
+        });
+        modify_reg(gpip(GPADC_CONFIG), |r| r | CONFIG_FIFO_CLR);
+        modify_cmd(|r| r | CMD_CONV_START);
+        self.converting = Some(channel);
+    }
+
+    /// Converts `channel` once and returns the raw FIFO entry
+    fn blocking_convert(&mut self, channel: u8) -> u32 {
+        self.start(channel);
+        loop {
+            if let Some(result) = self.finish() {
+                return result;
+            }
+        }
+    }
+
+    /// Returns the raw FIFO entry of the started conversion once it's there
+    fn finish(&mut self) -> Option<u32> {
+        if fifo_count() == 0 {
+            return None;
+        }
+
+        let result = read_reg(gpip(GPADC_DMA_RDATA));
+        modify_cmd(|r| r & !CMD_CONV_START);
+        self.converting = None;
+        Some(result)
+    }
+}
+
+/// The ADC registers saved by `suspend()`, in the order `resume()` writes them back
+fn saved_registers() -> [usize; 8] {
+    [
+        aon(GPADC_CONFIG1),
+        aon(GPADC_CONFIG2),
+        aon(GPADC_SCN_POS1),
+        aon(GPADC_SCN_POS2),
+        aon(GPADC_SCN_NEG1),
+        aon(GPADC_SCN_NEG2),
+        aon(GPADC_CMD),
+        gpip(GPADC_CONFIG),
+    ]
+}
+
+impl<PINS: Pins> Suspend for Adc<PINS> {
+    type State = SavedRegisters<8>;
+
+    /// Stops a running scan, conversion or stream, and saves the configuration
+    fn suspend(&mut self) -> Self::State {
+        self.stop();
+        SavedRegisters::save(saved_registers())
+    }
+}
+
+impl<PINS: Pins> Resume for Adc<PINS> {
+    fn resume(&mut self, state: Self::State) {
+        state.restore(saved_registers());
+        modify_reg(gpip(GPADC_CONFIG), |r| r | CONFIG_FIFO_CLR);
+    }
+}
+
+/// The difference of the two temperature sensor measurements at 0°C, from efuse if it's
+/// programmed and passes its parity check
+fn tsen_offset() -> i32 {
+    let trim = read_reg(EF_DATA_BASE + EF_TSEN_TRIM);
+    let refcode = trim & EF_TSEN_REFCODE_MASK;
+    let parity = refcode.count_ones() & 1 == 1;
+    if trim & EF_TSEN_EN != 0 && parity == (trim & EF_TSEN_PARITY != 0) {
+        refcode as i32
+    } else {
+        TSEN_DEFAULT_OFFSET
+    }
+}
+
+/// The gain correction of the ADC in 1/2048, from efuse if it's programmed and passes its parity
+/// check
+fn efuse_gain() -> u32 {
+    let trim = read_reg(EF_DATA_BASE + EF_ADC_GAIN_TRIM);
+    let coefficient = trim >> EF_ADC_GAIN_SHIFT & EF_ADC_GAIN_MASK;
+    let parity = coefficient.count_ones() & 1 == 1;
+    if trim & EF_ADC_GAIN_EN == 0 || parity != (trim & EF_ADC_GAIN_PARITY != 0) {
+        return CALIBRATION_UNITY;
+    }
+
+    // A 12 bit two's complement deviation from unity gain, as in the SDK
+    if coefficient & 0x800 != 0 {
+        CALIBRATION_UNITY + ((!coefficient + 1) & EF_ADC_GAIN_MASK)
+    } else {
+        CALIBRATION_UNITY - coefficient
+    }
+}
+
+/// Corrects the conversion results for the offset and gain error of the ADC
+#[derive(Copy, Clone, Debug)]
+struct Calibration {
+    /// The measured gain in 1/[CALIBRATION_UNITY]
+    gain: u32,
+    /// The result of converting ground, left aligned in 16 bits
+    offset: u32,
+}
+
+impl Calibration {
+    const NONE: Calibration = Calibration {
+        gain: CALIBRATION_UNITY,
+        offset: 0,
+    };
+
+    /// Corrects the value of a raw FIFO entry
+    fn apply(&self, raw: u32) -> u32 {
+        let value = (raw & RESULT_VALUE_MASK).saturating_sub(self.offset);
+        let value = (value * CALIBRATION_UNITY / self.gain).min(RESULT_VALUE_MASK);
+        raw & !RESULT_VALUE_MASK | value
+    }
+}
+
+/// Clocks the ADC and DAC at 32MHz from the PLL if it's enabled, or from the crystal or internal
+/// oscillator otherwise, and returns the clock
+pub(crate) fn setup_clock(clocks: &Clocks) -> Hertz {
+    if clocks.pll_enable() {
+        // 96MHz / 3
+        write_reg(glb(GLB_GPADC_32M_SRC_CTRL), GPADC_32M_DIV_EN | 2);
+    } else {
+        write_reg(
+            glb(GLB_GPADC_32M_SRC_CTRL),
+            GPADC_32M_DIV_EN | GPADC_32M_CLK_SEL_XCLK,
+        );
+    }
+    clocks.adc_clk()
+}
+
+/// The number of results in the FIFO
+fn fifo_count() -> u32 {
+    read_reg(gpip(GPADC_CONFIG)) >> CONFIG_FIFO_DATA_COUNT_SHIFT & CONFIG_FIFO_DATA_COUNT_MASK
+}
+
+/// Modifies `GPADC_CMD` in a critical section, as [start_capture] and [stop_capture] may change
+/// its start bit from an interrupt handler
+fn modify_cmd(f: impl FnOnce(u32) -> u32) {
+    critical_section::with(|_| modify_reg(aon(GPADC_CMD), f));
+}
+
+/// Starts the capture set up by [Adc::arm_capture]. Only the start bit is touched, in a critical
+/// section, so this can be called from any interrupt handler or callback.
+pub fn start_capture() {
+    modify_cmd(|r| r | CMD_CONV_START);
+}
+
+/// Stops the conversions of the capture set up by [Adc::arm_capture], e.g. on a second event,
+/// before its buffer is full. The results so far stay in the buffer, see `Transfer::abort()`.
+pub fn stop_capture() {
+    modify_cmd(|r| r & !CMD_CONV_START);
+}
+
+/// Starts a pass of the scan set up by [Adc::stream_triggered] on every kick, see
+/// `timer::Trigger`
+pub struct ScanTrigger;
+
+impl Trigger for ScanTrigger {
+    fn trigger() {
+        modify_cmd(|r| r & !CMD_CONV_START);
+        modify_cmd(|r| r | CMD_CONV_START);
+    }
+}
+
+/// The average of `values`. Panics if `values` is empty.
+pub fn average(values: &[u16]) -> u16 {
+    if values.is_empty() {
+        panic!("No ADC conversions to average");
+    }
+    let sum: u64 = values.iter().map(|value| *value as u64).sum();
+    (sum / values.len() as u64) as u16
+}
+
+/// The median of `values`, which are sorted in place. Panics if `values` is empty.
+pub fn median(values: &mut [u16]) -> u16 {
+    if values.is_empty() {
+        panic!("No ADC conversions to take the median of");
+    }
+    values.sort_unstable();
+    values[values.len() / 2]
+}
+
+/// Clears the given status flags, by pulsing their clear bits
+fn clear_flags(clear: u32) {
+    modify_reg(gpip(GPADC_CONFIG), |r| r | clear);
+    modify_reg(gpip(GPADC_CONFIG), |r| r & !clear);
+}
+
+/// Reads the FIFO into the registered callback, or wakes the task waiting for a result.
+/// Returns `false` if there's neither, so the interrupt is left for the user's `Gpadc` handler.
+pub(crate) fn dispatch_interrupt() -> bool {
+    let callback = match critical_section::with(|cs| CALLBACK.borrow(cs).get()) {
+        Some(callback) => callback,
+        #[cfg(feature = "async")]
+        None => return wake_task(),
+        #[cfg(not(feature = "async"))]
+        None => return false,
+    };
+
+    while fifo_count() != 0 {
+        let raw = callback.calibration.apply(read_reg(gpip(GPADC_DMA_RDATA)));
+        (callback.callback)(Sample::from_raw(raw, callback.resolution));
+    }
+    clear_flags(CONFIG_RDY_CLR | CONFIG_OVERRUN_CLR);
+
+    true
+}
+
+/// Wakes the task waiting for a result, and masks the interrupt, the results stay in the FIFO
+/// for the task to read. Returns `false` if no task is waiting.
+#[cfg(feature = "async")]
+fn wake_task() -> bool {
+    critical_section::with(|cs| match WAKER.borrow(cs).borrow_mut().take() {
+        Some(waker) => {
+            modify_reg(gpip(GPADC_CONFIG), |r| r | CONFIG_RDY_MASK);
+            clear_flags(CONFIG_RDY_CLR);
+            waker.wake();
+            true
+        }
+        None => false,
+    })
+}
+
+/// Completes once the FIFO holds a result
+#[cfg(feature = "async")]
+struct FifoFuture;
+
+#[cfg(feature = "async")]
+impl FifoFuture {
+    fn new() -> Self {
+        enable_interrupt(Interrupt::Gpadc);
+        FifoFuture
+    }
+}
+
+#[cfg(feature = "async")]
+impl Future for FifoFuture {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if fifo_count() != 0 {
+            return Poll::Ready(());
+        }
+
+        critical_section::with(|cs| {
+            WAKER.borrow(cs).replace(Some(cx.waker().clone()));
+            modify_reg(gpip(GPADC_CONFIG), |r| {
+                r & !(CONFIG_FIFO_THL_MASK << CONFIG_FIFO_THL_SHIFT | CONFIG_RDY_MASK)
+                    | (FifoThreshold::One as u32) << CONFIG_FIFO_THL_SHIFT
+            });
+        });
+
+        // The result may have arrived before the interrupt was unmasked
+        if fifo_count() != 0 {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl Drop for FifoFuture {
+    fn drop(&mut self) {
+        critical_section::with(|cs| {
+            WAKER.borrow(cs).replace(None);
+            modify_reg(gpip(GPADC_CONFIG), |r| r | CONFIG_RDY_MASK);
+        });
+    }
+}
+
+/// The number of results in the FIFO that raises the `Gpadc` interrupt
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FifoThreshold {
+    /// Every result
+    One = 0,
+    /// 4 results
+    Four = 1,
+    /// 8 results
+    Eight = 2,
+    /// 16 results
+    Sixteen = 3,
+}
+
+/// ADC configuration
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Config {
+    /// The gain of the programmable gain amplifier in front of the ADC
+    pub gain: Gain,
+    /// The reference voltage of the conversions
+    pub reference: Reference,
+    /// Divides the ADC clock down to the conversion clock, one of 4, 8, 12, 16, 20, 24 or 32
+    pub clock_divider: u32,
+    /// Conversion clock cycles the input settles before each conversion, up to [MAX_SETTLING]
+    pub settling: u8,
+    /// Corrects the results with the gain trim in efuse and an offset measured against ground
+    pub calibrate: bool,
+}
+
+impl Config {
+    /// Sets the gain of the programmable gain amplifier
+    pub fn gain(mut self, gain: Gain) -> Self {
+        self.gain = gain;
+
+        self
+    }
+
+    /// Sets the reference voltage
+    pub fn reference(mut self, reference: Reference) -> Self {
+        self.reference = reference;
+
+        self
+    }
+
+    /// Sets the conversion clock divider
+    pub fn clock_divider(mut self, clock_divider: u32) -> Self {
+        self.clock_divider = clock_divider;
+
+        self
+    }
+
+    /// Sets the settling delay before each conversion, in conversion clock cycles
+    pub fn settling(mut self, settling: u8) -> Self {
+        self.settling = settling;
+
+        self
+    }
+
+    /// Enables or disables the calibration of the results
+    pub fn calibrate(mut self, calibrate: bool) -> Self {
+        self.calibrate = calibrate;
+
+        self
+    }
+}
+
+impl Default for Config {
+    /// No gain, the 3.2V reference, a 1MHz conversion clock from 32MHz and calibrated results
+    fn default() -> Self {
+        Config {
+            gain: Gain::X1,
+            reference: Reference::Internal3V2,
+            clock_divider: 32,
+            settling: 0,
+            calibrate: true,
+        }
+    }
+}
+
+/// The gain of the programmable gain amplifier (PGA), which is bypassed at [Gain::X1]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Gain {
+    /// No amplification
+    X1,
+    /// Amplify by 2
+    X2,
+    /// Amplify by 4
+    X4,
+    /// Amplify by 8
+    X8,
+    /// Amplify by 16
+    X16,
+    /// Amplify by 32
+    X32,
+}
+
+impl Gain {
+    /// The amplification factor
+    pub fn factor(&self) -> u32 {
+        match self {
+            Gain::X1 => 1,
+            Gain::X2 => 2,
+            Gain::X4 => 4,
+            Gain::X8 => 8,
+            Gain::X16 => 16,
+            Gain::X32 => 32,
+        }
+    }
+
+    /// The gain codes of the two PGA stages, the second one at unity gain
+    fn stages(&self) -> (u32, u32) {
+        match self {
+            Gain::X1 => (0, 0),
+            Gain::X2 => (2, 1),
+            Gain::X4 => (3, 1),
+            Gain::X8 => (4, 1),
+            Gain::X16 => (5, 1),
+            Gain::X32 => (6, 1),
+        }
+    }
+}
+
+/// The reference voltage of the conversions. There's no input for an external reference, a full
+/// scale result equals the selected internal one.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Reference {
+    /// 3.2V, covering the full supply range
+    Internal3V2,
+    /// 2.0V, for a finer resolution of lower voltages
+    Internal2V0,
+}
+
+impl Reference {
+    /// The reference voltage in millivolts
+    pub fn millivolts(&self) -> u32 {
+        match self {
+            Reference::Internal3V2 => 3200,
+            Reference::Internal2V0 => 2000,
+        }
+    }
+}
+
+/// The resolution of the conversions. The higher resolutions average a number of conversions in
+/// hardware, which makes each result take as much longer.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Resolution {
+    /// 12 bit, a single conversion
+    Bits12 = 0,
+    /// 14 bit, averaged over 16 conversions
+    Bits14Avg16 = 1,
+    /// 14 bit, averaged over 64 conversions
+    Bits14Avg64 = 2,
+    /// 16 bit, averaged over 128 conversions
+    Bits16Avg128 = 3,
+    /// 16 bit, averaged over 256 conversions
+    Bits16Avg256 = 4,
+}
+
+impl Resolution {
+    /// The number of bits of the results
+    pub fn bits(&self) -> u32 {
+        match self {
+            Resolution::Bits12 => 12,
+            Resolution::Bits14Avg16 | Resolution::Bits14Avg64 => 14,
+            Resolution::Bits16Avg128 | Resolution::Bits16Avg256 => 16,
+        }
+    }
+
+    /// The number of conversions averaged into each result
+    pub fn averaged(&self) -> u32 {
+        match self {
+            Resolution::Bits12 => 1,
+            Resolution::Bits14Avg16 => 16,
+            Resolution::Bits14Avg64 => 64,
+            Resolution::Bits16Avg128 => 128,
+            Resolution::Bits16Avg256 => 256,
+        }
+    }
+}
+
+/// An internal source the ADC can convert, next to the analog pins
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum InternalSource {
+    /// Output A of the DAC
+    DacA = 12,
+    /// Output B of the DAC
+    DacB = 13,
+    /// The reference voltage
+    Vref = 16,
+    /// The DC test point of the analog blocks
+    DcTest = 17,
+    /// Half of the supply voltage (VBAT), see [Adc::read_vbat]
+    VbatHalf = 18,
+    /// Ground, e.g. to measure the offset of the ADC
+    Gnd = 23,
+}
+
+/// A conversion result of a scan
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Sample {
+    /// The ADC channel the value was converted from
+    pub channel: u8,
+    /// The value, at the resolution of the conversion
+    pub value: u16,
+}
+
+impl Sample {
+    /// Parses a raw FIFO entry converted at `resolution`, without calibration, see [Adc::sample]
+    pub fn from_raw(raw: u32, resolution: Resolution) -> Self {
+        let (channel, value) = parse_result(raw, resolution);
+        Sample { channel, value }
+    }
+}
+
+/// The channels a scan converts in order, up to [MAX_SCAN_CHANNELS]. A channel may be listed
+/// more than once, e.g. to sample it more often than the others.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ScanList {
+    channels: [u8; MAX_SCAN_CHANNELS],
+    len: usize,
+}
+
+impl ScanList {
+    /// An empty scan list
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends the channel of `PIN`, which has to be one of the pins of the ADC the list is
+    /// scanned with. Panics if the list is full.
+    pub fn add<PIN: AdcPin>(mut self) -> Self {
+        if self.len == MAX_SCAN_CHANNELS {
+            panic!("Too many ADC scan channels");
+        }
+        self.channels[self.len] = PIN::CHANNEL;
+        self.len += 1;
+
+        self
+    }
+
+    /// The number of channels in the list
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Check if the list is empty
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// Splits a FIFO entry into the channel it was converted from, and the value at `resolution`
+fn parse_result(result: u32, resolution: Resolution) -> (u8, u16) {
+    let channel = (result >> RESULT_POS_SHIFT) as u8 & CMD_SEL_MASK as u8;
+    // The value is left aligned in the 16 bit field
+    let value = (result & RESULT_VALUE_MASK) >> (16 - resolution.bits());
+    (channel, value as u16)
+}
+
+/// Encodes a conversion clock divider
+fn clock_divider_bits(divider: u32) -> u32 {
+    match divider {
+        4 => 1,
+        8 => 2,
+        12 => 3,
+        16 => 4,
+        20 => 5,
+        24 => 6,
+        32 => 7,
+        _ => panic!("Invalid ADC clock divider"),
+    }
+}
+
+/// An analog pin the ADC converts - DO NOT IMPLEMENT THIS TRAIT
+pub trait AdcPin: private::Sealed {
+    /// The ADC channel of the pin
+    const CHANNEL: u8;
+}
+
+/// The analog pins an [Adc] owns: none, a single pin or a tuple of up to 6 pins
+/// - DO NOT IMPLEMENT THIS TRAIT
+pub trait Pins: private::Sealed {
+    /// The ADC channels of the pins, one bit per channel
+    const CHANNELS: u16;
+}
+
+mod private {
+    pub trait Sealed {}
+}
+
+impl private::Sealed for () {}
+
+impl Pins for () {
+    const CHANNELS: u16 = 0;
+}
+
+macro_rules! impl_adc_pin {
+    ($($pin: ident: $channel: literal,)+) => {
+        $(
+            impl private::Sealed for crate::gpio::$pin<Analog> {}
+
+            impl AdcPin for crate::gpio::$pin<Analog> {
+                const CHANNEL: u8 = $channel;
+            }
+
+            impl Pins for crate::gpio::$pin<Analog> {
+                const CHANNELS: u16 = 1 << $channel;
+            }
+        )+
+    };
+}
+
+impl_adc_pin! {
+    Pin12: 0,
+    Pin4: 1,
+    Pin14: 2,
+    Pin13: 3,
+    Pin5: 4,
+    Pin6: 5,
+    Pin7: 6,
+    Pin9: 7,
+    Pin18: 8,
+    Pin19: 9,
+    Pin20: 10,
+    Pin21: 11,
+}
+
+macro_rules! impl_pins_tuple {
+    ($(($($p: ident),+),)+) => {
+        $(
+            impl<$($p: AdcPin),+> private::Sealed for ($($p,)+) {}
+
+            impl<$($p: AdcPin),+> Pins for ($($p,)+) {
+                const CHANNELS: u16 = $(1 << $p::CHANNEL)|+;
+            }
+        )+
+    };
+}
+
+impl_pins_tuple! {
+    (A),
+    (A, B),
+    (A, B, C),
+    (A, B, C, D),
+    (A, B, C, D, E),
+    (A, B, C, D, E, F),
+}
@@ -20,28 +20,211 @@
 //
 // Currently implemented clock tree configuration options:
 //   - internal 32Mhz RC oscillator for sysclock
+//   - XTAL for sysclock
 //   - XTAL driving PLL, sysclock frequencies of 48/80/120/160/192Mhz
-//   - UART using PLL if sysclock is using PLL
+//   - hclk and bclk dividers
+//   - machine timer (mtime) divider
+//   - serial flash clock source and divider
+//   - UART from the PLL or sysclock, optionally picked for a baudrate
+//   - low power profile with the PLL and XTAL powered down, switchable at runtime
 
 use crate::delay::*;
 use crate::gpio::ClkCfg;
 use crate::pac;
+use crate::reg::{
+    glb, hbn, modify_reg, pds, ram_delay, ram_read_reg, ram_write_reg, read_reg, GLB_BASE, HBN_BASE,
+};
 use core::num::NonZeroU32;
 use embedded_hal::delay::DelayNs;
-use embedded_time::rate::{Extensions, Hertz};
+use embedded_time::rate::{Baud, Extensions, Hertz};
 
 /// Internal high-speed RC oscillator frequency
 pub const RC32M: u32 = 32_000_000;
 /// UART peripheral clock frequency when PLL selected
 pub const UART_PLL_FREQ: u32 = 160_000_000;
 
-#[derive(PartialEq, Copy, Clone)]
+// see components\bl602\bl602_std\bl602_std\StdDriver\Inc\bl602_glb_reg.h
+const GLB_CLK_CFG0: usize = 0x00;
+const GLB_CLK_CFG2: usize = 0x08;
+const GLB_CGEN_CFG1: usize = 0x24;
+const GLB_CPU_CLK_CFG: usize = 0x90;
+const GLB_BCLK_DIS: usize = 0xffc;
+
+const CLK_CFG0_PLL_SEL_SHIFT: u32 = 4;
+const CLK_CFG0_PLL_SEL_MASK: u32 = 0x3;
+const CLK_CFG0_HCLK_DIV_SHIFT: u32 = 8;
+const CLK_CFG0_BCLK_DIV_SHIFT: u32 = 16;
+const CLK_CFG0_DIV_MASK: u32 = 0xff;
+
+const CLK_CFG2_SF_CLK_DIV_SHIFT: u32 = 8;
+const CLK_CFG2_SF_CLK_DIV_MASK: u32 = 0x7;
+const CLK_CFG2_SF_CLK_EN: u32 = 1 << 11;
+const CLK_CFG2_SF_CLK_SEL_SHIFT: u32 = 12;
+const CLK_CFG2_SF_CLK_SEL2_SHIFT: u32 = 14;
+const CLK_CFG2_SF_CLK_SEL_MASK: u32 = 0x3;
+const CLK_CFG2_SF_CLK_MASK: u32 = CLK_CFG2_SF_CLK_DIV_MASK << CLK_CFG2_SF_CLK_DIV_SHIFT
+    | CLK_CFG2_SF_CLK_SEL_MASK << CLK_CFG2_SF_CLK_SEL_SHIFT
+    | CLK_CFG2_SF_CLK_SEL_MASK << CLK_CFG2_SF_CLK_SEL2_SHIFT;
+
+const CPU_CLK_CFG_RTC_DIV_MASK: u32 = 0x1ffff;
+const CPU_CLK_CFG_RTC_EN: u32 = 1 << 18;
+/// The largest divider of the machine timer clock
+const MTIMER_MAX_DIV: u32 = CPU_CLK_CFG_RTC_DIV_MASK + 1;
+
+// see components\bl602\bl602_std\bl602_std\StdDriver\Inc\hbn_reg.h
+const HBN_GLB: usize = 0x30;
+const HBN_RC32K_CTRL0: usize = 0x200;
+const HBN_XTAL32K: usize = 0x204;
+
+const RTC_CTL_EN: u8 = 1 << 0;
+const RTC_TIME_H_LATCH: u32 = 1 << 31;
+const HBN_GLB_F32K_SEL_SHIFT: u32 = 3;
+const HBN_GLB_F32K_SEL_MASK: u32 = 0x3;
+const RC32K_EXT_CODE_EN: u32 = 1 << 19;
+const RC32K_CODE_FR_EXT_SHIFT: u32 = 22;
+const RC32K_CODE_FR_EXT_MASK: u32 = 0x3ff;
+const XTAL32K_PU_BUF: u32 = 1 << 18;
+const XTAL32K_PU: u32 = 1 << 19;
+
+// see components\bl602\bl602_std\bl602_std\StdDriver\Inc\pds_reg.h
+const PDS_RC32M_CTRL0: usize = 0x300;
+
+const RC32M_EXT_CODE_EN: u32 = 1 << 19;
+const RC32M_CODE_FR_EXT_SHIFT: u32 = 22;
+const RC32M_CODE_FR_EXT_MASK: u32 = 0xff;
+
+// see components\bl602\bl602_std\bl602_std\StdDriver\Src\bl602_ef_ctrl.c
+const EF_DATA_BASE: usize = 0x4000_7000;
+const EF_RC_TRIM: usize = 0x74;
+const EF_RC32M_TRIM_SHIFT: u32 = 10;
+const EF_RC32M_PARITY: u32 = 1 << 18;
+const EF_RC32M_EN: u32 = 1 << 19;
+const EF_RC32K_TRIM_SHIFT: u32 = 20;
+const EF_RC32K_PARITY: u32 = 1 << 30;
+const EF_RC32K_EN: u32 = 1 << 31;
+
+/// Ticks of the 32.768kHz clock in the 50ms a start of the crystal is checked over
+const XTAL32K_CHECK_TICKS: u32 = 1638;
+/// Times the start of the crystal is checked, waiting a second in total
+const XTAL32K_CHECKS: u32 = 20;
+/// Ticks of the 32K clock an oscillator is measured over, about 100ms
+const MEASURE_32K_TICKS: u32 = 3277;
+
+const HBN_GLB_ROOT_CLK_SEL_MASK: u32 = 0x3;
+const ROOT_CLK_SEL_RC32M: u32 = 0b00;
+const ROOT_CLK_SEL_XTAL: u32 = 0b01;
+const ROOT_CLK_SEL_PLL: u32 = 0b10;
+
+/// Frequency of the internal 32K oscillator
+const RC32K: u32 = 32_000;
+/// Frequency of the external 32K crystal
+const XTAL32K: u32 = 32_768;
+/// The clock of the ADC and DAC, divided from the PLL while it runs
+const GPADC_PLL_FREQ: u32 = 32_000_000;
+
+/// The highest recommended bus clock
+const MAX_BCLK: u32 = 80_000_000;
+
+/// System clock (fclk/hclk) sources
+///
+/// `Pll80Mhz` runs from the 160MHz PLL tap, divided by 2
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[repr(u32)]
 pub enum SysclkFreq {
     Rc32Mhz = 32_000_000,
     Pll48Mhz = 48_000_000,
+    Pll80Mhz = 80_000_000,
     Pll120Mhz = 120_000_000,
     Pll160Mhz = 160_000_000,
+    /// Out of spec
+    Pll192Mhz = 192_000_000,
+}
+
+impl SysclkFreq {
+    /// The PLL output tap the system clock is divided from, with the divider
+    const fn pll_tap(self) -> (u32, u32) {
+        match self {
+            SysclkFreq::Pll80Mhz => (160_000_000, 2),
+            _ => (self as u32, 1),
+        }
+    }
+}
+
+/// UART clock sources, which the UART clock is divided from by 1 to 7
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum UartClkSrc {
+    /// The system clock
+    Fclk,
+    /// The 160MHz PLL output, independent of the system clock
+    Pll160Mhz,
+}
+
+/// Serial flash clock sources
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FlashClk {
+    /// The 120MHz PLL tap
+    Pll120Mhz,
+    /// The external crystal
+    Xtal,
+    /// The 48MHz PLL tap
+    Pll48Mhz,
+    /// The 80MHz PLL tap
+    Pll80Mhz,
+    /// The bus clock
+    Bclk,
+    /// The 96MHz PLL tap
+    Pll96Mhz,
+}
+
+impl FlashClk {
+    /// The `sf_clk_sel` and `sf_clk_sel2` bits
+    fn bits(self) -> (u32, u32) {
+        match self {
+            FlashClk::Pll120Mhz => (0, 0),
+            FlashClk::Xtal => (0, 1),
+            FlashClk::Pll48Mhz => (0, 2),
+            FlashClk::Pll80Mhz => (1, 0),
+            FlashClk::Bclk => (2, 0),
+            FlashClk::Pll96Mhz => (3, 0),
+        }
+    }
+
+    fn from_bits(sel: u32, sel2: u32) -> Self {
+        match (sel, sel2) {
+            (0, 0) => FlashClk::Pll120Mhz,
+            (0, 1) => FlashClk::Xtal,
+            (0, _) => FlashClk::Pll48Mhz,
+            (1, _) => FlashClk::Pll80Mhz,
+            (2, _) => FlashClk::Bclk,
+            _ => FlashClk::Pll96Mhz,
+        }
+    }
+
+    /// The frequency of the source, `None` if it isn't running
+    fn freq(self, xtal_freq: Option<u32>, pll_enabled: bool, bclk: Hertz) -> Option<u32> {
+        match self {
+            FlashClk::Xtal => xtal_freq,
+            FlashClk::Bclk => Some(bclk.0),
+            _ if !pll_enabled => None,
+            FlashClk::Pll120Mhz => Some(120_000_000),
+            FlashClk::Pll48Mhz => Some(48_000_000),
+            FlashClk::Pll80Mhz => Some(80_000_000),
+            FlashClk::Pll96Mhz => Some(96_000_000),
+        }
+    }
+}
+
+/// Sources of the 32.768kHz clock of the RTC
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Clk32k {
+    /// The internal RC oscillator
+    Rc32k,
+    /// The external crystal
+    Xtal32k,
 }
 
 /// Frozen clock frequencies
@@ -50,10 +233,16 @@ pub enum SysclkFreq {
 #[derive(Clone, Copy)]
 pub struct Clocks {
     sysclk: Hertz,
+    bclk: Hertz,
     uart_clk: Hertz,
+    uart_clk_src: UartClkSrc,
     spi_clk: Hertz,
     i2c_clk: Hertz,
-    _xtal_freq: Option<Hertz>,
+    flash_clk: Option<Hertz>,
+    clk_32k: Clk32k,
+    xtal_freq: Option<Hertz>,
+    xclk: Hertz,
+    mtimer_clk: Option<Hertz>,
     pll_enable: bool,
 }
 
@@ -61,33 +250,206 @@ impl Clocks {
     pub fn new() -> Self {
         Clocks {
             sysclk: Hertz(RC32M),
+            bclk: Hertz(RC32M),
             uart_clk: Hertz(RC32M),
+            uart_clk_src: UartClkSrc::Fclk,
             spi_clk: Hertz(RC32M),
             i2c_clk: Hertz(RC32M),
-            _xtal_freq: None,
+            flash_clk: None,
+            clk_32k: Clk32k::Rc32k,
+            xtal_freq: None,
+            xclk: Hertz(RC32M),
+            mtimer_clk: None,
             pll_enable: false,
         }
     }
 
+    /// Reads back the clock configuration left by the bootloader, instead of configuring the
+    /// clocks like `Strict::freeze` does. Nothing is written, so the flash and the UART keep
+    /// running undisturbed, e.g. for applications started by boot2.
+    ///
+    /// `xtal_freq` is the frequency of the crystal, which the registers don't record. It's only
+    /// used if the crystal is powered on.
+    pub fn adopt(_clk_cfg: &mut ClkCfg, xtal_freq: Hertz) -> Clocks {
+        let xtal_freq = if unsafe { &*pac::AON::ptr() }
+            .rf_top_aon
+            .read()
+            .pu_xtal_aon()
+            .bit_is_set()
+        {
+            Some(xtal_freq.0)
+        } else {
+            None
+        };
+        let pll_enable = unsafe { &*pac::GLB::ptr() }
+            .clk_cfg0
+            .read()
+            .reg_pll_en()
+            .bit_is_set();
+
+        let (root_sel, pll_sel, hclk_div, bclk_div) = read_system_clk();
+        let root_clk = match root_sel {
+            ROOT_CLK_SEL_RC32M => RC32M,
+            ROOT_CLK_SEL_XTAL => xtal_freq.expect("Crystal isn't running"),
+            _ => match pll_sel {
+                0 => 48_000_000,
+                1 => 120_000_000,
+                2 => 160_000_000,
+                _ => 192_000_000,
+            },
+        };
+        let sysclk = root_clk / hclk_div;
+        let bclk = sysclk / bclk_div;
+
+        let hbn_glb = unsafe { &*pac::HBN::ptr() }.hbn_glb.read();
+        let uart_clk_src = if hbn_glb.hbn_uart_clk_sel().bit_is_set() {
+            UartClkSrc::Pll160Mhz
+        } else {
+            UartClkSrc::Fclk
+        };
+        let uart_src_clk = match uart_clk_src {
+            UartClkSrc::Pll160Mhz => UART_PLL_FREQ,
+            UartClkSrc::Fclk => sysclk,
+        };
+        let uart_clk_div = unsafe { &*pac::GLB::ptr() }
+            .clk_cfg2
+            .read()
+            .uart_clk_div()
+            .bits() as u32
+            + 1;
+        let clk_cfg3 = unsafe { &*pac::GLB::ptr() }.clk_cfg3.read();
+        let spi_clk_div = clk_cfg3.spi_clk_div().bits() as u32 + 1;
+        let i2c_clk_div = clk_cfg3.i2c_clk_div().bits() as u32 + 1;
+
+        let clk_32k = match hbn_glb.bits() >> HBN_GLB_F32K_SEL_SHIFT & HBN_GLB_F32K_SEL_MASK {
+            1 => Clk32k::Xtal32k,
+            _ => Clk32k::Rc32k,
+        };
+
+        Clocks {
+            sysclk: Hertz(sysclk),
+            bclk: Hertz(bclk),
+            uart_clk: Hertz(uart_src_clk / uart_clk_div),
+            uart_clk_src,
+            spi_clk: Hertz(bclk / spi_clk_div),
+            i2c_clk: Hertz(bclk / i2c_clk_div),
+            flash_clk: read_flash_clk(xtal_freq, pll_enable, Hertz(bclk)),
+            clk_32k,
+            xtal_freq: xtal_freq.map(Hertz),
+            xclk: read_xclk(xtal_freq),
+            mtimer_clk: read_mtimer_clk(Hertz(bclk)),
+            pll_enable,
+        }
+    }
+
+    /// System clock frequency
     pub fn sysclk(&self) -> Hertz {
         self.sysclk
     }
 
+    /// Fast clock (fclk) frequency, which is the system clock
+    pub const fn fclk(&self) -> Hertz {
+        self.sysclk
+    }
+
+    /// Core clock (hclk) frequency, which equals the system clock (fclk)
+    pub const fn hclk(&self) -> Hertz {
+        self.sysclk
+    }
+
+    /// Check if the PLL is running
     pub fn pll_enable(&self) -> bool {
         self.pll_enable
     }
 
+    /// Crystal clock mux (xclk) frequency, which is the external crystal if it's the root clock
+    /// or was selected before the PLL, and the internal RC oscillator otherwise
+    pub const fn xclk(&self) -> Hertz {
+        self.xclk
+    }
+
+    /// Bus clock (bclk) frequency
+    pub const fn bclk(&self) -> Hertz {
+        self.bclk
+    }
+
+    /// External crystal frequency, if the crystal was powered on for the PLL or the system clock
+    pub const fn xtal_freq(&self) -> Option<Hertz> {
+        self.xtal_freq
+    }
+
+    /// The source the UART clock is divided from
+    pub const fn uart_clk_src(&self) -> UartClkSrc {
+        self.uart_clk_src
+    }
+
+    /// Frequency of the source the UART clock is divided from, see `uart_clk_src()`
+    pub const fn uart_src_clk(&self) -> Hertz {
+        match self.uart_clk_src {
+            UartClkSrc::Pll160Mhz => Hertz(UART_PLL_FREQ),
+            UartClkSrc::Fclk => self.sysclk,
+        }
+    }
+
+    /// UART peripheral clock frequency, which the baudrate is divided from
     pub const fn uart_clk(&self) -> Hertz {
         self.uart_clk
     }
 
+    /// Clock the SPI and I2C clocks are divided from, which is the bus clock
+    pub const fn spi_i2c_src_clk(&self) -> Hertz {
+        self.bclk
+    }
+
+    /// SPI peripheral clock frequency, which the SPI clock is divided from
     pub const fn spi_clk(&self) -> Hertz {
         self.spi_clk
     }
 
+    /// I2C peripheral clock frequency, which the I2C clock is divided from
     pub const fn i2c_clk(&self) -> Hertz {
         self.i2c_clk
     }
+
+    /// ADC clock frequency, 32MHz from the PLL while it runs, and the crystal clock mux
+    /// otherwise
+    pub const fn adc_clk(&self) -> Hertz {
+        if self.pll_enable {
+            Hertz(GPADC_PLL_FREQ)
+        } else {
+            self.xclk
+        }
+    }
+
+    /// DAC clock frequency, which is shared with the ADC
+    pub const fn dac_clk(&self) -> Hertz {
+        self.adc_clk()
+    }
+
+    /// Tick rate of the RISC-V machine timer (`mtime`), divided from the bus clock. `None` if the
+    /// machine timer is stopped
+    pub const fn mtimer_clk(&self) -> Option<Hertz> {
+        self.mtimer_clk
+    }
+
+    /// Serial flash clock frequency, `None` if it wasn't configured and the boot setting is unknown
+    pub const fn flash_clk(&self) -> Option<Hertz> {
+        self.flash_clk
+    }
+
+    /// The 32.768kHz clock of the RTC, which is the internal RC oscillator if the external
+    /// crystal wasn't requested or didn't start
+    pub const fn clk_32k(&self) -> Clk32k {
+        self.clk_32k
+    }
+
+    /// Frequency of the 32K clock, see `clk_32k()`
+    pub const fn f32k(&self) -> Hertz {
+        match self.clk_32k {
+            Clk32k::Rc32k => Hertz(RC32K),
+            Clk32k::Xtal32k => Hertz(XTAL32K),
+        }
+    }
 }
 
 impl Default for Clocks {
@@ -96,6 +458,55 @@ impl Default for Clocks {
     }
 }
 
+#[cfg(feature = "defmt")]
+impl defmt::Format for Clocks {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "Clocks {{ sysclk: {=u32}, bclk: {=u32}, uart_clk: {=u32}, spi_clk: {=u32}, \
+             i2c_clk: {=u32}, flash_clk: {=?}, clk_32k: {}, xtal_freq: {=?}, xclk: {=u32}, \
+             mtimer_clk: {=?}, pll_enable: {=bool} }}",
+            self.sysclk.0,
+            self.bclk.0,
+            self.uart_clk.0,
+            self.spi_clk.0,
+            self.i2c_clk.0,
+            self.flash_clk.map(|freq| freq.0),
+            self.clk_32k,
+            self.xtal_freq.map(|freq| freq.0),
+            self.xclk.0,
+            self.mtimer_clk.map(|freq| freq.0),
+            self.pll_enable,
+        )
+    }
+}
+
+/// Invalid clock configurations, see [Strict::validate]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Error {
+    /// The crystal frequency is 0, or not supported by the PLL, or the crystal is used by a
+    /// configuration powering it down
+    Xtal,
+    /// The system clock needs the PLL, or the crystal and the PLL are both the root clock
+    Sysclk,
+    /// The system clock divider is out of range
+    Hclk,
+    /// The bus clock divider is out of range
+    Bclk,
+    /// The UART clock can't be divided from its source
+    UartClk,
+    /// The SPI clock can't be divided from the bus clock
+    SpiClk,
+    /// The I2C clock can't be divided from the bus clock
+    I2cClk,
+    /// The flash clock divider is out of range, or its source isn't running
+    FlashClk,
+    /// The machine timer clock can't be divided from the bus clock
+    MtimerClk,
+}
+
 /// Strict clock configurator
 ///
 /// This configurator only accepts strictly accurate value. If all available frequency
@@ -111,56 +522,193 @@ impl Default for Clocks {
 /// 50MHz into `Strict` would result in a panic when performing `freeze`; however input
 /// same 50MHz into `Precise` it would not panic, but would set and freeze into
 /// 50.20MHz as the frequency error is smallest.
+///
+/// The root clock of the system is the internal RC oscillator, the external crystal
+/// (`use_xtal`) or a tap of the PLL driven by the crystal (`use_pll` and `sys_clk`). The
+/// system clock (fclk/hclk) is divided from it, and the bus clock (bclk) from the system
+/// clock.
+///
+/// ## Example
+/// ```rust
+/// let clocks = Strict::new()
+///     .use_pll(40_000_000u32.Hz())
+///     .sys_clk(SysclkFreq::Pll120Mhz)
+///     .bclk_div(2)
+///     .flash_clk(FlashClk::Pll48Mhz, 1)
+///     .freeze(&mut parts.clk_cfg);
+/// ```
 pub struct Strict {
     target_i2c_clk: Option<NonZeroU32>,
     target_spi_clk: Option<NonZeroU32>,
     target_uart_clk: Option<NonZeroU32>,
+    target_baudrate: Option<NonZeroU32>,
+    uart_clk_src: Option<UartClkSrc>,
     pll_xtal_freq: Option<u32>,
+    root_xtal_freq: Option<u32>,
     sysclk: SysclkFreq,
+    hclk_div: Option<u32>,
+    bclk_div: Option<u32>,
+    flash_clk: Option<(FlashClk, u32)>,
+    xtal32k: bool,
+    trim_oscillators: bool,
+    power_down_pll: bool,
+    target_mtimer_clk: Option<NonZeroU32>,
 }
 
 impl Strict {
     /// Create a strict configurator
-    pub fn new() -> Self {
+    pub const fn new() -> Self {
         Strict {
             target_i2c_clk: None,
             target_spi_clk: None,
             target_uart_clk: None,
+            target_baudrate: None,
+            uart_clk_src: None,
             pll_xtal_freq: None,
+            root_xtal_freq: None,
             sysclk: SysclkFreq::Rc32Mhz,
+            hclk_div: None,
+            bclk_div: None,
+            flash_clk: None,
+            xtal32k: false,
+            trim_oscillators: true,
+            power_down_pll: false,
+            target_mtimer_clk: None,
+        }
+    }
+
+    /// Create a configurator for the low power profile, see [Profile::LowPower]
+    ///
+    /// Everything runs from the internal 32MHz RC oscillator, the flash is clocked from the bus
+    /// clock, and the PLL and the crystal are powered down, also if the bootloader started them.
+    pub const fn low_power() -> Self {
+        Strict {
+            flash_clk: Some((FlashClk::Bclk, 1)),
+            power_down_pll: true,
+            ..Strict::new()
         }
     }
 
     /// Sets the desired frequency for the I2C-CLK clock
-    pub fn i2c_clk(mut self, freq: impl Into<Hertz>) -> Self {
-        let freq_hz = freq.into().0;
+    pub fn i2c_clk(self, freq: impl Into<Hertz>) -> Self {
+        self.i2c_clk_hz(freq.into().0)
+    }
 
-        self.target_i2c_clk = NonZeroU32::new(freq_hz);
+    /// Sets the desired frequency for the I2C-CLK clock in Hz, like `i2c_clk` but usable in
+    /// a const
+    pub const fn i2c_clk_hz(mut self, freq: u32) -> Self {
+        self.target_i2c_clk = NonZeroU32::new(freq);
 
         self
     }
 
     /// Sets the desired frequency for the SPI-CLK clock
-    pub fn spi_clk(mut self, freq: impl Into<Hertz>) -> Self {
-        let freq_hz = freq.into().0;
+    pub fn spi_clk(self, freq: impl Into<Hertz>) -> Self {
+        self.spi_clk_hz(freq.into().0)
+    }
 
-        self.target_spi_clk = NonZeroU32::new(freq_hz);
+    /// Sets the desired frequency for the SPI-CLK clock in Hz, like `spi_clk` but usable in
+    /// a const
+    pub const fn spi_clk_hz(mut self, freq: u32) -> Self {
+        self.target_spi_clk = NonZeroU32::new(freq);
 
         self
     }
 
     /// Sets the desired frequency for the UART-CLK clock
-    pub fn uart_clk(mut self, freq: impl Into<Hertz>) -> Self {
-        let freq_hz = freq.into().0;
+    pub fn uart_clk(self, freq: impl Into<Hertz>) -> Self {
+        self.uart_clk_hz(freq.into().0)
+    }
+
+    /// Sets the desired frequency for the UART-CLK clock in Hz, like `uart_clk` but usable in
+    /// a const
+    pub const fn uart_clk_hz(mut self, freq: u32) -> Self {
+        self.target_uart_clk = NonZeroU32::new(freq);
+
+        self
+    }
+
+    /// Sets the tick rate of the RISC-V machine timer (`mtime`), which is divided from the bus
+    /// clock by 1 to 131072. The machine timer keeps the setting of the bootloader otherwise, see
+    /// `Clocks::mtimer_clk`.
+    pub fn mtimer_clk(self, freq: impl Into<Hertz>) -> Self {
+        self.mtimer_clk_hz(freq.into().0)
+    }
 
-        self.target_uart_clk = NonZeroU32::new(freq_hz);
+    /// Sets the tick rate of the machine timer in Hz, like `mtimer_clk` but usable in a const
+    pub const fn mtimer_clk_hz(mut self, freq: u32) -> Self {
+        self.target_mtimer_clk = NonZeroU32::new(freq);
+
+        self
+    }
+
+    /// Sets the source of the UART-CLK clock. Defaults to the 160MHz PLL output if the PLL is
+    /// enabled, and to the system clock otherwise
+    pub const fn uart_clk_src(mut self, source: UartClkSrc) -> Self {
+        self.uart_clk_src = Some(source);
+
+        self
+    }
+
+    /// Picks the UART-CLK clock, from the sources available and the dividers, which reaches the
+    /// given baudrate with the least error. The serial driver takes the clock from the frozen
+    /// [Clocks], so this is only needed for high baudrates like 2 or 3 Mbaud.
+    ///
+    /// Ignored if the UART-CLK clock is set with `uart_clk`.
+    pub fn uart_baudrate(self, baudrate: impl Into<Baud>) -> Self {
+        self.uart_baudrate_bps(baudrate.into().0)
+    }
+
+    /// Picks the UART-CLK clock for a baudrate in bit/s, like `uart_baudrate` but usable in a
+    /// const
+    pub const fn uart_baudrate_bps(mut self, baudrate: u32) -> Self {
+        self.target_baudrate = NonZeroU32::new(baudrate);
 
         self
     }
 
     /// Enables PLL clock source, using external XTAL frequency provided
-    pub fn use_pll(mut self, freq: impl Into<Hertz>) -> Self {
-        self.pll_xtal_freq = Some(freq.into().0);
+    ///
+    /// Supported crystals: 24MHz, 26MHz, 32MHz, 38.4MHz and 40MHz
+    pub fn use_pll(self, freq: impl Into<Hertz>) -> Self {
+        self.use_pll_hz(freq.into().0)
+    }
+
+    /// Enables PLL clock source, with the external XTAL frequency in Hz, like `use_pll` but usable
+    /// in a const
+    pub const fn use_pll_hz(mut self, freq: u32) -> Self {
+        self.pll_xtal_freq = Some(freq);
+
+        self
+    }
+
+    /// Runs the system clock straight from the external XTAL, with the frequency provided,
+    /// instead of the PLL or the internal RC oscillator
+    pub fn use_xtal(self, freq: impl Into<Hertz>) -> Self {
+        self.use_xtal_hz(freq.into().0)
+    }
+
+    /// Runs the system clock straight from the external XTAL, with the frequency in Hz, like
+    /// `use_xtal` but usable in a const
+    pub const fn use_xtal_hz(mut self, freq: u32) -> Self {
+        self.root_xtal_freq = Some(freq);
+
+        self
+    }
+
+    /// Clocks the RTC from an external 32.768kHz crystal instead of the internal RC oscillator.
+    /// `freeze` waits up to a second for the crystal to start, and falls back to the RC
+    /// oscillator if it doesn't, see `Clocks::clk_32k`.
+    pub const fn use_xtal32k(mut self) -> Self {
+        self.xtal32k = true;
+
+        self
+    }
+
+    /// Keeps the internal RC oscillators at their reset trim, instead of applying the factory
+    /// trim from efuse
+    pub const fn skip_oscillator_trim(mut self) -> Self {
+        self.trim_oscillators = false;
 
         self
     }
@@ -168,13 +716,145 @@ impl Strict {
     /// Set the system clock frequency (fclk/hclk)
     ///
     /// Supported frequencies:
-    ///   `32_000_000`, `48_000_000`, `80_000_000`, `120_000_000`, `160_000_000`
-    pub fn sys_clk(mut self, freq: SysclkFreq) -> Self {
+    ///   `32_000_000`, `48_000_000`, `80_000_000`, `120_000_000`, `160_000_000`, `192_000_000`
+    pub const fn sys_clk(mut self, freq: SysclkFreq) -> Self {
         self.sysclk = freq;
 
         self
     }
 
+    /// Divides the root clock by `div` (1 to 256) for the system clock (fclk/hclk), instead of
+    /// the divider implied by `sys_clk`
+    pub const fn hclk_div(mut self, div: u32) -> Self {
+        self.hclk_div = Some(div);
+
+        self
+    }
+
+    /// Divides the system clock by `div` (1 to 256) for the bus clock (bclk). By default the
+    /// bus clock is kept at or below 80MHz.
+    pub const fn bclk_div(mut self, div: u32) -> Self {
+        self.bclk_div = Some(div);
+
+        self
+    }
+
+    /// Clocks the serial flash from `source` divided by `div` (1 to 8). By default the flash
+    /// clock set up by the bootloader is kept.
+    pub const fn flash_clk(mut self, source: FlashClk, div: u32) -> Self {
+        self.flash_clk = Some((source, div));
+
+        self
+    }
+
+    /// Checks the configuration without touching the hardware, so invalid configurations can be
+    /// rejected at compile time. `freeze` panics on the same configurations.
+    ///
+    /// ## Example
+    /// ```rust
+    /// const CLOCKS: Strict = Strict::new()
+    ///     .use_pll_hz(40_000_000)
+    ///     .sys_clk(SysclkFreq::Pll160Mhz)
+    ///     .uart_clk_hz(UART_PLL_FREQ);
+    /// const _: () = assert!(CLOCKS.validate().is_ok());
+    ///
+    /// let clocks = CLOCKS.freeze(&mut parts.clk_cfg);
+    /// ```
+    // `RangeInclusive::contains` and `Option::unwrap_or` aren't const
+    #[allow(clippy::manual_range_contains)]
+    pub const fn validate(&self) -> Result<(), Error> {
+        let pll_enabled = match self.pll_xtal_freq {
+            Some(0) | None => false,
+            Some(24_000_000) | Some(26_000_000) | Some(32_000_000) | Some(38_400_000)
+            | Some(40_000_000) => true,
+            Some(_) => return Err(Error::Xtal),
+        };
+        if !pll_enabled && !matches!(self.sysclk, SysclkFreq::Rc32Mhz) {
+            return Err(Error::Sysclk);
+        }
+
+        let (root_clk, implied_hclk_div) = match self.root_xtal_freq {
+            Some(_) if pll_enabled => return Err(Error::Sysclk),
+            Some(0) => return Err(Error::Xtal),
+            Some(freq) => (freq, 1),
+            None if pll_enabled => self.sysclk.pll_tap(),
+            None => (RC32M, 1),
+        };
+        let xtal_enabled = pll_enabled || self.root_xtal_freq.is_some();
+        if self.power_down_pll && xtal_enabled {
+            return Err(Error::Xtal);
+        }
+
+        let hclk_div = match self.hclk_div {
+            Some(div) => div,
+            None => implied_hclk_div,
+        };
+        if hclk_div < 1 || hclk_div > 256 {
+            return Err(Error::Hclk);
+        }
+        let sysclk_freq = root_clk / hclk_div;
+
+        let bclk_div = match self.bclk_div {
+            Some(div) => div,
+            None => (sysclk_freq - 1) / MAX_BCLK + 1,
+        };
+        if bclk_div < 1 || bclk_div > 256 {
+            return Err(Error::Bclk);
+        }
+        let bclk = sysclk_freq / bclk_div;
+
+        // A UART clock picked for a baudrate is always reachable
+        let uart_src_clk = match self.uart_clk_src {
+            Some(UartClkSrc::Pll160Mhz) if !pll_enabled => return Err(Error::UartClk),
+            Some(UartClkSrc::Pll160Mhz) => UART_PLL_FREQ,
+            Some(UartClkSrc::Fclk) => sysclk_freq,
+            None if pll_enabled => UART_PLL_FREQ,
+            None => sysclk_freq,
+        };
+        if let Some(uart_clk) = self.target_uart_clk {
+            let div = uart_src_clk / uart_clk.get();
+            if div < 1 || div > 7 || div * uart_clk.get() != uart_src_clk {
+                return Err(Error::UartClk);
+            }
+        }
+
+        let spi_clk_div = match self.target_spi_clk {
+            Some(spi_clk) => bclk / spi_clk.get(),
+            None => bclk / 32_000_000,
+        };
+        if spi_clk_div < 1 || spi_clk_div > 0b100000 {
+            return Err(Error::SpiClk);
+        }
+
+        let i2c_clk_div = match self.target_i2c_clk {
+            Some(i2c_clk) => bclk / i2c_clk.get(),
+            None => bclk / 32_000_000,
+        };
+        if i2c_clk_div < 1 || i2c_clk_div > 255 {
+            return Err(Error::I2cClk);
+        }
+
+        if let Some(mtimer_clk) = self.target_mtimer_clk {
+            let div = bclk / mtimer_clk.get();
+            if div < 1 || div > MTIMER_MAX_DIV || div * mtimer_clk.get() != bclk {
+                return Err(Error::MtimerClk);
+            }
+        }
+
+        if let Some((source, div)) = self.flash_clk {
+            let running = match source {
+                FlashClk::Bclk => true,
+                FlashClk::Xtal => xtal_enabled,
+                _ => pll_enabled,
+            };
+            if !running || div < 1 || div > 8 {
+                return Err(Error::FlashClk);
+            }
+        }
+
+        Ok(())
+    }
+
     /// Calculate and balance clock registers to configure into the given clock value.
     /// If accurate value is not possible, this function panics.
     ///
@@ -188,51 +868,72 @@ impl Strict {
     /// If strictly accurate value of given `ck_sys` etc. is not reachable, this function
     /// panics.
     pub fn freeze(self, _clk_cfg: &mut ClkCfg) -> Clocks {
+        if let Err(error) = self.validate() {
+            panic!("Invalid clock configuration: {:?}", error);
+        }
+
         // Default to not using the PLL, and selecting the internal RC oscillator if nothing selected
         let pll_xtal_freq = self.pll_xtal_freq.unwrap_or(0);
         let pll_enabled = pll_xtal_freq != 0;
         let sysclk = self.sysclk;
 
-        // If sysclk isn't 32Mhz but PLL isn't enabled, panic
-        assert!(pll_enabled || sysclk == SysclkFreq::Rc32Mhz);
+        // Trim the RC oscillators before anything is derived from them
+        if self.trim_oscillators {
+            trim_rc_oscillators();
+        }
 
-        // If PLL is available we'll be using the PLL_160Mhz clock, otherwise sysclk
-        let uart_clk_src = if pll_enabled {
-            UART_PLL_FREQ
-        } else {
-            sysclk as u32
-        };
+        let xtal_freq = self.root_xtal_freq.or(self.pll_xtal_freq);
 
-        // UART config
-        let uart_clk = self
-            .target_uart_clk
-            .map(|f| f.get())
-            .unwrap_or(uart_clk_src);
+        // The root clock, and the divider for the system clock implied by its selection
+        let (root_clk, implied_hclk_div) = match self.root_xtal_freq {
+            Some(freq) => (freq, 1),
+            None if pll_enabled => sysclk.pll_tap(),
+            None => (RC32M, 1),
+        };
 
-        let uart_clk_div = {
-            let ans = uart_clk_src / uart_clk;
+        let hclk_div = self.hclk_div.unwrap_or(implied_hclk_div);
+        let sysclk_freq = root_clk / hclk_div;
+        let bclk_div = self.bclk_div.unwrap_or((sysclk_freq - 1) / MAX_BCLK + 1);
 
-            if !(1..=7).contains(&ans) || ans * uart_clk != uart_clk_src {
-                panic!("unreachable uart_clk")
-            }
+        // UART config
+        // If PLL is available we'll be using the PLL_160Mhz clock by default, otherwise sysclk
+        let uart_sources: &[UartClkSrc] = match self.uart_clk_src {
+            Some(ref source) => core::slice::from_ref(source),
+            None if pll_enabled => &[UartClkSrc::Pll160Mhz, UartClkSrc::Fclk],
+            None => &[UartClkSrc::Fclk],
+        };
+        let uart_src_freq = |source: UartClkSrc| match source {
+            UartClkSrc::Pll160Mhz => UART_PLL_FREQ,
+            UartClkSrc::Fclk => sysclk_freq,
+        };
 
-            ans as u8
+        let (uart_clk_src, uart_clk) = match (self.target_uart_clk, self.target_baudrate) {
+            (Some(uart_clk), _) => (uart_sources[0], uart_clk.get()),
+            (None, Some(baud)) => best_uart_clk(uart_sources, uart_src_freq, baud.get()),
+            (None, None) => (uart_sources[0], uart_src_freq(uart_sources[0])),
         };
+        let uart_src_clk = uart_src_freq(uart_clk_src);
+
+        let uart_clk_div = (uart_src_clk / uart_clk) as u8;
 
         // Enable system clock, PLL + crystal if required
         // omit if settings match boot defaults
-        if sysclk != SysclkFreq::Pll160Mhz || pll_xtal_freq != 40_000_000 {
+        if let Some(freq) = self.root_xtal_freq {
+            glb_set_system_clk_xtal(freq);
+        } else if sysclk != SysclkFreq::Pll160Mhz || pll_xtal_freq != 40_000_000 {
             match sysclk {
                 SysclkFreq::Rc32Mhz => glb_set_system_clk_rc32(),
-                _ => glb_set_system_clk_pll(sysclk as u32, pll_xtal_freq),
+                _ => glb_set_system_clk_pll(sysclk.pll_tap().0, pll_xtal_freq),
             };
         }
+        glb_set_system_clk_div((hclk_div - 1) as u8, (bclk_div - 1) as u8);
+        system_core_clock_set(sysclk_freq);
 
-        // If PLL is enabled, use that for the UART base clock
-        // Otherwise, use sysclk as the UART clock
-        unsafe { &*pac::HBN::ptr() }
-            .hbn_glb
-            .modify(|_, w| w.hbn_uart_clk_sel().bit(pll_enabled));
+        // Select the UART base clock, the PLL or sysclk
+        unsafe { &*pac::HBN::ptr() }.hbn_glb.modify(|_, w| {
+            w.hbn_uart_clk_sel()
+                .bit(uart_clk_src == UartClkSrc::Pll160Mhz)
+        });
 
         // Write UART clock divider
         unsafe { &*pac::GLB::ptr() }.clk_cfg2.modify(|_, w| unsafe {
@@ -249,14 +950,8 @@ impl Strict {
             .unwrap_or(32_000_000u32);
 
         // SPI Clock Divider (BUS_CLK/(N+1)), default BUS_CLK/4
-        let bus_clock = calculate_bus_clock();
-        let spi_clk_div = bus_clock.0 / spi_clk;
-
-        if spi_clk_div == 0 || spi_clk_div > 0b100000 {
-            panic!("Unreachable SPI_CLK");
-        }
-
-        let spi_clk_div = ((spi_clk_div - 1) & 0b11111) as u8;
+        let bus_clock = calculate_bus_clock(xtal_freq.unwrap_or(0));
+        let spi_clk_div = ((bus_clock.0 / spi_clk - 1) & 0b11111) as u8;
         // Write SPI clock divider
         unsafe { &*pac::GLB::ptr() }
             .clk_cfg3
@@ -269,24 +964,46 @@ impl Strict {
             .unwrap_or(32_000_000u32);
 
         // I2C Clock Divider (BUS_CLK/(N+1)), default BUS_CLK/255
-        let i2c_clk_div = bus_clock.0 / i2c_clk;
-
-        if i2c_clk_div == 0 || i2c_clk_div > 255 {
-            panic!("Unreachable I2C_CLK");
-        }
-
-        let i2c_clk_div = ((i2c_clk_div - 1) & 0xff) as u8;
+        let i2c_clk_div = ((bus_clock.0 / i2c_clk - 1) & 0xff) as u8;
         // Write I2C clock divider
         unsafe { &*pac::GLB::ptr() }
             .clk_cfg3
             .modify(|_, w| unsafe { w.i2c_clk_en().set_bit().i2c_clk_div().bits(i2c_clk_div) });
 
+        // Flash config, keeping the boot setting unless one is given
+        if let Some((source, div)) = self.flash_clk {
+            glb_set_flash_clk(source, div);
+        }
+        let flash_clk = read_flash_clk(xtal_freq, pll_enabled, bus_clock);
+
+        // Machine timer config, keeping the boot setting unless one is given
+        if let Some(mtimer_clk) = self.target_mtimer_clk {
+            glb_set_mtimer_clk_div(bus_clock.0 / mtimer_clk.get());
+        }
+
+        if self.power_down_pll {
+            glb_power_off_pll();
+            aon_power_off_xtal();
+        }
+
+        let clk_32k = if self.xtal32k {
+            hbn_power_on_xtal32k()
+        } else {
+            Clk32k::Rc32k
+        };
+
         Clocks {
-            sysclk: Hertz(sysclk as u32),
+            sysclk: Hertz(sysclk_freq),
+            bclk: bus_clock,
             uart_clk: Hertz(uart_clk),
+            uart_clk_src,
             spi_clk: Hertz(spi_clk),
             i2c_clk: Hertz(i2c_clk),
-            _xtal_freq: Some(Hertz(pll_xtal_freq)),
+            flash_clk,
+            clk_32k,
+            xtal_freq: xtal_freq.map(Hertz),
+            xclk: read_xclk(xtal_freq),
+            mtimer_clk: read_mtimer_clk(bus_clock),
             pll_enable: pll_enabled,
         }
     }
@@ -298,8 +1015,492 @@ impl Default for Strict {
     }
 }
 
-/// Gets the current bus clock rate
-fn calculate_bus_clock() -> Hertz {
+/// Enables the clock of the peripheral `P`, e.g. `enable::<Uart0>()`
+pub fn enable<P: ClockGate>() {
+    modify_reg(glb(GLB_CGEN_CFG1), |r| r | 1 << P::BIT);
+}
+
+/// Gates the clock of the peripheral `P` to save power. It keeps its configuration, but can't
+/// be accessed until it's enabled again.
+pub fn disable<P: ClockGate>() {
+    modify_reg(glb(GLB_CGEN_CFG1), |r| r & !(1 << P::BIT));
+}
+
+/// Whether the clock of the peripheral `P` is enabled
+pub fn is_enabled<P: ClockGate>() -> bool {
+    read_reg(glb(GLB_CGEN_CFG1)) & 1 << P::BIT != 0
+}
+
+/// The bit of the clock gate of `P` in `cgen_cfg1`
+pub(crate) fn gate_bit<P: ClockGate>() -> u32 {
+    1 << P::BIT
+}
+
+/// Measures the internal 32K RC oscillator against the crystal, e.g. to compensate the drift of
+/// the RTC with `Rtc::set_tick_rate`. Takes about 100ms, with interrupts disabled.
+///
+/// The core clock is the reference, so it has to run from the crystal or the PLL, and the RTC
+/// has to be clocked from the RC oscillator.
+///
+/// # Panics
+///
+/// If the core clock runs from the RC oscillator, or the 32K clock is the crystal.
+pub fn measure_rc32k(clocks: &Clocks) -> Hertz {
+    let cycles = count_rc32k_cycles(clocks);
+    Hertz((MEASURE_32K_TICKS as u64 * clocks.sysclk.0 as u64 / cycles) as u32)
+}
+
+/// Measures how far the internal 32K RC oscillator is off 32.768kHz, in ppm, positive when it
+/// runs fast, e.g. for `rtc::set_drift_ppm`. Like [measure_rc32k], but to a fraction of a ppm
+/// instead of 1Hz, which is about 30ppm.
+///
+/// # Panics
+///
+/// If the core clock runs from the RC oscillator, or the 32K clock is the crystal.
+pub fn measure_rc32k_ppm(clocks: &Clocks) -> i32 {
+    let cycles = count_rc32k_cycles(clocks);
+    // The cycles expected at 32.768kHz, in millionths
+    let nominal = MEASURE_32K_TICKS as u64 * clocks.sysclk.0 as u64 * 1_000_000 / 32_768;
+    (nominal / cycles) as i32 - 1_000_000
+}
+
+/// Core clock cycles in `MEASURE_32K_TICKS` ticks of the 32K RC oscillator
+fn count_rc32k_cycles(clocks: &Clocks) -> u64 {
+    if read_system_clk().0 == ROOT_CLK_SEL_RC32M {
+        panic!("The core clock isn't derived from the crystal");
+    }
+    if clocks.clk_32k != Clk32k::Rc32k {
+        panic!("The 32K clock isn't the RC oscillator");
+    }
+
+    count_cycles_in_32k_ticks(MEASURE_32K_TICKS)
+}
+
+/// Measures the internal 32MHz RC oscillator against the 32.768kHz crystal. Takes about 100ms,
+/// with interrupts disabled.
+///
+/// The RC oscillator is measured through the core clock, so the system clock has to run from
+/// it, e.g. with `Strict::low_power`, and the crystal has to be enabled with
+/// `Strict::use_xtal32k`.
+///
+/// # Panics
+///
+/// If the core clock doesn't run from the RC oscillator, or the 32K clock isn't the crystal.
+pub fn measure_rc32m(clocks: &Clocks) -> Hertz {
+    let (root_sel, _, hclk_div, _) = read_system_clk();
+    if root_sel != ROOT_CLK_SEL_RC32M {
+        panic!("The core clock isn't the RC oscillator");
+    }
+    if clocks.clk_32k != Clk32k::Xtal32k {
+        panic!("The 32K clock isn't the crystal");
+    }
+
+    let cycles = count_cycles_in_32k_ticks(MEASURE_32K_TICKS);
+    Hertz((cycles * hclk_div as u64 * XTAL32K as u64 / MEASURE_32K_TICKS as u64) as u32)
+}
+
+/// Peripherals with a clock gate - DO NOT IMPLEMENT THIS TRAIT
+pub trait ClockGate: private::Sealed {}
+
+macro_rules! impl_clock_gate {
+    ($($name: ident: $bit: literal, $doc: literal,)+) => {
+        $(
+            #[doc = $doc]
+            pub struct $name;
+
+            impl ClockGate for $name {}
+
+            impl private::Sealed for $name {
+                const BIT: u32 = $bit;
+            }
+        )+
+
+        /// The bits of all clock gates in `cgen_cfg1`
+        pub(crate) const CLOCK_GATES: u32 = $(1 << $bit)|+;
+    };
+}
+
+// see components\bl602\bl602_std\bl602_std\StdDriver\Inc\bl602_glb.h, GLB_AHB_SLAVE1_Type
+impl_clock_gate! {
+    Gpip: 2, "The ADC and DAC (clock gate)",
+    Dma: 12, "The DMA (clock gate)",
+    Uart0: 16, "UART0 (clock gate)",
+    Uart1: 17, "UART1 (clock gate)",
+    Spi: 18, "The SPI (clock gate)",
+    I2c: 19, "The I2C (clock gate)",
+    Pwm: 20, "The PWM (clock gate)",
+    Timer: 21, "The timers and the watchdog (clock gate)",
+    Ir: 22, "The IR remote (clock gate)",
+    Checksum: 23, "The checksum engine (clock gate)",
+}
+
+mod private {
+    pub trait Sealed {
+        /// The bit in `cgen_cfg1`
+        const BIT: u32;
+    }
+}
+
+/// System clock selection for [set_system_clock]
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SysClk {
+    /// The internal 32MHz RC oscillator
+    Rc32m,
+    /// The external crystal, which has to be powered on by `Strict::use_pll` or
+    /// `Strict::use_xtal`
+    Xtal,
+    /// A PLL tap, the PLL has to be enabled by `Strict::use_pll`
+    Pll(SysclkFreq),
+}
+
+/// Switches the system clock (fclk/hclk) at runtime, and updates `clocks` to match.
+///
+/// The bus clock is kept at or below 80MHz. The dividers of the UART, SPI, I2C and machine timer
+/// clocks are derived again so they keep their frequencies, and a flash clocked from the bus clock is
+/// divided so it doesn't run faster than before. The switch itself runs from RAM, as the flash
+/// can't be read while its clock changes.
+///
+/// Peripherals should be idle while switching, and timers, PWM channels and the watchdog
+/// clocked from fclk or bclk need to be set up again.
+///
+/// # Panics
+///
+/// If the crystal or the PLL isn't running for `sysclk`, or the UART, SPI, I2C, machine timer or
+/// flash clock can't be kept.
+pub fn set_system_clock(clocks: &mut Clocks, sysclk: SysClk) {
+    let uart_clk_src = clocks.uart_clk_src;
+    switch_clocks(clocks, sysclk, uart_clk_src, false);
+}
+
+/// Clock profiles for [set_profile]
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Profile {
+    /// Everything runs from the internal 32MHz RC oscillator, with the PLL and the crystal
+    /// powered down. The UART is clocked from fclk and the flash from the bus clock.
+    LowPower,
+    /// The system clock runs from a PLL tap, powering on the crystal and the PLL if needed. The
+    /// UART goes back to the 160MHz PLL output if its clock can be divided from it.
+    Performance {
+        /// The frequency of the crystal in Hz, see `Strict::use_pll`
+        xtal_freq: u32,
+        /// The system clock
+        sysclk: SysclkFreq,
+    },
+}
+
+/// Switches between the low power and the performance clock profile at runtime, and updates
+/// `clocks` to match. The low power profile can also be selected at boot with
+/// `Strict::low_power()`.
+///
+/// Like [set_system_clock], the UART, SPI and I2C clocks keep their frequencies, and the
+/// peripherals should be idle while switching. Timers, PWM channels, the watchdog, the ADC and
+/// the DAC need to be set up again, as their clocks may change or stop with the PLL.
+///
+/// # Panics
+///
+/// If the crystal frequency isn't supported by the PLL, or the UART, SPI, I2C or flash clock
+/// can't be kept.
+pub fn set_profile(clocks: &mut Clocks, profile: Profile) {
+    match profile {
+        Profile::LowPower => {
+            switch_clocks(clocks, SysClk::Rc32m, UartClkSrc::Fclk, true);
+            glb_power_off_pll();
+            aon_power_off_xtal();
+
+            clocks.pll_enable = false;
+            clocks.xtal_freq = None;
+        }
+        Profile::Performance { xtal_freq, sysclk } => {
+            if !clocks.pll_enable {
+                // Nothing may run from the PLL while it's (re)started, not even a PLL the
+                // bootloader left running
+                switch_clocks(clocks, SysClk::Rc32m, UartClkSrc::Fclk, true);
+
+                aon_power_on_xtal().unwrap();
+                pds_power_on_pll_rom(xtal_freq);
+                let mut delay = McycleDelay::new(system_core_clock_get());
+                delay.delay_us(55);
+                pds_enable_pll_all_clks();
+                unsafe { &*pac::GLB::ptr() }
+                    .clk_cfg0
+                    .modify(|_, w| w.reg_pll_en().set_bit());
+
+                clocks.pll_enable = true;
+                clocks.xtal_freq = Some(Hertz(xtal_freq));
+            }
+
+            let uart_clk = clocks.uart_clk.0;
+            let uart_clk_src =
+                if UART_PLL_FREQ % uart_clk == 0 && (1..=7).contains(&(UART_PLL_FREQ / uart_clk)) {
+                    UartClkSrc::Pll160Mhz
+                } else {
+                    UartClkSrc::Fclk
+                };
+            switch_clocks(clocks, SysClk::Pll(sysclk), uart_clk_src, false);
+        }
+    }
+}
+
+/// Runs `f`, which puts the chip to sleep, from RC32M with the PLL and the crystal powered down,
+/// then powers them on again and switches back to the system and flash clocks of `clocks`.
+/// The peripherals are idle while sleeping, so their clocks aren't kept in between.
+pub(crate) fn around_sleep<R>(clocks: &Clocks, f: impl FnOnce() -> R) -> R {
+    let xtal_freq = match clocks.xtal_freq {
+        Some(freq) => freq.0,
+        None => return f(),
+    };
+
+    let (root_sel, pll_sel, hclk_div, bclk_div) = read_system_clk();
+    let flash_cfg = unsafe { &*pac::GLB::ptr() }.clk_cfg2.read().bits() & CLK_CFG2_SF_CLK_MASK;
+
+    // The flash runs from the undivided bus clock at 32MHz in between
+    let (sel, sel2) = FlashClk::Bclk.bits();
+    let flash_bclk = sel << CLK_CFG2_SF_CLK_SEL_SHIFT | sel2 << CLK_CFG2_SF_CLK_SEL2_SHIFT;
+    critical_section::with(|_| {
+        switch_system_clk(ROOT_CLK_SEL_RC32M, pll_sel, 1, 1, Some(flash_bclk));
+    });
+    system_core_clock_set(RC32M);
+    if clocks.pll_enable {
+        glb_power_off_pll();
+    }
+    aon_power_off_xtal();
+
+    let result = f();
+
+    aon_power_on_xtal().unwrap();
+    if clocks.pll_enable {
+        pds_power_on_pll_rom(xtal_freq);
+        let mut delay = McycleDelay::new(system_core_clock_get());
+        delay.delay_us(55);
+        pds_enable_pll_all_clks();
+        unsafe { &*pac::GLB::ptr() }
+            .clk_cfg0
+            .modify(|_, w| w.reg_pll_en().set_bit());
+    }
+    critical_section::with(|_| {
+        switch_system_clk(root_sel, pll_sel, hclk_div, bclk_div, Some(flash_cfg));
+    });
+    system_core_clock_set(clocks.sysclk.0);
+
+    result
+}
+
+/// Switches the system clock and the UART clock source, and moves the flash clock to the bus
+/// clock if `flash_to_bclk` is set
+fn switch_clocks(
+    clocks: &mut Clocks,
+    sysclk: SysClk,
+    uart_clk_src: UartClkSrc,
+    flash_to_bclk: bool,
+) {
+    let xtal_freq = clocks.xtal_freq.map(|freq| freq.0);
+    let (root_sel, pll_tap, hclk_div) = match sysclk {
+        SysClk::Rc32m | SysClk::Pll(SysclkFreq::Rc32Mhz) => (ROOT_CLK_SEL_RC32M, None, 1),
+        SysClk::Xtal => (ROOT_CLK_SEL_XTAL, None, 1),
+        SysClk::Pll(freq) => {
+            let (tap, div) = freq.pll_tap();
+            (ROOT_CLK_SEL_PLL, Some(tap), div)
+        }
+    };
+    let root_clk = match (root_sel, pll_tap) {
+        (ROOT_CLK_SEL_XTAL, _) => xtal_freq.expect("Crystal isn't running"),
+        (_, Some(tap)) if clocks.pll_enable => tap,
+        (_, Some(_)) => panic!("PLL isn't running"),
+        _ => RC32M,
+    };
+    let sysclk_freq = root_clk / hclk_div;
+    let bclk_div = (sysclk_freq - 1) / MAX_BCLK + 1;
+    let bclk = Hertz(sysclk_freq / bclk_div);
+
+    // A UART clocked from the PLL keeps running, one clocked from fclk is divided again
+    let uart_clk_div = if uart_clk_src == UartClkSrc::Pll160Mhz {
+        if !clocks.pll_enable {
+            panic!("unreachable uart_clk")
+        }
+        None
+    } else {
+        let uart_clk = clocks.uart_clk.0;
+        let div = sysclk_freq / uart_clk;
+        if !(1..=7).contains(&div) || div * uart_clk != sysclk_freq {
+            panic!("unreachable uart_clk")
+        }
+        Some(div)
+    };
+
+    let spi_clk_div = bclk.0 / clocks.spi_clk.0;
+    if spi_clk_div == 0 || spi_clk_div > 0b100000 {
+        panic!("Unreachable SPI_CLK");
+    }
+    let i2c_clk_div = bclk.0 / clocks.i2c_clk.0;
+    if i2c_clk_div == 0 || i2c_clk_div > 255 {
+        panic!("Unreachable I2C_CLK");
+    }
+
+    // A running machine timer keeps its tick rate
+    let mtimer_clk_div = clocks.mtimer_clk.map(|mtimer_clk| {
+        let div = bclk.0 / mtimer_clk.0;
+        if !(1..=MTIMER_MAX_DIV).contains(&div) || div * mtimer_clk.0 != bclk.0 {
+            panic!("Unreachable MTIMER_CLK");
+        }
+        div
+    });
+
+    let r = unsafe { &*pac::GLB::ptr() }.clk_cfg2.read().bits();
+    let flash_source = FlashClk::from_bits(
+        r >> CLK_CFG2_SF_CLK_SEL_SHIFT & CLK_CFG2_SF_CLK_SEL_MASK,
+        r >> CLK_CFG2_SF_CLK_SEL2_SHIFT & CLK_CFG2_SF_CLK_SEL_MASK,
+    );
+    // The new divider and source of a flash clocked from the bus clock
+    let flash_cfg = if flash_source == FlashClk::Bclk || flash_to_bclk {
+        let div = match clocks.flash_clk {
+            Some(flash_clk) => (bclk.0 - 1) / flash_clk.0 + 1,
+            None => 1,
+        };
+        if div > 8 {
+            panic!("Unreachable FLASH_CLK");
+        }
+        let (sel, sel2) = FlashClk::Bclk.bits();
+        Some(
+            (div - 1) << CLK_CFG2_SF_CLK_DIV_SHIFT
+                | sel << CLK_CFG2_SF_CLK_SEL_SHIFT
+                | sel2 << CLK_CFG2_SF_CLK_SEL2_SHIFT,
+        )
+    } else {
+        None
+    };
+
+    // For frequencies above 120Mhz we need 2 clocks to access internal rom
+    if sysclk_freq > 120_000_000 {
+        unsafe { &*pac::L1C::ptr() }
+            .l1c_config
+            .modify(|_, w| w.irom_2t_access().set_bit());
+    }
+
+    let pll_sel = match pll_tap {
+        Some(48_000_000) => 0,
+        Some(120_000_000) => 1,
+        Some(192_000_000) => 3,
+        _ => 2,
+    };
+    critical_section::with(|_| {
+        switch_system_clk(root_sel, pll_sel, hclk_div, bclk_div, flash_cfg);
+    });
+    system_core_clock_set(sysclk_freq);
+
+    unsafe { &*pac::HBN::ptr() }.hbn_glb.modify(|_, w| {
+        w.hbn_uart_clk_sel()
+            .bit(uart_clk_src == UartClkSrc::Pll160Mhz)
+    });
+
+    if let Some(div) = uart_clk_div {
+        unsafe { &*pac::GLB::ptr() }
+            .clk_cfg2
+            .modify(|_, w| unsafe { w.uart_clk_div().bits(div as u8 - 1) });
+    }
+    unsafe { &*pac::GLB::ptr() }.clk_cfg3.modify(|_, w| unsafe {
+        w.spi_clk_div()
+            .bits((spi_clk_div - 1) as u8)
+            .i2c_clk_div()
+            .bits((i2c_clk_div - 1) as u8)
+    });
+    if let Some(div) = mtimer_clk_div {
+        glb_set_mtimer_clk_div(div);
+    }
+
+    clocks.sysclk = Hertz(sysclk_freq);
+    clocks.bclk = bclk;
+    clocks.uart_clk_src = uart_clk_src;
+    clocks.spi_clk = bclk / spi_clk_div;
+    clocks.i2c_clk = bclk / i2c_clk_div;
+    clocks.flash_clk = read_flash_clk(xtal_freq, clocks.pll_enable, bclk);
+    clocks.xclk = read_xclk(xtal_freq);
+    clocks.mtimer_clk = read_mtimer_clk(bclk);
+}
+
+/// Switches the root clock, the PLL tap and the system clock dividers, and the divider and source
+/// of the flash clock if given. The root clock is the RC oscillator in between.
+fn switch_system_clk(
+    root_sel: u32,
+    pll_sel: u32,
+    hclk_div: u32,
+    bclk_div: u32,
+    flash_cfg: Option<u32>,
+) {
+    let cfg0 = pll_sel << CLK_CFG0_PLL_SEL_SHIFT
+        | (hclk_div - 1) << CLK_CFG0_HCLK_DIV_SHIFT
+        | (bclk_div - 1) << CLK_CFG0_BCLK_DIV_SHIFT;
+    ram_switch_system_clk(root_sel, cfg0, flash_cfg.is_some(), flash_cfg.unwrap_or(0));
+}
+
+/// Does the switch of `switch_system_clk`, with the fields of `clk_cfg0` already shifted into
+/// `cfg0`.
+// This runs from RAM while the flash clock is gated, so it must not call into flash. The
+// registers are accessed and the delays spent with inline assembly, which can't be outlined into
+// calls, and the arguments are plain integers.
+#[inline(never)]
+#[link_section = ".data.bl602_hal.ram_switch_system_clk"]
+fn ram_switch_system_clk(root_sel: u32, cfg0: u32, switch_flash: bool, flash_cfg: u32) {
+    const HBN_GLB_ADDRESS: usize = HBN_BASE + HBN_GLB;
+    const CLK_CFG0_ADDRESS: usize = GLB_BASE + GLB_CLK_CFG0;
+    const CLK_CFG2_ADDRESS: usize = GLB_BASE + GLB_CLK_CFG2;
+    const BCLK_DIS_ADDRESS: usize = GLB_BASE + GLB_BCLK_DIS;
+    const CLK_CFG0_MASK: u32 = CLK_CFG0_PLL_SEL_MASK << CLK_CFG0_PLL_SEL_SHIFT
+        | CLK_CFG0_DIV_MASK << CLK_CFG0_HCLK_DIV_SHIFT
+        | CLK_CFG0_DIV_MASK << CLK_CFG0_BCLK_DIV_SHIFT;
+
+    unsafe {
+        // Run from RC32M while the PLL tap and the dividers change
+        let hbn_glb: u32 = ram_read_reg!(HBN_GLB_ADDRESS);
+        ram_write_reg!(HBN_GLB_ADDRESS, hbn_glb & !HBN_GLB_ROOT_CLK_SEL_MASK);
+
+        if switch_flash {
+            // The flash clock is gated while its source changes
+            let r = ram_read_reg!(CLK_CFG2_ADDRESS) & !CLK_CFG2_SF_CLK_EN;
+            ram_write_reg!(CLK_CFG2_ADDRESS, r);
+            let r = r & !CLK_CFG2_SF_CLK_MASK | flash_cfg;
+            ram_write_reg!(CLK_CFG2_ADDRESS, r);
+            ram_write_reg!(CLK_CFG2_ADDRESS, r | CLK_CFG2_SF_CLK_EN);
+        }
+
+        let r = ram_read_reg!(CLK_CFG0_ADDRESS);
+        ram_write_reg!(CLK_CFG0_ADDRESS, r & !CLK_CFG0_MASK | cfg0);
+        ram_write_reg!(BCLK_DIS_ADDRESS, 1u32);
+        ram_write_reg!(BCLK_DIS_ADDRESS, 0u32);
+        ram_delay!();
+
+        // Bit 0 selects the crystal or RC32M for the other clocks, which switching to the PLL
+        // keeps as it was before the switch, like the SDK does
+        let root_sel = if root_sel == ROOT_CLK_SEL_PLL {
+            ROOT_CLK_SEL_PLL | hbn_glb & ROOT_CLK_SEL_XTAL
+        } else {
+            root_sel
+        };
+        let r = ram_read_reg!(HBN_GLB_ADDRESS);
+        ram_write_reg!(HBN_GLB_ADDRESS, r & !HBN_GLB_ROOT_CLK_SEL_MASK | root_sel);
+        ram_delay!();
+    }
+}
+
+/// Gets the current root clock selection, PLL tap, and system and bus clock dividers
+fn read_system_clk() -> (u32, u32, u32, u32) {
+    let root_sel = unsafe { &*pac::HBN::ptr() }
+        .hbn_glb
+        .read()
+        .hbn_root_clk_sel()
+        .bits() as u32;
+    let cfg0 = unsafe { &*pac::GLB::ptr() }.clk_cfg0.read();
+    (
+        root_sel,
+        cfg0.reg_pll_sel().bits() as u32,
+        cfg0.reg_hclk_div().bits() as u32 + 1,
+        cfg0.reg_bclk_div().bits() as u32 + 1,
+    )
+}
+
+/// Gets the current bus clock rate, with the crystal running at `xtal_freq`
+fn calculate_bus_clock(xtal_freq: u32) -> Hertz {
     let root_clk_sel = unsafe { &*pac::GLB::ptr() }
         .clk_cfg0
         .read()
@@ -323,7 +1524,7 @@ fn calculate_bus_clock() -> Hertz {
 
     let root = match root_clk_sel {
         0 => 32_000_000_u32.Hz(),
-        1 => 40_000_000_u32.Hz(),
+        1 => xtal_freq.Hz(),
         _ => match pll_clk_sel {
             0 => 48_000_000_u32.Hz(),
             1 => 120_000_000_u32.Hz(),
@@ -389,8 +1590,6 @@ fn pds_select_xtal_as_pll_ref() {
 }
 
 // This is a reference implementation of `PDS_Power_Off_PLL`.
-// It is currently unused.
-#[allow(dead_code)]
 fn pds_power_off_pll() {
     unsafe { &*pac::PDS::ptr() }
         .pu_rst_clkpll
@@ -568,6 +1767,21 @@ fn pds_power_on_pll(freq: u32) {
         .modify(|_, w| w.clkpll_sdm_reset().clear_bit());
 }
 
+/// Disables the PLL and powers it down. Nothing may be clocked from it anymore.
+fn glb_power_off_pll() {
+    unsafe { &*pac::GLB::ptr() }
+        .clk_cfg0
+        .modify(|_, w| w.reg_pll_en().clear_bit());
+    pds_power_off_pll();
+}
+
+/// Powers down the crystal. Nothing may be clocked from it anymore.
+fn aon_power_off_xtal() {
+    unsafe { &*pac::AON::ptr() }
+        .rf_top_aon
+        .modify(|_, w| w.pu_xtal_aon().clear_bit().pu_xtal_buf_aon().clear_bit());
+}
+
 fn aon_power_on_xtal() -> Result<(), &'static str> {
     unsafe { &*pac::AON::ptr() }
         .rf_top_aon
@@ -596,6 +1810,131 @@ fn aon_power_on_xtal() -> Result<(), &'static str> {
     }
 }
 
+/// Applies the factory trim of the RC oscillators from efuse, for each trim that's programmed
+/// and passes its parity check
+fn trim_rc_oscillators() {
+    let trim = read_reg(EF_DATA_BASE + EF_RC_TRIM);
+    let valid = |code: u32, enabled: u32, parity: u32| {
+        trim & enabled != 0 && (code.count_ones() & 1 == 1) == (trim & parity != 0)
+    };
+
+    let rc32m = trim >> EF_RC32M_TRIM_SHIFT & RC32M_CODE_FR_EXT_MASK;
+    if valid(rc32m, EF_RC32M_EN, EF_RC32M_PARITY) {
+        modify_reg(pds(PDS_RC32M_CTRL0), |r| {
+            r & !(RC32M_CODE_FR_EXT_MASK << RC32M_CODE_FR_EXT_SHIFT)
+                | rc32m << RC32M_CODE_FR_EXT_SHIFT
+                | RC32M_EXT_CODE_EN
+        });
+    }
+
+    let rc32k = trim >> EF_RC32K_TRIM_SHIFT & RC32K_CODE_FR_EXT_MASK;
+    if valid(rc32k, EF_RC32K_EN, EF_RC32K_PARITY) {
+        modify_reg(hbn(HBN_RC32K_CTRL0), |r| {
+            r & !(RC32K_CODE_FR_EXT_MASK << RC32K_CODE_FR_EXT_SHIFT)
+                | rc32k << RC32K_CODE_FR_EXT_SHIFT
+                | RC32K_EXT_CODE_EN
+        });
+    }
+
+    // Let the oscillators settle
+    McycleDelay::new(system_core_clock_get()).delay_us(2);
+}
+
+/// Powers on the 32.768kHz crystal and selects it for the RTC once it runs at its frequency.
+/// Falls back to the RC oscillator if it doesn't start.
+fn hbn_power_on_xtal32k() -> Clk32k {
+    modify_reg(hbn(HBN_XTAL32K), |r| r | XTAL32K_PU | XTAL32K_PU_BUF);
+    hbn_set_f32k_sel(Clk32k::Xtal32k);
+
+    // The RTC counts the selected clock, run it while checking
+    let rtc_enabled = hbn_enable_rtc();
+
+    let mut delay = McycleDelay::new(system_core_clock_get());
+    let mut started = false;
+    for _ in 0..XTAL32K_CHECKS {
+        let start = hbn_rtc_ticks();
+        delay.delay_ms(50);
+        let ticks = hbn_rtc_ticks().wrapping_sub(start);
+        // Within 10% of the frequency
+        if ticks.abs_diff(XTAL32K_CHECK_TICKS) < XTAL32K_CHECK_TICKS / 10 {
+            started = true;
+            break;
+        }
+    }
+
+    if !rtc_enabled {
+        hbn_disable_rtc();
+    }
+    if started {
+        Clk32k::Xtal32k
+    } else {
+        hbn_set_f32k_sel(Clk32k::Rc32k);
+        modify_reg(hbn(HBN_XTAL32K), |r| r & !(XTAL32K_PU | XTAL32K_PU_BUF));
+        Clk32k::Rc32k
+    }
+}
+
+/// Gets the lower word of the RTC counter
+fn hbn_rtc_ticks() -> u32 {
+    let hbn = unsafe { &*pac::HBN::ptr() };
+    hbn.rtc_time_h
+        .modify(|r, w| unsafe { w.bits(r.bits() | RTC_TIME_H_LATCH) });
+    hbn.rtc_time_l.read().bits()
+}
+
+/// Starts the RTC counter, returns whether it was running already
+fn hbn_enable_rtc() -> bool {
+    let hbn_ctl = &unsafe { &*pac::HBN::ptr() }.hbn_ctl;
+    let enabled = hbn_ctl.read().rtc_ctl().bits() & RTC_CTL_EN != 0;
+    hbn_ctl.modify(|r, w| unsafe { w.rtc_ctl().bits(r.rtc_ctl().bits() | RTC_CTL_EN) });
+    enabled
+}
+
+/// Stops the RTC counter
+fn hbn_disable_rtc() {
+    unsafe { &*pac::HBN::ptr() }
+        .hbn_ctl
+        .modify(|r, w| unsafe { w.rtc_ctl().bits(r.rtc_ctl().bits() & !RTC_CTL_EN) });
+}
+
+/// Counts the core clock cycles in `ticks` periods of the 32K clock, running the RTC while
+/// counting. Interrupts are disabled meanwhile, so they don't delay the edges being seen.
+fn count_cycles_in_32k_ticks(ticks: u32) -> u64 {
+    let rtc_enabled = hbn_enable_rtc();
+
+    let cycles = critical_section::with(|_| {
+        // Start right after a tick
+        let previous = hbn_rtc_ticks();
+        let mut start_tick = hbn_rtc_ticks();
+        while start_tick == previous {
+            start_tick = hbn_rtc_ticks();
+        }
+        let start = McycleDelay::get_cycle_count();
+        while hbn_rtc_ticks().wrapping_sub(start_tick) < ticks {
+            core::hint::spin_loop();
+        }
+        McycleDelay::cycles_since(start)
+    });
+
+    if !rtc_enabled {
+        hbn_disable_rtc();
+    }
+    cycles
+}
+
+fn hbn_set_f32k_sel(source: Clk32k) {
+    let sel = match source {
+        Clk32k::Rc32k => 0,
+        Clk32k::Xtal32k => 1,
+    };
+    unsafe { &*pac::HBN::ptr() }.hbn_glb.modify(|r, w| unsafe {
+        w.bits(
+            r.bits() & !(HBN_GLB_F32K_SEL_MASK << HBN_GLB_F32K_SEL_SHIFT)
+                | sel << HBN_GLB_F32K_SEL_SHIFT,
+        )
+    });
+}
+
 fn hbn_set_root_clk_sel_pll() {
     unsafe { &*pac::HBN::ptr() }.hbn_glb.modify(|r, w| unsafe {
         w.hbn_root_clk_sel()
@@ -609,6 +1948,12 @@ fn hbn_set_root_clk_sel_rc32() {
         .modify(|_, w| unsafe { w.hbn_root_clk_sel().bits(0b00u8) });
 }
 
+fn hbn_set_root_clk_sel_xtal() {
+    unsafe { &*pac::HBN::ptr() }
+        .hbn_glb
+        .modify(|_, w| unsafe { w.hbn_root_clk_sel().bits(0b01u8) });
+}
+
 fn pds_enable_pll_all_clks() {
     unsafe { &*pac::PDS::ptr() }
         .clkpll_output_en
@@ -641,6 +1986,109 @@ fn glb_set_system_clk_rc32() {
         .modify(|_, w| w.pka_clk_sel().clear_bit());
 }
 
+/// Sets the system clock to run straight from the external crystal
+fn glb_set_system_clk_xtal(xtal_freq: u32) {
+    // Ensure clock is running off internal RC oscillator before changing anything else
+    glb_set_system_clk_rc32();
+
+    aon_power_on_xtal().unwrap();
+
+    hbn_set_root_clk_sel_xtal();
+    system_core_clock_set(xtal_freq);
+
+    let mut delay = McycleDelay::new(system_core_clock_get());
+    delay.delay_us(1);
+}
+
+/// Selects the serial flash clock, gating it while it's switched. Instructions can't be fetched
+/// from flash while its clock is gated, so the switch runs from RAM in `switch_system_clk`, which
+/// keeps the system clock as it is.
+fn glb_set_flash_clk(source: FlashClk, div: u32) {
+    let (sel, sel2) = source.bits();
+    let flash_cfg = (div - 1) << CLK_CFG2_SF_CLK_DIV_SHIFT
+        | sel << CLK_CFG2_SF_CLK_SEL_SHIFT
+        | sel2 << CLK_CFG2_SF_CLK_SEL2_SHIFT;
+
+    let (root_sel, pll_sel, hclk_div, bclk_div) = read_system_clk();
+    critical_section::with(|_| {
+        switch_system_clk(root_sel, pll_sel, hclk_div, bclk_div, Some(flash_cfg));
+    });
+}
+
+/// Gets the current serial flash clock rate, `None` if its source isn't known to run
+fn read_flash_clk(xtal_freq: Option<u32>, pll_enabled: bool, bclk: Hertz) -> Option<Hertz> {
+    let r = unsafe { &*pac::GLB::ptr() }.clk_cfg2.read().bits();
+    let source = FlashClk::from_bits(
+        r >> CLK_CFG2_SF_CLK_SEL_SHIFT & CLK_CFG2_SF_CLK_SEL_MASK,
+        r >> CLK_CFG2_SF_CLK_SEL2_SHIFT & CLK_CFG2_SF_CLK_SEL_MASK,
+    );
+    let div = (r >> CLK_CFG2_SF_CLK_DIV_SHIFT & CLK_CFG2_SF_CLK_DIV_MASK) + 1;
+    source
+        .freq(xtal_freq, pll_enabled, bclk)
+        .map(|freq| Hertz(freq / div))
+}
+
+/// Finds the source and the UART clock divided from it by 1 to 7 with the least baudrate error,
+/// preferring earlier sources and faster clocks
+fn best_uart_clk(
+    sources: &[UartClkSrc],
+    source_freq: impl Fn(UartClkSrc) -> u32,
+    baud: u32,
+) -> (UartClkSrc, u32) {
+    // The best clock, with the deviation of its bit period from the baudrate and the bit period
+    let mut best: Option<(UartClkSrc, u32, u64, u64)> = None;
+
+    for &source in sources {
+        for div in 1..=7 {
+            let uart_clk = source_freq(source) / div;
+            // The serial driver rounds the bit period to the nearest clock cycle
+            let period = ((uart_clk as u64 + baud as u64 / 2) / baud as u64).clamp(1, 65535);
+            let deviation = (uart_clk as u64).abs_diff(period * baud as u64);
+
+            // Compare the relative errors deviation / (period * baud) without dividing
+            let is_better = match best {
+                Some((_, _, best_deviation, best_period)) => {
+                    deviation * best_period < best_deviation * period
+                }
+                None => true,
+            };
+            if is_better {
+                best = Some((source, uart_clk, deviation, period));
+            }
+        }
+    }
+
+    match best {
+        Some((source, uart_clk, _, _)) => (source, uart_clk),
+        None => panic!("unreachable uart_clk"),
+    }
+}
+
+/// Gets the current crystal clock mux output, which is the crystal if bit 0 of the root clock
+/// selection is set
+fn read_xclk(xtal_freq: Option<u32>) -> Hertz {
+    match xtal_freq {
+        Some(freq) if read_system_clk().0 & ROOT_CLK_SEL_XTAL != 0 => Hertz(freq),
+        _ => Hertz(RC32M),
+    }
+}
+
+/// Divides the machine timer clock from the bus clock by `div` and enables it
+fn glb_set_mtimer_clk_div(div: u32) {
+    modify_reg(glb(GLB_CPU_CLK_CFG), |r| {
+        r & !CPU_CLK_CFG_RTC_DIV_MASK | (div - 1) | CPU_CLK_CFG_RTC_EN
+    });
+}
+
+/// Gets the current tick rate of the machine timer, `None` if it's stopped
+fn read_mtimer_clk(bclk: Hertz) -> Option<Hertz> {
+    let r = read_reg(glb(GLB_CPU_CLK_CFG));
+    if r & CPU_CLK_CFG_RTC_EN == 0 {
+        return None;
+    }
+    Some(Hertz(bclk.0 / ((r & CPU_CLK_CFG_RTC_DIV_MASK) + 1)))
+}
+
 /// Sets the system clock to use the PLL with external crystal
 fn glb_set_system_clk_pll(target_core_clk: u32, xtal_freq: u32) {
     // Ensure clock is running off internal RC oscillator before changing anything else
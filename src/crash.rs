@@ -0,0 +1,237 @@
+/*!
+  # Crash Record
+  With the `crash-record` feature, the HAL records panics and CPU exceptions in the retention
+  RAM, which survives the reset that follows, so the cause of a crash can be read out and e.g.
+  logged after the next start, without a debugger attached.
+
+  The feature brings a `#[panic_handler]`, in place of e.g. `panic-halt`, which records the
+  panic message and its location and restarts the chip with `power::reset_system`. Exceptions,
+  like an illegal instruction or a misaligned access, are recorded with `mcause`, `mepc` and
+  `mtval` before the `ExceptionHandler` of the application runs.
+
+  Only the first crash is recorded until the record is taken or cleared, as later ones are
+  often caused by it. Breakpoints and environment calls (`ebreak`, `ecall`) aren't recorded.
+
+  The record takes the last 128 bytes of the retention RAM, next to the value stored with
+  `power::retention::retained`.
+
+  ## Example
+  ```rust
+    if let Some(record) = crash::take() {
+        writeln!(
+            serial,
+            "crashed: {:?} mcause={:#x} mepc={:#x} {}",
+            record.kind,
+            record.mcause,
+            record.mepc,
+            record.message()
+        )
+        .ok();
+    }
+  ```
+*/
+
+use crate::reg::{read_reg, write_reg};
+
+// HBN_RAM_BASE, see components\bl602\bl602_std\bl602_std\Include\bl602.h
+const HBN_RAM: usize = 0x4001_0000;
+const HBN_RAM_SIZE: usize = 4 * 1024;
+
+/// The size of the record at the end of the retention RAM
+pub(crate) const RECORD_SIZE: usize = 128;
+const RECORD: usize = HBN_RAM + HBN_RAM_SIZE - RECORD_SIZE;
+
+const RECORD_MAGIC: usize = RECORD;
+const RECORD_KIND: usize = RECORD + 0x4;
+const RECORD_MCAUSE: usize = RECORD + 0x8;
+const RECORD_MEPC: usize = RECORD + 0xc;
+const RECORD_MTVAL: usize = RECORD + 0x10;
+const RECORD_MESSAGE_LEN: usize = RECORD + 0x14;
+const RECORD_MESSAGE: usize = RECORD + 0x18;
+
+/// The longest message kept, in bytes
+pub const MESSAGE_CAPACITY: usize = RECORD_SIZE - (RECORD_MESSAGE - RECORD);
+
+/// Marks a valid record
+const MAGIC: u32 = 0x4853_5243;
+
+const KIND_PANIC: u32 = 1;
+const KIND_EXCEPTION: u32 = 2;
+
+/// What crashed
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CrashKind {
+    /// A panic, with its message
+    Panic,
+    /// A CPU exception, with the trap registers
+    Exception,
+}
+
+/// A crash recorded before the last reset
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CrashRecord {
+    /// What crashed
+    pub kind: CrashKind,
+    /// The cause of the exception, or the `mcause` at the time of the panic
+    pub mcause: u32,
+    /// The address of the instruction raising the exception, or zero for a panic
+    pub mepc: u32,
+    /// The faulting address or instruction of the exception, or zero for a panic
+    pub mtval: u32,
+    message: [u8; MESSAGE_CAPACITY],
+    message_len: usize,
+}
+
+impl CrashRecord {
+    /// The panic message and location, cut at [MESSAGE_CAPACITY] bytes, or an empty string for
+    /// an exception
+    pub fn message(&self) -> &str {
+        let bytes = &self.message[..self.message_len];
+        match core::str::from_utf8(bytes) {
+            Ok(message) => message,
+            // Cut in the middle of a character
+            Err(e) => core::str::from_utf8(&bytes[..e.valid_up_to()]).unwrap_or(""),
+        }
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for CrashRecord {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "{}: mcause {=u32:#x}, mepc {=u32:#x}, mtval {=u32:#x}, {=str}",
+            self.kind,
+            self.mcause,
+            self.mepc,
+            self.mtval,
+            self.message()
+        )
+    }
+}
+
+/// Reads the crash recorded before the last reset, if any, without clearing it
+pub fn peek() -> Option<CrashRecord> {
+    if read_reg(RECORD_MAGIC) != MAGIC {
+        return None;
+    }
+
+    let kind = match read_reg(RECORD_KIND) {
+        KIND_PANIC => CrashKind::Panic,
+        KIND_EXCEPTION => CrashKind::Exception,
+        _ => return None,
+    };
+    let message_len = (read_reg(RECORD_MESSAGE_LEN) as usize).min(MESSAGE_CAPACITY);
+    let mut message = [0; MESSAGE_CAPACITY];
+    for (offset, byte) in message.iter_mut().enumerate().take(message_len) {
+        *byte = unsafe { ((RECORD_MESSAGE + offset) as *const u8).read_volatile() };
+    }
+
+    Some(CrashRecord {
+        kind,
+        mcause: read_reg(RECORD_MCAUSE),
+        mepc: read_reg(RECORD_MEPC),
+        mtval: read_reg(RECORD_MTVAL),
+        message,
+        message_len,
+    })
+}
+
+/// Reads and clears the crash recorded before the last reset, if any
+pub fn take() -> Option<CrashRecord> {
+    let record = peek();
+    clear();
+    record
+}
+
+/// Clears the crash record
+pub fn clear() {
+    write_reg(RECORD_MAGIC, 0);
+}
+
+/// Records a CPU exception, from the trap handler. Breakpoints and environment calls are left to
+/// the application, they aren't crashes.
+#[cfg(feature = "crash-record")]
+pub(crate) fn record_exception() {
+    use riscv::register::mcause::{Exception, Trap};
+    use riscv::register::{mcause, mepc, mtval};
+
+    let cause = mcause::read();
+    if matches!(
+        cause.cause(),
+        Trap::Exception(
+            Exception::Breakpoint
+                | Exception::UserEnvCall
+                | Exception::SupervisorEnvCall
+                | Exception::MachineEnvCall
+        )
+    ) {
+        return;
+    }
+
+    if !write_record(
+        KIND_EXCEPTION,
+        cause.bits() as u32,
+        mepc::read() as u32,
+        mtval::read() as u32,
+    ) {
+        return;
+    }
+    write_reg(RECORD_MESSAGE_LEN, 0);
+    write_reg(RECORD_MAGIC, MAGIC);
+}
+
+/// Writes the fixed part of the record, leaving it invalid. A valid record that hasn't been taken
+/// is kept instead, as it's the first of a cascade of crashes, and `false` is returned.
+#[cfg(feature = "crash-record")]
+fn write_record(kind: u32, mcause: u32, mepc: u32, mtval: u32) -> bool {
+    if read_reg(RECORD_MAGIC) == MAGIC {
+        return false;
+    }
+
+    write_reg(RECORD_KIND, kind);
+    write_reg(RECORD_MCAUSE, mcause);
+    write_reg(RECORD_MEPC, mepc);
+    write_reg(RECORD_MTVAL, mtval);
+    true
+}
+
+/// Formats the panic message into the record, cutting it at [MESSAGE_CAPACITY] bytes
+#[cfg(feature = "crash-record")]
+struct MessageWriter {
+    len: usize,
+}
+
+#[cfg(feature = "crash-record")]
+impl core::fmt::Write for MessageWriter {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for byte in s.bytes().take(MESSAGE_CAPACITY - self.len) {
+            unsafe { ((RECORD_MESSAGE + self.len) as *mut u8).write_volatile(byte) };
+            self.len += 1;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "crash-record")]
+#[panic_handler]
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    use core::fmt::Write;
+
+    unsafe { riscv::interrupt::disable() };
+
+    if write_record(
+        KIND_PANIC,
+        riscv::register::mcause::read().bits() as u32,
+        0,
+        0,
+    ) {
+        let mut writer = MessageWriter { len: 0 };
+        write!(writer, "{}", info).ok();
+        write_reg(RECORD_MESSAGE_LEN, writer.len as u32);
+        write_reg(RECORD_MAGIC, MAGIC);
+    }
+
+    crate::power::reset_system(crate::power::ResetPolicy::warm())
+}
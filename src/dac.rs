@@ -0,0 +1,926 @@
+/*!
+  # Digital to Analog Converter
+  The general purpose DAC (GPDAC) has two 10 bit channels, A on GPIO13 and B on GPIO14. The
+  output pins are put into analog mode with `into_analog()` and handed to the channel they
+  belong to. The channels work independently, or together as a [Pair].
+
+  The full scale output equals the reference, which is internal or supplied externally.
+
+  Samples played with the DMA are taken at the update rate set in [Config], which is derived from
+  the same 32MHz clock as the ADC. Without the PLL it's scaled to the crystal or internal
+  oscillator, [Dac::update_rate] reports the effective rate.
+
+  ## Example
+  ```rust
+    let mut dac = Dac::new(
+        parts.gpdac,
+        Config::default().update_rate(UpdateRate::Rate16k),
+        &clocks,
+    );
+    let mut channel_a = dac.channel(parts.pin13.into_analog());
+
+    channel_a.set_value(512);
+    channel_a.set_millivolts(1200);
+  ```
+
+  ## DMA example
+  A channel plays a buffer of samples at the update rate of the DAC, paced by the DMA. A
+  [Circular] playback loops over the buffer, which can be refilled half by half.
+  ```rust
+    static mut DESCRIPTORS: [Descriptor; 2] = [Descriptor::EMPTY; 2];
+
+    let buffer = dma_buffer!([u32; 256] = [0; 256]).unwrap();
+    // fill the buffer with one value per word ...
+    let playback = channel_a.play_circular(
+        channels.channel1,
+        buffer,
+        unsafe { &mut DESCRIPTORS },
+        None,
+    );
+  ```
+
+  ## Waveform example
+  A sine, triangle or sawtooth is synthesized into a buffer and played over and over.
+  ```rust
+    static mut TABLE: [u32; 512] = [0; 512];
+
+    // 1kHz, swinging between 0.5V and 1.5V
+    let sine = Signal {
+        waveform: Waveform::Sine,
+        frequency: 1_000u32.Hz(),
+        amplitude: 500,
+        offset: 1000,
+    };
+    let playback = channel_a.play_waveform(
+        channels.channel1,
+        sine,
+        unsafe { &mut TABLE },
+        unsafe { &mut DESCRIPTORS },
+    )
+    .map_err(|(error, ..)| error)
+    .unwrap();
+  ```
+
+  ## Pair example
+  A [Pair] updates both channels at once, from a value for each channel in every word played
+  with the DMA, e.g. to draw X/Y figures on an oscilloscope.
+  ```rust
+    let channel_b = dac.channel(parts.pin14.into_analog());
+    let mut pair = Pair::new(channel_a, channel_b);
+    pair.set_values(0, MAX_VALUE);
+
+    let buffer = dma_buffer!([u32; 256] = [0; 256]).unwrap();
+    for (i, word) in buffer.iter_mut().enumerate() {
+        // a diagonal line from the bottom left to the top right
+        *word = pair_sample(i as u16 * 4, i as u16 * 4);
+    }
+    let playback = pair.play_circular(
+        channels.channel1,
+        buffer,
+        unsafe { &mut DESCRIPTORS },
+        None,
+    );
+  ```
+
+  ## Audio example
+  [AudioOut] plays 8 or 16 bit PCM at any sample rate, adapted to the update rate of the DAC. It
+  takes the samples that fit into the half of its buffer that isn't playing, the rest is written
+  once the DMA moved on.
+  ```rust
+    static mut AUDIO: [u32; 1024] = [0; 1024];
+
+    fn on_underrun() {
+        // the samples weren't written in time, silence is played
+    }
+
+    let mut audio = AudioOut::new(
+        channel_a,
+        channels.channel1,
+        unsafe { &mut AUDIO },
+        unsafe { &mut DESCRIPTORS },
+        Some(on_underrun),
+    );
+
+    let mut pcm: &[i16] = &CLIP;
+    while !pcm.is_empty() {
+        let written = audio.write_i16(pcm, 22_050u32.Hz());
+        pcm = &pcm[written..];
+    }
+  ```
+*/
+
+use crate::{
+    adc,
+    clock::Clocks,
+    dma::{self, fifo, Circular, Descriptor, DmaChannel, Half, ReadBuffer, Transfer, WriteBuffer},
+    gpio,
+    reg::{glb, gpip, modify_reg, read_reg},
+};
+use core::cell::Cell;
+use core::num::NonZeroU16;
+use critical_section::Mutex;
+use embedded_time::rate::Hertz;
+
+// see components\bl602\bl602_std\bl602_std\StdDriver\Inc\bl602_glb_reg.h
+const GPDAC_CTRL: usize = 0x308;
+const GPDAC_ACTRL: usize = 0x30c;
+const GPDAC_BCTRL: usize = 0x310;
+const GPDAC_DATA: usize = 0x314;
+
+const CTRL_RSTN_ANA_A: u32 = 1 << 0;
+const CTRL_RSTN_ANA_B: u32 = 1 << 1;
+const CTRL_REF_SEL: u32 = 1 << 8;
+
+const XCTRL_EN: u32 = 1 << 0;
+const XCTRL_IO_EN: u32 = 1 << 1;
+const XCTRL_RNG_SHIFT: u32 = 18;
+const XCTRL_RNG_MASK: u32 = 0x3;
+
+const DATA_B_SHIFT: u32 = 0;
+const DATA_A_SHIFT: u32 = 16;
+const DATA_MASK: u32 = 0x3ff;
+
+// see components\bl602\bl602_std\bl602_std\StdDriver\Inc\bl602_gpip_reg.h
+const GPDAC_CONFIG: usize = 0x40;
+const GPDAC_DMA_CONFIG: usize = 0x44;
+
+const CONFIG_EN_A: u32 = 1 << 0;
+const CONFIG_EN_B: u32 = 1 << 1;
+const CONFIG_MODE_SHIFT: u32 = 8;
+const CONFIG_MODE_MASK: u32 = 0x7;
+const CONFIG_CH_A_SEL_SHIFT: u32 = 16;
+const CONFIG_CH_B_SEL_SHIFT: u32 = 20;
+const CONFIG_CH_SEL_MASK: u32 = 0xf;
+
+const DMA_CONFIG_TX_EN: u32 = 1 << 0;
+const DMA_CONFIG_FORMAT_SHIFT: u32 = 8;
+const DMA_CONFIG_FORMAT_MASK: u32 = 0xf;
+
+/// The channel output follows the data register
+const SOURCE_REGISTER: u32 = 0;
+/// The channel output follows the DMA samples
+const SOURCE_DMA: u32 = 1;
+
+/// One sample per word, for the channels taking samples from the DMA
+const FORMAT_SINGLE: u32 = 0;
+/// A sample for each channel per word, for a [Pair]
+const FORMAT_PAIR: u32 = 1;
+
+const PAIR_A_SHIFT: u32 = 0;
+const PAIR_B_SHIFT: u32 = 16;
+
+/// The value of silence, half way between 0 and the reference
+const SILENCE: u32 = 512;
+
+/// The clock the update rates are specified at
+const NOMINAL_CLOCK: u32 = 32_000_000;
+
+/// The full output range
+const RANGE_FULL: u32 = 3;
+
+/// The largest value of a channel
+pub const MAX_VALUE: u16 = 0x3ff;
+
+/// DAC error
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The frequency of the signal is 0, or above half the update rate
+    InvalidFrequency,
+    /// The buffer is too short for a period of the signal
+    BufferTooShort,
+}
+
+/// The reference voltage of the DAC, which is its full scale output
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Reference {
+    /// The internal 3.2V reference
+    Internal,
+    /// A reference supplied externally, with its voltage in millivolts
+    External(NonZeroU16),
+}
+
+impl Reference {
+    /// The reference voltage in millivolts
+    pub fn millivolts(&self) -> u32 {
+        match self {
+            Reference::Internal => 3200,
+            Reference::External(millivolts) => millivolts.get() as u32,
+        }
+    }
+}
+
+/// The rate at which samples played with the DMA are taken, from a 32MHz clock
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum UpdateRate {
+    /// 512kHz
+    Rate512k,
+    /// 32kHz
+    Rate32k,
+    /// 16kHz
+    Rate16k,
+    /// 8kHz
+    Rate8k,
+}
+
+impl UpdateRate {
+    /// The rate from a 32MHz clock
+    pub fn nominal(&self) -> Hertz {
+        match self {
+            UpdateRate::Rate512k => Hertz(512_000),
+            UpdateRate::Rate32k => Hertz(32_000),
+            UpdateRate::Rate16k => Hertz(16_000),
+            UpdateRate::Rate8k => Hertz(8_000),
+        }
+    }
+
+    fn bits(&self) -> u32 {
+        match self {
+            UpdateRate::Rate32k => 0,
+            UpdateRate::Rate16k => 1,
+            UpdateRate::Rate8k => 3,
+            UpdateRate::Rate512k => 4,
+        }
+    }
+}
+
+/// DAC configuration
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Config {
+    /// The reference voltage of both channels
+    pub reference: Reference,
+    /// The rate of the samples played with the DMA
+    pub update_rate: UpdateRate,
+}
+
+impl Config {
+    /// Sets the reference voltage
+    pub fn reference(mut self, reference: Reference) -> Self {
+        self.reference = reference;
+
+        self
+    }
+
+    /// Sets the rate of the samples played with the DMA
+    pub fn update_rate(mut self, update_rate: UpdateRate) -> Self {
+        self.update_rate = update_rate;
+
+        self
+    }
+}
+
+impl Default for Config {
+    /// The internal reference and a 32kHz update rate
+    fn default() -> Self {
+        Config {
+            reference: Reference::Internal,
+            update_rate: UpdateRate::Rate32k,
+        }
+    }
+}
+
+/// The general purpose DAC
+pub struct Dac {
+    gpdac: gpio::Gpdac,
+    config: Config,
+    clock: Hertz,
+    /// The channels handed out and not released yet, by index
+    channels: u8,
+}
+
+impl Dac {
+    /// Takes the analog part of the DAC out of reset, selects the reference and sets the update
+    /// rate. The channels are enabled once their pin is handed to [Dac::channel].
+    pub fn new(gpdac: gpio::Gpdac, config: Config, clocks: &Clocks) -> Self {
+        let clock = adc::setup_clock(clocks);
+        modify_reg(glb(GPDAC_CTRL), |r| {
+            let r = r | CTRL_RSTN_ANA_A | CTRL_RSTN_ANA_B;
+            match config.reference {
+                Reference::Internal => r & !CTRL_REF_SEL,
+                Reference::External(_) => r | CTRL_REF_SEL,
+            }
+        });
+        modify_reg(gpip(GPDAC_CONFIG), |r| {
+            r & !(CONFIG_MODE_MASK << CONFIG_MODE_SHIFT)
+                | config.update_rate.bits() << CONFIG_MODE_SHIFT
+        });
+
+        Dac {
+            gpdac,
+            config,
+            clock,
+            channels: 0,
+        }
+    }
+
+    /// The configuration of the DAC
+    pub fn config(&self) -> Config {
+        self.config
+    }
+
+    /// The effective rate of the samples played with the DMA, which differs from the configured
+    /// one if the DAC isn't clocked at 32MHz
+    pub fn update_rate(&self) -> Hertz {
+        let nominal = self.config.update_rate.nominal().0 as u64;
+        Hertz((nominal * self.clock.0 as u64 / NOMINAL_CLOCK as u64) as u32)
+    }
+
+    /// Enables the channel that drives `pin`, starting at 0
+    pub fn channel<PIN: OutputPin>(&mut self, pin: PIN) -> Channel<PIN> {
+        let channel = Channel {
+            pin,
+            reference: self.config.reference,
+            update_rate: self.update_rate(),
+        };
+        channel.write(0);
+        modify_reg(glb(channel.control()), |r| {
+            r & !(XCTRL_RNG_MASK << XCTRL_RNG_SHIFT)
+                | RANGE_FULL << XCTRL_RNG_SHIFT
+                | XCTRL_EN
+                | XCTRL_IO_EN
+        });
+        self.channels |= 1 << PIN::INDEX;
+        channel
+    }
+
+    /// Disables `channel` and releases its pin
+    pub fn release<PIN: OutputPin>(&mut self, mut channel: Channel<PIN>) -> PIN {
+        channel.stop_playback();
+        modify_reg(glb(channel.control()), |r| r & !(XCTRL_EN | XCTRL_IO_EN));
+        self.channels &= !(1 << PIN::INDEX);
+        channel.pin
+    }
+
+    /// Puts the analog part of the DAC back into reset and releases its registers. Panics if a
+    /// channel hasn't been handed back to [Dac::release].
+    pub fn free(self) -> gpio::Gpdac {
+        if self.channels != 0 {
+            panic!("DAC channels still in use");
+        }
+        modify_reg(glb(GPDAC_CTRL), |r| {
+            r & !(CTRL_RSTN_ANA_A | CTRL_RSTN_ANA_B)
+        });
+        self.gpdac
+    }
+}
+
+/// A DAC channel driving its output pin
+pub struct Channel<PIN> {
+    pin: PIN,
+    reference: Reference,
+    update_rate: Hertz,
+}
+
+impl<PIN: OutputPin> Channel<PIN> {
+    /// Sets the output to `value` out of [MAX_VALUE]. Panics if `value` is larger.
+    pub fn set_value(&mut self, value: u16) {
+        if value > MAX_VALUE {
+            panic!("Invalid DAC value");
+        }
+        self.write(value);
+    }
+
+    /// Sets the output to `millivolts`, saturating at the reference voltage
+    pub fn set_millivolts(&mut self, millivolts: u16) {
+        self.write(self.to_value(millivolts));
+    }
+
+    /// The value the output is set to
+    pub fn value(&self) -> u16 {
+        (read_reg(glb(GPDAC_DATA)) >> self.data_shift() & DATA_MASK) as u16
+    }
+
+    /// Plays `buffer` once using the DMA, one sample per update of the DAC. Each word holds a
+    /// value in its low 10 bits. The output keeps the last value once the transfer is done,
+    /// until [Channel::stop_playback] hands it back to [Channel::set_value].
+    pub fn play<CH, B>(&mut self, channel: CH, buffer: B) -> Transfer<CH, B, dma::Peripheral>
+    where
+        CH: DmaChannel,
+        B: ReadBuffer<Word = u32>,
+    {
+        self.start_playback();
+        Transfer::memory_to_peripheral(channel, buffer, fifo::Gpdac)
+    }
+
+    /// Plays `buffer` over and over using the DMA, see [Channel::play] and [Circular].
+    /// `on_done` is called from the DMA interrupt with every half that was played, which can be
+    /// refilled meanwhile.
+    pub fn play_circular<CH, B>(
+        &mut self,
+        channel: CH,
+        buffer: B,
+        descriptors: &'static mut [Descriptor; 2],
+        on_done: Option<fn(Half)>,
+    ) -> Circular<CH, B>
+    where
+        CH: DmaChannel,
+        B: WriteBuffer<Word = u32>,
+    {
+        self.start_playback();
+        Circular::memory_to_peripheral(channel, buffer, fifo::Gpdac, descriptors, on_done)
+    }
+
+    /// Plays one period of `signal` over and over using the DMA, see [Channel::play_circular].
+    ///
+    /// The period is synthesized into the start of `buffer`, with as many samples as fit into a
+    /// period of the signal at the update rate, rounded down to an even number. The frequency
+    /// that's played is the update rate divided by that. If the frequency is 0 or above half the
+    /// update rate, or `buffer` is too short, the channel, the buffer and the descriptors are
+    /// returned along with the error.
+    #[allow(clippy::type_complexity)]
+    pub fn play_waveform<CH>(
+        &mut self,
+        channel: CH,
+        signal: Signal,
+        buffer: &'static mut [u32],
+        descriptors: &'static mut [Descriptor; 2],
+    ) -> Result<
+        Circular<CH, &'static mut [u32]>,
+        (Error, CH, &'static mut [u32], &'static mut [Descriptor; 2]),
+    >
+    where
+        CH: DmaChannel,
+    {
+        if signal.frequency.0 == 0 {
+            return Err((Error::InvalidFrequency, channel, buffer, descriptors));
+        }
+        let len = (self.update_rate.0 / signal.frequency.0) as usize & !1;
+        if len < 2 {
+            return Err((Error::InvalidFrequency, channel, buffer, descriptors));
+        }
+        if len > buffer.len() {
+            return Err((Error::BufferTooShort, channel, buffer, descriptors));
+        }
+
+        let table = &mut buffer[..len];
+        synthesize(
+            signal.waveform,
+            self.to_value(signal.amplitude),
+            self.to_value(signal.offset),
+            table,
+        );
+        Ok(self.play_circular(channel, table, descriptors, None))
+    }
+
+    /// Stops taking samples from the DMA, the output follows [Channel::set_value] again. The
+    /// other channel keeps playing, if it does.
+    pub fn stop_playback(&mut self) {
+        modify_reg(gpip(GPDAC_CONFIG), |r| {
+            r & !(self.enable() | CONFIG_CH_SEL_MASK << self.source_shift())
+                | SOURCE_REGISTER << self.source_shift()
+        });
+
+        // The DMA requests are shared by both channels
+        let config = read_reg(gpip(GPDAC_CONFIG));
+        let dma_sourced = [CONFIG_CH_A_SEL_SHIFT, CONFIG_CH_B_SEL_SHIFT]
+            .iter()
+            .any(|shift| config >> shift & CONFIG_CH_SEL_MASK == SOURCE_DMA);
+        if !dma_sourced {
+            modify_reg(gpip(GPDAC_DMA_CONFIG), |r| r & !DMA_CONFIG_TX_EN);
+        }
+    }
+
+    fn start_playback(&mut self) {
+        modify_reg(gpip(GPDAC_CONFIG), |r| {
+            r & !(CONFIG_CH_SEL_MASK << self.source_shift())
+                | SOURCE_DMA << self.source_shift()
+                | self.enable()
+        });
+        modify_reg(gpip(GPDAC_DMA_CONFIG), |r| {
+            r & !(DMA_CONFIG_FORMAT_MASK << DMA_CONFIG_FORMAT_SHIFT)
+                | FORMAT_SINGLE << DMA_CONFIG_FORMAT_SHIFT
+                | DMA_CONFIG_TX_EN
+        });
+    }
+
+    fn enable(&self) -> u32 {
+        match PIN::INDEX {
+            0 => CONFIG_EN_A,
+            _ => CONFIG_EN_B,
+        }
+    }
+
+    fn source_shift(&self) -> u32 {
+        match PIN::INDEX {
+            0 => CONFIG_CH_A_SEL_SHIFT,
+            _ => CONFIG_CH_B_SEL_SHIFT,
+        }
+    }
+
+    /// Converts millivolts into a value, saturating at the reference voltage
+    fn to_value(&self, millivolts: u16) -> u16 {
+        let value = millivolts as u32 * (MAX_VALUE as u32 + 1) / self.reference.millivolts();
+        value.min(MAX_VALUE as u32) as u16
+    }
+
+    fn write(&self, value: u16) {
+        let shift = self.data_shift();
+        modify_reg(glb(GPDAC_DATA), |r| {
+            r & !(DATA_MASK << shift) | (value as u32) << shift
+        });
+    }
+
+    fn control(&self) -> usize {
+        match PIN::INDEX {
+            0 => GPDAC_ACTRL,
+            _ => GPDAC_BCTRL,
+        }
+    }
+
+    fn data_shift(&self) -> u32 {
+        match PIN::INDEX {
+            0 => DATA_A_SHIFT,
+            _ => DATA_B_SHIFT,
+        }
+    }
+}
+
+/// Both DAC channels, updated together
+pub struct Pair {
+    a: Channel<gpio::Pin13<gpio::Analog>>,
+    b: Channel<gpio::Pin14<gpio::Analog>>,
+}
+
+impl Pair {
+    /// Pairs channel A and channel B
+    pub fn new(
+        a: Channel<gpio::Pin13<gpio::Analog>>,
+        b: Channel<gpio::Pin14<gpio::Analog>>,
+    ) -> Self {
+        Pair { a, b }
+    }
+
+    /// Sets the output of channel A to `a` and of channel B to `b`, out of [MAX_VALUE]. Panics if
+    /// either is larger.
+    pub fn set_values(&mut self, a: u16, b: u16) {
+        if a > MAX_VALUE || b > MAX_VALUE {
+            panic!("Invalid DAC value");
+        }
+        modify_reg(glb(GPDAC_DATA), |r| {
+            r & !(DATA_MASK << DATA_A_SHIFT | DATA_MASK << DATA_B_SHIFT)
+                | (a as u32) << DATA_A_SHIFT
+                | (b as u32) << DATA_B_SHIFT
+        });
+    }
+
+    /// Sets the output of channel A to `a` and of channel B to `b` in millivolts, saturating at
+    /// the reference voltage
+    pub fn set_millivolts(&mut self, a: u16, b: u16) {
+        let values = (self.a.to_value(a), self.b.to_value(b));
+        self.set_values(values.0, values.1);
+    }
+
+    /// Plays `buffer` once using the DMA, updating both channels from every word, see
+    /// [pair_sample]. The outputs keep the last values once the transfer is done, until
+    /// [Pair::stop_playback] hands them back to [Pair::set_values].
+    pub fn play<CH, B>(&mut self, channel: CH, buffer: B) -> Transfer<CH, B, dma::Peripheral>
+    where
+        CH: DmaChannel,
+        B: ReadBuffer<Word = u32>,
+    {
+        self.start_playback();
+        Transfer::memory_to_peripheral(channel, buffer, fifo::Gpdac)
+    }
+
+    /// Plays `buffer` over and over using the DMA, see [Pair::play] and [Channel::play_circular]
+    pub fn play_circular<CH, B>(
+        &mut self,
+        channel: CH,
+        buffer: B,
+        descriptors: &'static mut [Descriptor; 2],
+        on_done: Option<fn(Half)>,
+    ) -> Circular<CH, B>
+    where
+        CH: DmaChannel,
+        B: WriteBuffer<Word = u32>,
+    {
+        self.start_playback();
+        Circular::memory_to_peripheral(channel, buffer, fifo::Gpdac, descriptors, on_done)
+    }
+
+    /// Stops taking samples from the DMA, the outputs follow [Pair::set_values] again
+    pub fn stop_playback(&mut self) {
+        self.a.stop_playback();
+        self.b.stop_playback();
+    }
+
+    /// Stops playing and returns channel A and channel B
+    pub fn split(
+        mut self,
+    ) -> (
+        Channel<gpio::Pin13<gpio::Analog>>,
+        Channel<gpio::Pin14<gpio::Analog>>,
+    ) {
+        self.stop_playback();
+        (self.a, self.b)
+    }
+
+    fn start_playback(&mut self) {
+        let sources = CONFIG_CH_SEL_MASK << CONFIG_CH_A_SEL_SHIFT
+            | CONFIG_CH_SEL_MASK << CONFIG_CH_B_SEL_SHIFT;
+        modify_reg(gpip(GPDAC_CONFIG), |r| {
+            r & !sources
+                | SOURCE_DMA << CONFIG_CH_A_SEL_SHIFT
+                | SOURCE_DMA << CONFIG_CH_B_SEL_SHIFT
+                | CONFIG_EN_A
+                | CONFIG_EN_B
+        });
+        modify_reg(gpip(GPDAC_DMA_CONFIG), |r| {
+            r & !(DMA_CONFIG_FORMAT_MASK << DMA_CONFIG_FORMAT_SHIFT)
+                | FORMAT_PAIR << DMA_CONFIG_FORMAT_SHIFT
+                | DMA_CONFIG_TX_EN
+        });
+    }
+}
+
+/// Packs the values for channel A and channel B into a word played by a [Pair]
+pub fn pair_sample(a: u16, b: u16) -> u32 {
+    (a as u32 & DATA_MASK) << PAIR_A_SHIFT | (b as u32 & DATA_MASK) << PAIR_B_SHIFT
+}
+
+/// The shape of a synthesized waveform
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Waveform {
+    /// A sine, starting at the offset on the way up
+    Sine,
+    /// A triangle, starting at the bottom
+    Triangle,
+    /// A sawtooth, rising from the bottom to the top
+    Sawtooth,
+}
+
+impl Waveform {
+    /// The value at `position` out of `len`, from -32767 to 32767
+    fn sample(&self, position: usize, len: usize) -> i32 {
+        // The phase in 1/65536 of a period
+        let phase = (position as u64 * 65536 / len as u64) as i64;
+        let value = match self {
+            Waveform::Sine => {
+                // Bhaskara's approximation of each half, in millidegrees
+                let angle = phase * 360_000 / 65536;
+                let half = angle % 180_000;
+                let product = half * (180_000 - half);
+                let value = 32767 * 4 * product / (40_500_000_000 - product);
+                if angle < 180_000 {
+                    value
+                } else {
+                    -value
+                }
+            }
+            Waveform::Triangle => {
+                if phase < 32768 {
+                    phase * 2 - 32768
+                } else {
+                    98304 - phase * 2
+                }
+            }
+            Waveform::Sawtooth => phase - 32768,
+        };
+        value.max(-32767).min(32767) as i32
+    }
+}
+
+/// A periodic signal played with [Channel::play_waveform]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Signal {
+    /// The shape of the signal
+    pub waveform: Waveform,
+    /// The frequency of the signal
+    pub frequency: Hertz,
+    /// The swing around the offset in millivolts, saturating at 0 and the reference voltage
+    pub amplitude: u16,
+    /// The center of the signal in millivolts
+    pub offset: u16,
+}
+
+/// Fills `table` with one period of `waveform`, as values for the DMA. The waveform swings by
+/// `amplitude` around `offset`, both in DAC values, saturating at 0 and [MAX_VALUE].
+pub fn synthesize(waveform: Waveform, amplitude: u16, offset: u16, table: &mut [u32]) {
+    let len = table.len();
+    for (position, entry) in table.iter_mut().enumerate() {
+        let value = offset as i32 + amplitude as i32 * waveform.sample(position, len) / 32767;
+        *entry = value.max(0).min(MAX_VALUE as i32) as u32;
+    }
+}
+
+/// State of the [AudioOut] playing, shared with the DMA interrupt
+#[derive(Copy, Clone)]
+struct AudioState {
+    start: u32,
+    half_items: usize,
+    filled: [bool; 2],
+    on_underrun: Option<fn()>,
+}
+
+static AUDIO: Mutex<Cell<Option<AudioState>>> = Mutex::new(Cell::new(None));
+
+fn half_index(half: Half) -> usize {
+    match half {
+        Half::First => 0,
+        Half::Second => 1,
+    }
+}
+
+/// Silences the half that was played, so it isn't repeated if it isn't refilled in time, and
+/// reports an underrun if the half that's playing now wasn't filled
+fn audio_half_played(half: Half) {
+    let state = critical_section::with(|cs| {
+        let cell = AUDIO.borrow(cs);
+        let mut state = cell.get()?;
+        state.filled[half_index(half)] = false;
+        cell.set(Some(state));
+        Some(state)
+    });
+    let state = match state {
+        Some(state) => state,
+        None => return,
+    };
+
+    let offset = half_index(half) * state.half_items;
+    let played = unsafe {
+        core::slice::from_raw_parts_mut((state.start as *mut u32).add(offset), state.half_items)
+    };
+    played.iter_mut().for_each(|sample| *sample = SILENCE);
+
+    if !state.filled[half_index(half.other())] {
+        if let Some(on_underrun) = state.on_underrun {
+            on_underrun();
+        }
+    }
+}
+
+/// Plays PCM audio on a DAC channel, double buffered with a circular DMA transfer. Only one
+/// can play at a time. Dropping it stops playing.
+pub struct AudioOut<CH: DmaChannel, PIN: OutputPin> {
+    parts: Option<(Channel<PIN>, Circular<CH, &'static mut [u32]>)>,
+    /// The half being written, and the samples written into it so far
+    fill: (Half, usize),
+    /// The position in the PCM samples, in 1/65536 of a sample
+    phase: u64,
+}
+
+impl<CH, PIN> AudioOut<CH, PIN>
+where
+    CH: DmaChannel,
+    PIN: OutputPin,
+{
+    /// Starts playing silence from `buffer`, whose halves are filled by the writes in turn. The
+    /// latency is about the duration of the buffer. `on_underrun` is called from the DMA
+    /// interrupt if a half starts playing before it was filled. Panics if another `AudioOut` is
+    /// playing.
+    pub fn new(
+        mut channel: Channel<PIN>,
+        dma_channel: CH,
+        buffer: &'static mut [u32],
+        descriptors: &'static mut [Descriptor; 2],
+        on_underrun: Option<fn()>,
+    ) -> Self {
+        let state = AudioState {
+            start: buffer.as_ptr() as u32,
+            half_items: buffer.len() / 2,
+            filled: [false; 2],
+            on_underrun,
+        };
+        let playing = critical_section::with(|cs| {
+            let cell = AUDIO.borrow(cs);
+            let playing = cell.get().is_some();
+            if !playing {
+                cell.set(Some(state));
+            }
+            playing
+        });
+        if playing {
+            panic!("Another AudioOut is playing");
+        }
+
+        buffer.iter_mut().for_each(|sample| *sample = SILENCE);
+        let circular =
+            channel.play_circular(dma_channel, buffer, descriptors, Some(audio_half_played));
+        AudioOut {
+            parts: Some((channel, circular)),
+            fill: (Half::First, 0),
+            phase: 0,
+        }
+    }
+
+    /// Writes unsigned 8 bit PCM samples recorded at `rate`, and returns how many were taken
+    pub fn write_u8(&mut self, samples: &[u8], rate: Hertz) -> usize {
+        self.write(samples, rate, |sample| (sample as u32) << 2)
+    }
+
+    /// Writes signed 16 bit PCM samples recorded at `rate`, and returns how many were taken
+    pub fn write_i16(&mut self, samples: &[i16], rate: Hertz) -> usize {
+        self.write(samples, rate, |sample| (sample as i32 + 32768) as u32 >> 6)
+    }
+
+    /// Stops playing, and returns the DAC channel, the DMA channel, the buffer and the
+    /// descriptors
+    pub fn free(
+        mut self,
+    ) -> (
+        Channel<PIN>,
+        CH,
+        &'static mut [u32],
+        &'static mut [Descriptor; 2],
+    ) {
+        self.finish()
+    }
+
+    fn finish(
+        &mut self,
+    ) -> (
+        Channel<PIN>,
+        CH,
+        &'static mut [u32],
+        &'static mut [Descriptor; 2],
+    ) {
+        let (mut channel, circular) = self.parts.take().unwrap();
+        let (dma_channel, buffer, descriptors) = circular.stop();
+        channel.stop_playback();
+        critical_section::with(|cs| AUDIO.borrow(cs).set(None));
+        (channel, dma_channel, buffer, descriptors)
+    }
+
+    /// Resamples `samples` from `rate` to the update rate by repeating or skipping samples, into
+    /// the half of the buffer that isn't playing, unless it's filled already
+    fn write<S: Copy>(&mut self, samples: &[S], rate: Hertz, convert: fn(S) -> u32) -> usize {
+        let AudioOut { parts, fill, phase } = self;
+        let (channel, circular) = parts.as_mut().unwrap();
+        let step = rate.0 as u64 * 65536 / channel.update_rate.0 as u64;
+        let mut consumed = 0;
+
+        let _ = circular.peek(|slice, half| {
+            let filled = critical_section::with(|cs| match AUDIO.borrow(cs).get() {
+                Some(state) => state.filled[half_index(half)],
+                None => true,
+            });
+            if filled {
+                return;
+            }
+            // A half that started playing while it was written is abandoned
+            if fill.0 != half {
+                *fill = (half, 0);
+            }
+
+            while fill.1 < slice.len() {
+                let index = (*phase >> 16) as usize;
+                if index >= samples.len() {
+                    break;
+                }
+                slice[fill.1] = convert(samples[index]);
+                fill.1 += 1;
+                *phase += step;
+            }
+            consumed = ((*phase >> 16) as usize).min(samples.len());
+            *phase -= (consumed as u64) << 16;
+
+            if fill.1 == slice.len() {
+                critical_section::with(|cs| {
+                    let cell = AUDIO.borrow(cs);
+                    if let Some(mut state) = cell.get() {
+                        state.filled[half_index(half)] = true;
+                        cell.set(Some(state));
+                    }
+                });
+                *fill = (half.other(), 0);
+            }
+        });
+
+        consumed
+    }
+}
+
+impl<CH: DmaChannel, PIN: OutputPin> Drop for AudioOut<CH, PIN> {
+    fn drop(&mut self) {
+        if self.parts.is_some() {
+            self.finish();
+        }
+    }
+}
+
+/// Output pins of the DAC channels - DO NOT IMPLEMENT THIS TRAIT
+pub trait OutputPin: private::Sealed {}
+
+impl OutputPin for gpio::Pin13<gpio::Analog> {}
+impl OutputPin for gpio::Pin14<gpio::Analog> {}
+
+mod private {
+    use crate::gpio;
+
+    pub trait Sealed {
+        /// 0 for channel A, 1 for channel B
+        const INDEX: usize;
+    }
+
+    impl Sealed for gpio::Pin13<gpio::Analog> {
+        const INDEX: usize = 0;
+    }
+
+    impl Sealed for gpio::Pin14<gpio::Analog> {
+        const INDEX: usize = 1;
+    }
+}
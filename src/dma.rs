@@ -0,0 +1,2447 @@
+/*!
+  # Direct Memory Access
+  The DMA controller has four channels, which move data between memory and peripherals without
+  the CPU. Each channel is configured for its transfer direction, data width, burst size and the
+  peripheral handshake, and has its own enable and interrupt flags.
+
+  A transfer of up to 4095 items is started by setting the source and destination address and
+  enabling the channel. Setting the addresses is unsafe, as the DMA writes to any memory it's
+  told to.
+
+  Addresses have to be aligned to the item width, and the source and destination widths may
+  differ as long as the transfer fills whole destination items; the HAL panics otherwise instead
+  of letting the controller silently drop or duplicate bytes. Larger bursts move more items per
+  bus request, which speeds up memory copies, but a peripheral burst shouldn't exceed the FIFO
+  threshold that raises the request.
+
+  ## Example
+  ```rust
+    let mut channels = dp.DMA.split();
+
+    let config = Config::default()
+        .direction(Direction::MemoryToPeripheral)
+        .width(Width::Byte)
+        .increment(true, false)
+        .dst_peripheral(fifo::Uart0Tx);
+    channels.channel0.configure(&config);
+
+    unsafe {
+        channels.channel0.set_source(buffer.as_ptr() as u32);
+        channels.channel0.set_destination(UART0_FIFO_WDATA);
+    }
+    channels.channel0.set_transfer_size(buffer.len() as u16);
+    channels.channel0.enable();
+
+    while !channels.channel0.is_transfer_complete() {}
+    channels.channel0.clear_transfer_complete();
+  ```
+
+  ## Priority
+  The arbitration between requesting channels is fixed in hardware and can't be configured, the
+  controller has no priority register: channel 0 has the highest priority and channel 3 the
+  lowest, see [DmaChannel::priority]. The priority of a transfer is only chosen by the channel it
+  runs on. Once a channel is granted the bus it completes its current burst before a channel with
+  a higher priority can take over, so the latency of a high priority channel is bounded by the
+  burst size of the other channels.
+
+  To keep a latency critical stream, like audio output, from being starved by a bulk transfer:
+  - put the latency critical stream on a lower channel number than the bulk transfer
+  - keep the burst size of the bulk transfer small, e.g. [Burst::Incr4]
+
+  ## Memory copy example
+  Large copies, e.g. framebuffer blits, are faster with the DMA than with `copy_from_slice()`.
+  The widest item width the buffers are aligned for is used.
+  ```rust
+    mem_copy(&mut channels.channel1, &frame, &mut framebuffer).unwrap();
+
+    // Or let the HAL pick the CPU for small copies
+    copy_best_effort(&mut channels.channel1, &line, &mut framebuffer[..line.len()]);
+
+    // Or continue from the DMA interrupt, with buffers that live long enough
+    let copy = mem_copy_with_interrupt(channels.channel1, frame, framebuffer, Some(on_copied));
+    // ...
+    copy.wait().unwrap();
+    let (channel1, frame, framebuffer) = copy.free();
+  ```
+
+  ## Fill example
+  The DMA can also fill memory with a value, e.g. to clear a framebuffer.
+  ```rust
+    mem_fill(&mut channels.channel1, 0, &mut framebuffer).unwrap();
+
+    // Or fill a buffer the transfer owns
+    let transfer = Transfer::fill(channels.channel1, 0xffffu16, pixels);
+  ```
+
+  ## Scatter-gather example
+  A [Chain] streams several buffers in one transfer, using the linked list items of the DMA.
+  ```rust
+    static mut DESCRIPTORS: [Descriptor; 4] = [Descriptor::EMPTY; 4];
+    static mut HEADER: [u8; 4] = [0xaa, 0x55, 0x00, 0x10];
+
+    let transfer = Chain::memory_to_peripheral(unsafe { &mut DESCRIPTORS }, fifo::Uart0Tx)
+        .push(unsafe { &HEADER })
+        .push(payload)
+        .start(channels.channel3);
+    let (channel3, descriptors) = transfer.wait().ok().unwrap();
+  ```
+
+  ## Async example
+  With the `async` feature a transfer can be awaited, the task is woken from the DMA interrupt.
+  ```rust
+    let transfer = Transfer::memory_to_memory(channels.channel1, frame, framebuffer);
+    let (channel1, frame, framebuffer) = transfer.wait_async().await.ok().unwrap();
+  ```
+
+  ## Circular example
+  A [Circular] transfer streams through the two halves of a buffer until it's stopped, and
+  reports every completed half.
+  ```rust
+    static mut SAMPLES: [u32; 256] = [0; 256];
+    static mut DESCRIPTORS: [Descriptor; 2] = [Descriptor::EMPTY; 2];
+
+    let mut circular = Circular::peripheral_to_memory(
+        channels.channel0,
+        fifo::Gpadc,
+        unsafe { &mut SAMPLES },
+        unsafe { &mut DESCRIPTORS },
+        Some(on_half_done),
+    );
+
+    loop {
+        // The half the DMA isn't writing to
+        circular.peek(|samples, _half| process(samples)).unwrap();
+    }
+  ```
+
+  ## Queue example
+  A [Queue] starts the next buffer from the DMA interrupt as soon as the current one is written,
+  so back to back output has no gaps.
+  ```rust
+    let mut queue = Queue::memory_to_peripheral(channels.channel1, fifo::Uart0Tx);
+    queue.push(line0).ok();
+    queue.push(line1).ok();
+
+    // Refill the buffers once they're written
+    if let Some(line) = queue.pop() {
+        // ...
+    }
+  ```
+
+  ## Buffer placement
+  The DMA can't access the flash, where `const` and `static` data is placed, and starting a
+  transfer from there panics. Buffers have to be a `static mut`, which is placed in RAM, or a
+  [DmaBuffer], which can't be placed in the flash and is aligned for any item width.
+
+  ## Transfer example
+  A [Transfer] owns its buffers until it's done, so they can't be touched while the DMA uses them.
+  ```rust
+    let buffer = dma_buffer!([u8; 64] = [0; 64]).unwrap();
+
+    let transfer = Transfer::peripheral_to_memory(channels.channel2, fifo::Uart0Rx, buffer);
+    match transfer.wait() {
+        Ok((channel2, uart_rx, buffer)) => { /* the buffer is full */ }
+        Err((Error::Bus, channel2, uart_rx, buffer)) => { /* the transfer was aborted */ }
+    }
+  ```
+*/
+
+use crate::{
+    interrupts::{enable_interrupt, Interrupt},
+    pac,
+    reg::{dma, modify_reg, read_reg, write_reg},
+};
+use bl602_pac::DMA;
+#[cfg(feature = "async")]
+use core::{
+    cell::RefCell,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+use core::{
+    cell::{Cell, UnsafeCell},
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    sync::atomic::{compiler_fence, AtomicU32, Ordering},
+};
+use critical_section::Mutex;
+pub use embedded_dma::{ReadBuffer, WriteBuffer};
+use embedded_dma::{ReadTarget, WriteTarget};
+
+// The channel registers are accessed by offset, so one implementation serves all channels
+const INT_TC_STATUS: usize = 0x04;
+const INT_TC_CLEAR: usize = 0x08;
+const INT_ERROR_STATUS: usize = 0x0c;
+const INT_ERR_CLEAR: usize = 0x10;
+const RAW_INT_TC_STATUS: usize = 0x14;
+const RAW_INT_ERROR_STATUS: usize = 0x18;
+const ENABLED_CHANNELS: usize = 0x1c;
+const TOP_CONFIG: usize = 0x30;
+
+const CHANNEL_BASE: usize = 0x100;
+const CHANNEL_STRIDE: usize = 0x100;
+const SRC_ADDR: usize = 0x00;
+const DST_ADDR: usize = 0x04;
+const LLI: usize = 0x08;
+const CONTROL: usize = 0x0c;
+const CONFIG: usize = 0x10;
+
+// Control register
+const CONTROL_TRANSFER_SIZE_MASK: u32 = 0xfff;
+const CONTROL_SRC_BURST_SHIFT: u32 = 12;
+const CONTROL_DST_BURST_SHIFT: u32 = 15;
+const CONTROL_SRC_WIDTH_SHIFT: u32 = 18;
+const CONTROL_DST_WIDTH_SHIFT: u32 = 21;
+const CONTROL_SRC_INCREMENT: u32 = 1 << 26;
+const CONTROL_DST_INCREMENT: u32 = 1 << 27;
+const CONTROL_TC_INTERRUPT: u32 = 1 << 31;
+
+// Config register
+const CONFIG_ENABLE: u32 = 1 << 0;
+const CONFIG_SRC_PERIPHERAL_SHIFT: u32 = 1;
+const CONFIG_DST_PERIPHERAL_SHIFT: u32 = 6;
+const CONFIG_FLOW_CONTROL_SHIFT: u32 = 11;
+const CONFIG_ERROR_INTERRUPT_MASK: u32 = 1 << 14;
+const CONFIG_TC_INTERRUPT_MASK: u32 = 1 << 15;
+const CONFIG_ACTIVE: u32 = 1 << 17;
+const CONFIG_HALT: u32 = 1 << 18;
+
+/// Largest number of items in one transfer
+pub const MAX_TRANSFER_SIZE: u16 = 4095;
+
+/// DMA error
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Error {
+    /// The transfer was aborted by a bus error, e.g. on an invalid address
+    Bus,
+    /// The DMA moved on to the half of a circular buffer that was being accessed
+    Overrun,
+}
+
+/// Direction of a transfer, which also selects the side that controls the flow
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Direction {
+    MemoryToMemory,
+    MemoryToPeripheral,
+    PeripheralToMemory,
+    PeripheralToPeripheral,
+}
+
+impl Direction {
+    fn bits(self) -> u32 {
+        match self {
+            Direction::MemoryToMemory => 0,
+            Direction::MemoryToPeripheral => 1,
+            Direction::PeripheralToMemory => 2,
+            Direction::PeripheralToPeripheral => 3,
+        }
+    }
+}
+
+/// Width of a single item of a transfer
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Width {
+    /// 8 bits
+    Byte,
+    /// 16 bits
+    HalfWord,
+    /// 32 bits
+    Word,
+}
+
+impl Width {
+    fn bits(self) -> u32 {
+        match self {
+            Width::Byte => 0,
+            Width::HalfWord => 1,
+            Width::Word => 2,
+        }
+    }
+
+    /// Size of an item in bytes
+    pub fn bytes(self) -> usize {
+        match self {
+            Width::Byte => 1,
+            Width::HalfWord => 2,
+            Width::Word => 4,
+        }
+    }
+}
+
+/// Number of items moved per bus request
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Burst {
+    Single,
+    Incr4,
+    Incr8,
+    Incr16,
+}
+
+impl Burst {
+    fn bits(self) -> u32 {
+        match self {
+            Burst::Single => 0,
+            Burst::Incr4 => 1,
+            Burst::Incr8 => 2,
+            Burst::Incr16 => 3,
+        }
+    }
+}
+
+/// Peripheral handshake lines, which pace a transfer to the peripheral's FIFO
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Request {
+    Uart0Rx,
+    Uart0Tx,
+    Uart1Rx,
+    Uart1Tx,
+    I2cRx,
+    I2cTx,
+    SpiRx,
+    SpiTx,
+    Gpadc,
+    Gpdac,
+}
+
+impl Request {
+    fn bits(self) -> u32 {
+        match self {
+            Request::Uart0Rx => 0,
+            Request::Uart0Tx => 1,
+            Request::Uart1Rx => 2,
+            Request::Uart1Tx => 3,
+            Request::I2cRx => 6,
+            Request::I2cTx => 7,
+            Request::SpiRx => 10,
+            Request::SpiTx => 11,
+            Request::Gpadc => 22,
+            Request::Gpdac => 23,
+        }
+    }
+}
+
+/// DMA channel configuration
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Config {
+    pub direction: Direction,
+    pub src_width: Width,
+    pub dst_width: Width,
+    pub src_burst: Burst,
+    pub dst_burst: Burst,
+    pub src_increment: bool,
+    pub dst_increment: bool,
+    pub src_request: Option<Request>,
+    pub dst_request: Option<Request>,
+}
+
+impl Config {
+    /// Sets the transfer direction
+    pub fn direction(mut self, direction: Direction) -> Self {
+        self.direction = direction;
+
+        self
+    }
+
+    /// Sets the item width of both the source and the destination
+    pub fn width(mut self, width: Width) -> Self {
+        self.src_width = width;
+        self.dst_width = width;
+
+        self
+    }
+
+    /// Sets the item width the source is read with. It may differ from the destination width,
+    /// the items are then packed or unpacked by the controller.
+    pub fn src_width(mut self, width: Width) -> Self {
+        self.src_width = width;
+
+        self
+    }
+
+    /// Sets the item width the destination is written with
+    pub fn dst_width(mut self, width: Width) -> Self {
+        self.dst_width = width;
+
+        self
+    }
+
+    /// Sets the burst size of both the source and the destination
+    pub fn burst(mut self, burst: Burst) -> Self {
+        self.src_burst = burst;
+        self.dst_burst = burst;
+
+        self
+    }
+
+    /// Sets the number of items read per request. For a peripheral it shouldn't exceed the FIFO
+    /// threshold that raises the request.
+    pub fn src_burst(mut self, burst: Burst) -> Self {
+        self.src_burst = burst;
+
+        self
+    }
+
+    /// Sets the number of items written per request
+    pub fn dst_burst(mut self, burst: Burst) -> Self {
+        self.dst_burst = burst;
+
+        self
+    }
+
+    /// Sets whether the source and destination addresses increment after every item.
+    /// Peripheral FIFOs have a fixed address.
+    pub fn increment(mut self, src: bool, dst: bool) -> Self {
+        self.src_increment = src;
+        self.dst_increment = dst;
+
+        self
+    }
+
+    /// Sets the handshake of the source peripheral
+    pub fn src_request(mut self, request: Request) -> Self {
+        self.src_request = Some(request);
+
+        self
+    }
+
+    /// Sets the handshake of the destination peripheral
+    pub fn dst_request(mut self, request: Request) -> Self {
+        self.dst_request = Some(request);
+
+        self
+    }
+
+    /// Sets the handshake of the peripheral the channel reads from
+    pub fn src_peripheral(self, src: impl PeripheralSource) -> Self {
+        self.src_request(src.peripheral().request)
+    }
+
+    /// Sets the handshake of the peripheral the channel writes to
+    pub fn dst_peripheral(self, dst: impl PeripheralDestination) -> Self {
+        self.dst_request(dst.peripheral().request)
+    }
+
+    fn control_bits(&self) -> u32 {
+        let mut control = self.src_burst.bits() << CONTROL_SRC_BURST_SHIFT
+            | self.dst_burst.bits() << CONTROL_DST_BURST_SHIFT
+            | self.src_width.bits() << CONTROL_SRC_WIDTH_SHIFT
+            | self.dst_width.bits() << CONTROL_DST_WIDTH_SHIFT;
+        if self.src_increment {
+            control |= CONTROL_SRC_INCREMENT;
+        }
+        if self.dst_increment {
+            control |= CONTROL_DST_INCREMENT;
+        }
+        control
+    }
+
+    /// Panics if the addresses aren't aligned to their item width, or if `items` source items
+    /// don't fill a whole number of destination items
+    fn check(&self, src: u32, dst: u32, items: usize) {
+        check_transfer(
+            self.src_width.bytes(),
+            self.dst_width.bytes(),
+            src,
+            dst,
+            items,
+        );
+    }
+
+    fn config_bits(&self) -> u32 {
+        let src_peripheral = self.src_request.map_or(0, Request::bits);
+        let dst_peripheral = self.dst_request.map_or(0, Request::bits);
+        src_peripheral << CONFIG_SRC_PERIPHERAL_SHIFT
+            | dst_peripheral << CONFIG_DST_PERIPHERAL_SHIFT
+            | self.direction.bits() << CONFIG_FLOW_CONTROL_SHIFT
+    }
+}
+
+/// The flash is mapped here, where `const` and `static` data is placed
+const XIP_FLASH: core::ops::Range<u32> = 0x2300_0000..0x2400_0000;
+
+/// Panics if `address` isn't aligned to `bytes`, or if it's in the flash, which the DMA can't
+/// access
+fn check_address(address: u32, bytes: usize) {
+    if XIP_FLASH.contains(&address) {
+        panic!("DMA buffer in flash");
+    }
+    if address as usize % bytes != 0 {
+        panic!("Unaligned DMA address");
+    }
+}
+
+fn check_transfer(src_bytes: usize, dst_bytes: usize, src: u32, dst: u32, items: usize) {
+    check_address(src, src_bytes);
+    check_address(dst, dst_bytes);
+    if items > MAX_TRANSFER_SIZE as usize || items * src_bytes % dst_bytes != 0 {
+        panic!("Invalid DMA transfer size");
+    }
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            direction: Direction::MemoryToMemory,
+            src_width: Width::Word,
+            dst_width: Width::Word,
+            src_burst: Burst::Single,
+            dst_burst: Burst::Single,
+            src_increment: true,
+            dst_increment: true,
+            src_request: None,
+            dst_request: None,
+        }
+    }
+}
+
+/// Register access of one channel, shared by the channel types
+#[derive(Copy, Clone)]
+struct Regs {
+    index: usize,
+}
+
+impl Regs {
+    fn address(self, register: usize) -> usize {
+        dma(CHANNEL_BASE + self.index * CHANNEL_STRIDE + register)
+    }
+
+    fn mask(self) -> u32 {
+        1 << self.index
+    }
+
+    fn configure(self, config: &Config) {
+        self.disable();
+        // Keep the interrupt enables, which are configured separately
+        modify_reg(self.address(CONFIG), |r| {
+            r & (CONFIG_ERROR_INTERRUPT_MASK | CONFIG_TC_INTERRUPT_MASK) | config.config_bits()
+        });
+        // The terminal count flag is only raised with the I bit set, the interrupt is masked in
+        // the config register
+        modify_reg(self.address(CONTROL), |r| {
+            r & CONTROL_TRANSFER_SIZE_MASK | config.control_bits() | CONTROL_TC_INTERRUPT
+        });
+        write_reg(self.address(LLI), 0);
+    }
+
+    fn set_transfer_size(self, size: u16) {
+        if size > MAX_TRANSFER_SIZE {
+            panic!("Invalid DMA transfer size");
+        }
+        modify_reg(self.address(CONTROL), |r| {
+            r & !CONTROL_TRANSFER_SIZE_MASK | size as u32
+        });
+    }
+
+    /// Clears the transfer complete and error flags of the channel
+    fn clear_status(self) {
+        write_reg(dma(INT_TC_CLEAR), self.mask());
+        write_reg(dma(INT_ERR_CLEAR), self.mask());
+    }
+
+    fn enable(self) {
+        self.clear_status();
+        modify_reg(self.address(CONFIG), |r| r | CONFIG_ENABLE);
+    }
+
+    fn disable(self) {
+        modify_reg(self.address(CONFIG), |r| r & !CONFIG_ENABLE);
+    }
+
+    /// Stops accepting new requests and waits for the data in the channel FIFO to drain, before
+    /// disabling the channel
+    fn halt(self) {
+        self.pause();
+        modify_reg(self.address(CONFIG), |r| r & !(CONFIG_ENABLE | CONFIG_HALT));
+    }
+
+    fn is_enabled(self) -> bool {
+        read_reg(dma(ENABLED_CHANNELS)) & self.mask() != 0
+    }
+
+    /// Stops accepting new requests and waits for the data in the channel FIFO to drain, with the
+    /// channel left enabled
+    fn pause(self) {
+        modify_reg(self.address(CONFIG), |r| r | CONFIG_HALT);
+        while read_reg(self.address(CONFIG)) & CONFIG_ACTIVE != 0 {}
+    }
+
+    fn resume(self) {
+        modify_reg(self.address(CONFIG), |r| r & !CONFIG_HALT);
+    }
+
+    fn is_paused(self) -> bool {
+        read_reg(self.address(CONFIG)) & CONFIG_HALT != 0
+    }
+
+    /// The number of source items left, which counts down as the source is read
+    fn remaining(self) -> usize {
+        (read_reg(self.address(CONTROL)) & CONTROL_TRANSFER_SIZE_MASK) as usize
+    }
+
+    /// The configured source and destination item widths, in bytes
+    fn widths(self) -> (usize, usize) {
+        let control = read_reg(self.address(CONTROL));
+        let src = (control >> CONTROL_SRC_WIDTH_SHIFT) & 0b111;
+        let dst = (control >> CONTROL_DST_WIDTH_SHIFT) & 0b111;
+        (1 << src, 1 << dst)
+    }
+
+    /// The error that aborted the transfer, raised whether or not the interrupt is enabled. The
+    /// controller disables the channel on an error.
+    fn error(self) -> Option<Error> {
+        if read_reg(dma(RAW_INT_ERROR_STATUS)) & self.mask() != 0 {
+            Some(Error::Bus)
+        } else {
+            None
+        }
+    }
+
+    fn write_tc_interrupt(self, enable: bool) {
+        modify_reg(self.address(CONFIG), |r| {
+            if enable {
+                r | CONFIG_TC_INTERRUPT_MASK
+            } else {
+                r & !CONFIG_TC_INTERRUPT_MASK
+            }
+        });
+    }
+
+    fn write_error_interrupt(self, enable: bool) {
+        modify_reg(self.address(CONFIG), |r| {
+            if enable {
+                r | CONFIG_ERROR_INTERRUPT_MASK
+            } else {
+                r & !CONFIG_ERROR_INTERRUPT_MASK
+            }
+        });
+    }
+}
+
+macro_rules! impl_channel {
+    ($name: ident, $index: literal) => {
+        #[doc = "DMA channel "]
+        #[doc = stringify!($index)]
+        pub struct $name {
+            _private: (),
+        }
+
+        impl $name {
+            const REGS: Regs = Regs { index: $index };
+
+            /// Configures the channel, which is disabled first. The addresses and transfer size
+            /// are set separately.
+            pub fn configure(&mut self, config: &Config) {
+                Self::REGS.configure(config);
+            }
+
+            /// Sets the address the transfer reads from. Panics if it isn't aligned to the
+            /// configured source width.
+            ///
+            /// # Safety
+            ///
+            /// The address has to stay valid for reading until the transfer is complete.
+            pub unsafe fn set_source(&mut self, address: u32) {
+                check_address(address, Self::REGS.widths().0);
+                write_reg(Self::REGS.address(SRC_ADDR), address);
+            }
+
+            /// Sets the address the transfer writes to. Panics if it isn't aligned to the
+            /// configured destination width.
+            ///
+            /// # Safety
+            ///
+            /// The address has to stay valid for writing, and may not be accessed otherwise, until
+            /// the transfer is complete.
+            pub unsafe fn set_destination(&mut self, address: u32) {
+                check_address(address, Self::REGS.widths().1);
+                write_reg(Self::REGS.address(DST_ADDR), address);
+            }
+
+            /// Sets the number of items to transfer, in units of the source width.
+            /// Panics if `size` is larger than [MAX_TRANSFER_SIZE], or if the items don't fill a
+            /// whole number of destination items.
+            pub fn set_transfer_size(&mut self, size: u16) {
+                let (src_bytes, dst_bytes) = Self::REGS.widths();
+                if size as usize * src_bytes % dst_bytes != 0 {
+                    panic!("Invalid DMA transfer size");
+                }
+                Self::REGS.set_transfer_size(size);
+            }
+
+            /// Starts the transfer, after clearing the interrupt flags of the channel
+            pub fn enable(&mut self) {
+                Self::REGS.enable();
+            }
+
+            /// Stops the transfer immediately, data in the channel FIFO may be lost
+            pub fn disable(&mut self) {
+                Self::REGS.disable();
+            }
+
+            /// Stops the transfer after the data in the channel FIFO has been written
+            pub fn halt(&mut self) {
+                Self::REGS.halt();
+            }
+
+            /// Check if the channel is enabled, which it is until the transfer is complete
+            pub fn is_enabled(&self) -> bool {
+                Self::REGS.is_enabled()
+            }
+
+            /// Check if the transfer is complete. This flag is raised whether or not the
+            /// interrupt is enabled.
+            pub fn is_transfer_complete(&self) -> bool {
+                read_reg(dma(RAW_INT_TC_STATUS)) & Self::REGS.mask() != 0
+            }
+
+            /// The number of source items left to transfer
+            pub fn remaining(&self) -> usize {
+                Self::REGS.remaining()
+            }
+
+            /// Clears the transfer complete flag, which acknowledges its interrupt
+            pub fn clear_transfer_complete(&mut self) {
+                write_reg(dma(INT_TC_CLEAR), Self::REGS.mask());
+            }
+
+            /// Check if the transfer was aborted by a bus error
+            pub fn is_error(&self) -> bool {
+                self.error().is_some()
+            }
+
+            /// The error that aborted the transfer, if any
+            pub fn error(&self) -> Option<Error> {
+                Self::REGS.error()
+            }
+
+            /// Clears the error flag, which acknowledges its interrupt
+            pub fn clear_error(&mut self) {
+                write_reg(dma(INT_ERR_CLEAR), Self::REGS.mask());
+            }
+
+            /// Enable the interrupt raised when the transfer is complete.
+            pub fn enable_transfer_complete_interrupt(&mut self) {
+                Self::REGS.write_tc_interrupt(true);
+            }
+
+            /// Disable the interrupt raised when the transfer is complete.
+            pub fn disable_transfer_complete_interrupt(&mut self) {
+                Self::REGS.write_tc_interrupt(false);
+            }
+
+            /// Enable the interrupt raised on a bus error.
+            pub fn enable_error_interrupt(&mut self) {
+                Self::REGS.write_error_interrupt(true);
+            }
+
+            /// Disable the interrupt raised on a bus error.
+            pub fn disable_error_interrupt(&mut self) {
+                Self::REGS.write_error_interrupt(false);
+            }
+        }
+    };
+}
+
+impl_channel!(Channel0, 0);
+impl_channel!(Channel1, 1);
+impl_channel!(Channel2, 2);
+impl_channel!(Channel3, 3);
+
+/// A DMA channel, for functions that work with any channel - DO NOT IMPLEMENT THIS TRAIT
+pub trait DmaChannel: private::Sealed {
+    /// Arbitration priority of the channel, 0 is the highest. The priority is fixed by the
+    /// channel number and can't be changed, see [Priority](index.html#priority).
+    fn priority(&self) -> u8 {
+        Self::INDEX as u8
+    }
+}
+
+impl DmaChannel for Channel0 {}
+impl DmaChannel for Channel1 {}
+impl DmaChannel for Channel2 {}
+impl DmaChannel for Channel3 {}
+
+mod private {
+    use super::Width;
+
+    pub trait Sealed {
+        const INDEX: usize;
+    }
+
+    pub trait SealedPeripheral {
+        fn peripheral(&self) -> super::Peripheral;
+    }
+
+    pub trait SealedItem {
+        const WIDTH: Width;
+    }
+
+    impl SealedItem for u8 {
+        const WIDTH: Width = Width::Byte;
+    }
+    impl SealedItem for u16 {
+        const WIDTH: Width = Width::HalfWord;
+    }
+    impl SealedItem for u32 {
+        const WIDTH: Width = Width::Word;
+    }
+
+    impl Sealed for super::Channel0 {
+        const INDEX: usize = 0;
+    }
+    impl Sealed for super::Channel1 {
+        const INDEX: usize = 1;
+    }
+    impl Sealed for super::Channel2 {
+        const INDEX: usize = 2;
+    }
+    impl Sealed for super::Channel3 {
+        const INDEX: usize = 3;
+    }
+}
+
+/// Progress of a memory copy, which may take several transfers
+#[derive(Copy, Clone)]
+struct CopyState {
+    src: u32,
+    dst: u32,
+    remaining: usize,
+    width: Width,
+    fill: bool,
+    on_done: Option<fn()>,
+    done: bool,
+    error: bool,
+}
+
+impl CopyState {
+    fn new(src: u32, dst: u32, len: usize, on_done: Option<fn()>) -> Self {
+        check_address(src, 1);
+        check_address(dst, 1);
+
+        // The widest item width the addresses and the length are aligned for
+        let alignment = src | dst | len as u32;
+        let width = if alignment & 0b11 == 0 {
+            Width::Word
+        } else if alignment & 0b1 == 0 {
+            Width::HalfWord
+        } else {
+            Width::Byte
+        };
+
+        CopyState {
+            src,
+            dst,
+            remaining: len,
+            width,
+            fill: false,
+            on_done,
+            done: false,
+            error: false,
+        }
+    }
+
+    fn config(&self) -> Config {
+        Config::default()
+            .width(self.width)
+            .burst(Burst::Incr4)
+            .increment(!self.fill, true)
+    }
+
+    /// Starts the next transfer of up to [MAX_TRANSFER_SIZE] items
+    fn start_chunk(&mut self, regs: Regs) {
+        let items = (self.remaining / self.width.bytes()).min(MAX_TRANSFER_SIZE as usize);
+        let bytes = items * self.width.bytes();
+
+        write_reg(regs.address(SRC_ADDR), self.src);
+        write_reg(regs.address(DST_ADDR), self.dst);
+        regs.set_transfer_size(items as u16);
+        compiler_fence(Ordering::SeqCst);
+        regs.enable();
+
+        if !self.fill {
+            self.src += bytes as u32;
+        }
+        self.dst += bytes as u32;
+        self.remaining -= bytes;
+    }
+}
+
+/// Memory copies completed from the DMA interrupt, indexed by channel
+static COPIES: Mutex<Cell<[Option<CopyState>; 4]>> = Mutex::new(Cell::new([None; 4]));
+
+/// Tasks waiting for a transfer, indexed by channel
+#[cfg(feature = "async")]
+static WAKERS: Mutex<RefCell<[Option<Waker>; 4]>> =
+    Mutex::new(RefCell::new([None, None, None, None]));
+
+/// Wakes the tasks waiting for the channels in `pending`, and masks their interrupts, the raw
+/// flags stay set for the task to read. Returns the channels that had a task waiting.
+#[cfg(feature = "async")]
+fn wake_tasks(pending: u32) -> u32 {
+    critical_section::with(|cs| {
+        let mut wakers = WAKERS.borrow(cs).borrow_mut();
+        let mut handled = 0;
+        for (index, waker) in wakers.iter_mut().enumerate() {
+            let regs = Regs { index };
+            if pending & regs.mask() == 0 {
+                continue;
+            }
+            if let Some(waker) = waker.take() {
+                regs.write_tc_interrupt(false);
+                regs.write_error_interrupt(false);
+                waker.wake();
+                handled |= regs.mask();
+            }
+        }
+        handled
+    })
+}
+
+/// Completes once the transfer on a channel is done, or was aborted by an error
+#[cfg(feature = "async")]
+struct DoneFuture {
+    regs: Regs,
+}
+
+#[cfg(feature = "async")]
+impl DoneFuture {
+    fn new(index: usize) -> Self {
+        enable_interrupt(Interrupt::Dma);
+        DoneFuture {
+            regs: Regs { index },
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        (read_reg(dma(RAW_INT_TC_STATUS)) | read_reg(dma(RAW_INT_ERROR_STATUS))) & self.regs.mask()
+            != 0
+    }
+}
+
+#[cfg(feature = "async")]
+impl Future for DoneFuture {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.is_done() {
+            return Poll::Ready(());
+        }
+
+        critical_section::with(|cs| {
+            WAKERS.borrow(cs).borrow_mut()[self.regs.index] = Some(cx.waker().clone());
+            self.regs.write_tc_interrupt(true);
+            self.regs.write_error_interrupt(true);
+        });
+
+        // The transfer may have completed before the interrupts were unmasked
+        if self.is_done() {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl Drop for DoneFuture {
+    fn drop(&mut self) {
+        critical_section::with(|cs| {
+            WAKERS.borrow(cs).borrow_mut()[self.regs.index] = None;
+            self.regs.write_tc_interrupt(false);
+            self.regs.write_error_interrupt(false);
+        });
+    }
+}
+
+/// Continues the memory copies of `mem_copy_with_interrupt()`, and calls their callbacks once
+/// they're done, and calls the callbacks of circular transfers for every completed half.
+/// Returns `false` if any interrupt is left for the user's `Dma` handler.
+pub(crate) fn dispatch_interrupt() -> bool {
+    let tc = read_reg(dma(INT_TC_STATUS));
+    let err = read_reg(dma(INT_ERROR_STATUS));
+    let mut unhandled = (tc | err) & 0b1111;
+    let mut callbacks: [Option<fn()>; 4] = [None; 4];
+    let mut half_callbacks: [Option<(fn(Half), Half)>; 4] = [None; 4];
+
+    critical_section::with(|cs| {
+        let cell = QUEUES.borrow(cs);
+        let mut queues = cell.get();
+        for (index, queue) in queues.iter_mut().enumerate() {
+            let regs = Regs { index };
+            let queue = match queue {
+                Some(queue) if (tc | err) & regs.mask() != 0 => queue,
+                _ => continue,
+            };
+
+            unhandled &= !regs.mask();
+            write_reg(dma(INT_TC_CLEAR), regs.mask());
+            write_reg(dma(INT_ERR_CLEAR), regs.mask());
+
+            if err & regs.mask() != 0 {
+                queue.error = true;
+                queue.busy = false;
+                regs.disable();
+            } else {
+                queue.completed = queue.completed.wrapping_add(1);
+                match queue.next.take() {
+                    Some((src, items)) => queue.start(regs, src, items),
+                    None => queue.busy = false,
+                }
+            }
+        }
+        cell.set(queues);
+    });
+
+    critical_section::with(|cs| {
+        let cell = CIRCULARS.borrow(cs);
+        let mut circulars = cell.get();
+        for (index, circular) in circulars.iter_mut().enumerate() {
+            let regs = Regs { index };
+            let circular = match circular {
+                Some(circular) if !circular.error && (tc | err) & regs.mask() != 0 => circular,
+                _ => continue,
+            };
+
+            unhandled &= !regs.mask();
+            write_reg(dma(INT_TC_CLEAR), regs.mask());
+            write_reg(dma(INT_ERR_CLEAR), regs.mask());
+
+            if err & regs.mask() != 0 {
+                circular.error = true;
+                regs.disable();
+                regs.write_tc_interrupt(false);
+                regs.write_error_interrupt(false);
+            } else {
+                half_callbacks[index] = circular.on_done.map(|f| (f, circular.next));
+                circular.next = circular.next.other();
+            }
+        }
+        cell.set(circulars);
+    });
+
+    critical_section::with(|cs| {
+        let cell = COPIES.borrow(cs);
+        let mut copies = cell.get();
+        for (index, copy) in copies.iter_mut().enumerate() {
+            let regs = Regs { index };
+            let copy = match copy {
+                Some(copy) if !copy.done && (tc | err) & regs.mask() != 0 => copy,
+                _ => continue,
+            };
+
+            unhandled &= !regs.mask();
+            write_reg(dma(INT_TC_CLEAR), regs.mask());
+            write_reg(dma(INT_ERR_CLEAR), regs.mask());
+
+            if err & regs.mask() != 0 {
+                copy.error = true;
+                copy.done = true;
+            } else if copy.remaining > 0 {
+                copy.start_chunk(regs);
+            } else {
+                copy.done = true;
+            }
+
+            if copy.done {
+                regs.write_tc_interrupt(false);
+                regs.write_error_interrupt(false);
+                callbacks[index] = copy.on_done;
+            }
+        }
+        cell.set(copies);
+    });
+
+    for callback in callbacks.iter().flatten() {
+        callback();
+    }
+    for (callback, half) in half_callbacks.iter().flatten() {
+        callback(*half);
+    }
+
+    #[cfg(feature = "async")]
+    {
+        unhandled &= !wake_tasks(unhandled);
+    }
+
+    unhandled == 0
+}
+
+/// Copies `src` to `dst` with the DMA, blocking until the copy is done. Panics if the lengths of
+/// the buffers differ.
+pub fn mem_copy<CH: DmaChannel>(
+    _channel: &mut CH,
+    src: &[u8],
+    dst: &mut [u8],
+) -> Result<(), Error> {
+    if src.len() != dst.len() {
+        panic!("DMA buffer lengths differ");
+    }
+
+    let copy = CopyState::new(
+        src.as_ptr() as u32,
+        dst.as_mut_ptr() as u32,
+        src.len(),
+        None,
+    );
+    run_blocking(Regs { index: CH::INDEX }, copy)
+}
+
+/// Smallest copies of word aligned buffers that are faster with the DMA than with the CPU, see
+/// the `dma_copy_benchmark` example
+const DMA_COPY_MIN_WORDS: usize = 256;
+
+/// Smallest copies of half-word aligned buffers that are faster with the DMA than with the CPU
+const DMA_COPY_MIN_HALF_WORDS: usize = 512;
+
+/// Copies `src` to `dst` with whichever is faster, the CPU or the DMA, blocking until the copy is
+/// done. Panics if the lengths of the buffers differ.
+///
+/// Setting up the DMA costs more than copying a small buffer with the CPU, and the DMA is slow
+/// with byte wide items, so the DMA is only used for large buffers that are aligned to at least
+/// a half-word. The CPU also copies buffers in the flash, and finishes a copy the DMA aborted
+/// with a bus error.
+pub fn copy_best_effort<CH: DmaChannel>(channel: &mut CH, src: &[u8], dst: &mut [u8]) {
+    if src.len() != dst.len() {
+        panic!("DMA buffer lengths differ");
+    }
+
+    let alignment = src.as_ptr() as usize | dst.as_ptr() as usize | src.len();
+    let use_dma = if XIP_FLASH.contains(&(src.as_ptr() as u32)) {
+        false
+    } else if alignment & 0b11 == 0 {
+        src.len() / 4 >= DMA_COPY_MIN_WORDS
+    } else if alignment & 0b1 == 0 {
+        src.len() / 2 >= DMA_COPY_MIN_HALF_WORDS
+    } else {
+        false
+    };
+
+    if !use_dma || mem_copy(channel, src, dst).is_err() {
+        dst.copy_from_slice(src);
+    }
+}
+
+/// Fills `dst` with `value` with the DMA, blocking until it's done. The value is read from a
+/// word the source address doesn't increment from, and written with the widest item width
+/// `dst` is aligned for, so clearing large buffers like framebuffers is fast.
+pub fn mem_fill<CH: DmaChannel>(_channel: &mut CH, value: u8, dst: &mut [u8]) -> Result<(), Error> {
+    let src = fill_word(CH::INDEX, u32::from_ne_bytes([value; 4]));
+    let mut copy = CopyState::new(src, dst.as_mut_ptr() as u32, dst.len(), None);
+    copy.fill = true;
+    run_blocking(Regs { index: CH::INDEX }, copy)
+}
+
+/// Words fill transfers read their value from, indexed by channel
+static FILL_WORDS: [AtomicU32; 4] = [
+    AtomicU32::new(0),
+    AtomicU32::new(0),
+    AtomicU32::new(0),
+    AtomicU32::new(0),
+];
+
+/// Stores `value` as the fill word of a channel, and returns its address
+fn fill_word(index: usize, value: u32) -> u32 {
+    FILL_WORDS[index].store(value, Ordering::Relaxed);
+    compiler_fence(Ordering::SeqCst);
+    &FILL_WORDS[index] as *const AtomicU32 as u32
+}
+
+/// Runs a memory copy or fill, blocking until it's done
+fn run_blocking(regs: Regs, mut copy: CopyState) -> Result<(), Error> {
+    regs.configure(&copy.config());
+    regs.write_tc_interrupt(false);
+    regs.write_error_interrupt(false);
+
+    while copy.remaining > 0 {
+        copy.start_chunk(regs);
+        while read_reg(dma(RAW_INT_TC_STATUS)) & regs.mask() == 0 {
+            if let Some(error) = regs.error() {
+                regs.disable();
+                write_reg(dma(INT_ERR_CLEAR), regs.mask());
+                return Err(error);
+            }
+        }
+        write_reg(dma(INT_TC_CLEAR), regs.mask());
+    }
+    compiler_fence(Ordering::SeqCst);
+
+    Ok(())
+}
+
+/// A memory copy completed from the DMA interrupt, started with `mem_copy_with_interrupt()`.
+/// It owns the buffers until the copy is done. Dropping it stops the copy.
+pub struct MemCopy<CH: DmaChannel, S, D> {
+    parts: Option<(CH, S, D)>,
+}
+
+impl<CH: DmaChannel, S, D> MemCopy<CH, S, D> {
+    /// Check if the copy is done, or was aborted by an error
+    pub fn is_done(&self) -> bool {
+        match copy_state(CH::INDEX) {
+            Some(copy) => copy.done,
+            None => true,
+        }
+    }
+
+    /// Blocks until the copy is done
+    pub fn wait(&self) -> Result<(), Error> {
+        while !self.is_done() {}
+        compiler_fence(Ordering::SeqCst);
+
+        match copy_state(CH::INDEX) {
+            Some(copy) if copy.error => Err(Error::Bus),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the channel and the buffers, stopping the copy if it isn't done yet
+    pub fn free(mut self) -> (CH, S, D) {
+        stop_copy(CH::INDEX);
+        self.parts.take().unwrap()
+    }
+}
+
+impl<CH: DmaChannel, S, D> Drop for MemCopy<CH, S, D> {
+    fn drop(&mut self) {
+        if self.parts.is_some() {
+            stop_copy(CH::INDEX);
+        }
+    }
+}
+
+fn copy_state(index: usize) -> Option<CopyState> {
+    critical_section::with(|cs| COPIES.borrow(cs).get()[index])
+}
+
+fn stop_copy(index: usize) {
+    let regs = Regs { index };
+    critical_section::with(|cs| {
+        let cell = COPIES.borrow(cs);
+        let mut copies = cell.get();
+        copies[index] = None;
+        cell.set(copies);
+    });
+    regs.halt();
+    regs.write_tc_interrupt(false);
+    regs.write_error_interrupt(false);
+    compiler_fence(Ordering::SeqCst);
+}
+
+/// Starts copying `src` to `dst` with the DMA, continuing from the DMA interrupt, which is
+/// enabled. `on_done` is called from the interrupt once the copy is done. Panics if the lengths of
+/// the buffers differ.
+pub fn mem_copy_with_interrupt<CH, S, D>(
+    channel: CH,
+    src: S,
+    mut dst: D,
+    on_done: Option<fn()>,
+) -> MemCopy<CH, S, D>
+where
+    CH: DmaChannel,
+    S: ReadBuffer<Word = u8>,
+    D: WriteBuffer<Word = u8>,
+{
+    let (src_ptr, src_len) = unsafe { src.read_buffer() };
+    let (dst_ptr, dst_len) = unsafe { dst.write_buffer() };
+    if src_len != dst_len {
+        panic!("DMA buffer lengths differ");
+    }
+
+    let regs = Regs { index: CH::INDEX };
+    let mut copy = CopyState::new(src_ptr as u32, dst_ptr as u32, src_len, on_done);
+    regs.configure(&copy.config());
+    regs.write_tc_interrupt(true);
+    regs.write_error_interrupt(true);
+
+    critical_section::with(|cs| {
+        if copy.remaining > 0 {
+            copy.start_chunk(regs);
+        } else {
+            copy.done = true;
+        }
+        let cell = COPIES.borrow(cs);
+        let mut copies = cell.get();
+        copies[CH::INDEX] = Some(copy);
+        cell.set(copies);
+    });
+    enable_interrupt(Interrupt::Dma);
+
+    MemCopy {
+        parts: Some((channel, src, dst)),
+    }
+}
+
+/// A buffer the DMA can access, aligned to a word so any item width can be used.
+///
+/// `const` and `static` data is placed in the flash, which the DMA can't access. A `DmaBuffer`
+/// can't be placed there: it can't be shared between threads, so it can't be a `static`, and a
+/// reference to a `const` one doesn't live long enough for a transfer. It can only be a
+/// `static mut`, which is placed in RAM, e.g. with [dma_buffer!](crate::dma_buffer).
+#[repr(C, align(4))]
+pub struct DmaBuffer<T> {
+    buffer: UnsafeCell<T>,
+}
+
+impl<T> DmaBuffer<T> {
+    /// A buffer initialized to `buffer`
+    pub const fn new(buffer: T) -> Self {
+        DmaBuffer {
+            buffer: UnsafeCell::new(buffer),
+        }
+    }
+}
+
+impl<T> Deref for DmaBuffer<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.buffer.get() }
+    }
+}
+
+impl<T> DerefMut for DmaBuffer<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.buffer.get_mut()
+    }
+}
+
+unsafe impl<W, const N: usize> ReadTarget for DmaBuffer<[W; N]>
+where
+    W: Item + embedded_dma::Word,
+{
+    type Word = W;
+
+    fn as_read_buffer(&self) -> (*const W, usize) {
+        (self.as_ptr(), N)
+    }
+}
+
+unsafe impl<W, const N: usize> WriteTarget for DmaBuffer<[W; N]>
+where
+    W: Item + embedded_dma::Word,
+{
+    type Word = W;
+
+    fn as_write_buffer(&mut self) -> (*mut W, usize) {
+        (self.as_mut_ptr(), N)
+    }
+}
+
+/// Creates a [DmaBuffer](crate::dma::DmaBuffer) in RAM, and returns a `&'static mut` reference to
+/// it the first time it's run, and `None` after that.
+///
+/// ```rust
+///     let buffer: &'static mut DmaBuffer<[u8; 64]> = dma_buffer!([u8; 64] = [0; 64]).unwrap();
+/// ```
+#[macro_export]
+macro_rules! dma_buffer {
+    ($ty: ty = $init: expr) => {{
+        static mut BUFFER: $crate::dma::DmaBuffer<$ty> = $crate::dma::DmaBuffer::new($init);
+        static TAKEN: core::sync::atomic::AtomicBool = core::sync::atomic::AtomicBool::new(false);
+
+        if TAKEN.swap(true, core::sync::atomic::Ordering::AcqRel) {
+            None
+        } else {
+            Some(unsafe { &mut BUFFER })
+        }
+    }};
+}
+
+/// Items of a DMA buffer: `u8`, `u16` or `u32` - DO NOT IMPLEMENT THIS TRAIT
+pub trait Item: private::SealedItem {}
+
+impl Item for u8 {}
+impl Item for u16 {}
+impl Item for u32 {}
+
+/// A peripheral data register a transfer reads from or writes to, paced by the handshake of the
+/// peripheral
+#[derive(Copy, Clone, Debug)]
+pub struct Peripheral {
+    address: u32,
+    request: Request,
+}
+
+impl Peripheral {
+    /// A peripheral data register at `address`, with the handshake `request`.
+    ///
+    /// # Safety
+    ///
+    /// `address` has to be the data register, usually a FIFO, of the peripheral the handshake
+    /// belongs to, as the DMA reads or writes it without further checks.
+    pub unsafe fn new(address: u32, request: Request) -> Self {
+        Peripheral { address, request }
+    }
+}
+
+/// A peripheral a transfer can read from: a [Peripheral], or one of the FIFOs in [fifo]
+/// - DO NOT IMPLEMENT THIS TRAIT
+pub trait PeripheralSource: private::SealedPeripheral {}
+
+/// A peripheral a transfer can write to: a [Peripheral], or one of the FIFOs in [fifo]
+/// - DO NOT IMPLEMENT THIS TRAIT
+pub trait PeripheralDestination: private::SealedPeripheral {}
+
+impl private::SealedPeripheral for Peripheral {
+    fn peripheral(&self) -> Peripheral {
+        *self
+    }
+}
+
+impl PeripheralSource for Peripheral {}
+impl PeripheralDestination for Peripheral {}
+
+/// The peripheral FIFOs with a DMA handshake, with their data register and request line, so a
+/// transfer can't pair the address of one peripheral with the handshake of another, or read from
+/// a transmit FIFO.
+pub mod fifo {
+    use super::{private, Peripheral, PeripheralDestination, PeripheralSource, Request};
+
+    macro_rules! impl_fifo {
+        ($name: ident, $address: literal, $request: ident, $direction: ident, $doc: literal) => {
+            #[doc = $doc]
+            #[derive(Copy, Clone, Debug)]
+            pub struct $name;
+
+            impl private::SealedPeripheral for $name {
+                fn peripheral(&self) -> Peripheral {
+                    Peripheral {
+                        address: $address,
+                        request: Request::$request,
+                    }
+                }
+            }
+
+            impl $direction for $name {}
+        };
+    }
+
+    impl_fifo!(
+        Uart0Rx,
+        0x4000_a08c,
+        Uart0Rx,
+        PeripheralSource,
+        "UART0 receive FIFO"
+    );
+    impl_fifo!(
+        Uart0Tx,
+        0x4000_a088,
+        Uart0Tx,
+        PeripheralDestination,
+        "UART0 transmit FIFO"
+    );
+    impl_fifo!(
+        Uart1Rx,
+        0x4000_a18c,
+        Uart1Rx,
+        PeripheralSource,
+        "UART1 receive FIFO"
+    );
+    impl_fifo!(
+        Uart1Tx,
+        0x4000_a188,
+        Uart1Tx,
+        PeripheralDestination,
+        "UART1 transmit FIFO"
+    );
+    impl_fifo!(
+        SpiRx,
+        0x4000_a28c,
+        SpiRx,
+        PeripheralSource,
+        "SPI receive FIFO"
+    );
+    impl_fifo!(
+        SpiTx,
+        0x4000_a288,
+        SpiTx,
+        PeripheralDestination,
+        "SPI transmit FIFO"
+    );
+    impl_fifo!(
+        I2cRx,
+        0x4000_a38c,
+        I2cRx,
+        PeripheralSource,
+        "I2C receive FIFO"
+    );
+    impl_fifo!(
+        I2cTx,
+        0x4000_a388,
+        I2cTx,
+        PeripheralDestination,
+        "I2C transmit FIFO"
+    );
+    impl_fifo!(
+        Gpadc,
+        0x4000_2004,
+        Gpadc,
+        PeripheralSource,
+        "ADC result FIFO"
+    );
+    impl_fifo!(
+        Gpdac,
+        0x4000_2048,
+        Gpdac,
+        PeripheralDestination,
+        "DAC sample FIFO"
+    );
+}
+
+/// A single DMA transfer, which owns its buffers until it's done, so they can't be used or freed
+/// while the DMA accesses them. Dropping it stops the transfer.
+///
+/// The buffers are [ReadBuffer]s and [WriteBuffer]s of the `embedded-dma` crate, e.g.
+/// `&'static mut [u8]` or a buffer in a static singleton, so a buffer on the stack can't outlive
+/// its stack frame while the DMA still writes to it.
+pub struct Transfer<CH: DmaChannel, S, D> {
+    parts: Option<(CH, S, D)>,
+    items: usize,
+}
+
+impl<CH, S, D> Transfer<CH, S, D>
+where
+    CH: DmaChannel,
+    S: ReadBuffer,
+    S::Word: Item,
+    D: WriteBuffer<Word = S::Word>,
+{
+    /// Starts copying `src` to `dst`. Panics if the lengths of the buffers differ, or if they're
+    /// longer than [MAX_TRANSFER_SIZE] items.
+    pub fn memory_to_memory(channel: CH, src: S, mut dst: D) -> Self {
+        let (src_ptr, src_len) = unsafe { src.read_buffer() };
+        let (dst_ptr, dst_len) = unsafe { dst.write_buffer() };
+        if src_len != dst_len {
+            panic!("DMA buffer lengths differ");
+        }
+
+        let config = Config::default().width(S::Word::WIDTH);
+        start::<CH>(&config, src_ptr as u32, dst_ptr as u32, src_len);
+        Transfer {
+            parts: Some((channel, src, dst)),
+            items: src_len,
+        }
+    }
+}
+
+impl<CH, W, D> Transfer<CH, W, D>
+where
+    CH: DmaChannel,
+    W: Item + Into<u32>,
+    D: WriteBuffer<Word = W>,
+{
+    /// Starts filling `dst` with `value`, which is read by the DMA from a word that the source
+    /// address doesn't increment from. Panics if the buffer is longer than [MAX_TRANSFER_SIZE]
+    /// items.
+    pub fn fill(channel: CH, value: W, mut dst: D) -> Self {
+        let (dst_ptr, dst_len) = unsafe { dst.write_buffer() };
+
+        // Little endian, so the narrower items read the low part of the word
+        let src = fill_word(CH::INDEX, value.into());
+        let config = Config::default().width(W::WIDTH).increment(false, true);
+        start::<CH>(&config, src, dst_ptr as u32, dst_len);
+        Transfer {
+            parts: Some((channel, value, dst)),
+            items: dst_len,
+        }
+    }
+}
+
+impl<CH, S> Transfer<CH, S, Peripheral>
+where
+    CH: DmaChannel,
+    S: ReadBuffer,
+    S::Word: Item,
+{
+    /// Starts writing `src` to the peripheral `dst`. Panics if the buffer is longer than
+    /// [MAX_TRANSFER_SIZE] items.
+    pub fn memory_to_peripheral(channel: CH, src: S, dst: impl PeripheralDestination) -> Self {
+        let dst = dst.peripheral();
+        let (src_ptr, src_len) = unsafe { src.read_buffer() };
+
+        let config = Config::default()
+            .direction(Direction::MemoryToPeripheral)
+            .width(S::Word::WIDTH)
+            .increment(true, false)
+            .dst_request(dst.request);
+        start::<CH>(&config, src_ptr as u32, dst.address, src_len);
+        Transfer {
+            parts: Some((channel, src, dst)),
+            items: src_len,
+        }
+    }
+}
+
+impl<CH, D> Transfer<CH, Peripheral, D>
+where
+    CH: DmaChannel,
+    D: WriteBuffer,
+    D::Word: Item,
+{
+    /// Starts filling `dst` from the peripheral `src`. Panics if the buffer is longer than
+    /// [MAX_TRANSFER_SIZE] items.
+    pub fn peripheral_to_memory(channel: CH, src: impl PeripheralSource, mut dst: D) -> Self {
+        let src = src.peripheral();
+        let (dst_ptr, dst_len) = unsafe { dst.write_buffer() };
+
+        let config = Config::default()
+            .direction(Direction::PeripheralToMemory)
+            .width(D::Word::WIDTH)
+            .increment(false, true)
+            .src_request(src.request);
+        start::<CH>(&config, src.address, dst_ptr as u32, dst_len);
+        Transfer {
+            parts: Some((channel, src, dst)),
+            items: dst_len,
+        }
+    }
+}
+
+impl<CH: DmaChannel, S, D> Transfer<CH, S, D> {
+    /// Check if the transfer is done, or was aborted by an error
+    pub fn is_done(&self) -> bool {
+        let regs = Regs { index: CH::INDEX };
+        read_reg(dma(RAW_INT_TC_STATUS)) & regs.mask() != 0 || self.is_error()
+    }
+
+    /// Check if the transfer was aborted by a bus error
+    pub fn is_error(&self) -> bool {
+        self.error().is_some()
+    }
+
+    /// The error that aborted the transfer, if any
+    pub fn error(&self) -> Option<Error> {
+        Regs { index: CH::INDEX }.error()
+    }
+
+    /// Blocks until the transfer is done, and returns the channel and the buffers. If the
+    /// transfer was aborted by an error, they're returned along with the error.
+    pub fn wait(mut self) -> Result<(CH, S, D), (Error, CH, S, D)> {
+        while !self.is_done() {}
+        let error = self.error();
+        let (channel, src, dst) = self.finish();
+        match error {
+            Some(error) => Err((error, channel, src, dst)),
+            None => Ok((channel, src, dst)),
+        }
+    }
+
+    /// Waits for the transfer without blocking, the task is woken from the DMA interrupt, which
+    /// is enabled. Dropping the future stops the transfer.
+    #[cfg(feature = "async")]
+    pub async fn wait_async(self) -> Result<(CH, S, D), (Error, CH, S, D)> {
+        DoneFuture::new(CH::INDEX).await;
+        self.wait()
+    }
+
+    /// The number of items left to transfer, e.g. to show progress or for flow control. It
+    /// counts the items read from the source, some of which may still be in the channel FIFO.
+    pub fn remaining(&self) -> usize {
+        Regs { index: CH::INDEX }.remaining()
+    }
+
+    /// Pauses the transfer: the channel stops accepting requests and the data in the channel
+    /// FIFO is written out before this returns. Waiting for a paused transfer blocks until it's
+    /// resumed.
+    pub fn pause(&mut self) {
+        Regs { index: CH::INDEX }.pause();
+    }
+
+    /// Resumes a paused transfer
+    pub fn resume(&mut self) {
+        Regs { index: CH::INDEX }.resume();
+    }
+
+    /// Check if the transfer is paused
+    pub fn is_paused(&self) -> bool {
+        Regs { index: CH::INDEX }.is_paused()
+    }
+
+    /// Stops the transfer after the data in the channel FIFO has been written, and returns the
+    /// channel, the buffers and the number of items that were transferred
+    pub fn abort(mut self) -> (CH, S, D, usize) {
+        let (channel, src, dst) = self.finish();
+        (channel, src, dst, self.items - self.remaining())
+    }
+
+    fn finish(&mut self) -> (CH, S, D) {
+        let regs = Regs { index: CH::INDEX };
+        regs.halt();
+        write_reg(dma(INT_TC_CLEAR), regs.mask());
+        write_reg(dma(INT_ERR_CLEAR), regs.mask());
+        compiler_fence(Ordering::SeqCst);
+        self.parts.take().unwrap()
+    }
+}
+
+impl<CH: DmaChannel, S, D> Drop for Transfer<CH, S, D> {
+    fn drop(&mut self) {
+        if self.parts.is_some() {
+            self.finish();
+        }
+    }
+}
+
+/// Configures the channel and starts a single transfer of `items` items
+fn start<CH: DmaChannel>(config: &Config, src: u32, dst: u32, items: usize) {
+    config.check(src, dst, items);
+
+    let regs = Regs { index: CH::INDEX };
+    regs.configure(config);
+    regs.write_tc_interrupt(false);
+    regs.write_error_interrupt(false);
+    write_reg(regs.address(SRC_ADDR), src);
+    write_reg(regs.address(DST_ADDR), dst);
+    regs.set_transfer_size(items as u16);
+    compiler_fence(Ordering::SeqCst);
+    regs.enable();
+}
+
+/// A linked list item (LLI), one link of a chained transfer. The DMA loads the next item from
+/// memory when a link is done, so the items have to stay in place until the transfer is done.
+#[derive(Copy, Clone, Debug, Default)]
+#[repr(C)]
+pub struct Descriptor {
+    src: u32,
+    dst: u32,
+    next: u32,
+    control: u32,
+}
+
+impl Descriptor {
+    /// An unused descriptor, to initialize a static array of descriptors
+    pub const EMPTY: Descriptor = Descriptor {
+        src: 0,
+        dst: 0,
+        next: 0,
+        control: 0,
+    };
+}
+
+/// Builds a chain of buffers streamed in one hardware transfer, e.g. a header and a payload
+/// written to a UART or SPI FIFO without copying them together first.
+///
+/// Every link is described by a [Descriptor] in `descriptors`, and buffers longer than
+/// [MAX_TRANSFER_SIZE] items take several links.
+pub struct Chain<W: Item> {
+    descriptors: &'static mut [Descriptor],
+    len: usize,
+    config: Config,
+    src: Option<Peripheral>,
+    dst: Option<Peripheral>,
+    _item: PhantomData<W>,
+}
+
+impl<W: Item> Chain<W> {
+    /// A chain of buffers written to the peripheral `dst`
+    pub fn memory_to_peripheral(
+        descriptors: &'static mut [Descriptor],
+        dst: impl PeripheralDestination,
+    ) -> Self {
+        let dst = dst.peripheral();
+        let config = Config::default()
+            .direction(Direction::MemoryToPeripheral)
+            .width(W::WIDTH)
+            .increment(true, false)
+            .dst_request(dst.request);
+        Chain {
+            descriptors,
+            len: 0,
+            config,
+            src: None,
+            dst: Some(dst),
+            _item: PhantomData,
+        }
+    }
+
+    /// A chain of memory to memory copies
+    pub fn memory_to_memory(descriptors: &'static mut [Descriptor]) -> Self {
+        Chain {
+            descriptors,
+            len: 0,
+            config: Config::default().width(W::WIDTH),
+            src: None,
+            dst: None,
+            _item: PhantomData,
+        }
+    }
+
+    /// A chain of buffers filled from the peripheral `src`
+    pub fn peripheral_to_memory(
+        descriptors: &'static mut [Descriptor],
+        src: impl PeripheralSource,
+    ) -> Self {
+        let src = src.peripheral();
+        let config = Config::default()
+            .direction(Direction::PeripheralToMemory)
+            .width(W::WIDTH)
+            .increment(false, true)
+            .src_request(src.request);
+        Chain {
+            descriptors,
+            len: 0,
+            config,
+            src: Some(src),
+            dst: None,
+            _item: PhantomData,
+        }
+    }
+
+    /// Sets the burst size of both the source and the destination
+    pub fn burst(mut self, burst: Burst) -> Self {
+        self.config = self.config.burst(burst);
+        self
+    }
+
+    /// Appends `buffer` to a chain written to a peripheral. Panics if the descriptors run out,
+    /// or if the buffer is in the flash.
+    pub fn push(mut self, buffer: &'static [W]) -> Self {
+        let dst = match self.dst {
+            Some(dst) => dst.address,
+            None => panic!("DMA chain doesn't write to a peripheral"),
+        };
+        unsafe { self.push_raw(buffer.as_ptr() as u32, dst, buffer.len()) };
+        self
+    }
+
+    /// Appends a link moving `items` items from `src` to `dst`, where the address of a peripheral
+    /// side is ignored. Panics if the descriptors run out.
+    ///
+    /// # Safety
+    ///
+    /// The memory at `src` and `dst` has to stay valid, and `dst` may not be accessed otherwise,
+    /// until the transfer is done.
+    pub unsafe fn push_raw(&mut self, mut src: u32, mut dst: u32, mut items: usize) {
+        if let Some(peripheral) = self.src {
+            src = peripheral.address;
+        }
+        if let Some(peripheral) = self.dst {
+            dst = peripheral.address;
+        }
+
+        let bytes = W::WIDTH.bytes() as u32;
+        check_address(src, bytes as usize);
+        check_address(dst, bytes as usize);
+        while items > 0 {
+            let count = items.min(MAX_TRANSFER_SIZE as usize);
+            let descriptor = match self.descriptors.get_mut(self.len) {
+                Some(descriptor) => descriptor,
+                None => panic!("Not enough DMA descriptors"),
+            };
+            *descriptor = Descriptor {
+                src,
+                dst,
+                next: 0,
+                control: count as u32,
+            };
+            self.len += 1;
+
+            if self.config.src_increment {
+                src += count as u32 * bytes;
+            }
+            if self.config.dst_increment {
+                dst += count as u32 * bytes;
+            }
+            items -= count;
+        }
+    }
+
+    /// Links the descriptors and starts the transfer on `channel`
+    pub fn start<CH: DmaChannel>(self, channel: CH) -> ChainTransfer<CH> {
+        if self.len == 0 {
+            panic!("Empty DMA chain");
+        }
+
+        // Link the items, with the terminal count flag raised by the last one only
+        for index in 0..self.len - 1 {
+            let next = &self.descriptors[index + 1] as *const Descriptor as u32;
+            self.descriptors[index].next = next;
+        }
+        for descriptor in self.descriptors[..self.len].iter_mut() {
+            descriptor.control =
+                descriptor.control & CONTROL_TRANSFER_SIZE_MASK | self.config.control_bits();
+        }
+        self.descriptors[self.len - 1].control |= CONTROL_TC_INTERRUPT;
+
+        let regs = Regs { index: CH::INDEX };
+        regs.configure(&self.config);
+        regs.write_tc_interrupt(false);
+        regs.write_error_interrupt(false);
+
+        // The first item is loaded into the channel registers, the rest is loaded by the DMA
+        let first = self.descriptors[0];
+        write_reg(regs.address(SRC_ADDR), first.src);
+        write_reg(regs.address(DST_ADDR), first.dst);
+        write_reg(regs.address(LLI), first.next);
+        write_reg(regs.address(CONTROL), first.control);
+        compiler_fence(Ordering::SeqCst);
+        regs.enable();
+
+        ChainTransfer {
+            parts: Some((channel, self.descriptors)),
+        }
+    }
+}
+
+/// A running chained transfer, which owns the descriptors until it's done. Dropping it stops the
+/// transfer.
+pub struct ChainTransfer<CH: DmaChannel> {
+    parts: Option<(CH, &'static mut [Descriptor])>,
+}
+
+impl<CH: DmaChannel> ChainTransfer<CH> {
+    /// Check if the last link is done, or the transfer was aborted by an error
+    pub fn is_done(&self) -> bool {
+        let regs = Regs { index: CH::INDEX };
+        read_reg(dma(RAW_INT_TC_STATUS)) & regs.mask() != 0 || self.is_error()
+    }
+
+    /// Check if the transfer was aborted by a bus error
+    pub fn is_error(&self) -> bool {
+        self.error().is_some()
+    }
+
+    /// The error that aborted the transfer, if any
+    pub fn error(&self) -> Option<Error> {
+        Regs { index: CH::INDEX }.error()
+    }
+
+    /// Blocks until the transfer is done, and returns the channel and the descriptors. If the
+    /// transfer was aborted by an error, they're returned along with the error.
+    pub fn wait(
+        mut self,
+    ) -> Result<(CH, &'static mut [Descriptor]), (Error, CH, &'static mut [Descriptor])> {
+        while !self.is_done() {}
+        let error = self.error();
+        let (channel, descriptors) = self.finish();
+        match error {
+            Some(error) => Err((error, channel, descriptors)),
+            None => Ok((channel, descriptors)),
+        }
+    }
+
+    /// Waits for the last link without blocking, the task is woken from the DMA interrupt,
+    /// which is enabled. Dropping the future stops the transfer.
+    #[cfg(feature = "async")]
+    pub async fn wait_async(
+        self,
+    ) -> Result<(CH, &'static mut [Descriptor]), (Error, CH, &'static mut [Descriptor])> {
+        DoneFuture::new(CH::INDEX).await;
+        self.wait()
+    }
+
+    /// Stops the transfer, and returns the channel and the descriptors
+    pub fn abort(mut self) -> (CH, &'static mut [Descriptor]) {
+        self.finish()
+    }
+
+    fn finish(&mut self) -> (CH, &'static mut [Descriptor]) {
+        let regs = Regs { index: CH::INDEX };
+        regs.halt();
+        write_reg(dma(INT_TC_CLEAR), regs.mask());
+        write_reg(dma(INT_ERR_CLEAR), regs.mask());
+        compiler_fence(Ordering::SeqCst);
+        self.parts.take().unwrap()
+    }
+}
+
+impl<CH: DmaChannel> Drop for ChainTransfer<CH> {
+    fn drop(&mut self) {
+        if self.parts.is_some() {
+            self.finish();
+        }
+    }
+}
+
+/// One half of a circular buffer
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Half {
+    First,
+    Second,
+}
+
+impl Half {
+    /// The other half
+    pub fn other(self) -> Half {
+        match self {
+            Half::First => Half::Second,
+            Half::Second => Half::First,
+        }
+    }
+}
+
+/// State of a circular transfer, shared with the DMA interrupt
+#[derive(Copy, Clone)]
+struct CircularState {
+    next: Half,
+    on_done: Option<fn(Half)>,
+    error: bool,
+}
+
+/// Circular transfers, indexed by channel
+static CIRCULARS: Mutex<Cell<[Option<CircularState>; 4]>> = Mutex::new(Cell::new([None; 4]));
+
+/// A circular transfer, which streams to or from the two halves of a buffer in turn until it's
+/// stopped, e.g. for continuous ADC sampling or audio output. The two linked list items in
+/// `descriptors` point to each other, so the DMA never stops at the end of the buffer.
+///
+/// The DMA interrupt is raised when either half is done, and `on_done` is called from it with the
+/// half that was just completed, which can then be processed or refilled with [Circular::peek]
+/// while the DMA moves through the other half. Dropping it stops the transfer.
+pub struct Circular<CH: DmaChannel, B> {
+    parts: Option<(CH, B, &'static mut [Descriptor; 2])>,
+    start: u32,
+    half_bytes: u32,
+    half_items: usize,
+    address_register: usize,
+}
+
+impl<CH, B> Circular<CH, B>
+where
+    CH: DmaChannel,
+    B: WriteBuffer,
+    B::Word: Item,
+{
+    /// Continuously fills `buffer` from the peripheral `src`. Panics if the buffer can't be split
+    /// into two halves of at most [MAX_TRANSFER_SIZE] items.
+    pub fn peripheral_to_memory(
+        channel: CH,
+        src: impl PeripheralSource,
+        mut buffer: B,
+        descriptors: &'static mut [Descriptor; 2],
+        on_done: Option<fn(Half)>,
+    ) -> Self {
+        let src = src.peripheral();
+        let (ptr, len) = unsafe { buffer.write_buffer() };
+        let config = Config::default()
+            .direction(Direction::PeripheralToMemory)
+            .width(B::Word::WIDTH)
+            .increment(false, true)
+            .src_request(src.request);
+        let start = ptr as u32;
+        let half_bytes = (len / 2 * B::Word::WIDTH.bytes()) as u32;
+        descriptors[0] = Descriptor {
+            src: src.address,
+            dst: start,
+            ..Descriptor::EMPTY
+        };
+        descriptors[1] = Descriptor {
+            src: src.address,
+            dst: start + half_bytes,
+            ..Descriptor::EMPTY
+        };
+        Self::start(
+            channel,
+            buffer,
+            descriptors,
+            &config,
+            len,
+            DST_ADDR,
+            on_done,
+        )
+    }
+
+    /// Continuously writes `buffer` to the peripheral `dst`. Panics if the buffer can't be split
+    /// into two halves of at most [MAX_TRANSFER_SIZE] items.
+    pub fn memory_to_peripheral(
+        channel: CH,
+        mut buffer: B,
+        dst: impl PeripheralDestination,
+        descriptors: &'static mut [Descriptor; 2],
+        on_done: Option<fn(Half)>,
+    ) -> Self {
+        let dst = dst.peripheral();
+        let (ptr, len) = unsafe { buffer.write_buffer() };
+        let config = Config::default()
+            .direction(Direction::MemoryToPeripheral)
+            .width(B::Word::WIDTH)
+            .increment(true, false)
+            .dst_request(dst.request);
+        let start = ptr as u32;
+        let half_bytes = (len / 2 * B::Word::WIDTH.bytes()) as u32;
+        descriptors[0] = Descriptor {
+            src: start,
+            dst: dst.address,
+            ..Descriptor::EMPTY
+        };
+        descriptors[1] = Descriptor {
+            src: start + half_bytes,
+            dst: dst.address,
+            ..Descriptor::EMPTY
+        };
+        Self::start(
+            channel,
+            buffer,
+            descriptors,
+            &config,
+            len,
+            SRC_ADDR,
+            on_done,
+        )
+    }
+
+    fn start(
+        channel: CH,
+        buffer: B,
+        descriptors: &'static mut [Descriptor; 2],
+        config: &Config,
+        len: usize,
+        address_register: usize,
+        on_done: Option<fn(Half)>,
+    ) -> Self {
+        let half_items = len / 2;
+        if len % 2 != 0 || half_items == 0 || half_items > MAX_TRANSFER_SIZE as usize {
+            panic!("Invalid DMA transfer size");
+        }
+
+        // Both halves raise the terminal count flag, and each links to the other
+        let control = config.control_bits() | half_items as u32 | CONTROL_TC_INTERRUPT;
+        descriptors[0].control = control;
+        descriptors[1].control = control;
+        descriptors[0].next = &descriptors[1] as *const Descriptor as u32;
+        descriptors[1].next = &descriptors[0] as *const Descriptor as u32;
+
+        let regs = Regs { index: CH::INDEX };
+        regs.configure(config);
+        regs.write_tc_interrupt(true);
+        regs.write_error_interrupt(true);
+
+        let first = descriptors[0];
+        let start = match address_register {
+            SRC_ADDR => first.src,
+            _ => first.dst,
+        };
+        check_address(start, B::Word::WIDTH.bytes());
+        write_reg(regs.address(SRC_ADDR), first.src);
+        write_reg(regs.address(DST_ADDR), first.dst);
+        write_reg(regs.address(LLI), first.next);
+        write_reg(regs.address(CONTROL), first.control);
+
+        critical_section::with(|cs| {
+            let cell = CIRCULARS.borrow(cs);
+            let mut circulars = cell.get();
+            circulars[CH::INDEX] = Some(CircularState {
+                next: Half::First,
+                on_done,
+                error: false,
+            });
+            cell.set(circulars);
+        });
+        compiler_fence(Ordering::SeqCst);
+        regs.enable();
+        enable_interrupt(Interrupt::Dma);
+
+        Circular {
+            parts: Some((channel, buffer, descriptors)),
+            start,
+            half_bytes: (half_items * B::Word::WIDTH.bytes()) as u32,
+            half_items,
+            address_register,
+        }
+    }
+
+    /// The half the DMA is currently reading or writing
+    pub fn current_half(&self) -> Half {
+        let regs = Regs { index: CH::INDEX };
+        let address = read_reg(regs.address(self.address_register));
+        if address < self.start + self.half_bytes {
+            Half::First
+        } else {
+            Half::Second
+        }
+    }
+
+    /// Check if the transfer was stopped by a bus error
+    pub fn is_error(&self) -> bool {
+        match critical_section::with(|cs| CIRCULARS.borrow(cs).get()[CH::INDEX]) {
+            Some(circular) => circular.error,
+            None => false,
+        }
+    }
+
+    /// Gives access to the half of the buffer the DMA isn't using. Returns [Error::Overrun] if
+    /// the DMA moved on to that half before `f` returned, in which case the data may be torn.
+    pub fn peek<R>(&mut self, f: impl FnOnce(&mut [B::Word], Half) -> R) -> Result<R, Error> {
+        if self.is_error() {
+            return Err(Error::Bus);
+        }
+
+        let half = self.current_half().other();
+        let offset = match half {
+            Half::First => 0,
+            Half::Second => self.half_bytes,
+        };
+        compiler_fence(Ordering::SeqCst);
+        let slice = unsafe {
+            core::slice::from_raw_parts_mut((self.start + offset) as *mut B::Word, self.half_items)
+        };
+        let result = f(slice, half);
+        compiler_fence(Ordering::SeqCst);
+
+        if self.current_half() == half {
+            Err(Error::Overrun)
+        } else {
+            Ok(result)
+        }
+    }
+
+    /// Stops the transfer, and returns the channel, the buffer and the descriptors
+    pub fn stop(mut self) -> (CH, B, &'static mut [Descriptor; 2]) {
+        self.finish()
+    }
+
+    fn finish(&mut self) -> (CH, B, &'static mut [Descriptor; 2]) {
+        stop_circular(CH::INDEX);
+        self.parts.take().unwrap()
+    }
+}
+
+impl<CH: DmaChannel, B> Drop for Circular<CH, B> {
+    fn drop(&mut self) {
+        if self.parts.is_some() {
+            stop_circular(CH::INDEX);
+        }
+    }
+}
+
+fn stop_circular(index: usize) {
+    let regs = Regs { index };
+    critical_section::with(|cs| {
+        let cell = CIRCULARS.borrow(cs);
+        let mut circulars = cell.get();
+        circulars[index] = None;
+        cell.set(circulars);
+    });
+    regs.halt();
+    regs.write_tc_interrupt(false);
+    regs.write_error_interrupt(false);
+    write_reg(dma(INT_TC_CLEAR), regs.mask());
+    write_reg(dma(INT_ERR_CLEAR), regs.mask());
+    compiler_fence(Ordering::SeqCst);
+}
+
+/// State of a transfer queue, shared with the DMA interrupt
+#[derive(Copy, Clone)]
+struct QueueState {
+    dst: u32,
+    next: Option<(u32, usize)>,
+    completed: u32,
+    busy: bool,
+    error: bool,
+}
+
+impl QueueState {
+    fn start(&mut self, regs: Regs, src: u32, items: usize) {
+        write_reg(regs.address(SRC_ADDR), src);
+        write_reg(regs.address(DST_ADDR), self.dst);
+        regs.set_transfer_size(items as u16);
+        compiler_fence(Ordering::SeqCst);
+        regs.enable();
+        self.busy = true;
+    }
+}
+
+/// Transfer queues, indexed by channel
+static QUEUES: Mutex<Cell<[Option<QueueState>; 4]>> = Mutex::new(Cell::new([None; 4]));
+
+/// A queue of buffers written to a peripheral back to back, e.g. UART output or SPI display
+/// updates. The next buffer is started from the DMA interrupt the moment the current one is
+/// done, so there's no gap in between.
+///
+/// The queue owns up to two buffers: the one being written, and the one queued after it. Buffers
+/// that have been written are taken back with [Queue::pop]. Dropping it stops the transfer.
+pub struct Queue<CH: DmaChannel, B> {
+    channel: Option<CH>,
+    current: Option<B>,
+    queued: Option<B>,
+    popped: u32,
+}
+
+impl<CH, B> Queue<CH, B>
+where
+    CH: DmaChannel,
+    B: ReadBuffer,
+    B::Word: Item,
+{
+    /// An empty queue writing to the peripheral `dst`
+    pub fn memory_to_peripheral(channel: CH, dst: impl PeripheralDestination) -> Self {
+        let dst = dst.peripheral();
+        let config = Config::default()
+            .direction(Direction::MemoryToPeripheral)
+            .width(B::Word::WIDTH)
+            .increment(true, false)
+            .dst_request(dst.request);
+
+        let regs = Regs { index: CH::INDEX };
+        regs.configure(&config);
+        regs.write_tc_interrupt(true);
+        regs.write_error_interrupt(true);
+        critical_section::with(|cs| {
+            let cell = QUEUES.borrow(cs);
+            let mut queues = cell.get();
+            queues[CH::INDEX] = Some(QueueState {
+                dst: dst.address,
+                next: None,
+                completed: 0,
+                busy: false,
+                error: false,
+            });
+            cell.set(queues);
+        });
+        enable_interrupt(Interrupt::Dma);
+
+        Queue {
+            channel: Some(channel),
+            current: None,
+            queued: None,
+            popped: 0,
+        }
+    }
+
+    /// Queues `buffer`, which is started right away if the channel is idle. Returns the buffer
+    /// if two buffers are queued already, or after a bus error. Panics if the buffer is longer
+    /// than [MAX_TRANSFER_SIZE] items, or in the flash.
+    pub fn push(&mut self, buffer: B) -> Result<(), B> {
+        if self.queued.is_some() || self.is_error() {
+            return Err(buffer);
+        }
+
+        let (ptr, items) = unsafe { buffer.read_buffer() };
+        let src = ptr as u32;
+        check_address(src, B::Word::WIDTH.bytes());
+        if items > MAX_TRANSFER_SIZE as usize {
+            panic!("Invalid DMA transfer size");
+        }
+
+        let regs = Regs { index: CH::INDEX };
+        critical_section::with(|cs| {
+            let cell = QUEUES.borrow(cs);
+            let mut queues = cell.get();
+            if let Some(queue) = queues[CH::INDEX].as_mut() {
+                if queue.busy {
+                    queue.next = Some((src, items));
+                } else {
+                    queue.start(regs, src, items);
+                }
+            }
+            cell.set(queues);
+        });
+
+        if self.current.is_none() {
+            self.current = Some(buffer);
+        } else {
+            self.queued = Some(buffer);
+        }
+        Ok(())
+    }
+
+    /// Takes back the oldest buffer, if it has been written
+    pub fn pop(&mut self) -> Option<B> {
+        let completed = match queue_state(CH::INDEX) {
+            Some(queue) => queue.completed,
+            None => return None,
+        };
+        if completed == self.popped {
+            return None;
+        }
+
+        self.popped = self.popped.wrapping_add(1);
+        let buffer = self.current.take();
+        self.current = self.queued.take();
+        buffer
+    }
+
+    /// Check if all queued buffers have been written, or the queue was stopped by a bus error
+    pub fn is_idle(&self) -> bool {
+        match queue_state(CH::INDEX) {
+            Some(queue) => !queue.busy,
+            None => true,
+        }
+    }
+
+    /// Check if the queue was stopped by a bus error
+    pub fn is_error(&self) -> bool {
+        match queue_state(CH::INDEX) {
+            Some(queue) => queue.error,
+            None => false,
+        }
+    }
+
+    /// Stops the transfer, and returns the channel and the buffers that haven't been taken back
+    pub fn free(mut self) -> (CH, Option<B>, Option<B>) {
+        stop_queue(CH::INDEX);
+        (
+            self.channel.take().unwrap(),
+            self.current.take(),
+            self.queued.take(),
+        )
+    }
+}
+
+impl<CH: DmaChannel, B> Drop for Queue<CH, B> {
+    fn drop(&mut self) {
+        if self.channel.is_some() {
+            stop_queue(CH::INDEX);
+        }
+    }
+}
+
+fn queue_state(index: usize) -> Option<QueueState> {
+    critical_section::with(|cs| QUEUES.borrow(cs).get()[index])
+}
+
+fn stop_queue(index: usize) {
+    let regs = Regs { index };
+    critical_section::with(|cs| {
+        let cell = QUEUES.borrow(cs);
+        let mut queues = cell.get();
+        queues[index] = None;
+        cell.set(queues);
+    });
+    regs.halt();
+    regs.write_tc_interrupt(false);
+    regs.write_error_interrupt(false);
+    write_reg(dma(INT_TC_CLEAR), regs.mask());
+    write_reg(dma(INT_ERR_CLEAR), regs.mask());
+    compiler_fence(Ordering::SeqCst);
+}
+
+/// DMA channels obtained from [DMA.split](bl602_pac::Peripherals::DMA)
+pub struct Channels {
+    pub channel0: Channel0,
+    pub channel1: Channel1,
+    pub channel2: Channel2,
+    pub channel3: Channel3,
+}
+
+/// Extension trait to split the DMA peripheral into independent channels
+pub trait DmaExt {
+    fn split(self) -> Channels;
+}
+
+impl DmaExt for DMA {
+    /// Enables the clock and the controller, and splits it into its channels
+    fn split(self) -> Channels {
+        // DMA clock gate
+        unsafe { &*pac::GLB::ptr() }
+            .cgen_cfg1
+            .modify(|r, w| unsafe { w.bits(r.bits() | 1 << 12) });
+        modify_reg(dma(TOP_CONFIG), |r| r | 1);
+
+        Channels {
+            channel0: Channel0 { _private: () },
+            channel1: Channel1 { _private: () },
+            channel2: Channel2 { _private: () },
+            channel3: Channel3 { _private: () },
+        }
+    }
+}
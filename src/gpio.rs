@@ -203,6 +203,11 @@ pub struct ClkCfg {
     pub(crate) _ownership: (),
 }
 
+/// General purpose DAC registers, see [Dac](crate::dac::Dac)
+pub struct Gpdac {
+    pub(crate) _ownership: (),
+}
+
 /*
 // todo: english
     在GPIO模式下，可以设置内部上下拉，以类型状态机模式设计
@@ -236,6 +241,12 @@ pub struct Spi;
 /// I2C pin mode (type state)
 pub struct I2c;
 
+/// PWM pin mode (type state)
+pub struct Pwm;
+
+/// Analog pin mode (type state), for the ADC, DAC and comparators
+pub struct Analog;
+
 #[doc(hidden)]
 pub trait UartPin<SIG> {}
 
@@ -244,7 +255,7 @@ pub trait UartPin<SIG> {}
 pub use self::pin::*;
 
 macro_rules! impl_glb {
-    ($($Pini: ident: ($pini: ident, $gpio_cfgctli: ident, $UartSigi: ident, $sigi: ident, $spi_kind: ident, $i2c_kind: ident, $gpio_i: ident, $gpio_int_mode_seti: ident) ,)+) => {
+    ($($Pini: ident: ($pini: ident, $gpio_cfgctli: ident, $UartSigi: ident, $sigi: ident, $spi_kind: ident, $i2c_kind: ident, $pwm_ch: ident, $gpio_i: ident, $gpio_int_mode_seti: ident) ,)+) => {
         impl GlbExt for pac::GLB {
             fn split(self) -> Parts {
                 Parts {
@@ -258,6 +269,7 @@ macro_rules! impl_glb {
                     uart_mux6: UartMux6 { _mode: PhantomData },
                     uart_mux7: UartMux7 { _mode: PhantomData },
                     clk_cfg: ClkCfg { _ownership: () },
+                    gpdac: Gpdac { _ownership: () },
                 }
             }
         }
@@ -274,6 +286,7 @@ macro_rules! impl_glb {
             pub uart_mux6: UartMux6<Uart0Cts>,
             pub uart_mux7: UartMux7<Uart0Cts>,
             pub clk_cfg: ClkCfg,
+            pub gpdac: Gpdac,
         }
 
         /// GPIO pins
@@ -405,6 +418,13 @@ macro_rules! impl_glb {
                         // 6 -> GPIO_FUN_I2C_x
                         self.into_pin_with_mode(6, true, false, true)
                     }
+
+                    /// Configures the pin to PWM alternate mode, driven by the only channel it can
+                    /// be routed to
+                    pub fn [<into_pwm_ $pwm_ch>](self) -> $Pini<Pwm> {
+                        // 8 -> GPIO_FUN_PWM
+                        self.into_pin_with_mode(8, false, false, false)
+                    }
                 }
             }
 
@@ -428,14 +448,22 @@ macro_rules! impl_glb {
             impl<MODE> InternalOutputPinImp for $Pini<Output<MODE>> {
                 paste::paste! {
                     fn set_high_inner(&self) {
-                        let glb = unsafe { &*pac::GLB::ptr() };
-                        glb.gpio_cfgctl32.modify(|_, w| w.[<reg_ $gpio_i _o>]().set_bit())
+                        // The register holds the outputs of all pins, which are also
+                        // driven from interrupts, e.g. by the comparator mirrors
+                        critical_section::with(|_| {
+                            let glb = unsafe { &*pac::GLB::ptr() };
+                            glb.gpio_cfgctl32.modify(|_, w| w.[<reg_ $gpio_i _o>]().set_bit())
+                        })
                     }
                 }
                 paste::paste! {
                     fn set_low_inner(&self)  {
-                        let glb = unsafe { &*pac::GLB::ptr() };
-                        glb.gpio_cfgctl32.modify(|_, w| w.[<reg_ $gpio_i _o>]().clear_bit())
+                        // The register holds the outputs of all pins, which are also
+                        // driven from interrupts, e.g. by the comparator mirrors
+                        critical_section::with(|_| {
+                            let glb = unsafe { &*pac::GLB::ptr() };
+                            glb.gpio_cfgctl32.modify(|_, w| w.[<reg_ $gpio_i _o>]().clear_bit())
+                        })
                     }
                 }
             }
@@ -611,27 +639,72 @@ macro_rules! impl_glb {
 // There are Pin0 to Pin22, totally 23 pins
 // todo: generate macros
 impl_glb! {
-    Pin0: (pin0, gpio_cfgctl0, UartSig0, sig0, miso, scl, gpio_0, gpio_int_mode_set1),
-    Pin1: (pin1, gpio_cfgctl0, UartSig1, sig1, mosi, sda, gpio_1, gpio_int_mode_set1),
-    Pin2: (pin2, gpio_cfgctl1, UartSig2, sig2, ss, scl, gpio_2, gpio_int_mode_set1),
-    Pin3: (pin3, gpio_cfgctl1, UartSig3, sig3, sclk, sda, gpio_3, gpio_int_mode_set1),
-    Pin4: (pin4, gpio_cfgctl2, UartSig4, sig4, miso, scl, gpio_4, gpio_int_mode_set1),
-    Pin5: (pin5, gpio_cfgctl2, UartSig5, sig5, mosi, sda, gpio_5, gpio_int_mode_set1),
-    Pin6: (pin6, gpio_cfgctl3, UartSig6, sig6, ss, scl, gpio_6, gpio_int_mode_set1),
-    Pin7: (pin7, gpio_cfgctl3, UartSig7, sig7, sclk, sda, gpio_7, gpio_int_mode_set1),
-    Pin8: (pin8, gpio_cfgctl4, UartSig0, sig0, miso, scl, gpio_8, gpio_int_mode_set1),
-    Pin9: (pin9, gpio_cfgctl4, UartSig1, sig1, mosi, sda, gpio_9, gpio_int_mode_set1),
-    Pin10: (pin10, gpio_cfgctl5, UartSig2, sig2, ss, scl, gpio_10, gpio_int_mode_set2),
-    Pin11: (pin11, gpio_cfgctl5, UartSig3, sig3, sclk, sda, gpio_11, gpio_int_mode_set2),
-    Pin12: (pin12, gpio_cfgctl6, UartSig4, sig4, miso, scl, gpio_12, gpio_int_mode_set2),
-    Pin13: (pin13, gpio_cfgctl6, UartSig5, sig5, mosi, sda, gpio_13, gpio_int_mode_set2),
-    Pin14: (pin14, gpio_cfgctl7, UartSig6, sig6, ss, scl, gpio_14, gpio_int_mode_set2),
-    Pin15: (pin15, gpio_cfgctl7, UartSig7, sig7, sclk, sda, gpio_15, gpio_int_mode_set2),
-    Pin16: (pin16, gpio_cfgctl8, UartSig0, sig0, miso, scl, gpio_16, gpio_int_mode_set2),
-    Pin17: (pin17, gpio_cfgctl8, UartSig1, sig1, mosi, sda, gpio_17, gpio_int_mode_set2),
-    Pin18: (pin18, gpio_cfgctl9, UartSig2, sig2, ss, scl, gpio_18, gpio_int_mode_set2),
-    Pin19: (pin19, gpio_cfgctl9, UartSig3, sig3, sclk, sda, gpio_19, gpio_int_mode_set2),
-    Pin20: (pin20, gpio_cfgctl10, UartSig4, sig4, miso, scl, gpio_20, gpio_int_mode_set3),
-    Pin21: (pin21, gpio_cfgctl10, UartSig5, sig5, mosi, sda, gpio_21, gpio_int_mode_set3),
-    Pin22: (pin22, gpio_cfgctl11, UartSig6, sig6, ss, scl, gpio_22, gpio_int_mode_set3),
+    Pin0: (pin0, gpio_cfgctl0, UartSig0, sig0, miso, scl, ch0, gpio_0, gpio_int_mode_set1),
+    Pin1: (pin1, gpio_cfgctl0, UartSig1, sig1, mosi, sda, ch1, gpio_1, gpio_int_mode_set1),
+    Pin2: (pin2, gpio_cfgctl1, UartSig2, sig2, ss, scl, ch2, gpio_2, gpio_int_mode_set1),
+    Pin3: (pin3, gpio_cfgctl1, UartSig3, sig3, sclk, sda, ch3, gpio_3, gpio_int_mode_set1),
+    Pin4: (pin4, gpio_cfgctl2, UartSig4, sig4, miso, scl, ch4, gpio_4, gpio_int_mode_set1),
+    Pin5: (pin5, gpio_cfgctl2, UartSig5, sig5, mosi, sda, ch0, gpio_5, gpio_int_mode_set1),
+    Pin6: (pin6, gpio_cfgctl3, UartSig6, sig6, ss, scl, ch1, gpio_6, gpio_int_mode_set1),
+    Pin7: (pin7, gpio_cfgctl3, UartSig7, sig7, sclk, sda, ch2, gpio_7, gpio_int_mode_set1),
+    Pin8: (pin8, gpio_cfgctl4, UartSig0, sig0, miso, scl, ch3, gpio_8, gpio_int_mode_set1),
+    Pin9: (pin9, gpio_cfgctl4, UartSig1, sig1, mosi, sda, ch4, gpio_9, gpio_int_mode_set1),
+    Pin10: (pin10, gpio_cfgctl5, UartSig2, sig2, ss, scl, ch0, gpio_10, gpio_int_mode_set2),
+    Pin11: (pin11, gpio_cfgctl5, UartSig3, sig3, sclk, sda, ch1, gpio_11, gpio_int_mode_set2),
+    Pin12: (pin12, gpio_cfgctl6, UartSig4, sig4, miso, scl, ch2, gpio_12, gpio_int_mode_set2),
+    Pin13: (pin13, gpio_cfgctl6, UartSig5, sig5, mosi, sda, ch3, gpio_13, gpio_int_mode_set2),
+    Pin14: (pin14, gpio_cfgctl7, UartSig6, sig6, ss, scl, ch4, gpio_14, gpio_int_mode_set2),
+    Pin15: (pin15, gpio_cfgctl7, UartSig7, sig7, sclk, sda, ch0, gpio_15, gpio_int_mode_set2),
+    Pin16: (pin16, gpio_cfgctl8, UartSig0, sig0, miso, scl, ch1, gpio_16, gpio_int_mode_set2),
+    Pin17: (pin17, gpio_cfgctl8, UartSig1, sig1, mosi, sda, ch2, gpio_17, gpio_int_mode_set2),
+    Pin18: (pin18, gpio_cfgctl9, UartSig2, sig2, ss, scl, ch3, gpio_18, gpio_int_mode_set2),
+    Pin19: (pin19, gpio_cfgctl9, UartSig3, sig3, sclk, sda, ch4, gpio_19, gpio_int_mode_set2),
+    Pin20: (pin20, gpio_cfgctl10, UartSig4, sig4, miso, scl, ch0, gpio_20, gpio_int_mode_set3),
+    Pin21: (pin21, gpio_cfgctl10, UartSig5, sig5, mosi, sda, ch1, gpio_21, gpio_int_mode_set3),
+    Pin22: (pin22, gpio_cfgctl11, UartSig6, sig6, ss, scl, ch2, gpio_22, gpio_int_mode_set3),
+}
+
+macro_rules! impl_analog {
+    ($($Pini: ident: ($gpio_cfgctli: ident, $gpio_i: ident),)+) => {
+        $(
+        impl<MODE> $Pini<MODE> {
+            paste::paste! {
+                /// Configures the pin to analog mode, with the input and output drivers and the
+                /// pull resistors disabled
+                pub fn into_analog(self) -> $Pini<Analog> {
+                    let glb = unsafe { &*pac::GLB::ptr() };
+
+                    // 10 -> GPIO_FUN_ANALOG
+                    glb.$gpio_cfgctli.modify(|_r, w| unsafe { w
+                        .[<reg_ $gpio_i _func_sel>]().bits(10)
+                        .[<reg_ $gpio_i _ie>]().clear_bit()
+                        .[<reg_ $gpio_i _pu>]().clear_bit()
+                        .[<reg_ $gpio_i _pd>]().clear_bit()
+                        .[<reg_ $gpio_i _drv>]().bits(0)
+                        .[<reg_ $gpio_i _smt>]().clear_bit()
+                    });
+                    glb.gpio_cfgctl34.modify(|_, w| w.[<reg_ $gpio_i _oe>]().clear_bit());
+
+                    $Pini { _mode: PhantomData }
+                }
+            }
+        }
+        )+
+    };
+}
+
+// The pins that can be routed to the ADC, DAC or comparators
+impl_analog! {
+    Pin4: (gpio_cfgctl2, gpio_4),
+    Pin5: (gpio_cfgctl2, gpio_5),
+    Pin6: (gpio_cfgctl3, gpio_6),
+    Pin7: (gpio_cfgctl3, gpio_7),
+    Pin9: (gpio_cfgctl4, gpio_9),
+    Pin12: (gpio_cfgctl6, gpio_12),
+    Pin13: (gpio_cfgctl6, gpio_13),
+    Pin14: (gpio_cfgctl7, gpio_14),
+    Pin18: (gpio_cfgctl9, gpio_18),
+    Pin19: (gpio_cfgctl9, gpio_19),
+    Pin20: (gpio_cfgctl10, gpio_20),
+    Pin21: (gpio_cfgctl10, gpio_21),
 }
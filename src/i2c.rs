@@ -24,12 +24,17 @@ use embedded_hal_zero::blocking::i2c::Write as WriteZero;
 use embedded_time::rate::Hertz;
 
 use crate::delay::McycleDelay;
-use crate::{clock::Clocks, pac};
+use crate::{
+    clock::Clocks,
+    pac,
+    power::suspend::{Resume, SavedRegisters, Suspend},
+};
 
 use self::private::Sealed;
 
 /// I2C error
 #[derive(Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Error {
     /// Rx overflow occurred
     RxOverflow,
@@ -105,6 +110,8 @@ pub struct I2c<I2C, PINS> {
     pins: PINS,
     /// timeout (in microseconds)
     timeout: u16,
+    /// core clock, which the timeout is counted in
+    sysclk: Hertz,
 }
 
 impl<PINS> I2c<pac::I2C, PINS>
@@ -171,6 +178,7 @@ where
             i2c,
             pins,
             timeout: 2048,
+            sysclk: clocks.sysclk(),
         }
     }
 
@@ -192,6 +200,40 @@ where
     }
 }
 
+impl<PINS> Suspend for I2c<pac::I2C, PINS> {
+    type State = SavedRegisters<7>;
+
+    fn suspend(&mut self) -> Self::State {
+        let i2c = &self.i2c;
+        SavedRegisters([
+            i2c.i2c_prd_start.read().bits(),
+            i2c.i2c_prd_stop.read().bits(),
+            i2c.i2c_prd_data.read().bits(),
+            i2c.i2c_int_sts.read().bits(),
+            i2c.i2c_fifo_config_0.read().bits(),
+            i2c.i2c_fifo_config_1.read().bits(),
+            i2c.i2c_config.read().bits(),
+        ])
+    }
+}
+
+impl<PINS> Resume for I2c<pac::I2C, PINS> {
+    fn resume(&mut self, state: Self::State) {
+        let i2c = &self.i2c;
+        let [prd_start, prd_stop, prd_data, int_sts, fifo0, fifo1, config] = state.0;
+        i2c.i2c_prd_start.write(|w| unsafe { w.bits(prd_start) });
+        i2c.i2c_prd_stop.write(|w| unsafe { w.bits(prd_stop) });
+        i2c.i2c_prd_data.write(|w| unsafe { w.bits(prd_data) });
+        // The interrupt masks and enables, the status bits are read only or cleared by writing 1
+        i2c.i2c_int_sts.write(|w| unsafe { w.bits(int_sts) });
+        i2c.i2c_fifo_config_0.write(|w| unsafe { w.bits(fifo0) });
+        i2c.i2c_fifo_config_1.write(|w| unsafe { w.bits(fifo1) });
+        // Without starting a transfer
+        i2c.i2c_config
+            .write(|w| unsafe { w.bits(config).cr_i2c_m_en().clear_bit() });
+    }
+}
+
 impl<PINS> i2cAlpha::ErrorType for I2c<pac::I2C, PINS> {
     type Error = Error;
 }
@@ -240,9 +282,7 @@ where
                 .set_bit()
         });
 
-        // We don't know what the CPU frequency is. Assume maximum of 192Mhz
-        // This might make our timeouts longer than expected if frequency is lower.
-        let mut delay = McycleDelay::new(192_000_000);
+        let mut delay = McycleDelay::new(self.sysclk.0);
         for value in tmp.iter_mut() {
             let start_time = McycleDelay::get_cycle_count();
             while self.i2c.i2c_fifo_config_1.read().rx_fifo_cnt().bits() == 0 {
@@ -309,9 +349,7 @@ where
                 .set_bit()
         });
 
-        // We don't know what the CPU frequency is. Assume maximum of 192Mhz
-        // This might make our timeouts longer than expected if frequency is lower.
-        let mut delay = McycleDelay::new(192_000_000);
+        let mut delay = McycleDelay::new(self.sysclk.0);
         for value in tmp.iter() {
             let start_time = McycleDelay::get_cycle_count();
             while self.i2c.i2c_fifo_config_1.read().tx_fifo_cnt().bits() == 0 {
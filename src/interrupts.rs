@@ -17,7 +17,13 @@
 
   ## The following functions can be implemented as interrupt handlers
   ```rust
+    fn Bor();
+    fn Dma();
+    fn Gpadc();
     fn Gpio();
+    fn HbnOut0();
+    fn HbnOut1();
+    fn Pwm();
     fn TimerCh0();
     fn TimerCh1();
     fn Watchdog();
@@ -27,7 +33,13 @@
 use riscv::register::mcause;
 
 extern "C" {
+    fn Bor(trap_frame: &mut TrapFrame);
+    fn Dma(trap_frame: &mut TrapFrame);
+    fn Gpadc(trap_frame: &mut TrapFrame);
     fn Gpio(trap_frame: &mut TrapFrame);
+    fn HbnOut0(trap_frame: &mut TrapFrame);
+    fn HbnOut1(trap_frame: &mut TrapFrame);
+    fn Pwm(trap_frame: &mut TrapFrame);
     fn TimerCh0(trap_frame: &mut TrapFrame);
     fn TimerCh1(trap_frame: &mut TrapFrame);
     fn Watchdog(trap_frame: &mut TrapFrame);
@@ -40,7 +52,13 @@ const CLIC_HART0_ADDR: u32 = 0x02800000;
 const CLIC_INTIE: u32 = 0x400;
 const CLIC_INTIP: u32 = 0x000;
 
+const BOR_IRQ: u32 = IRQ_NUM_BASE + 53;
+const DMA_IRQ: u32 = IRQ_NUM_BASE + 15;
+const GPADC_IRQ: u32 = IRQ_NUM_BASE + 25;
 const GPIO_IRQ: u32 = IRQ_NUM_BASE + 44;
+const HBN_OUT0_IRQ: u32 = IRQ_NUM_BASE + 51;
+const HBN_OUT1_IRQ: u32 = IRQ_NUM_BASE + 52;
+const PWM_IRQ: u32 = IRQ_NUM_BASE + 34;
 const TIMER_CH0_IRQ: u32 = IRQ_NUM_BASE + 36;
 const TIMER_CH1_IRQ: u32 = IRQ_NUM_BASE + 37;
 const WATCHDOG_IRQ: u32 = IRQ_NUM_BASE + 38;
@@ -126,6 +144,8 @@ pub unsafe extern "C" fn start_trap_rust_hal(trap_frame: *mut TrapFrame) {
 
     let cause = mcause::read();
     if cause.is_exception() {
+        #[cfg(feature = "crash-record")]
+        crate::crash::record_exception();
         _start_trap_rust(trap_frame);
     } else {
         let code = cause.code();
@@ -137,27 +157,103 @@ pub unsafe extern "C" fn start_trap_rust_hal(trap_frame: *mut TrapFrame) {
 
             match interrupt {
                 Interrupt::Unknown => _start_trap_rust(trap_frame),
+                Interrupt::Bor => Bor(trap_frame.as_mut().unwrap()),
+                Interrupt::Dma => {
+                    if crate::dma::dispatch_interrupt() {
+                        clear_interrupt(Interrupt::Dma);
+                    } else {
+                        Dma(trap_frame.as_mut().unwrap());
+                    }
+                }
+                Interrupt::Gpadc => {
+                    if crate::adc::dispatch_interrupt() {
+                        clear_interrupt(Interrupt::Gpadc);
+                    } else {
+                        Gpadc(trap_frame.as_mut().unwrap());
+                    }
+                }
                 Interrupt::Gpio => Gpio(trap_frame.as_mut().unwrap()),
-                Interrupt::TimerCh0 => TimerCh0(trap_frame.as_mut().unwrap()),
-                Interrupt::TimerCh1 => TimerCh1(trap_frame.as_mut().unwrap()),
-                Interrupt::Watchdog => Watchdog(trap_frame.as_mut().unwrap()),
+                Interrupt::HbnOut0 => HbnOut0(trap_frame.as_mut().unwrap()),
+                Interrupt::HbnOut1 => {
+                    if crate::acomp::dispatch_interrupt() {
+                        clear_interrupt(Interrupt::HbnOut1);
+                    } else {
+                        HbnOut1(trap_frame.as_mut().unwrap());
+                    }
+                }
+                Interrupt::Pwm => {
+                    if crate::pwm::dispatch_interrupt() {
+                        clear_interrupt(Interrupt::Pwm);
+                    } else {
+                        Pwm(trap_frame.as_mut().unwrap());
+                    }
+                }
+                Interrupt::TimerCh0 => {
+                    if crate::timer::dispatch_interrupt(crate::timer::Channel::Channel0) {
+                        clear_interrupt(Interrupt::TimerCh0);
+                    } else {
+                        TimerCh0(trap_frame.as_mut().unwrap());
+                    }
+                }
+                Interrupt::TimerCh1 => {
+                    if crate::timer::dispatch_interrupt(crate::timer::Channel::Channel1) {
+                        clear_interrupt(Interrupt::TimerCh1);
+                    } else {
+                        TimerCh1(trap_frame.as_mut().unwrap());
+                    }
+                }
+                Interrupt::Watchdog => {
+                    if crate::watchdog::dispatch_interrupt() {
+                        clear_interrupt(Interrupt::Watchdog);
+                    } else {
+                        Watchdog(trap_frame.as_mut().unwrap());
+                    }
+                }
             };
         }
     }
 }
 
 /// Available interrupts
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Interrupt {
     #[doc(hidden)]
     Unknown,
+    /// Brown Out Interrupt, see `power::bor::listen()`
+    Bor,
+    /// DMA Interrupt, shared by all channels
+    /// Channels copying with `mem_copy_with_interrupt()`, running a `Circular` transfer or a
+    /// `Queue`, or awaited with `wait_async()` are handled by the HAL, the `Dma()` handler is only
+    /// called for the remaining channels
+    Dma,
+    /// ADC FIFO Interrupt
+    /// Handled by the HAL while a callback is registered through `set_sample_callback()`, or a
+    /// task awaits `read_async()` or `scan_async()`
+    Gpadc,
     /// GPIO Interrupt
     Gpio,
+    /// Hibernate Domain Interrupt 0, raised by the RTC alarm
+    HbnOut0,
+    /// Hibernate Domain Interrupt 1, raised by the analog comparators
+    /// Comparators with a callback registered through `set_edge_callback()` or mirrored with
+    /// `mirror_to()` are handled by the HAL, the `HbnOut1()` handler is only called for the
+    /// remaining ones
+    HbnOut1,
+    /// PWM Interrupt
+    /// Channels with a callback registered through `set_period_callback()` are handled by the HAL,
+    /// the `Pwm()` handler is only called for the remaining channels
+    Pwm,
     /// Timer Channel 0 Interrupt
+    /// Comparator 0 matches of a channel started with `start_periodic()` or `start_one_shot()` are
+    /// handled by the HAL, the `TimerCh0()` handler is only called for the other comparators
     TimerCh0,
     /// Timer Channel 1 Interrupt
+    /// Handled like `TimerCh0`
     TimerCh1,
     /// Watchdog Timer Interrupt
     /// Used when WDT is configured in Interrupt mode using ConfiguredWatchdog0::set_mode()
+    /// The first timeout of `start_escalating()` is handled by the HAL, without calling the
+    /// `Watchdog()` handler
     Watchdog,
 }
 
@@ -165,7 +261,13 @@ impl Interrupt {
     fn to_irq(&self) -> u32 {
         match &self {
             Interrupt::Unknown => panic!("Unknown interrupt has no irq number"),
+            Interrupt::Bor => BOR_IRQ,
+            Interrupt::Dma => DMA_IRQ,
+            Interrupt::Gpadc => GPADC_IRQ,
             Interrupt::Gpio => GPIO_IRQ,
+            Interrupt::HbnOut0 => HBN_OUT0_IRQ,
+            Interrupt::HbnOut1 => HBN_OUT1_IRQ,
+            Interrupt::Pwm => PWM_IRQ,
             Interrupt::TimerCh0 => TIMER_CH0_IRQ,
             Interrupt::TimerCh1 => TIMER_CH1_IRQ,
             Interrupt::Watchdog => WATCHDOG_IRQ,
@@ -174,7 +276,13 @@ impl Interrupt {
 
     fn from(irq: u32) -> Interrupt {
         match irq {
+            BOR_IRQ => Interrupt::Bor,
+            DMA_IRQ => Interrupt::Dma,
+            GPADC_IRQ => Interrupt::Gpadc,
             GPIO_IRQ => Interrupt::Gpio,
+            HBN_OUT0_IRQ => Interrupt::HbnOut0,
+            HBN_OUT1_IRQ => Interrupt::HbnOut1,
+            PWM_IRQ => Interrupt::Pwm,
             TIMER_CH0_IRQ => Interrupt::TimerCh0,
             TIMER_CH1_IRQ => Interrupt::TimerCh1,
             WATCHDOG_IRQ => Interrupt::Watchdog,
@@ -201,6 +309,15 @@ pub fn disable_interrupt(interrupt: Interrupt) {
     }
 }
 
+/// Set the given interrupt pending, so its handler runs as soon as it's enabled
+pub(crate) fn pend_interrupt(interrupt: Interrupt) {
+    let irq = interrupt.to_irq();
+    let ptr = (CLIC_HART0_ADDR + CLIC_INTIP + irq) as *mut u8;
+    unsafe {
+        ptr.write_volatile(1);
+    }
+}
+
 /// Clear the given interrupt.
 /// Usually the interrupt needs to be cleared also on the peripheral level.
 pub fn clear_interrupt(interrupt: Interrupt) {
@@ -34,18 +34,29 @@
 
 pub use bl602_pac as pac;
 
+pub mod acomp;
+pub mod adc;
 pub mod checksum;
 pub mod clock;
+pub mod crash;
+pub mod dac;
 pub mod delay;
+pub mod dma;
 pub mod gpio;
 pub mod i2c;
 pub mod interrupts;
+pub mod power;
+pub mod profile;
+pub mod pwm;
+pub mod reset;
 pub mod rtc;
 pub mod serial;
 pub mod spi;
 pub mod timer;
 pub mod watchdog;
 
+mod reg;
+
 /// HAL crate prelude
 pub mod prelude {
     pub use crate::gpio::GlbExt as _bl602_hal_gpio_GlbExt;
@@ -0,0 +1,392 @@
+/*!
+  # Power Management
+  The chip saves power by powering down parts of it while it sleeps.
+
+  - [bor]: The brown out detector watches the supply voltage.
+  - [hbn]: Hibernate powers down everything but the always-on domain. Waking up restarts the
+    chip, so only the RTC and the retention RAM keep their state.
+  - [pds]: Power down sleep stops the clocks and powers down a choice of domains. It returns
+    after waking up, unless the CPU was powered down.
+  - [regulator]: The core voltage is tuned to the clocks to save current.
+  - [retention]: The retention RAM keeps values through hibernate and resets.
+  - [suspend]: Drivers save and restore their registers around sleeps that reset them.
+
+  ## Wakeup cause example
+  [wakeup_cause] tells why the chip (re)started, from the reset flags and the wake sources of
+  hibernate, so the startup path can branch on it.
+  ```rust
+    match power::wakeup_cause() {
+        WakeupCause::RtcWakeup => {
+            // take the next measurement and hibernate again
+        }
+        WakeupCause::GpioWakeup { pin: 7 } => {
+            // the button was pressed
+        }
+        WakeupCause::Watchdog | WakeupCause::BrownOut => {
+            // recover
+        }
+        _ => {
+            // cold start
+        }
+    }
+    power::clear_wakeup_cause();
+  ```
+
+  ## Idle example
+  [idle] gates the peripheral clocks, except for the ones kept, while it waits for an
+  interrupt. It needs no setup and saves a few mA in loops polling e.g. a timer.
+  ```rust
+    let keep = KeepClocks::new().with::<clock::Timer>().with::<clock::Uart0>();
+    loop {
+        power::idle(keep);
+        // handle the events ...
+    }
+  ```
+
+  ## Tickless idle example
+  [idle_until_next_event] asks a scheduler for its next deadline through [NextEvent], and
+  sleeps in PDS until then if it's far enough away, waits for an interrupt if it's closer, or
+  returns right away if it's due. The scheduler's counter has to keep counting while the chip
+  sleeps, e.g. from the 1kHz clock.
+  ```rust
+    let policy = SleepPolicy::new(KeepClocks::new().with::<clock::Timer>())
+        .level(pds::Level::Pds1)
+        .min_sleep(10u32.milliseconds());
+    loop {
+        power::idle_until_next_event(&scheduler, &policy, &clocks);
+        scheduler.poll(|id| {
+            // ...
+        });
+    }
+  ```
+  A scheduler of its own, or a time driver, reports its deadline by implementing [NextEvent].
+
+  ## Software reset example
+  [reset_system] and [reset_cpu] restart the chip, either warm, keeping the retention RAM and the
+  HBN scratch registers, or like a cold boot, as chosen by a [ResetPolicy].
+  ```rust
+    if update_installed {
+        power::reset_system(ResetPolicy::cold());
+    }
+    // keeps e.g. a retained counter of the restarts
+    power::reset_cpu(ResetPolicy::warm());
+  ```
+*/
+
+use crate::{
+    clock::{self, ClockGate, Clocks},
+    pac,
+    power::hbn::WakeupSource,
+    reg::{glb, read_reg, write_reg},
+    reset::{self, ResetReason},
+};
+use embedded_time::duration::Nanoseconds;
+
+pub mod bor;
+pub mod hbn;
+pub mod pds;
+pub mod regulator;
+pub mod retention;
+pub mod suspend;
+
+// see components\bl602\bl602_std\bl602_std\StdDriver\Inc\bl602_glb_reg.h
+const GLB_CGEN_CFG1: usize = 0x24;
+
+const SWRST_CFG2_CTRL_PWRON_RST: u32 = 1 << 0;
+const SWRST_CFG2_CTRL_CPU_RESET: u32 = 1 << 1;
+const SWRST_CFG2_CTRL_SYS_RESET: u32 = 1 << 2;
+
+/// Set in HBN_RSV3 before a software reset
+const SOFTWARE_RESET_FLAG: u32 = 0x5357_5253;
+
+/// Why the chip (re)started
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum WakeupCause {
+    /// Power on, or a reset without a flag of its own, like the reset pin
+    PowerOn,
+    /// Wakeup from hibernate by GPIO7 or GPIO8
+    GpioWakeup {
+        /// The pin number
+        pin: u8,
+    },
+    /// Wakeup from hibernate by the RTC
+    RtcWakeup,
+    /// Wakeup from hibernate by an analog comparator
+    ComparatorWakeup,
+    /// The watchdog timed out in reset mode
+    Watchdog,
+    /// The supply voltage dropped below the brown out threshold
+    BrownOut,
+    /// A reset with [reset_system] or [reset_cpu]
+    SoftwareReset,
+}
+
+/// Tells why the chip (re)started. When several causes are flagged, the watchdog is reported
+/// first, then a brown out, a software reset and a wakeup from hibernate.
+///
+/// The flags survive resets until they're cleared with [clear_wakeup_cause].
+pub fn wakeup_cause() -> WakeupCause {
+    let hbn = unsafe { &*pac::HBN::ptr() };
+    match reset::reset_reason() {
+        ResetReason::Watchdog => WakeupCause::Watchdog,
+        ResetReason::BrownOut => WakeupCause::BrownOut,
+        _ if hbn.hbn_rsv3.read().bits() == SOFTWARE_RESET_FLAG => WakeupCause::SoftwareReset,
+        ResetReason::HibernateWakeup => match hbn::wakeup_source() {
+            Some(WakeupSource::Pin7) => WakeupCause::GpioWakeup { pin: 7 },
+            Some(WakeupSource::Pin8) => WakeupCause::GpioWakeup { pin: 8 },
+            Some(WakeupSource::Rtc) => WakeupCause::RtcWakeup,
+            Some(WakeupSource::Acomp0 | WakeupSource::Acomp1) => WakeupCause::ComparatorWakeup,
+            // e.g. the reset pin while hibernating
+            None => WakeupCause::PowerOn,
+        },
+        ResetReason::PowerOn => WakeupCause::PowerOn,
+    }
+}
+
+/// Clears the reset flags, the wake sources of hibernate and the software reset flag, so the
+/// next start reports its own cause
+pub fn clear_wakeup_cause() {
+    hbn::clear_wakeup_source();
+    reset::clear_reset_reason();
+    unsafe { &*pac::HBN::ptr() }
+        .hbn_rsv3
+        .write(|w| unsafe { w.bits(0) });
+}
+
+/// The peripheral clocks [idle] keeps running
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct KeepClocks {
+    mask: u32,
+}
+
+impl KeepClocks {
+    /// Keeps none of the gated clocks running
+    pub fn new() -> Self {
+        KeepClocks { mask: 0 }
+    }
+
+    /// Keeps the clock of the peripheral `P` running, e.g. `with::<clock::Timer>()`
+    pub fn with<P: ClockGate>(mut self) -> Self {
+        self.mask |= clock::gate_bit::<P>();
+
+        self
+    }
+}
+
+/// Gates the peripheral clocks, except for `keep` and the ones gated already, and waits for an
+/// interrupt, then ungates them again. The interrupt is handled after the clocks are back.
+///
+/// The peripheral raising the interrupt has to be kept, as a gated peripheral can't raise one.
+pub fn idle(keep: KeepClocks) {
+    critical_section::with(|_| {
+        let cgen = read_reg(glb(GLB_CGEN_CFG1));
+        write_reg(
+            glb(GLB_CGEN_CFG1),
+            cgen & !(clock::CLOCK_GATES & !keep.mask),
+        );
+
+        unsafe { riscv::asm::wfi() };
+
+        write_reg(glb(GLB_CGEN_CFG1), cgen);
+    });
+}
+
+/// A scheduler reporting its next deadline to [idle_until_next_event], implemented by
+/// `timer::Scheduler`
+pub trait NextEvent {
+    /// The time left until the next deadline, zero if it's due, or `None` without any
+    fn time_until_next_event(&self) -> Option<Nanoseconds<u64>>;
+}
+
+/// How [idle_until_next_event] waits
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SleepPolicy {
+    /// The peripheral clocks kept running while waiting for an interrupt
+    pub keep: KeepClocks,
+    /// The level of PDS sleep, which has to keep the CPU powered on
+    pub level: pds::Level,
+    /// The least time until the next deadline to sleep in PDS, as powering down and up again
+    /// takes a while
+    pub min_sleep: Nanoseconds<u64>,
+}
+
+impl SleepPolicy {
+    /// Keeps the `keep` clocks while waiting for an interrupt, and sleeps in PDS0 for at least
+    /// 5ms
+    pub fn new(keep: KeepClocks) -> Self {
+        SleepPolicy {
+            keep,
+            level: pds::Level::Pds0,
+            min_sleep: Nanoseconds(5_000_000),
+        }
+    }
+
+    /// Sets the level of PDS sleep
+    pub fn level(mut self, level: pds::Level) -> Self {
+        self.level = level;
+
+        self
+    }
+
+    /// Sets the least time until the next deadline to sleep in PDS
+    pub fn min_sleep(mut self, min_sleep: impl Into<Nanoseconds<u64>>) -> Self {
+        self.min_sleep = min_sleep.into();
+
+        self
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for SleepPolicy {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "SleepPolicy {{ keep: {}, level: {}, min_sleep: {=u64} }}",
+            self.keep,
+            self.level,
+            self.min_sleep.0
+        )
+    }
+}
+
+/// What [idle_until_next_event] did
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum IdleAction {
+    /// Returned right away, as the next deadline is due
+    StayedAwake,
+    /// Waited for an interrupt with [idle]
+    WaitedForInterrupt,
+    /// Slept in PDS until the next deadline
+    Slept,
+}
+
+/// Waits for the next deadline of `next` as economically as `policy` allows: it sleeps in PDS
+/// when the deadline is at least `policy.min_sleep` away, waits for an interrupt with [idle] when
+/// it's closer or there's none, and returns right away when it's due. Deadlines further away
+/// than [pds::max_duration] are slept towards for that long, and [IdleAction::Slept] returned.
+///
+/// # Panics
+///
+/// If `policy.level` powers the CPU down.
+pub fn idle_until_next_event(
+    next: &impl NextEvent,
+    policy: &SleepPolicy,
+    clocks: &Clocks,
+) -> IdleAction {
+    if policy.level.powers_down_cpu() {
+        panic!("PDS level powers the CPU down");
+    }
+
+    match next.time_until_next_event() {
+        Some(left) if left.0 == 0 => IdleAction::StayedAwake,
+        Some(left) if left >= policy.min_sleep => {
+            // A deadline past the range of the sleep timer is reached over several sleeps
+            let left = core::cmp::min(left, pds::max_duration());
+            pds::sleep(policy.level, left, clocks);
+            IdleAction::Slept
+        }
+        _ => {
+            idle(policy.keep);
+            IdleAction::WaitedForInterrupt
+        }
+    }
+}
+
+/// What a software reset keeps of the always-on domain
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ResetPolicy {
+    keep_scratch: bool,
+    keep_retention: bool,
+}
+
+impl ResetPolicy {
+    /// A warm restart, keeping the HBN scratch registers and the retention RAM
+    pub fn warm() -> Self {
+        ResetPolicy {
+            keep_scratch: true,
+            keep_retention: true,
+        }
+    }
+
+    /// A restart like a cold boot, clearing the HBN scratch registers, the retention RAM and the
+    /// reset flags
+    pub fn cold() -> Self {
+        ResetPolicy {
+            keep_scratch: false,
+            keep_retention: false,
+        }
+    }
+
+    /// Keeps the HBN scratch registers, which hold the hibernate flag, the time of the RTC
+    /// calendar and the system clock
+    pub fn keep_scratch(mut self, keep: bool) -> Self {
+        self.keep_scratch = keep;
+
+        self
+    }
+
+    /// Keeps the retention RAM
+    pub fn keep_retention(mut self, keep: bool) -> Self {
+        self.keep_retention = keep;
+
+        self
+    }
+}
+
+impl Default for ResetPolicy {
+    fn default() -> Self {
+        ResetPolicy::warm()
+    }
+}
+
+/// Resets the whole system, except for the always-on domain, like the reset pin. The next start
+/// reports `WakeupCause::SoftwareReset`.
+pub fn reset_system(policy: ResetPolicy) -> ! {
+    software_reset(policy, SWRST_CFG2_CTRL_SYS_RESET)
+}
+
+/// Resets the CPU, which starts over from the boot ROM, while the peripherals keep running. The
+/// next start reports `WakeupCause::SoftwareReset`.
+pub fn reset_cpu(policy: ResetPolicy) -> ! {
+    software_reset(policy, SWRST_CFG2_CTRL_CPU_RESET)
+}
+
+fn software_reset(policy: ResetPolicy, reset: u32) -> ! {
+    unsafe { riscv::interrupt::disable() };
+
+    let hbn = unsafe { &*pac::HBN::ptr() };
+    if !policy.keep_scratch {
+        hbn.hbn_rsv0.write(|w| unsafe { w.bits(0) });
+        hbn.hbn_rsv1.write(|w| unsafe { w.bits(0) });
+        hbn.hbn_rsv2.write(|w| unsafe { w.bits(0) });
+    }
+    if !policy.keep_retention {
+        retention::erase();
+    }
+    if !policy.keep_scratch && !policy.keep_retention {
+        hbn::clear_wakeup_source();
+        reset::clear_reset_reason();
+    }
+    hbn.hbn_rsv3
+        .write(|w| unsafe { w.bits(SOFTWARE_RESET_FLAG) });
+
+    // The boot ROM expects the default clocks
+    let glb = unsafe { &*pac::GLB::ptr() };
+    hbn.hbn_glb
+        .modify(|_, w| unsafe { w.hbn_root_clk_sel().bits(0) });
+    glb.clk_cfg0
+        .modify(|_, w| unsafe { w.reg_hclk_div().bits(0).reg_bclk_div().bits(0) });
+
+    let r = glb.swrst_cfg2.read().bits()
+        & !(SWRST_CFG2_CTRL_PWRON_RST | SWRST_CFG2_CTRL_CPU_RESET | SWRST_CFG2_CTRL_SYS_RESET);
+    glb.swrst_cfg2.write(|w| unsafe { w.bits(r) });
+    glb.swrst_cfg2.write(|w| unsafe { w.bits(r | reset) });
+
+    loop {
+        core::hint::spin_loop();
+    }
+}
@@ -0,0 +1,109 @@
+/*!
+  # Brown Out Reset
+  The brown out detector (BOR) in the always-on domain watches the supply voltage. When it drops
+  below the threshold, it either resets the chip, or only raises the `Bor` interrupt, so firmware
+  can save its state or log the event instead of resetting blind.
+
+  ## Example
+  ```rust
+    bor::enable(Threshold::Mv2400, Action::Interrupt);
+    bor::listen();
+    enable_interrupt(Interrupt::Bor);
+
+    #[no_mangle]
+    fn Bor() {
+        // save the state to flash ...
+        bor::clear_interrupt();
+    }
+  ```
+
+  A brown out reset is reported as `ResetReason::BrownOut` by
+  [reset_reason](crate::reset::reset_reason).
+*/
+
+use crate::pac;
+
+// see components\bl602\bl602_std\bl602_std\StdDriver\Inc\hbn_reg.h
+const IRQ_MODE_BOR_EN: u32 = 1 << 18;
+
+const IRQ_BOR: u32 = 1 << 18;
+
+const BOR_CFG_BOR_SEL: u32 = 1 << 0;
+const BOR_CFG_BOR_VTH: u32 = 1 << 1;
+const BOR_CFG_PU_BOR: u32 = 1 << 2;
+const BOR_CFG_R_BOR_OUT: u32 = 1 << 3;
+
+/// The supply voltage below which a brown out is detected
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Threshold {
+    /// 2.0V
+    Mv2000,
+    /// 2.4V
+    Mv2400,
+}
+
+/// What a brown out does
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Action {
+    /// Reset the chip, like at power on
+    Reset,
+    /// Only flag the brown out, and raise the `Bor` interrupt when listening
+    Interrupt,
+}
+
+/// Powers on the detector with `threshold`, doing `action` on a brown out
+pub fn enable(threshold: Threshold, action: Action) {
+    unsafe { &*pac::HBN::ptr() }.hbn_bor_cfg.modify(|r, w| {
+        let mut bits = r.bits() & !(BOR_CFG_BOR_SEL | BOR_CFG_BOR_VTH) | BOR_CFG_PU_BOR;
+        if threshold == Threshold::Mv2400 {
+            bits |= BOR_CFG_BOR_VTH;
+        }
+        if action == Action::Reset {
+            bits |= BOR_CFG_BOR_SEL;
+        }
+        unsafe { w.bits(bits) }
+    });
+}
+
+/// Powers down the detector
+pub fn disable() {
+    unsafe { &*pac::HBN::ptr() }
+        .hbn_bor_cfg
+        .modify(|r, w| unsafe { w.bits(r.bits() & !BOR_CFG_PU_BOR) });
+}
+
+/// Returns `true` while the supply voltage is below the threshold
+pub fn is_below_threshold() -> bool {
+    unsafe { &*pac::HBN::ptr() }.hbn_bor_cfg.read().bits() & BOR_CFG_R_BOR_OUT != 0
+}
+
+/// Raises the `Bor` interrupt on a brown out
+pub fn listen() {
+    unsafe { &*pac::HBN::ptr() }
+        .hbn_irq_mode
+        .modify(|r, w| unsafe { w.bits(r.bits() | IRQ_MODE_BOR_EN) });
+}
+
+/// Stops raising the `Bor` interrupt
+pub fn unlisten() {
+    unsafe { &*pac::HBN::ptr() }
+        .hbn_irq_mode
+        .modify(|r, w| unsafe { w.bits(r.bits() & !IRQ_MODE_BOR_EN) });
+}
+
+/// Returns `true` if a brown out was detected since the flag was cleared
+pub fn is_interrupt_pending() -> bool {
+    unsafe { &*pac::HBN::ptr() }.hbn_irq_stat.read().bits() & IRQ_BOR != 0
+}
+
+/// Clears the brown out flag and its interrupt
+pub fn clear_interrupt() {
+    unsafe { &*pac::HBN::ptr() }
+        .hbn_irq_clr
+        .write(|w| unsafe { w.bits(IRQ_BOR) });
+    unsafe { &*pac::HBN::ptr() }
+        .hbn_irq_clr
+        .write(|w| unsafe { w.bits(0) });
+}
@@ -0,0 +1,367 @@
+/*!
+  # Hibernate
+  Hibernate (HBN) powers down everything but the always-on (AON) domain, which draws only a few
+  microamps. The level chooses what else stays powered:
+
+  - [Level::Hbn0]: the HBN core and the RTC keep running
+  - [Level::Hbn1]: the RTC keeps running
+  - [Level::Hbn2]: only the AON domain stays on, so the RTC can't wake the chip
+
+  GPIO7 and GPIO8, the RTC and the analog comparators wake the chip up. Waking up restarts it
+  through the boot ROM, so [hibernate] never returns. Afterwards
+  [reset_reason](crate::reset::reset_reason) reports `ResetReason::HibernateWakeup` and
+  [wakeup_source] tells what woke the chip.
+
+  ## Example
+  ```rust
+    match hbn::wakeup_source() {
+        Some(WakeupSource::Rtc) => {
+            // time for the next measurement
+        }
+        Some(WakeupSource::Pin7) => {
+            // the button was pressed
+        }
+        _ => {
+            // power on, set everything up
+        }
+    }
+    hbn::clear_wakeup_source();
+    reset::clear_reset_reason();
+
+    // take a measurement ...
+
+    let config = hbn::Config::new(Level::Hbn1)
+        .wake_on_pins(WakePins::Pin7, Trigger::FallingEdge)
+        .wake_after(60.seconds());
+    hbn::hibernate(&config);
+  ```
+
+  The comparators wake the chip up on the edges set with
+  [Acomp::wake_on](crate::acomp::Acomp::wake_on), they keep running in every level.
+*/
+
+use crate::{
+    pac,
+    reg::{ram_read_reg, ram_write_reg, HBN_BASE},
+    reset::HBN_STATUS_ENTER_FLAG,
+    rtc,
+};
+use embedded_time::duration::Nanoseconds;
+
+// see components\bl602\bl602_std\bl602_std\StdDriver\Inc\hbn_reg.h
+// `enter_hbn` runs from RAM, so it accesses these by address
+const HBN_CTL: usize = 0x00;
+const HBN_GLB: usize = 0x30;
+
+const CTL_RTC_COMP_SHIFT: u32 = 1;
+const CTL_RTC_COMP_MASK: u32 = 0x7;
+const CTL_RTC_COMP_BIT0_39: u32 = 1;
+const CTL_HBN_MODE: u32 = 1 << 7;
+const CTL_PWRDN_HBN_CORE: u32 = 1 << 9;
+const CTL_PWRDN_HBN_RTC: u32 = 1 << 11;
+const CTL_RTC_DLY_OPTION: u32 = 1 << 24;
+const CTL_PWR_ON_OPTION: u32 = 1 << 25;
+
+const TIME_H_MASK: u32 = 0xff;
+const RTC_TIME_H_LATCH: u32 = 1 << 31;
+
+const IRQ_MODE_PIN_WAKEUP_MODE_MASK: u32 = 0x7;
+const IRQ_MODE_PIN_WAKEUP_MASK_SHIFT: u32 = 3;
+const IRQ_MODE_AON_PAD_IE_SMT_SHIFT: u32 = 8;
+const IRQ_MODE_PINS_MASK: u32 = 0x3;
+
+const IRQ_GPIO7: u32 = 1 << 0;
+const IRQ_GPIO8: u32 = 1 << 1;
+const IRQ_RTC: u32 = 1 << 16;
+const IRQ_ACOMP0: u32 = 1 << 20;
+const IRQ_ACOMP1: u32 = 1 << 22;
+const IRQ_WAKEUP: u32 = IRQ_GPIO7 | IRQ_GPIO8 | IRQ_RTC | IRQ_ACOMP0 | IRQ_ACOMP1;
+
+const HBN_GLB_ROOT_CLK_SEL_MASK: u32 = 0x3;
+
+const SRAM_RETRAM_RET: u32 = 1 << 6;
+const SRAM_RETRAM_SLP: u32 = 1 << 7;
+
+/// The RTC counts at 32.768kHz in hibernate
+const RTC_TICK_RATE: u64 = 32_768;
+/// The compare value of the RTC is 40 bits wide
+const RTC_COMPARE_MASK: u64 = (1 << 40) - 1;
+
+/// How deep the chip hibernates
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Level {
+    /// The HBN core and the RTC stay powered
+    Hbn0,
+    /// The HBN core is powered down, the RTC keeps running
+    Hbn1,
+    /// The HBN core and the RTC are powered down
+    Hbn2,
+}
+
+impl Level {
+    fn power_down(&self) -> u32 {
+        match self {
+            Level::Hbn0 => 0,
+            Level::Hbn1 => CTL_PWRDN_HBN_CORE,
+            Level::Hbn2 => CTL_PWRDN_HBN_CORE | CTL_PWRDN_HBN_RTC,
+        }
+    }
+}
+
+/// The pins that wake the chip up, only GPIO7 and GPIO8 are in the AON domain
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum WakePins {
+    /// GPIO7
+    Pin7 = 0b01,
+    /// GPIO8
+    Pin8 = 0b10,
+    /// GPIO7 and GPIO8
+    Both = 0b11,
+}
+
+/// The level or edge of a pin that wakes the chip up
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Trigger {
+    /// A falling edge, sampled with the 32kHz clock
+    FallingEdge = 0,
+    /// A rising edge, sampled with the 32kHz clock
+    RisingEdge = 1,
+    /// A low level, sampled with the 32kHz clock
+    LowLevel = 2,
+    /// A high level, sampled with the 32kHz clock
+    HighLevel = 3,
+    /// A falling edge, without sampling, for pulses shorter than a 32kHz cycle
+    AsyncFallingEdge = 4,
+    /// A rising edge, without sampling, for pulses shorter than a 32kHz cycle
+    AsyncRisingEdge = 5,
+    /// A low level, without sampling
+    AsyncLowLevel = 6,
+    /// A high level, without sampling
+    AsyncHighLevel = 7,
+}
+
+/// What woke the chip up from hibernate
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum WakeupSource {
+    /// GPIO7
+    Pin7,
+    /// GPIO8
+    Pin8,
+    /// The RTC reached the time set with [Config::wake_after], or the alarm
+    Rtc,
+    /// Analog comparator 0
+    Acomp0,
+    /// Analog comparator 1
+    Acomp1,
+}
+
+/// Hibernate configuration
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Config {
+    /// How deep the chip hibernates
+    pub level: Level,
+    /// Keeps the content of the 4KiB retention RAM
+    pub retain_ram: bool,
+    /// The pins that wake the chip up and on what
+    pub wake_pins: Option<(WakePins, Trigger)>,
+    /// RTC ticks after which the chip wakes up
+    pub wake_ticks: Option<u64>,
+    /// Keeps the RTC alarm armed
+    pub wake_on_alarm: bool,
+}
+
+impl Config {
+    /// Hibernates at `level` with the retention RAM kept and without any wake source
+    pub fn new(level: Level) -> Self {
+        Config {
+            level,
+            retain_ram: true,
+            wake_pins: None,
+            wake_ticks: None,
+            wake_on_alarm: false,
+        }
+    }
+
+    /// Sets the level
+    pub fn level(mut self, level: Level) -> Self {
+        self.level = level;
+
+        self
+    }
+
+    /// Keeps or powers down the retention RAM
+    pub fn retain_ram(mut self, retain: bool) -> Self {
+        self.retain_ram = retain;
+
+        self
+    }
+
+    /// Wakes the chip up on `trigger` on `pins`
+    pub fn wake_on_pins(mut self, pins: WakePins, trigger: Trigger) -> Self {
+        self.wake_pins = Some((pins, trigger));
+
+        self
+    }
+
+    /// Wakes the chip up with the RTC after `duration`, compensating the drift of the 32K clock
+    /// set with `rtc::set_drift_ppm` or `rtc::calibrate` at the time of the call. The RTC is
+    /// powered down in `Level::Hbn2`, so it can't be used there.
+    ///
+    /// This uses the comparator of the alarm, so it replaces [Config::wake_on_alarm].
+    pub fn wake_after(mut self, duration: impl Into<Nanoseconds<u64>>) -> Self {
+        let micros = duration.into().0 / 1_000;
+        let ticks = rtc::compensate_ticks(micros * RTC_TICK_RATE / 1_000_000);
+        self.wake_ticks = Some(ticks.max(1));
+        self.wake_on_alarm = false;
+
+        self
+    }
+
+    /// Wakes the chip up at the time of the alarm set with
+    /// [Rtc::set_alarm](crate::rtc::Rtc::set_alarm), instead of after a duration. The RTC is
+    /// powered down in `Level::Hbn2`, so it can't be used there.
+    pub fn wake_on_alarm(mut self) -> Self {
+        self.wake_on_alarm = true;
+        self.wake_ticks = None;
+
+        self
+    }
+}
+
+/// Enters hibernate with `config`, the chip restarts when it wakes up.
+///
+/// Panics when the RTC should wake the chip from `Level::Hbn2`, which powers the RTC down.
+pub fn hibernate(config: &Config) -> ! {
+    if config.level == Level::Hbn2 && (config.wake_ticks.is_some() || config.wake_on_alarm) {
+        panic!("The RTC can't wake the chip from HBN2");
+    }
+
+    unsafe { riscv::interrupt::disable() };
+
+    // Pending wake sources would wake the chip up right away
+    let hbn = unsafe { &*pac::HBN::ptr() };
+    hbn.hbn_irq_clr.write(|w| unsafe { w.bits(IRQ_WAKEUP) });
+    hbn.hbn_irq_clr.write(|w| unsafe { w.bits(0) });
+
+    let (pins, mode) = match config.wake_pins {
+        Some((pins, trigger)) => (pins as u32, trigger as u32),
+        None => (0, 0),
+    };
+    hbn.hbn_irq_mode.modify(|r, w| unsafe {
+        w.bits(
+            r.bits()
+                & !(IRQ_MODE_PIN_WAKEUP_MODE_MASK
+                    | IRQ_MODE_PINS_MASK << IRQ_MODE_PIN_WAKEUP_MASK_SHIFT
+                    | IRQ_MODE_PINS_MASK << IRQ_MODE_AON_PAD_IE_SMT_SHIFT)
+                | mode
+                | (!pins & IRQ_MODE_PINS_MASK) << IRQ_MODE_PIN_WAKEUP_MASK_SHIFT
+                | pins << IRQ_MODE_AON_PAD_IE_SMT_SHIFT,
+        )
+    });
+
+    if !config.wake_on_alarm {
+        hbn.hbn_ctl.modify(|r, w| unsafe {
+            w.bits(r.bits() & !(CTL_RTC_COMP_MASK << CTL_RTC_COMP_SHIFT))
+        });
+    }
+    if let Some(ticks) = config.wake_ticks {
+        let compare = (rtc_ticks() + ticks) & RTC_COMPARE_MASK;
+        hbn.hbn_time_l.write(|w| unsafe { w.bits(compare as u32) });
+        hbn.hbn_time_h
+            .modify(|r, w| unsafe { w.bits(r.bits() & !TIME_H_MASK | (compare >> 32) as u32) });
+        hbn.hbn_ctl.modify(|r, w| unsafe {
+            w.bits(r.bits() & !CTL_RTC_DLY_OPTION | CTL_RTC_COMP_BIT0_39 << CTL_RTC_COMP_SHIFT)
+        });
+    }
+
+    hbn.hbn_sram.modify(|r, w| {
+        let bits = r.bits() & !(SRAM_RETRAM_RET | SRAM_RETRAM_SLP);
+        if config.retain_ram {
+            unsafe { w.bits(bits | SRAM_RETRAM_RET) }
+        } else {
+            unsafe { w.bits(bits | SRAM_RETRAM_SLP) }
+        }
+    });
+
+    hbn.hbn_ctl.modify(|r, w| unsafe {
+        w.bits(
+            r.bits() & !(CTL_PWRDN_HBN_CORE | CTL_PWRDN_HBN_RTC | CTL_PWR_ON_OPTION)
+                | config.level.power_down(),
+        )
+    });
+
+    // Tells the boot ROM and `reset_reason()` that the chip wakes up from hibernate
+    hbn.hbn_rsv0
+        .write(|w| unsafe { w.bits(HBN_STATUS_ENTER_FLAG) });
+
+    enter_hbn()
+}
+
+/// Tells what woke the chip up from hibernate, or `None` if it didn't wake up from hibernate.
+/// With several sources pending, the pins are reported first, then the RTC and the comparators.
+pub fn wakeup_source() -> Option<WakeupSource> {
+    if unsafe { &*pac::HBN::ptr() }.hbn_rsv0.read().bits() != HBN_STATUS_ENTER_FLAG {
+        return None;
+    }
+
+    let status = unsafe { &*pac::HBN::ptr() }.hbn_irq_stat.read().bits();
+    if status & IRQ_GPIO7 != 0 {
+        Some(WakeupSource::Pin7)
+    } else if status & IRQ_GPIO8 != 0 {
+        Some(WakeupSource::Pin8)
+    } else if status & IRQ_RTC != 0 {
+        Some(WakeupSource::Rtc)
+    } else if status & IRQ_ACOMP0 != 0 {
+        Some(WakeupSource::Acomp0)
+    } else if status & IRQ_ACOMP1 != 0 {
+        Some(WakeupSource::Acomp1)
+    } else {
+        None
+    }
+}
+
+/// Clears the pending wake sources and stops the RTC compare. The hibernate flag itself is
+/// cleared with [clear_reset_reason](crate::reset::clear_reset_reason).
+pub fn clear_wakeup_source() {
+    let hbn = unsafe { &*pac::HBN::ptr() };
+    hbn.hbn_ctl
+        .modify(|r, w| unsafe { w.bits(r.bits() & !(CTL_RTC_COMP_MASK << CTL_RTC_COMP_SHIFT)) });
+    hbn.hbn_irq_clr.write(|w| unsafe { w.bits(IRQ_WAKEUP) });
+    hbn.hbn_irq_clr.write(|w| unsafe { w.bits(0) });
+}
+
+fn rtc_ticks() -> u64 {
+    let hbn = unsafe { &*pac::HBN::ptr() };
+    hbn.rtc_time_h
+        .modify(|r, w| unsafe { w.bits(r.bits() | RTC_TIME_H_LATCH) });
+    let h = hbn.rtc_time_h.read().bits() & TIME_H_MASK;
+    let l = hbn.rtc_time_l.read().bits();
+    (h as u64) << 32 | l as u64
+}
+
+/// Switches to RC32M and enters hibernate. It runs from RAM, as the flash goes away with the
+/// core domain.
+#[inline(never)]
+#[link_section = ".data.bl602_hal.enter_hbn"]
+fn enter_hbn() -> ! {
+    const HBN_GLB_ADDRESS: usize = HBN_BASE + HBN_GLB;
+    const HBN_CTL_ADDRESS: usize = HBN_BASE + HBN_CTL;
+
+    unsafe {
+        let r = ram_read_reg!(HBN_GLB_ADDRESS);
+        ram_write_reg!(HBN_GLB_ADDRESS, r & !HBN_GLB_ROOT_CLK_SEL_MASK);
+
+        let r = ram_read_reg!(HBN_CTL_ADDRESS);
+        ram_write_reg!(HBN_CTL_ADDRESS, r | CTL_HBN_MODE);
+
+        // The core domain is powered down within a few cycles
+        loop {
+            core::arch::asm!("wfi");
+        }
+    }
+}
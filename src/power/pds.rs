@@ -0,0 +1,207 @@
+/*!
+  # Power Down Sleep
+  Power down sleep (PDS) stops the clocks of the chip and powers down some of its domains, until
+  the sleep timer expires or another wake source, like the alarm set with
+  [Rtc::set_alarm](crate::rtc::Rtc::set_alarm) or a comparator set up with
+  [Acomp::wake_on](crate::acomp::Acomp::wake_on), fires. The level chooses which domains are
+  powered down:
+
+  | Level  | Wireless | CPU | Core misc |
+  |--------|----------|-----|-----------|
+  | `Pds0` | on       | on  | on        |
+  | `Pds1` | off      | on  | on        |
+  | `Pds2` | on       | off | on        |
+  | `Pds3` | off      | off | on        |
+  | `Pds4` | on       | on  | off       |
+  | `Pds5` | off      | on  | off       |
+  | `Pds6` | on       | off | off       |
+  | `Pds7` | off      | off | off       |
+
+  With the CPU powered on, [sleep] returns after waking up, with the system clock as before.
+  With the CPU powered down, waking up restarts the chip like a wakeup from hibernate. Powering
+  down the core misc domain resets the peripherals, which [sleep_with] saves and restores.
+
+  The watchdog is handled according to its
+  [SleepBehavior](crate::watchdog::SleepBehavior) while sleeping.
+
+  ## Example
+  ```rust
+    loop {
+        // take a measurement and send it ...
+
+        pds::sleep(Level::Pds1, 500.milliseconds(), &clocks);
+    }
+  ```
+*/
+
+use crate::{
+    clock,
+    clock::Clocks,
+    pac,
+    power::suspend::Resume,
+    reg::{ram_read_reg, ram_write_reg, PDS_BASE},
+    rtc, watchdog,
+};
+use embedded_time::duration::Nanoseconds;
+
+// see components\bl602\bl602_std\bl602_std\StdDriver\Inc\pds_reg.h
+// `enter_pds` runs from RAM, so it accesses this by address
+const PDS_CTL: usize = 0x00;
+
+const CTL_START_PS: u32 = 1 << 0;
+const CTL_SLEEP_FOREVER: u32 = 1 << 1;
+const CTL_NP_WFI_MASK: u32 = 1 << 21;
+
+const CTL4_CPU_SHIFT: u32 = 0;
+const CTL4_WB_SHIFT: u32 = 12;
+const CTL4_MISC_SHIFT: u32 = 24;
+const CTL4_PWR_OFF: u32 = 1 << 0;
+const CTL4_RESET: u32 = 1 << 1;
+const CTL4_MEM_STBY: u32 = 1 << 2;
+const CTL4_GATE_CLK: u32 = 1 << 3;
+const CTL4_DOMAIN_MASK: u32 = 0xf;
+
+/// The sleep timer counts the 32kHz clock
+const SLEEP_TICK_RATE: u64 = 32_768;
+/// The sleep timer also counts while the domains power down and up again, which takes this many
+/// ticks
+const MIN_SLEEP_TICKS: u64 = 39;
+
+/// How deep the chip sleeps, see the table in the module documentation
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Level {
+    /// Only the clocks are stopped
+    Pds0 = 0,
+    /// The wireless domain is powered down
+    Pds1 = 1,
+    /// The CPU is powered down
+    Pds2 = 2,
+    /// The wireless domain and the CPU are powered down
+    Pds3 = 3,
+    /// The core misc domain is powered down
+    Pds4 = 4,
+    /// The wireless and the core misc domain are powered down
+    Pds5 = 5,
+    /// The CPU and the core misc domain are powered down
+    Pds6 = 6,
+    /// The wireless domain, the CPU and the core misc domain are powered down
+    Pds7 = 7,
+}
+
+impl Level {
+    fn powers_down_wireless(&self) -> bool {
+        *self as u32 & 0b001 != 0
+    }
+
+    pub(crate) fn powers_down_cpu(&self) -> bool {
+        *self as u32 & 0b010 != 0
+    }
+
+    fn powers_down_misc(&self) -> bool {
+        *self as u32 & 0b100 != 0
+    }
+
+    /// The `PDS_CTL4` bits, a powered down domain is also reset and its memories are put on
+    /// standby. The clocks of all domains are gated.
+    fn ctl4(&self) -> u32 {
+        let domain = |off: bool| {
+            if off {
+                CTL4_PWR_OFF | CTL4_RESET | CTL4_MEM_STBY | CTL4_GATE_CLK
+            } else {
+                CTL4_GATE_CLK
+            }
+        };
+
+        domain(self.powers_down_cpu()) << CTL4_CPU_SHIFT
+            | domain(self.powers_down_wireless()) << CTL4_WB_SHIFT
+            | domain(self.powers_down_misc()) << CTL4_MISC_SHIFT
+    }
+}
+
+/// The longest duration [sleep] accepts, with the drift of the 32K clock set with
+/// `rtc::set_drift_ppm` compensated
+pub fn max_duration() -> Nanoseconds<u64> {
+    let ticks = u32::MAX as u64 * 1_000_000 / (1_000_000 + rtc::drift_ppm() as i64) as u64;
+    Nanoseconds(ticks * 1_000_000 / SLEEP_TICK_RATE * 1_000)
+}
+
+/// Sleeps at `level` for `duration`, or until another wake source fires. The system clock
+/// runs from RC32M with the PLL and the crystal powered down in between, and is switched back to
+/// `clocks` after waking up.
+///
+/// Durations shorter than about 1.2ms are rounded up, as the sleep timer also counts while the
+/// chip powers down and up. The drift of the 32K clock set with `rtc::set_drift_ppm` is
+/// compensated.
+///
+/// # Panics
+///
+/// If `duration` is longer than the sleep timer can count, about 36 hours, see [max_duration].
+pub fn sleep(level: Level, duration: impl Into<Nanoseconds<u64>>, clocks: &Clocks) {
+    let micros = duration.into().0 / 1_000;
+    let ticks = rtc::compensate_ticks(micros * SLEEP_TICK_RATE / 1_000_000).max(MIN_SLEEP_TICKS);
+    if ticks > u32::MAX as u64 {
+        panic!("Sleep duration out of range");
+    }
+
+    let pds = unsafe { &*pac::PDS::ptr() };
+    pds.pds_time1.write(|w| unsafe { w.bits(ticks as u32) });
+    pds.pds_ctl4.modify(|r, w| unsafe {
+        w.bits(
+            r.bits()
+                & !(CTL4_DOMAIN_MASK << CTL4_CPU_SHIFT
+                    | CTL4_DOMAIN_MASK << CTL4_WB_SHIFT
+                    | CTL4_DOMAIN_MASK << CTL4_MISC_SHIFT)
+                | level.ctl4(),
+        )
+    });
+    // The sleep starts once the CPU waits for an interrupt
+    pds.pds_ctl
+        .modify(|r, w| unsafe { w.bits(r.bits() & !(CTL_SLEEP_FOREVER | CTL_NP_WFI_MASK)) });
+
+    watchdog::around_sleep(|| {
+        clock::around_sleep(clocks, || {
+            critical_section::with(|_| enter_pds(level.powers_down_cpu()));
+        })
+    });
+}
+
+/// Sleeps like [sleep], and suspends `peripherals` before and resumes them after it when `level`
+/// powers down the core misc domain, which resets them. `peripherals` is a driver, like `Serial`,
+/// or a tuple of them, see [suspend](super::suspend).
+///
+/// # Panics
+///
+/// If `duration` is longer than the sleep timer can count, about 36 hours.
+pub fn sleep_with<P: Resume>(
+    level: Level,
+    duration: impl Into<Nanoseconds<u64>>,
+    clocks: &Clocks,
+    mut peripherals: P,
+) {
+    if level.powers_down_misc() {
+        let state = peripherals.suspend();
+        sleep(level, duration, clocks);
+        peripherals.resume(state);
+    } else {
+        sleep(level, duration, clocks);
+    }
+}
+
+/// Starts the sleep and waits for it to end. It runs from RAM, as the flash is clocked from the
+/// RC oscillator that stops while sleeping. With the CPU powered down it never returns.
+#[inline(never)]
+#[link_section = ".data.bl602_hal.enter_pds"]
+fn enter_pds(cpu_off: bool) {
+    const PDS_CTL_ADDRESS: usize = PDS_BASE + PDS_CTL;
+
+    unsafe {
+        let r = ram_read_reg!(PDS_CTL_ADDRESS);
+        ram_write_reg!(PDS_CTL_ADDRESS, r | CTL_START_PS);
+
+        core::arch::asm!("wfi");
+        while cpu_off {
+            core::arch::asm!("wfi");
+        }
+    }
+}
@@ -0,0 +1,217 @@
+/*!
+  # Regulators
+  The digital core runs from the 1.1V LDOs in the always-on domain: the SoC LDO supplies the
+  core, and the RT and AON LDOs supply the always-on domain. Their output voltage is tuned in
+  steps of 50mV between 0.6V and 1.35V.
+
+  Lowering the core voltage saves active current, but the core has to keep up with the system
+  clock, so [set_core_preset] only offers voltages with margin for the current clocks. The 1.8V
+  DCDC supplies the flash and the 1.8V pads on most modules, and can be powered down while the
+  chip hibernates.
+
+  The core is always supplied by the SoC LDO: switching it over to a DCDC isn't offered, as the
+  register headers of the SDK describe no such selection for the BL602.
+
+  ## Example
+  ```rust
+    // 80MHz is enough for this application, so run it at 1.0V
+    let clocks = Strict::new()
+        .use_pll(40_000_000u32.Hz())
+        .sys_clk(SysclkFreq::Pll80Mhz)
+        .freeze(&mut parts.clk_cfg);
+    regulator::set_core_preset(Preset::LowPower, &clocks).unwrap();
+
+    // Without the flash powered while hibernating
+    regulator::set_hibernate_voltage(Voltage::Mv900).unwrap();
+    regulator::power_dcdc18_in_hibernate(false);
+    hbn::hibernate(&hbn::Config::new(Level::Hbn0).wake_after(10.seconds()));
+  ```
+*/
+
+use crate::{clock::Clocks, pac};
+
+// see components\bl602\bl602_std\bl602_std\StdDriver\Inc\hbn_reg.h
+const CTL_LDO11_RT_VOUT_SEL_SHIFT: u32 = 15;
+const CTL_LDO11_AON_VOUT_SEL_SHIFT: u32 = 19;
+const CTL_PU_DCDC18_AON: u32 = 1 << 23;
+
+const GLB_LDO11SOC_VOUT_SEL_SHIFT: u32 = 16;
+const GLB_LDO11_RT_VOUT_SEL_SHIFT: u32 = 24;
+const GLB_LDO11_AON_VOUT_SEL_SHIFT: u32 = 28;
+
+const VOUT_SEL_MASK: u32 = 0xf;
+
+/// The fastest system clock [Preset::LowPower] is offered for
+const LOW_POWER_MAX_SYSCLK: u32 = 80_000_000;
+
+/// Regulator error
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The system clock is too fast for the voltage
+    ClockTooFast,
+    /// The voltage is outside of the safe range
+    OutOfRange,
+}
+
+/// The output voltage of an 1.1V LDO
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Voltage {
+    /// 0.60V
+    Mv600 = 0,
+    /// 0.65V
+    Mv650 = 1,
+    /// 0.70V
+    Mv700 = 2,
+    /// 0.75V
+    Mv750 = 3,
+    /// 0.80V
+    Mv800 = 4,
+    /// 0.85V
+    Mv850 = 5,
+    /// 0.90V
+    Mv900 = 6,
+    /// 0.95V
+    Mv950 = 7,
+    /// 1.00V
+    Mv1000 = 8,
+    /// 1.05V
+    Mv1050 = 9,
+    /// 1.10V, the default
+    Mv1100 = 10,
+    /// 1.15V
+    Mv1150 = 11,
+    /// 1.20V
+    Mv1200 = 12,
+    /// 1.25V
+    Mv1250 = 13,
+    /// 1.30V
+    Mv1300 = 14,
+    /// 1.35V
+    Mv1350 = 15,
+}
+
+impl Voltage {
+    /// The voltage in mV
+    pub fn millivolts(self) -> u32 {
+        600 + self as u32 * 50
+    }
+
+    fn from_bits(bits: u32) -> Voltage {
+        match bits & VOUT_SEL_MASK {
+            0 => Voltage::Mv600,
+            1 => Voltage::Mv650,
+            2 => Voltage::Mv700,
+            3 => Voltage::Mv750,
+            4 => Voltage::Mv800,
+            5 => Voltage::Mv850,
+            6 => Voltage::Mv900,
+            7 => Voltage::Mv950,
+            8 => Voltage::Mv1000,
+            9 => Voltage::Mv1050,
+            10 => Voltage::Mv1100,
+            11 => Voltage::Mv1150,
+            12 => Voltage::Mv1200,
+            13 => Voltage::Mv1250,
+            14 => Voltage::Mv1300,
+            _ => Voltage::Mv1350,
+        }
+    }
+}
+
+/// A core voltage with margin for the clocks it's offered for
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Preset {
+    /// 1.0V, for system clocks up to 80MHz
+    LowPower,
+    /// 1.1V, the default
+    Nominal,
+    /// 1.2V, with extra margin at 160MHz and above
+    Performance,
+}
+
+impl Preset {
+    /// The core voltage of the preset
+    pub fn voltage(self) -> Voltage {
+        match self {
+            Preset::LowPower => Voltage::Mv1000,
+            Preset::Nominal => Voltage::Mv1100,
+            Preset::Performance => Voltage::Mv1200,
+        }
+    }
+}
+
+/// The voltage of the SoC LDO, which supplies the core
+pub fn core_voltage() -> Voltage {
+    Voltage::from_bits(
+        unsafe { &*pac::HBN::ptr() }.hbn_glb.read().bits() >> GLB_LDO11SOC_VOUT_SEL_SHIFT,
+    )
+}
+
+/// Sets the voltage of the 1.1V LDOs to `preset`, if it has margin for the system clock of
+/// `clocks`. Clocks set up afterwards have to stay within it as well.
+pub fn set_core_preset(preset: Preset, clocks: &Clocks) -> Result<(), Error> {
+    if preset == Preset::LowPower && clocks.sysclk().0 > LOW_POWER_MAX_SYSCLK {
+        return Err(Error::ClockTooFast);
+    }
+
+    unsafe { set_core_voltage(preset.voltage()) };
+    Ok(())
+}
+
+/// Sets the voltage of the 1.1V LDOs, like the SDK does, without checking it against the clocks.
+///
+/// # Safety
+///
+/// The core and the always-on domain stop working reliably at too low voltages, and wear out
+/// faster at too high ones, so `voltage` needs margin for the system clock and the chip.
+pub unsafe fn set_core_voltage(voltage: Voltage) {
+    let bits = voltage as u32;
+    unsafe { &*pac::HBN::ptr() }.hbn_glb.modify(|r, w| {
+        w.bits(
+            r.bits()
+                & !(VOUT_SEL_MASK << GLB_LDO11SOC_VOUT_SEL_SHIFT
+                    | VOUT_SEL_MASK << GLB_LDO11_RT_VOUT_SEL_SHIFT
+                    | VOUT_SEL_MASK << GLB_LDO11_AON_VOUT_SEL_SHIFT)
+                | bits << GLB_LDO11SOC_VOUT_SEL_SHIFT
+                | bits << GLB_LDO11_RT_VOUT_SEL_SHIFT
+                | bits << GLB_LDO11_AON_VOUT_SEL_SHIFT,
+        )
+    });
+}
+
+/// Sets the voltage of the RT and AON LDOs while the chip hibernates, between 0.8V and 1.1V.
+/// The always-on domain only keeps the RTC and the retention RAM then, which run at lower
+/// voltages than the core.
+pub fn set_hibernate_voltage(voltage: Voltage) -> Result<(), Error> {
+    if voltage < Voltage::Mv800 || voltage > Voltage::Mv1100 {
+        return Err(Error::OutOfRange);
+    }
+
+    let bits = voltage as u32;
+    unsafe { &*pac::HBN::ptr() }.hbn_ctl.modify(|r, w| unsafe {
+        w.bits(
+            r.bits()
+                & !(VOUT_SEL_MASK << CTL_LDO11_RT_VOUT_SEL_SHIFT
+                    | VOUT_SEL_MASK << CTL_LDO11_AON_VOUT_SEL_SHIFT)
+                | bits << CTL_LDO11_RT_VOUT_SEL_SHIFT
+                | bits << CTL_LDO11_AON_VOUT_SEL_SHIFT,
+        )
+    });
+    Ok(())
+}
+
+/// Keeps the 1.8V DCDC powered while the chip hibernates, or powers it down. It supplies the
+/// flash and the 1.8V pads on most modules, which then draw no current while hibernating, but
+/// the pads don't hold their level either.
+pub fn power_dcdc18_in_hibernate(on: bool) {
+    unsafe { &*pac::HBN::ptr() }.hbn_ctl.modify(|r, w| unsafe {
+        if on {
+            w.bits(r.bits() | CTL_PU_DCDC18_AON)
+        } else {
+            w.bits(r.bits() & !CTL_PU_DCDC18_AON)
+        }
+    });
+}
@@ -0,0 +1,158 @@
+/*!
+  # Retention RAM
+  The 4KiB retention RAM is in the always-on domain, so it keeps its content through hibernate,
+  unless it's powered down with `hbn::Config::retain_ram(false)`, and through resets like a
+  watchdog reset. It loses its content at power on.
+
+  [retained] stores one value of an [AnyBitPattern] type in it, behind a header with a magic number, the
+  size of the type and a CRC-32 of the value, so a value that was never stored, or didn't survive,
+  reads as `None`. The last 128 bytes keep the [crash record](crate::crash).
+
+  ## Example
+  ```rust
+    #[derive(Copy, Clone, Default)]
+    struct State {
+        sequence: u32,
+        calibration: i16,
+    }
+
+    // Any bytes make a valid `State`, as its fields are integers
+    unsafe impl AnyBitPattern for State {}
+
+    let mut state = retention::retained::<State>();
+    let mut value = state.get().unwrap_or_default();
+    value.sequence += 1;
+    state.set(value);
+
+    hbn::hibernate(&hbn::Config::new(Level::Hbn1).wake_after(60.seconds()));
+  ```
+*/
+
+use crate::{
+    crash,
+    reg::{read_reg, write_reg},
+};
+use core::marker::PhantomData;
+use core::mem::{align_of, size_of};
+
+// HBN_RAM_BASE, see components\bl602\bl602_std\bl602_std\Include\bl602.h
+const HBN_RAM: usize = 0x4001_0000;
+const HBN_RAM_SIZE: usize = 4 * 1024;
+
+const HEADER_MAGIC: usize = HBN_RAM;
+const HEADER_SIZE: usize = HBN_RAM + 0x4;
+const HEADER_CRC: usize = HBN_RAM + 0x8;
+/// The value follows the header, aligned for any type
+const DATA: usize = HBN_RAM + 0x10;
+
+/// Marks a valid value in the retention RAM
+const MAGIC: u32 = 0x4e54_4552;
+
+/// Types for which any bit pattern is a valid value, so they can be read back from bytes written
+/// by an earlier version of the firmware, or as a value of another type
+///
+/// # Safety
+///
+/// Every bit pattern of the size of the type has to be a valid value, so e.g. `bool`, `char`,
+/// enums, references and types containing them must not implement it.
+pub unsafe trait AnyBitPattern: Copy {}
+
+macro_rules! impl_any_bit_pattern {
+    ($($t: ty),+) => {
+        $(unsafe impl AnyBitPattern for $t {})+
+    };
+}
+
+impl_any_bit_pattern!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize, f32, f64);
+
+unsafe impl<T: AnyBitPattern, const N: usize> AnyBitPattern for [T; N] {}
+
+/// A value of type `T` in the retention RAM, see [retained]
+pub struct Retained<T> {
+    _value: PhantomData<T>,
+}
+
+/// Gives access to a value of type `T` in the retention RAM. There's room for one value, so a
+/// `T` of another type invalidates it.
+///
+/// The stored bytes are only read back as a `T` when the magic number, the size and the CRC
+/// match. They may still have been written by an earlier version of the firmware or as another
+/// type of the same size, which is why `T` has to accept any bit pattern.
+///
+/// # Panics
+///
+/// If `T` doesn't fit into the retention RAM, or is aligned to more than 16 bytes.
+pub fn retained<T: AnyBitPattern>() -> Retained<T> {
+    if size_of::<T>() > HBN_RAM_SIZE - (DATA - HBN_RAM) - crash::RECORD_SIZE {
+        panic!("Type too large for the retention RAM");
+    }
+    if align_of::<T>() > DATA - HBN_RAM {
+        panic!("Type aligned to more than 16 bytes");
+    }
+
+    Retained {
+        _value: PhantomData,
+    }
+}
+
+impl<T: AnyBitPattern> Retained<T> {
+    /// Reads the value, or `None` if none was stored or it didn't survive
+    pub fn get(&self) -> Option<T> {
+        if read_reg(HEADER_MAGIC) != MAGIC
+            || read_reg(HEADER_SIZE) != size_of::<T>() as u32
+            || read_reg(HEADER_CRC) != data_crc(size_of::<T>())
+        {
+            return None;
+        }
+
+        Some(unsafe { (DATA as *const T).read_volatile() })
+    }
+
+    /// Stores `value`
+    pub fn set(&mut self, value: T) {
+        // Invalid while the value is written
+        write_reg(HEADER_MAGIC, 0);
+
+        unsafe { (DATA as *mut T).write_volatile(value) };
+        write_reg(HEADER_SIZE, size_of::<T>() as u32);
+        write_reg(HEADER_CRC, data_crc(size_of::<T>()));
+
+        write_reg(HEADER_MAGIC, MAGIC);
+    }
+
+    /// Changes the stored value with `f`, starting from `default` if none was stored
+    pub fn update(&mut self, default: T, f: impl FnOnce(&mut T)) {
+        let mut value = self.get().unwrap_or(default);
+        f(&mut value);
+        self.set(value);
+    }
+
+    /// Invalidates the stored value
+    pub fn clear(&mut self) {
+        write_reg(HEADER_MAGIC, 0);
+    }
+}
+
+/// Clears the whole retention RAM
+pub(crate) fn erase() {
+    for address in (HBN_RAM..HBN_RAM + HBN_RAM_SIZE).step_by(4) {
+        write_reg(address, 0);
+    }
+}
+
+/// CRC-32 (IEEE 802.3) of the `size` bytes of the value
+fn data_crc(size: usize) -> u32 {
+    let mut crc = !0u32;
+    for offset in 0..size {
+        let byte = unsafe { ((DATA + offset) as *const u8).read_volatile() };
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                crc >> 1 ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
@@ -0,0 +1,121 @@
+/*!
+  # Suspend and resume
+  PDS levels powering down the core misc domain, like `Pds4`, reset the UART, SPI, I2C, PWM and
+  ADC with it. Their drivers implement [Suspend] and [Resume] to save the configuration registers
+  before sleeping and write them back after waking up, and
+  [pds::sleep_with](super::pds::sleep_with) does both around the sleep, so the drivers don't have
+  to be set up again.
+
+  Tuples of up to 6 peripherals, and mutable references to them, are suspended together in
+  order, and resumed in reverse order.
+
+  ## Example
+  ```rust
+    loop {
+        let value = adc.blocking_read::<Pin4<Analog>>();
+        writeln!(serial, "{}", adc.to_millivolts(value)).ok();
+
+        pds::sleep_with(Level::Pds4, 1.seconds(), &clocks, (&mut serial, &mut adc));
+    }
+  ```
+
+  A peripheral has to be idle when it's suspended: transfers in progress and data in the FIFOs
+  are lost. Pin functions and peripheral clocks are configured in the GLB, which keeps them.
+*/
+
+/// A peripheral whose register state can be saved before sleeping
+pub trait Suspend {
+    /// The saved register state
+    type State;
+
+    /// Saves the register state. The peripheral should be idle.
+    fn suspend(&mut self) -> Self::State;
+}
+
+/// A peripheral whose register state can be restored after sleeping
+pub trait Resume: Suspend {
+    /// Restores the register state saved by `suspend()`
+    fn resume(&mut self, state: Self::State);
+}
+
+/// Register values saved by `Suspend::suspend()`, written back in order by `Resume::resume()`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SavedRegisters<const N: usize>(pub(crate) [u32; N]);
+
+impl<const N: usize> SavedRegisters<N> {
+    /// Reads the registers at `addresses`
+    pub(crate) fn save(addresses: [usize; N]) -> Self {
+        let mut values = [0; N];
+        for (value, address) in values.iter_mut().zip(addresses.iter()) {
+            *value = unsafe { (*address as *const u32).read_volatile() };
+        }
+
+        SavedRegisters(values)
+    }
+
+    /// Writes the values back to `addresses`, which have to be the ones they were saved from
+    pub(crate) fn restore(&self, addresses: [usize; N]) {
+        for (value, address) in self.0.iter().zip(addresses.iter()) {
+            unsafe { (*address as *mut u32).write_volatile(*value) };
+        }
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl<const N: usize> defmt::Format for SavedRegisters<N> {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(f, "SavedRegisters({=[u32]:#x})", &self.0[..])
+    }
+}
+
+impl<T: Suspend> Suspend for &mut T {
+    type State = T::State;
+
+    fn suspend(&mut self) -> Self::State {
+        T::suspend(self)
+    }
+}
+
+impl<T: Resume> Resume for &mut T {
+    fn resume(&mut self, state: Self::State) {
+        T::resume(self, state)
+    }
+}
+
+macro_rules! impl_tuple {
+    ($(($($t: ident: $i: tt),+),)+) => {
+        $(
+            impl<$($t: Suspend),+> Suspend for ($($t,)+) {
+                type State = ($($t::State,)+);
+
+                fn suspend(&mut self) -> Self::State {
+                    ($(self.$i.suspend(),)+)
+                }
+            }
+
+            impl<$($t: Resume),+> Resume for ($($t,)+) {
+                fn resume(&mut self, state: Self::State) {
+                    impl_tuple!(@resume self, state, [$($i)+]);
+                }
+            }
+        )+
+    };
+    (@resume $self: ident, $state: ident, [$($i: tt)+]) => {
+        impl_tuple!(@reverse $self, $state, [$($i)+], []);
+    };
+    (@reverse $self: ident, $state: ident, [$first: tt $($rest: tt)*], [$($reversed: tt)*]) => {
+        impl_tuple!(@reverse $self, $state, [$($rest)*], [$first $($reversed)*]);
+    };
+    (@reverse $self: ident, $state: ident, [], [$($i: tt)+]) => {
+        $($self.$i.resume($state.$i);)+
+    };
+}
+
+impl_tuple! {
+    (A: 0),
+    (A: 0, B: 1),
+    (A: 0, B: 1, C: 2),
+    (A: 0, B: 1, C: 2, D: 3),
+    (A: 0, B: 1, C: 2, D: 3, E: 4),
+    (A: 0, B: 1, C: 2, D: 3, E: 4, F: 5),
+}
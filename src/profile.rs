@@ -0,0 +1,91 @@
+/*!
+  # Profiling
+  Cycle-accurate measurements of code paths, using the machine-mode cycle counter (`mcycle`) and
+  retired instruction counter (`minstret`) of the CPU.
+
+  Both counters are 64 bits wide, so they don't wrap in practice. Times are converted with the
+  system clock frequency of the frozen [Clocks], so the system clock must not be changed while a
+  [Stopwatch] is running.
+
+  ## Example
+  ```rust
+    let stopwatch = Stopwatch::start(&clocks);
+    spi.write(&buffer).unwrap();
+    let cycles = stopwatch.elapsed_cycles();
+    let ns = stopwatch.elapsed_ns();
+  ```
+*/
+
+use crate::clock::Clocks;
+use embedded_time::rate::Hertz;
+
+/// Measures the cycles, instructions and time elapsed since it was started
+#[derive(Copy, Clone)]
+pub struct Stopwatch {
+    start_cycle: u64,
+    start_instret: u64,
+    sysclk: Hertz,
+}
+
+impl Stopwatch {
+    /// Starts measuring from now, converting cycles to time with the system clock of `clocks`
+    #[inline]
+    pub fn start(clocks: &Clocks) -> Self {
+        Stopwatch {
+            start_instret: riscv::register::minstret::read64(),
+            start_cycle: riscv::register::mcycle::read64(),
+            sysclk: clocks.sysclk(),
+        }
+    }
+
+    /// Restarts the measurement, returning the cycles elapsed until now
+    #[inline]
+    pub fn restart(&mut self) -> u64 {
+        let now = riscv::register::mcycle::read64();
+        let elapsed = now.wrapping_sub(self.start_cycle);
+        self.start_instret = riscv::register::minstret::read64();
+        self.start_cycle = now;
+        elapsed
+    }
+
+    /// CPU cycles elapsed since the start
+    #[inline]
+    pub fn elapsed_cycles(&self) -> u64 {
+        riscv::register::mcycle::read64().wrapping_sub(self.start_cycle)
+    }
+
+    /// Instructions retired since the start
+    #[inline]
+    pub fn elapsed_instructions(&self) -> u64 {
+        riscv::register::minstret::read64().wrapping_sub(self.start_instret)
+    }
+
+    /// Nanoseconds elapsed since the start
+    pub fn elapsed_ns(&self) -> u64 {
+        cycles_to_ns(self.elapsed_cycles(), self.sysclk)
+    }
+
+    /// Microseconds elapsed since the start
+    pub fn elapsed_us(&self) -> u64 {
+        self.elapsed_ns() / 1_000
+    }
+
+    /// The system clock frequency used to convert cycles to time
+    pub fn sysclk(&self) -> Hertz {
+        self.sysclk
+    }
+}
+
+/// Runs `f` and returns its result with the number of CPU cycles it took
+#[inline]
+pub fn measure_cycles<R>(f: impl FnOnce() -> R) -> (R, u64) {
+    let start = riscv::register::mcycle::read64();
+    let result = f();
+    let cycles = riscv::register::mcycle::read64().wrapping_sub(start);
+    (result, cycles)
+}
+
+/// Converts a number of CPU cycles at `sysclk` to nanoseconds
+pub fn cycles_to_ns(cycles: u64, sysclk: Hertz) -> u64 {
+    (cycles as u128 * 1_000_000_000_u128 / sysclk.0 as u128) as u64
+}
@@ -0,0 +1,1321 @@
+/*!
+  # Pulse Width Modulation
+  The chip has five PWM channels. Each channel has its own 16-bit counter, clock source, clock
+  divider, period and two thresholds. The output of a channel is high while its counter is between
+  threshold 1 and threshold 2.
+
+  Channel `n` can be routed to every GPIO pin `m` for which `m % 5 == n`, by configuring that pin
+  with `into_pwm_chn`, e.g. `into_pwm_ch2` for GPIO7. Configuring a channel takes one of its pins,
+  so a channel can't be set up without an output. Further pins can be routed to the same channel
+  at any time.
+
+  The clock source, divider and period registers are calculated from the frozen [Clocks], so the
+  period can be given as a duration or as a frequency. The duty cycle is set through the
+  [SetDutyCycle](embedded_hal::pwm::SetDutyCycle) trait, which also offers percentage and fraction
+  based setters.
+
+  Instead of a duty cycle, both thresholds can be set with `set_thresholds()`, or a pulse can be
+  placed anywhere within the period with `set_pulse()`, e.g. to strobe a sensor at a fixed point of
+  the period.
+
+  A configured channel can be stopped and restarted with `disable()` and `enable()` without losing
+  its configuration. `force_low()` and `force_high()` park the output at a fixed level, e.g. to turn
+  off a motor driver, until `release()` is called.
+
+  ## Example
+  ```rust
+    use embedded_hal::pwm::SetDutyCycle;
+    use embedded_time::rate::*;
+
+    let pwm_pin = parts.pin17.into_pwm_ch2(); // 17 % 5 == 2
+    let channels = dp.PWM.split();
+
+    let mut ch2 = channels.channel2.set_frequency(pwm_pin, 1_000u32.Hz(), &clocks);
+    ch2.set_duty_cycle_percent(25).unwrap();
+  ```
+
+  ## Period interrupt example
+  A callback registered on a channel is called from the `Pwm` interrupt at the end of a period,
+  where it can set the duty cycle of the next period.
+  ```rust
+    fn next_sample(channel: Channel) {
+        channel.set_duty_cycle(channel.max_duty_cycle() / 2);
+    }
+
+    ch2.enable_period_interrupt(1);
+    ch2.set_period_callback(next_sample);
+    enable_interrupt(Interrupt::Pwm);
+  ```
+
+  ## Waveform playback example
+  A table of duty cycles can be played back with one entry per period, e.g. for LED fades or PWM
+  audio. The PWM block has no DMA request line (the DMA controller is only paced by the UART, SPI,
+  I2C, GPADC and GPDAC), so the table is streamed by the HAL from the period interrupt. This takes a
+  few register writes per period and no user code.
+  ```rust
+    static FADE: [u16; 4] = [0, 1000, 2000, 1000];
+
+    ch2.play_waveform(&FADE, true);
+    enable_interrupt(Interrupt::Pwm);
+  ```
+
+  ## Servo example
+  ```rust
+    let channel = channels.channel2.set_frequency(parts.pin7.into_pwm_ch2(), 50u32.Hz(), &clocks);
+    let mut servo = Servo::new(channel);
+
+    // This servo turns 0 to 180 degrees for 500 to 2500 us pulses
+    servo.set_limits(500, 2500, 180);
+    servo.set_angle(45);
+  ```
+
+  ## Tone example
+  ```rust
+    let buzzer_pin = parts.pin4.into_pwm_ch4();
+    let channel = channels.channel4.set_frequency(buzzer_pin, 1_000u32.Hz(), &clocks);
+    let mut buzzer = Tone::new(channel, &clocks);
+
+    // Blocking
+    buzzer.play(440u32.Hz(), 200u32.milliseconds());
+
+    // Non-blocking
+    buzzer.start(880u32.Hz(), 200u32.milliseconds());
+    while buzzer.wait().is_err() {
+        // Do other things while the tone is playing
+    }
+  ```
+
+  ## Pulse burst example
+  ```rust
+    fn burst_done(channel: Channel) {
+        // The channel has been stopped after the 200th pulse
+    }
+
+    // 200 pulses of 10 counter ticks, e.g. stepper motor steps
+    ch2.emit_pulses(200, 10, Some(burst_done));
+    enable_interrupt(Interrupt::Pwm);
+  ```
+
+  ## Multi-channel update example
+  ```rust
+    // Switch an RGB LED to orange in one go
+    Pwm::update_all(&[
+        (Channel::Channel0, 1000),
+        (Channel::Channel1, 400),
+        (Channel::Channel2, 0),
+    ]);
+  ```
+
+  ## Complementary outputs example
+  Two configured channels can be combined into a [ChannelPair], which shares the period of the
+  first channel and positions the pulse of the second channel relative to it.
+  ```rust
+    let (high_pin, low_pin) = (parts.pin0.into_pwm_ch0(), parts.pin1.into_pwm_ch1());
+    let high_side = channels.channel0.set_frequency(high_pin, 20_000u32.Hz(), &clocks);
+    let low_side = channels.channel1.set_frequency(low_pin, 20_000u32.Hz(), &clocks);
+    let mut bridge = ChannelPair::new(high_side, low_side);
+
+    // Half of the period on the high side, with 10 counter ticks where both sides are off
+    bridge.set_complementary(bridge.max_duty_cycle() / 2, 10);
+  ```
+
+  ## Clock output example
+  The chip has no dedicated clock output, but a channel can divide one of its clock sources onto
+  its pin, e.g. to verify the bus clock or to calibrate the 32K oscillator against an external
+  reference. The highest output frequency is half of the source clock.
+  ```rust
+    let clk_pin = parts.pin3.into_pwm_ch3();
+    let clk_out = channels.channel3.clock_out(clk_pin, ClockSource::Bclk, 1, &clocks);
+    // 40MHz with an 80MHz bus clock
+    let freq = clk_out.frequency();
+  ```
+  # Units
+  This library uses embedded_time::{duration::*, rate::*} for time units. You can use any supported units as long as they can be cast into Nanoseconds::<u64> for durations, or Hertz for cycles.
+*/
+
+use crate::{
+    clock::Clocks,
+    gpio, pac,
+    power::suspend::{Resume, SavedRegisters, Suspend},
+    reg::read_mtime,
+};
+use bl602_pac::PWM;
+use core::cell::Cell;
+use core::convert::Infallible;
+use critical_section::Mutex;
+use embedded_hal::pwm::{ErrorType, SetDutyCycle};
+use embedded_time::{duration::*, rate::*};
+use paste::paste;
+use void::Void;
+
+/// Clock sources for a PWM channel.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ClockSource {
+    /// Crystal clock mux output, see `Clocks::xclk()`
+    Xclk,
+    /// Bus clock
+    Bclk,
+    /// 32K clock
+    Rc32Khz,
+}
+
+impl ClockSource {
+    fn reg_clk_sel(&self) -> u8 {
+        match self {
+            ClockSource::Xclk => 0,
+            ClockSource::Bclk => 1,
+            ClockSource::Rc32Khz => 2,
+        }
+    }
+
+    fn from_reg_clk_sel(bits: u8) -> ClockSource {
+        match bits {
+            0 => ClockSource::Xclk,
+            1 => ClockSource::Bclk,
+            _ => ClockSource::Rc32Khz,
+        }
+    }
+
+    fn hertz(&self, clocks: &Clocks) -> Hertz {
+        match self {
+            ClockSource::Xclk => clocks.xclk(),
+            ClockSource::Bclk => clocks.bclk(),
+            ClockSource::Rc32Khz => clocks.f32k(),
+        }
+    }
+}
+
+/// Clock source, divider and period register values of a channel
+#[derive(Copy, Clone, Debug)]
+struct Timing {
+    source: ClockSource,
+    divider: u16,
+    period: u16,
+}
+
+impl Timing {
+    /// Returns the timing that needs the least division of `source` to count `ticks` source clock
+    /// cycles in one period, if any.
+    fn from_source_ticks(source: ClockSource, ticks: u64) -> Option<Timing> {
+        let divider = ((ticks + 0xFFFE) / 0xFFFF).max(1);
+        if divider > 0xFFFF {
+            return None;
+        }
+
+        let period = ticks / divider;
+        // We need at least two counter values to output anything but a constant level
+        if period < 2 {
+            return None;
+        }
+
+        Some(Timing {
+            source,
+            divider: divider as u16,
+            period: period as u16,
+        })
+    }
+
+    /// Finds the timing with the highest duty cycle resolution for the period given in source
+    /// clock cycles by `ticks_for`.
+    ///
+    /// # Panics
+    ///
+    /// If the period is not reachable from any of the clock sources, this function panics.
+    fn best(clocks: &Clocks, ticks_for: impl Fn(u64) -> Option<u64>) -> Timing {
+        let mut best: Option<Timing> = None;
+
+        for source in [ClockSource::Bclk, ClockSource::Xclk, ClockSource::Rc32Khz].iter() {
+            let timing = ticks_for(source.hertz(clocks).0 as u64)
+                .and_then(|ticks| Timing::from_source_ticks(*source, ticks));
+
+            if let Some(timing) = timing {
+                let is_better = match best {
+                    Some(b) => timing.period > b.period,
+                    None => true,
+                };
+                if is_better {
+                    best = Some(timing);
+                }
+            }
+        }
+
+        match best {
+            Some(timing) => timing,
+            None => panic!("Unreachable PWM period"),
+        }
+    }
+
+    fn from_period(period: Nanoseconds<u64>, clocks: &Clocks) -> Timing {
+        Timing::best(clocks, |source_hz| {
+            period
+                .integer()
+                .checked_mul(source_hz)
+                .map(|ticks| ticks / 1_000_000_000_u64)
+        })
+    }
+
+    fn from_frequency(freq: Hertz, clocks: &Clocks) -> Timing {
+        if freq.0 == 0 {
+            panic!("Unreachable PWM period");
+        }
+
+        Timing::best(clocks, |source_hz| Some(source_hz / freq.0 as u64))
+    }
+
+    fn source_ticks(&self) -> u64 {
+        self.divider as u64 * self.period as u64
+    }
+}
+
+/// PWM channel identifier, used where a channel is selected at runtime
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Channel {
+    Channel0,
+    Channel1,
+    Channel2,
+    Channel3,
+    Channel4,
+}
+
+/// Period interrupt callbacks, indexed by channel
+static CALLBACKS: Mutex<Cell<[Option<fn(Channel)>; 5]>> = Mutex::new(Cell::new([None; 5]));
+
+/// A table of duty cycles played back from the period interrupt
+#[derive(Copy, Clone)]
+struct Waveform {
+    table: &'static [u16],
+    position: usize,
+    repeat: bool,
+}
+
+/// Waveforms being played back, indexed by channel
+static WAVEFORMS: Mutex<Cell<[Option<Waveform>; 5]>> = Mutex::new(Cell::new([None; 5]));
+
+/// Callbacks for the end of a pulse burst, indexed by channel
+static PULSES_DONE: Mutex<Cell<[Option<fn(Channel)>; 5]>> = Mutex::new(Cell::new([None; 5]));
+
+/// Calls the associated function `$func` of the configured channel type belonging to `$channel`
+macro_rules! with_channel {
+    ($channel: expr, $func: ident $(, $arg: expr)*) => {
+        match $channel {
+            Channel::Channel0 => ConfiguredPwmChannel0::$func($($arg),*),
+            Channel::Channel1 => ConfiguredPwmChannel1::$func($($arg),*),
+            Channel::Channel2 => ConfiguredPwmChannel2::$func($($arg),*),
+            Channel::Channel3 => ConfiguredPwmChannel3::$func($($arg),*),
+            Channel::Channel4 => ConfiguredPwmChannel4::$func($($arg),*),
+        }
+    };
+}
+
+impl Channel {
+    fn index(self) -> usize {
+        match self {
+            Channel::Channel0 => 0,
+            Channel::Channel1 => 1,
+            Channel::Channel2 => 2,
+            Channel::Channel3 => 3,
+            Channel::Channel4 => 4,
+        }
+    }
+
+    fn from_index(index: usize) -> Channel {
+        match index {
+            0 => Channel::Channel0,
+            1 => Channel::Channel1,
+            2 => Channel::Channel2,
+            3 => Channel::Channel3,
+            4 => Channel::Channel4,
+            _ => panic!("PWM channel index out of range"),
+        }
+    }
+
+    /// The current period in counter ticks, which is the maximum duty cycle.
+    pub fn max_duty_cycle(self) -> u16 {
+        with_channel!(self, read_period)
+    }
+
+    /// The current duty cycle, between 0 and `max_duty_cycle()`.
+    pub fn get_duty_cycle(self) -> u16 {
+        with_channel!(self, read_duty)
+    }
+
+    /// Sets the duty cycle, between 0 and `max_duty_cycle()`.
+    ///
+    /// This is meant to be used from period callbacks, which don't own the configured channel.
+    pub fn set_duty_cycle(self, duty: u16) {
+        with_channel!(self, write_duty, duty.min(self.max_duty_cycle()))
+    }
+
+    /// The current thresholds as `(low, high)`. The output is high while the counter is at or
+    /// above `low` and below `high`.
+    pub fn get_thresholds(self) -> (u16, u16) {
+        with_channel!(self, read_thresholds)
+    }
+
+    /// Sets both thresholds, so the active pulse runs from counter value `low` up to `high`. Both
+    /// are clamped to `max_duty_cycle()`. A `high` at or below `low` keeps the output low.
+    pub fn set_thresholds(self, low: u16, high: u16) {
+        let period = self.max_duty_cycle();
+        with_channel!(self, write_thresholds, low.min(period), high.min(period))
+    }
+
+    /// Will only become true if the period interrupt of the channel is enabled
+    pub fn is_interrupt_pending(self) -> bool {
+        let pwm = unsafe { &*pac::PWM::ptr() };
+        pwm.pwm_int_config.read().pwm_interrupt_sts().bits() & (1 << self.index()) != 0
+    }
+
+    /// Clears the period interrupt of the channel
+    pub fn clear_interrupt(self) {
+        let pwm = unsafe { &*pac::PWM::ptr() };
+        let mask = 1 << self.index();
+        critical_section::with(|_| {
+            pwm.pwm_int_config
+                .modify(|r, w| unsafe { w.pwm_int_clear().bits(r.pwm_int_clear().bits() | mask) });
+            pwm.pwm_int_config
+                .modify(|r, w| unsafe { w.pwm_int_clear().bits(r.pwm_int_clear().bits() & !mask) });
+        });
+    }
+
+    fn set_callback(self, callback: Option<fn(Channel)>) {
+        critical_section::with(|cs| {
+            let cell = CALLBACKS.borrow(cs);
+            let mut callbacks = cell.get();
+            callbacks[self.index()] = callback;
+            cell.set(callbacks);
+        });
+    }
+
+    fn set_waveform(self, waveform: Option<Waveform>) {
+        critical_section::with(|cs| {
+            let cell = WAVEFORMS.borrow(cs);
+            let mut waveforms = cell.get();
+            waveforms[self.index()] = waveform;
+            cell.set(waveforms);
+        });
+    }
+
+    fn is_waveform_playing(self) -> bool {
+        critical_section::with(|cs| WAVEFORMS.borrow(cs).get()[self.index()].is_some())
+    }
+
+    fn timing(self) -> Timing {
+        with_channel!(self, read_timing)
+    }
+
+    /// Outputs a pulse of `width` counter ticks starting at counter value `start`. A pulse
+    /// crossing the end of the period wraps around to its start, which is done by inverting the
+    /// output polarity. Does nothing while the channel has no period set.
+    pub fn set_pulse(self, start: u16, width: u16) {
+        let period = self.max_duty_cycle();
+        if period == 0 {
+            return;
+        }
+        let start = start % period;
+        let end = start as u32 + width.min(period) as u32;
+
+        if end <= period as u32 {
+            with_channel!(self, write_inverted, false);
+            with_channel!(self, write_thresholds, start, end as u16);
+        } else {
+            // The output is only high between the thresholds, so a wrapping pulse is made by
+            // inverting the gap between its end and its start
+            with_channel!(self, write_inverted, true);
+            with_channel!(self, write_thresholds, (end - period as u32) as u16, start);
+        }
+    }
+}
+
+/// Operations on several PWM channels at once
+pub struct Pwm;
+
+impl Pwm {
+    /// Sets the duty cycle of several channels together, e.g. for color changes of an RGB LED
+    /// without intermediate colors. The duty cycles are clamped to `max_duty_cycle()` of their
+    /// channel.
+    ///
+    /// The PWM block has no shadow register hold, so the thresholds are written back-to-back in a
+    /// critical section instead. All channels take over their new duty cycle within a few bus
+    /// clock cycles, which only leaves a channel whose period ends in that window one period late.
+    pub fn update_all(updates: &[(Channel, u16)]) {
+        let mut duties = [None; 5];
+        for (channel, duty) in updates.iter() {
+            duties[channel.index()] = Some((*duty).min(channel.max_duty_cycle()));
+        }
+
+        critical_section::with(|_| {
+            for (index, duty) in duties.iter().enumerate() {
+                if let Some(duty) = duty {
+                    with_channel!(Channel::from_index(index), write_duty, *duty);
+                }
+            }
+        });
+    }
+}
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// Pins that a PWM channel can drive - DO NOT IMPLEMENT THIS TRAIT
+pub trait PwmPin<CHANNEL>: private::Sealed {}
+
+macro_rules! impl_pwm_pin {
+    ($($channel: ident: [$($pin: ident),+],)+) => {
+        $($(
+            impl PwmPin<$channel> for gpio::$pin<gpio::Pwm> {}
+            impl private::Sealed for gpio::$pin<gpio::Pwm> {}
+        )+)+
+    };
+}
+
+// Channel n drives the pins m for which m % 5 == n
+impl_pwm_pin! {
+    PwmChannel0: [Pin0, Pin5, Pin10, Pin15, Pin20],
+    PwmChannel1: [Pin1, Pin6, Pin11, Pin16, Pin21],
+    PwmChannel2: [Pin2, Pin7, Pin12, Pin17, Pin22],
+    PwmChannel3: [Pin3, Pin8, Pin13, Pin18],
+    PwmChannel4: [Pin4, Pin9, Pin14, Pin19],
+}
+
+/// Configured PWM channels - DO NOT IMPLEMENT THIS TRAIT
+pub trait ConfiguredChannel: SetDutyCycle + private::Sealed {
+    /// The runtime identifier of the channel
+    fn channel(&self) -> Channel;
+
+    /// The actual period of the channel, after rounding to the available clock ticks.
+    fn period(&self) -> Nanoseconds<u64>;
+
+    /// Changes the period of the channel, keeping the current duty cycle ratio.
+    fn set_period(&mut self, period: Nanoseconds<u64>);
+
+    /// Changes the frequency of the channel, keeping the current duty cycle ratio.
+    fn set_frequency(&mut self, freq: Hertz);
+}
+
+/// Two channels counting in lockstep, with the pulse of the second channel positioned relative to
+/// the first one. This gives phase-shifted outputs, or complementary outputs with dead time for
+/// half-bridge drivers.
+///
+/// Both counters are started right after each other, so they stay a few clock cycles apart at most.
+pub struct ChannelPair<A, B> {
+    first: A,
+    second: B,
+}
+
+impl<A: ConfiguredChannel, B: ConfiguredChannel> ChannelPair<A, B> {
+    /// Gives `second` the clock source, divider and period of `first`, and restarts both counters
+    /// together. Both outputs start low.
+    pub fn new(first: A, second: B) -> Self {
+        let mut pair = ChannelPair { first, second };
+        let timing = pair.first.channel().timing();
+
+        pair.disable();
+        with_channel!(pair.second.channel(), write_timing, timing);
+        pair.set_phase_shifted(0, 0, 0);
+        pair.enable();
+
+        pair
+    }
+
+    /// Starts both counters.
+    pub fn enable(&mut self) {
+        with_channel!(self.first.channel(), start);
+        with_channel!(self.second.channel(), start);
+    }
+
+    /// Stops both counters.
+    pub fn disable(&mut self) {
+        with_channel!(self.first.channel(), stop);
+        with_channel!(self.second.channel(), stop);
+    }
+
+    /// The shared period in counter ticks, which is the maximum duty cycle of both channels.
+    pub fn max_duty_cycle(&self) -> u16 {
+        self.first.channel().max_duty_cycle()
+    }
+
+    /// Outputs pulses of `first_duty` and `second_duty` counter ticks, with the pulse of the second
+    /// channel starting `phase` ticks after the one of the first channel.
+    pub fn set_phase_shifted(&mut self, first_duty: u16, second_duty: u16, phase: u16) {
+        self.first.channel().set_pulse(0, first_duty);
+        self.second.channel().set_pulse(phase, second_duty);
+    }
+
+    /// Drives the outputs complementary: the first channel is high for `duty` counter ticks and
+    /// the second one for the rest of the period, except for `dead_time` ticks after each edge of
+    /// the first channel, in which both outputs are low.
+    pub fn set_complementary(&mut self, duty: u16, dead_time: u16) {
+        let period = self.max_duty_cycle();
+        let duty = duty.min(period);
+        let second_duty = period
+            .saturating_sub(duty)
+            .saturating_sub(dead_time.saturating_mul(2));
+
+        self.first.channel().set_pulse(0, duty);
+        self.second
+            .channel()
+            .set_pulse(duty.saturating_add(dead_time), second_duty);
+    }
+
+    /// Returns the channels, with both outputs set to a duty cycle of 0. The counters keep running.
+    pub fn free(self) -> (A, B) {
+        for channel in [self.first.channel(), self.second.channel()].iter() {
+            with_channel!(*channel, write_inverted, false);
+            with_channel!(*channel, write_duty, 0);
+        }
+        (self.first, self.second)
+    }
+}
+
+/// Period callback stopping the channel after the last pulse of a burst
+fn finish_pulses(channel: Channel) {
+    with_channel!(channel, stop);
+    with_channel!(channel, write_interrupt_enable, false);
+    channel.set_callback(None);
+
+    let on_done = critical_section::with(|cs| {
+        let cell = PULSES_DONE.borrow(cs);
+        let mut callbacks = cell.get();
+        let on_done = callbacks[channel.index()].take();
+        cell.set(callbacks);
+        on_done
+    });
+
+    if let Some(on_done) = on_done {
+        on_done(channel);
+    }
+}
+
+/// Period callback writing the next entry of a waveform table
+fn play_next_sample(channel: Channel) {
+    let sample = critical_section::with(|cs| {
+        let cell = WAVEFORMS.borrow(cs);
+        let mut waveforms = cell.get();
+        let mut sample = None;
+
+        if let Some(waveform) = waveforms[channel.index()].as_mut() {
+            sample = waveform.table.get(waveform.position).copied();
+            waveform.position += 1;
+            if waveform.repeat && waveform.position >= waveform.table.len() {
+                waveform.position = 0;
+            }
+        }
+
+        if sample.is_none() {
+            waveforms[channel.index()] = None;
+        }
+        cell.set(waveforms);
+
+        sample
+    });
+
+    match sample {
+        Some(duty) => channel.set_duty_cycle(duty),
+        None => {
+            // The playback has ended, release the period interrupt of the channel
+            with_channel!(channel, write_interrupt_enable, false);
+            channel.set_callback(None);
+        }
+    }
+}
+
+/// Handles the pending channel interrupts that have a callback registered.
+/// Returns `false` if any pending channel interrupt is left for the user's `Pwm` handler.
+pub(crate) fn dispatch_interrupt() -> bool {
+    let callbacks = critical_section::with(|cs| CALLBACKS.borrow(cs).get());
+    let mut handled = true;
+
+    for (index, callback) in callbacks.iter().enumerate() {
+        let channel = Channel::from_index(index);
+        if !channel.is_interrupt_pending() {
+            continue;
+        }
+
+        match callback {
+            Some(callback) => {
+                channel.clear_interrupt();
+                callback(channel);
+            }
+            None => handled = false,
+        }
+    }
+
+    handled
+}
+
+macro_rules! impl_pwm_channel {
+    ($name: ident, $conf_name: ident, $variant: ident, $channel: literal) => {
+        paste! {
+            #[doc = "PWM Channel " $channel]
+            pub struct $name {}
+
+            #[doc = "A configured PWM channel " $channel " ready to use."]
+            pub struct $conf_name {
+                clocks: Clocks,
+            }
+
+            impl $name {
+                /// Configures the channel to repeat with the given period, using the clock source
+                /// which gives the highest duty cycle resolution. The duty cycle starts at 0.
+                ///
+                /// `pin` has to be a pin configured with `into_pwm_ch` and this channel number.
+                pub fn set_period<PIN: PwmPin<$name>>(
+                    self,
+                    _pin: PIN,
+                    period: impl Into<Nanoseconds<u64>>,
+                    clocks: &Clocks,
+                ) -> $conf_name {
+                    let timing = Timing::from_period(period.into(), clocks);
+                    let mut channel = $conf_name { clocks: *clocks };
+                    channel.apply_timing(timing, 0);
+                    channel.enable();
+                    channel
+                }
+
+                /// Configures the channel to repeat with the given frequency, using the clock
+                /// source which gives the highest duty cycle resolution. The duty cycle starts at 0.
+                ///
+                /// `pin` has to be a pin configured with `into_pwm_ch` and this channel number.
+                pub fn set_frequency<PIN: PwmPin<$name>>(
+                    self,
+                    _pin: PIN,
+                    freq: impl Into<Hertz>,
+                    clocks: &Clocks,
+                ) -> $conf_name {
+                    let timing = Timing::from_frequency(freq.into(), clocks);
+                    let mut channel = $conf_name { clocks: *clocks };
+                    channel.apply_timing(timing, 0);
+                    channel.enable();
+                    channel
+                }
+
+                /// Outputs `source` divided by `2 * divider` with a duty cycle of 50%, e.g. to
+                /// measure the clock configuration with a scope or a frequency counter.
+                ///
+                /// `pin` has to be a pin configured with `into_pwm_ch` and this channel number.
+                ///
+                /// # Panics
+                ///
+                /// If `divider` is 0, this function panics.
+                pub fn clock_out<PIN: PwmPin<$name>>(
+                    self,
+                    _pin: PIN,
+                    source: ClockSource,
+                    divider: u16,
+                    clocks: &Clocks,
+                ) -> $conf_name {
+                    if divider == 0 {
+                        panic!("Unreachable PWM period");
+                    }
+
+                    // Two counter values per period: high for the first, low for the second
+                    let timing = Timing { source, divider, period: 2 };
+                    let mut channel = $conf_name { clocks: *clocks };
+                    channel.apply_timing(timing, 1);
+                    channel.enable();
+                    channel
+                }
+            }
+
+            impl $conf_name {
+                /// The runtime identifier of this channel
+                pub fn channel(&self) -> Channel {
+                    Channel::$variant
+                }
+
+                /// Starts generating the PWM signal.
+                pub fn enable(&mut self) {
+                    Self::start();
+                }
+
+                /// Stops the counter of the channel. The configuration is kept, so the signal can
+                /// be resumed with `enable()`.
+                ///
+                /// Use `force_low()` or `force_high()` if the output has to be held at a known level.
+                pub fn disable(&mut self) {
+                    Self::stop();
+                }
+
+                /// Check if the channel is enabled / running
+                pub fn is_enabled(&self) -> bool {
+                    let pwm = unsafe { &*pac::PWM::ptr() };
+                    pwm.[<pwm $channel _config>].read().pwm_stop_en().bit_is_clear()
+                }
+
+                /// Forces the output low, regardless of the duty cycle. The counter keeps running.
+                pub fn force_low(&mut self) {
+                    let pwm = unsafe { &*pac::PWM::ptr() };
+                    pwm.[<pwm $channel _config>].modify(|_r, w| {
+                        w.pwm_sw_force_val().clear_bit().pwm_sw_mode().set_bit()
+                    });
+                }
+
+                /// Forces the output high, regardless of the duty cycle. The counter keeps running.
+                pub fn force_high(&mut self) {
+                    let pwm = unsafe { &*pac::PWM::ptr() };
+                    pwm.[<pwm $channel _config>].modify(|_r, w| {
+                        w.pwm_sw_force_val().set_bit().pwm_sw_mode().set_bit()
+                    });
+                }
+
+                /// Releases a level set by `force_low()` or `force_high()`, so the PWM signal is
+                /// output again.
+                pub fn release(&mut self) {
+                    let pwm = unsafe { &*pac::PWM::ptr() };
+                    pwm.[<pwm $channel _config>].modify(|_r, w| w.pwm_sw_mode().clear_bit());
+                }
+
+                /// Check if the output is currently forced to a fixed level
+                pub fn is_forced(&self) -> bool {
+                    let pwm = unsafe { &*pac::PWM::ptr() };
+                    pwm.[<pwm $channel _config>].read().pwm_sw_mode().bit_is_set()
+                }
+
+                /// Inverts the output polarity, so the duty cycle sets the low time of the signal.
+                /// Useful for active-low loads such as LEDs wired to VCC.
+                pub fn set_inverted(&mut self, inverted: bool) {
+                    Self::write_inverted(inverted);
+                }
+
+                /// Check if the output polarity is inverted
+                pub fn is_inverted(&self) -> bool {
+                    let pwm = unsafe { &*pac::PWM::ptr() };
+                    pwm.[<pwm $channel _config>].read().pwm_out_inv().bit_is_set()
+                }
+
+                /// Changes the period of the channel, keeping the current duty cycle ratio.
+                pub fn set_period(&mut self, period: impl Into<Nanoseconds<u64>>) {
+                    let timing = Timing::from_period(period.into(), &self.clocks);
+                    self.change_timing(timing);
+                }
+
+                /// Changes the frequency of the channel, keeping the current duty cycle ratio.
+                pub fn set_frequency(&mut self, freq: impl Into<Hertz>) {
+                    let timing = Timing::from_frequency(freq.into(), &self.clocks);
+                    self.change_timing(timing);
+                }
+
+                /// The actual period of the channel, after rounding to the available clock ticks.
+                pub fn period(&self) -> Nanoseconds<u64> {
+                    let timing = Self::read_timing();
+                    let source_hz = timing.source.hertz(&self.clocks).0 as u64;
+                    Nanoseconds::<u64>::new(timing.source_ticks() * 1_000_000_000_u64 / source_hz)
+                }
+
+                /// The actual frequency of the channel, after rounding to the available clock ticks.
+                pub fn frequency(&self) -> Hertz {
+                    let timing = Self::read_timing();
+                    let source_hz = timing.source.hertz(&self.clocks).0 as u64;
+                    Hertz((source_hz / timing.source_ticks()) as u32)
+                }
+
+                /// The clock source selected for the current period.
+                pub fn clock_source(&self) -> ClockSource {
+                    Self::read_timing().source
+                }
+
+                /// The current duty cycle, between 0 and `max_duty_cycle()`.
+                pub fn get_duty_cycle(&self) -> u16 {
+                    Self::read_duty()
+                }
+
+                /// The current thresholds as `(low, high)`. The output is high while the counter
+                /// is at or above `low` and below `high`.
+                pub fn get_thresholds(&self) -> (u16, u16) {
+                    Self::read_thresholds()
+                }
+
+                /// Sets both thresholds, so the active pulse runs from counter value `low` up to
+                /// `high`. Both are clamped to `max_duty_cycle()`. A `high` at or below `low`
+                /// keeps the output low.
+                ///
+                /// The duty cycle setters always move the pulse back to the start of the period.
+                pub fn set_thresholds(&mut self, low: u16, high: u16) {
+                    Channel::$variant.set_thresholds(low, high);
+                }
+
+                /// Outputs a pulse of `width` counter ticks starting at counter value `start`. A
+                /// pulse crossing the end of the period wraps around to its start, which is done
+                /// by inverting the output polarity. The polarity stays inverted until
+                /// `set_inverted(false)` is called or a pulse is set that does not wrap.
+                pub fn set_pulse(&mut self, start: u16, width: u16) {
+                    Channel::$variant.set_pulse(start, width);
+                }
+
+                /// Enable the period interrupt, raised every `periods` periods of the channel.
+                pub fn enable_period_interrupt(&mut self, periods: u16) {
+                    let pwm = unsafe { &*pac::PWM::ptr() };
+                    pwm.[<pwm $channel _interrupt>].modify(|_r, w| unsafe {
+                        w.pwm_int_period_cnt().bits(periods).pwm_int_enable().set_bit()
+                    });
+                }
+
+                /// Disable the period interrupt.
+                pub fn disable_period_interrupt(&mut self) {
+                    Self::write_interrupt_enable(false);
+                }
+
+                /// Will only become true if `enable_period_interrupt` is active
+                pub fn is_period_interrupt(&self) -> bool {
+                    Channel::$variant.is_interrupt_pending()
+                }
+
+                /// Clear the period interrupt.
+                pub fn clear_period_interrupt(&self) {
+                    Channel::$variant.clear_interrupt()
+                }
+
+                /// Registers `callback` to be called from the `Pwm` interrupt whenever the period
+                /// interrupt of this channel is raised. The HAL clears the channel interrupt before
+                /// calling it, and the `Pwm()` handler won't be called for this channel anymore.
+                ///
+                /// The callback can update the duty cycle through
+                /// [Channel::set_duty_cycle], so the new value applies from the next period on.
+                pub fn set_period_callback(&mut self, callback: fn(Channel)) {
+                    Channel::$variant.set_callback(Some(callback));
+                }
+
+                /// Removes a callback registered by `set_period_callback()`.
+                pub fn clear_period_callback(&mut self) {
+                    Channel::$variant.set_callback(None);
+                }
+
+                /// Plays back `table` as duty cycles, one entry per period. With `repeat` the table
+                /// is looped until `stop_waveform()` is called, otherwise the last entry is kept.
+                ///
+                /// This uses the period interrupt and callback of the channel until the playback
+                /// ends. The `Pwm` interrupt has to be enabled with
+                /// `enable_interrupt(Interrupt::Pwm)`.
+                pub fn play_waveform(&mut self, table: &'static [u16], repeat: bool) {
+                    Channel::$variant.set_waveform(Some(Waveform {
+                        table,
+                        position: 0,
+                        repeat,
+                    }));
+                    self.set_period_callback(play_next_sample);
+                    self.enable_period_interrupt(1);
+                }
+
+                /// Stops a playback started by `play_waveform()`, keeping the current duty cycle.
+                pub fn stop_waveform(&mut self) {
+                    self.disable_period_interrupt();
+                    self.clear_period_callback();
+                    Channel::$variant.set_waveform(None);
+                }
+
+                /// Check if a waveform is being played back on the channel
+                pub fn is_waveform_playing(&self) -> bool {
+                    Channel::$variant.is_waveform_playing()
+                }
+
+                /// Outputs exactly `count` pulses of `width` counter ticks and then stops the
+                /// channel, e.g. for stepper motor steps or ultrasonic bursts. `on_done` is called
+                /// from the `Pwm` interrupt once the last pulse has ended, after which
+                /// `is_enabled()` returns false.
+                ///
+                /// The chip has no hardware pulse counter that stops the channel, so the HAL stops
+                /// it from the period interrupt. The pulses are placed at the end of each period,
+                /// so the `Pwm` interrupt has to be served within `max_duty_cycle() - width`
+                /// counter ticks, before the output would go high again. The `Pwm` interrupt has
+                /// to be enabled with `enable_interrupt(Interrupt::Pwm)`.
+                ///
+                /// Call `set_duty_cycle()` and `enable()` to go back to a continuous signal.
+                pub fn emit_pulses(&mut self, count: u16, width: u16, on_done: Option<fn(Channel)>) {
+                    self.stop_waveform();
+                    self.disable();
+                    if count == 0 {
+                        return;
+                    }
+
+                    let period = Self::read_period();
+                    let width = width.min(period - 1);
+                    Self::write_inverted(false);
+                    Self::write_thresholds(period - width, period);
+
+                    critical_section::with(|cs| {
+                        let cell = PULSES_DONE.borrow(cs);
+                        let mut callbacks = cell.get();
+                        callbacks[$channel] = on_done;
+                        cell.set(callbacks);
+                    });
+
+                    Channel::$variant.clear_interrupt();
+                    self.set_period_callback(finish_pulses);
+                    self.enable_period_interrupt(count);
+                    self.enable();
+                }
+
+                fn start() {
+                    let pwm = unsafe { &*pac::PWM::ptr() };
+                    pwm.[<pwm $channel _config>].modify(|_r, w| w.pwm_stop_en().clear_bit());
+                }
+
+                fn stop() {
+                    let pwm = unsafe { &*pac::PWM::ptr() };
+                    pwm.[<pwm $channel _config>].modify(|_r, w| w.pwm_stop_en().set_bit());
+                    while pwm.[<pwm $channel _config>].read().pwm_sts_top().bit_is_clear() {}
+                }
+
+                fn write_inverted(inverted: bool) {
+                    let pwm = unsafe { &*pac::PWM::ptr() };
+                    pwm.[<pwm $channel _config>].modify(|_r, w| w.pwm_out_inv().bit(inverted));
+                }
+
+                fn write_interrupt_enable(enable: bool) {
+                    let pwm = unsafe { &*pac::PWM::ptr() };
+                    pwm.[<pwm $channel _interrupt>].modify(|_r, w| w.pwm_int_enable().bit(enable));
+                }
+
+                fn read_period() -> u16 {
+                    let pwm = unsafe { &*pac::PWM::ptr() };
+                    pwm.[<pwm $channel _period>].read().pwm_period().bits()
+                }
+
+                fn read_duty() -> u16 {
+                    let (thre1, thre2) = Self::read_thresholds();
+                    thre2.saturating_sub(thre1)
+                }
+
+                fn read_thresholds() -> (u16, u16) {
+                    let pwm = unsafe { &*pac::PWM::ptr() };
+                    let thre1 = pwm.[<pwm $channel _thre1>].read().pwm_thre1().bits();
+                    let thre2 = pwm.[<pwm $channel _thre2>].read().pwm_thre2().bits();
+                    (thre1, thre2)
+                }
+
+                fn write_duty(duty: u16) {
+                    Self::write_thresholds(0, duty);
+                }
+
+                fn write_thresholds(thre1: u16, thre2: u16) {
+                    let pwm = unsafe { &*pac::PWM::ptr() };
+                    pwm.[<pwm $channel _thre1>].write(|w| unsafe { w.pwm_thre1().bits(thre1) });
+                    pwm.[<pwm $channel _thre2>].write(|w| unsafe { w.pwm_thre2().bits(thre2) });
+                }
+
+                fn read_timing() -> Timing {
+                    let pwm = unsafe { &*pac::PWM::ptr() };
+                    let clk_sel = pwm.[<pwm $channel _config>].read().reg_clk_sel().bits();
+                    Timing {
+                        source: ClockSource::from_reg_clk_sel(clk_sel),
+                        divider: pwm.[<pwm $channel _clkdiv>].read().pwm_clk_div().bits(),
+                        period: Self::read_period(),
+                    }
+                }
+
+                /// Writes the clock configuration. The channel has to be stopped.
+                fn write_timing(timing: Timing) {
+                    let pwm = unsafe { &*pac::PWM::ptr() };
+                    pwm.[<pwm $channel _config>].modify(|_r, w| unsafe {
+                        w.reg_clk_sel().bits(timing.source.reg_clk_sel())
+                    });
+                    pwm.[<pwm $channel _clkdiv>]
+                        .write(|w| unsafe { w.pwm_clk_div().bits(timing.divider) });
+                    pwm.[<pwm $channel _period>]
+                        .write(|w| unsafe { w.pwm_period().bits(timing.period) });
+                }
+
+                fn change_timing(&mut self, timing: Timing) {
+                    let duty =
+                        self.get_duty_cycle() as u32 * timing.period as u32 / Self::read_period() as u32;
+                    let was_enabled = self.is_enabled();
+                    self.apply_timing(timing, duty as u16);
+                    if was_enabled {
+                        self.enable();
+                    }
+                }
+
+                /// Writes the clock configuration and duty cycle, leaving the channel disabled
+                fn apply_timing(&mut self, timing: Timing, duty: u16) {
+                    // The channel has to be stopped while its clock configuration is changed
+                    self.disable();
+                    Self::write_timing(timing);
+                    Self::write_duty(duty);
+                }
+            }
+
+            impl ErrorType for $conf_name {
+                type Error = Infallible;
+            }
+
+            impl SetDutyCycle for $conf_name {
+                fn max_duty_cycle(&self) -> u16 {
+                    Self::read_period()
+                }
+
+                fn set_duty_cycle(&mut self, duty: u16) -> Result<(), Self::Error> {
+                    Self::write_duty(duty.min(Self::read_period()));
+                    Ok(())
+                }
+            }
+
+            impl Suspend for $conf_name {
+                type State = SavedRegisters<6>;
+
+                fn suspend(&mut self) -> Self::State {
+                    let pwm = unsafe { &*pac::PWM::ptr() };
+                    SavedRegisters([
+                        pwm.[<pwm $channel _clkdiv>].read().bits(),
+                        pwm.[<pwm $channel _thre1>].read().bits(),
+                        pwm.[<pwm $channel _thre2>].read().bits(),
+                        pwm.[<pwm $channel _period>].read().bits(),
+                        pwm.[<pwm $channel _interrupt>].read().bits(),
+                        pwm.[<pwm $channel _config>].read().bits(),
+                    ])
+                }
+            }
+
+            impl Resume for $conf_name {
+                fn resume(&mut self, state: Self::State) {
+                    let pwm = unsafe { &*pac::PWM::ptr() };
+                    let [clkdiv, thre1, thre2, period, interrupt, config] = state.0;
+                    pwm.[<pwm $channel _clkdiv>].write(|w| unsafe { w.bits(clkdiv) });
+                    pwm.[<pwm $channel _thre1>].write(|w| unsafe { w.bits(thre1) });
+                    pwm.[<pwm $channel _thre2>].write(|w| unsafe { w.bits(thre2) });
+                    pwm.[<pwm $channel _period>].write(|w| unsafe { w.bits(period) });
+                    pwm.[<pwm $channel _interrupt>].write(|w| unsafe { w.bits(interrupt) });
+                    // Starts the counter again, if it was running
+                    pwm.[<pwm $channel _config>].write(|w| unsafe { w.bits(config) });
+                }
+            }
+
+            impl private::Sealed for $conf_name {}
+
+            impl ConfiguredChannel for $conf_name {
+                fn channel(&self) -> Channel {
+                    Channel::$variant
+                }
+
+                fn period(&self) -> Nanoseconds<u64> {
+                    $conf_name::period(self)
+                }
+
+                fn set_period(&mut self, period: Nanoseconds<u64>) {
+                    $conf_name::set_period(self, period)
+                }
+
+                fn set_frequency(&mut self, freq: Hertz) {
+                    $conf_name::set_frequency(self, freq)
+                }
+            }
+
+            impl embedded_hal_zero::PwmPin for $conf_name {
+                type Duty = u16;
+
+                fn disable(&mut self) {
+                    $conf_name::disable(self)
+                }
+
+                fn enable(&mut self) {
+                    $conf_name::enable(self)
+                }
+
+                fn get_duty(&self) -> Self::Duty {
+                    self.get_duty_cycle()
+                }
+
+                fn get_max_duty(&self) -> Self::Duty {
+                    self.max_duty_cycle()
+                }
+
+                fn set_duty(&mut self, duty: Self::Duty) {
+                    self.set_duty_cycle(duty).ok();
+                }
+            }
+        }
+    };
+}
+
+impl_pwm_channel!(PwmChannel0, ConfiguredPwmChannel0, Channel0, 0);
+impl_pwm_channel!(PwmChannel1, ConfiguredPwmChannel1, Channel1, 1);
+impl_pwm_channel!(PwmChannel2, ConfiguredPwmChannel2, Channel2, 2);
+impl_pwm_channel!(PwmChannel3, ConfiguredPwmChannel3, Channel3, 3);
+impl_pwm_channel!(PwmChannel4, ConfiguredPwmChannel4, Channel4, 4);
+
+/// PWM channels obtained from [PWM.split](bl602_pac::Peripherals::PWM)
+pub struct Channels {
+    pub channel0: PwmChannel0,
+    pub channel1: PwmChannel1,
+    pub channel2: PwmChannel2,
+    pub channel3: PwmChannel3,
+    pub channel4: PwmChannel4,
+}
+
+/// Extension trait to split PWM peripheral into independent channels
+pub trait PwmExt {
+    fn split(self) -> Channels;
+}
+
+impl PwmExt for PWM {
+    fn split(self) -> Channels {
+        Channels {
+            channel0: PwmChannel0 {},
+            channel1: PwmChannel1 {},
+            channel2: PwmChannel2 {},
+            channel3: PwmChannel3 {},
+            channel4: PwmChannel4 {},
+        }
+    }
+}
+
+/// An RC servo driven by a PWM channel with the usual 20 ms (50 Hz) period.
+///
+/// The angle is mapped linearly onto the pulse width. By default 0 to 180 degrees map onto pulses
+/// of 1000 to 2000 us, which every servo accepts. Most servos have a wider range, which can be
+/// calibrated with `set_limits()`.
+pub struct Servo<P> {
+    channel: P,
+    min_pulse_us: u32,
+    max_pulse_us: u32,
+    max_angle: u16,
+    pulse_us: u32,
+}
+
+impl<P: ConfiguredChannel> Servo<P> {
+    /// Sets the servo period on `channel` and moves the servo to the middle of its range.
+    pub fn new(mut channel: P) -> Self {
+        channel.set_period(Nanoseconds::<u64>::new(20_000_000));
+
+        let mut servo = Servo {
+            channel,
+            min_pulse_us: 1000,
+            max_pulse_us: 2000,
+            max_angle: 180,
+            pulse_us: 1500,
+        };
+        servo.set_pulse_us(1500);
+        servo
+    }
+
+    /// Calibrates the servo: `min_pulse_us` is the pulse width at 0 degrees and `max_pulse_us`
+    /// the one at `max_angle` degrees. Pulses outside of this range are never output.
+    ///
+    /// # Panics
+    ///
+    /// If `min_pulse_us` isn't below `max_pulse_us`, if `max_pulse_us` doesn't fit in the period
+    /// or if `max_angle` is 0, this function panics.
+    pub fn set_limits(&mut self, min_pulse_us: u32, max_pulse_us: u32, max_angle: u16) {
+        let period_us = self.channel.period().integer() / 1_000;
+        if min_pulse_us >= max_pulse_us || max_pulse_us as u64 > period_us || max_angle == 0 {
+            panic!("Invalid servo limits");
+        }
+
+        self.min_pulse_us = min_pulse_us;
+        self.max_pulse_us = max_pulse_us;
+        self.max_angle = max_angle;
+        self.set_pulse_us(self.pulse_us);
+    }
+
+    /// Moves the servo to `degrees`, clamped to the calibrated range.
+    pub fn set_angle(&mut self, degrees: u16) {
+        let degrees = degrees.min(self.max_angle) as u32;
+        let range_us = self.max_pulse_us - self.min_pulse_us;
+        self.set_pulse_us(self.min_pulse_us + range_us * degrees / self.max_angle as u32);
+    }
+
+    /// The current angle, rounded to whole degrees.
+    pub fn angle(&self) -> u16 {
+        let range_us = self.max_pulse_us - self.min_pulse_us;
+        let offset_us = self.pulse_us - self.min_pulse_us;
+        ((offset_us * self.max_angle as u32 + range_us / 2) / range_us) as u16
+    }
+
+    /// Outputs pulses of `pulse_us` microseconds, clamped to the calibrated range.
+    pub fn set_pulse_us(&mut self, pulse_us: u32) {
+        let pulse_us = pulse_us.max(self.min_pulse_us).min(self.max_pulse_us);
+        let period_us = self.channel.period().integer() / 1_000;
+        let ticks = pulse_us as u64 * self.channel.max_duty_cycle() as u64 / period_us;
+
+        self.channel.set_duty_cycle(ticks as u16).ok();
+        self.pulse_us = pulse_us;
+    }
+
+    /// The width of the pulses currently output, in microseconds.
+    pub fn pulse_us(&self) -> u32 {
+        self.pulse_us
+    }
+
+    /// Returns the PWM channel, which keeps outputting the current pulse width.
+    pub fn free(self) -> P {
+        self.channel
+    }
+}
+
+/// Square wave generator for buzzers and speakers, using a PWM channel at half duty cycle.
+///
+/// The duration of a tone is timed with the machine timer (`mtime`), so it has to be running, see
+/// [Clocks::mtimer_clk].
+pub struct Tone<P> {
+    channel: P,
+    mtimer_clk: Hertz,
+    start_tick: u64,
+    duration_ticks: u64,
+}
+
+impl<P: ConfiguredChannel> Tone<P> {
+    /// Creates a tone generator on `channel`, which starts silent.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the machine timer is stopped in `clocks`.
+    pub fn new(mut channel: P, clocks: &Clocks) -> Self {
+        let mtimer_clk = clocks.mtimer_clk().expect("the machine timer is stopped");
+
+        channel.set_duty_cycle(0).ok();
+        Tone {
+            channel,
+            mtimer_clk,
+            start_tick: 0,
+            duration_ticks: 0,
+        }
+    }
+
+    /// Plays `freq` for `duration`, blocking until it has ended. A frequency of 0 is a rest.
+    pub fn play(&mut self, freq: impl Into<Hertz>, duration: impl Into<Nanoseconds<u64>>) {
+        self.start(freq, duration);
+        nb::block!(self.wait()).ok();
+    }
+
+    /// Starts playing `freq` for `duration` and returns immediately. `wait()` has to be polled to
+    /// silence the output once the duration has passed. A frequency of 0 is a rest.
+    pub fn start(&mut self, freq: impl Into<Hertz>, duration: impl Into<Nanoseconds<u64>>) {
+        let freq = freq.into();
+        let duration: Nanoseconds<u64> = duration.into();
+
+        if freq.0 == 0 {
+            self.channel.set_duty_cycle(0).ok();
+        } else {
+            self.channel.set_frequency(freq);
+            self.channel.set_duty_cycle_fraction(1, 2).ok();
+        }
+
+        let ticks = duration.integer() as u128 * self.mtimer_clk.0 as u128 / 1_000_000_000;
+        self.start_tick = read_mtime();
+        self.duration_ticks = ticks.min(u64::MAX as u128) as u64;
+    }
+
+    /// Silences the output once the duration given to `start()` has passed.
+    pub fn wait(&mut self) -> nb::Result<(), Void> {
+        if read_mtime().wrapping_sub(self.start_tick) < self.duration_ticks {
+            return Err(nb::Error::WouldBlock);
+        }
+
+        self.stop();
+        Ok(())
+    }
+
+    /// Silences the output right away.
+    pub fn stop(&mut self) {
+        self.channel.set_duty_cycle(0).ok();
+        self.duration_ticks = 0;
+    }
+
+    /// Returns the PWM channel, with its output silenced.
+    pub fn free(mut self) -> P {
+        self.stop();
+        self.channel
+    }
+}
@@ -0,0 +1,98 @@
+/*!
+  # Raw register access
+  Registers and fields the PAC doesn't describe are accessed by address, with the offsets from
+  the register headers of the SDK. Registers the PAC does describe are accessed through it.
+*/
+
+use crate::pac;
+
+// see components\bl602\bl602_std\bl602_std\Include\bl602.h
+pub(crate) const GLB_BASE: usize = 0x4000_0000;
+pub(crate) const PDS_BASE: usize = 0x4000_e000;
+pub(crate) const HBN_BASE: usize = 0x4000_f000;
+
+// CLIC_CTRL_ADDR + CLIC_MTIME, see components\bl602\bl602_std\bl602_std\RISCV\Core\Include\clic.h
+const MTIME: usize = 0x0200_bff8;
+
+pub(crate) fn aon(offset: usize) -> usize {
+    pac::AON::ptr() as usize + offset
+}
+
+pub(crate) fn dma(offset: usize) -> usize {
+    pac::DMA::ptr() as usize + offset
+}
+
+pub(crate) fn glb(offset: usize) -> usize {
+    pac::GLB::ptr() as usize + offset
+}
+
+pub(crate) fn gpip(offset: usize) -> usize {
+    pac::GPIP::ptr() as usize + offset
+}
+
+pub(crate) fn hbn(offset: usize) -> usize {
+    pac::HBN::ptr() as usize + offset
+}
+
+pub(crate) fn pds(offset: usize) -> usize {
+    pac::PDS::ptr() as usize + offset
+}
+
+pub(crate) fn read_reg(address: usize) -> u32 {
+    unsafe { (address as *const u32).read_volatile() }
+}
+
+pub(crate) fn write_reg(address: usize, value: u32) {
+    unsafe { (address as *mut u32).write_volatile(value) }
+}
+
+pub(crate) fn modify_reg(address: usize, f: impl FnOnce(u32) -> u32) {
+    write_reg(address, f(read_reg(address)));
+}
+
+/// Reads the 64-bit machine timer, whose halves are read separately
+pub(crate) fn read_mtime() -> u64 {
+    loop {
+        let high = read_reg(MTIME + 4);
+        let low = read_reg(MTIME);
+        if read_reg(MTIME + 4) == high {
+            return (high as u64) << 32 | low as u64;
+        }
+    }
+}
+
+/// Reads the register at `address` from code that runs from RAM while the flash is unavailable.
+/// Unlike `read_reg` it's an inline `lw`, so it can't be left as a call into flash.
+macro_rules! ram_read_reg {
+    ($address: expr) => {{
+        let value: u32;
+        core::arch::asm!(
+            "lw {value}, 0({address})",
+            address = in(reg) $address,
+            value = out(reg) value,
+            options(nostack, preserves_flags)
+        );
+        value
+    }};
+}
+
+/// Writes `value` to the register at `address`, inline like `ram_read_reg`
+macro_rules! ram_write_reg {
+    ($address: expr, $value: expr) => {
+        core::arch::asm!(
+            "sw {value}, 0({address})",
+            address = in(reg) $address,
+            value = in(reg) $value,
+            options(nostack, preserves_flags)
+        )
+    };
+}
+
+/// Spends 8 `nop`s, inline, for the delays of code that runs from RAM
+macro_rules! ram_delay {
+    () => {
+        core::arch::asm!(".rept 8", "nop", ".endr", options(nostack, preserves_flags))
+    };
+}
+
+pub(crate) use {ram_delay, ram_read_reg, ram_write_reg};
@@ -0,0 +1,69 @@
+/*!
+  # Reset Reason
+  Tells why the chip was reset, so firmware can branch on e.g. recovering from a watchdog reset.
+
+  The flags live in the always-on domain, so they survive resets until they're cleared with
+  `clear_reset_reason()`. Without clearing them, a later power on would still report the earlier
+  reason.
+
+  ## Example
+  ```rust
+    match reset_reason() {
+        ResetReason::Watchdog => {
+            // the firmware hung, e.g. skip the step that was running
+        }
+        _ => {}
+    }
+    clear_reset_reason();
+  ```
+*/
+
+use crate::{pac, watchdog::unlocked};
+
+/// Set by the boot ROM and the SDK in HBN_RSV0 when the chip enters hibernate
+pub(crate) const HBN_STATUS_ENTER_FLAG: u32 = 0x4e42_4845;
+/// BOR interrupt status in HBN_IRQ_STAT
+const HBN_IRQ_STAT_BOR: u32 = 1 << 18;
+
+/// The reason of the last reset
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ResetReason {
+    /// Power on, or a reset without a flag of its own, like the reset pin or a software reset
+    PowerOn,
+    /// The watchdog timed out in reset mode
+    Watchdog,
+    /// The supply voltage dropped below the brown out threshold
+    BrownOut,
+    /// Wakeup from hibernate, which restarts the chip
+    HibernateWakeup,
+}
+
+/// Reads the reason of the last reset. When several flags are set, the watchdog is reported first,
+/// then a brown out and then a wakeup from hibernate.
+pub fn reset_reason() -> ResetReason {
+    let timer = unsafe { &*pac::TIMER::ptr() };
+    let hbn = unsafe { &*pac::HBN::ptr() };
+
+    if timer.wsr.read().wts().bit_is_set() {
+        ResetReason::Watchdog
+    } else if hbn.hbn_irq_stat.read().bits() & HBN_IRQ_STAT_BOR != 0 {
+        ResetReason::BrownOut
+    } else if hbn.hbn_rsv0.read().bits() == HBN_STATUS_ENTER_FLAG {
+        ResetReason::HibernateWakeup
+    } else {
+        ResetReason::PowerOn
+    }
+}
+
+/// Clears the reset flags, so the next reset reports its own reason
+pub fn clear_reset_reason() {
+    let hbn = unsafe { &*pac::HBN::ptr() };
+
+    unlocked(|wdt| wdt.clear_reset_flag());
+
+    hbn.hbn_irq_clr
+        .write(|w| unsafe { w.bits(HBN_IRQ_STAT_BOR) });
+    hbn.hbn_irq_clr.write(|w| unsafe { w.bits(0) });
+    hbn.hbn_rsv0.write(|w| unsafe { w.bits(0) });
+}
@@ -6,10 +6,191 @@
   ```rust
     let rtc = Rtc::new(dp.HBN);
   ```
+
+  ## Drift compensation example
+  The RTC counts at a nominal 32.768kHz. When it runs from the internal RC oscillator, it's off
+  by up to a few percent, so [calibrate] measures it against the crystal and compensates the
+  drift. The compensation applies to the RTC and to the timed wakeups from PDS and hibernate.
+  ```rust
+    let rtc = Rtc::new(dp.HBN);
+    rtc::calibrate(&clocks);
+  ```
+
+  The oscillator drifts with the temperature, so long sleeps stay accurate when it's measured
+  again every now and then. The compensation is lost in hibernate, but the drift can be kept in
+  the retention RAM, or measured externally, and set with [set_drift_ppm].
+  ```rust
+    let mut drift = retention::retained::<i32>();
+    match drift.get() {
+        Some(ppm) => rtc::set_drift_ppm(ppm),
+        None => drift.set(rtc::calibrate(&clocks)),
+    }
+  ```
+
+  ## Calendar example
+  The RTC is in the always-on domain, so it keeps counting through PDS and HBN sleep, except in
+  `Level::Hbn2`. After a wakeup from hibernate, [Rtc::resume] takes over the running counter
+  instead of starting it again, and the calendar keeps its time.
+  ```rust
+    let mut rtc = match reset_reason() {
+        ResetReason::HibernateWakeup => Rtc::resume(dp.HBN),
+        _ => {
+            let mut rtc = Rtc::new(dp.HBN);
+            rtc.set_unix_time(1_700_000_000);
+            rtc
+        }
+    };
+    let now = rtc.unix_time();
+  ```
+
+  ## Alarm example
+  The alarm raises the `HbnOut0` interrupt at an absolute calendar time, and wakes the chip from
+  PDS sleep, or from hibernate with `hbn::Config::wake_on_alarm()`. E.g. to wake up every hour on
+  the hour:
+  ```rust
+    let next_hour = (rtc.unix_time() / 3600 + 1) * 3600;
+    rtc.set_alarm(next_hour).unwrap();
+    hbn::hibernate(&hbn::Config::new(Level::Hbn1).wake_on_alarm());
+  ```
+
+  While awake, the alarm is handled in the `HbnOut0()` interrupt handler.
+  ```rust
+    enable_interrupt(Interrupt::HbnOut0);
+
+    #[no_mangle]
+    fn HbnOut0() {
+        // ...
+        rtc.clear_alarm();
+    }
+  ```
+
+  With the `rtcc` feature the RTC implements the traits of the
+  [rtcc](https://docs.rs/rtcc) crate, to get and set the civil date and time in UTC.
+  ```rust
+    use rtcc::{DateTimeAccess, NaiveDate};
+
+    let datetime = NaiveDate::from_ymd_opt(2024, 3, 1)
+        .and_then(|date| date.and_hms_opt(12, 0, 0))
+        .unwrap();
+    rtc.set_datetime(&datetime).unwrap();
+    let datetime = rtc.datetime().unwrap();
+  ```
 */
 
-use bl602_pac::HBN;
+use crate::clock::{self, Clocks};
+use bl602_pac::{HBN, PDS};
+use core::cell::Cell;
+use critical_section::Mutex;
+use embedded_time::rate::Hertz;
 use embedded_time::Clock;
+#[cfg(feature = "rtcc")]
+use rtcc::{DateTimeAccess, Datelike, Timelike};
+
+/// Nominal rate of the counter
+const NOMINAL_TICK_RATE: u32 = 32_768;
+/// Compares the whole 40 bits of the counter, in `rtc_ctl`
+const RTC_CTL_COMP_BIT0_39: u8 = 1 << 1;
+/// The comparator bits in `rtc_ctl`
+const RTC_CTL_COMP_MASK: u8 = 0b1110;
+/// The compare value is 40 bits wide, the high part in HBN_TIME_H
+const TIME_H_MASK: u32 = 0xff;
+/// RTC interrupt in HBN_IRQ_STAT and HBN_IRQ_CLR
+const HBN_IRQ_RTC: u32 = 1 << 16;
+/// The HBN_OUT0 wakeup source in PDS_INT
+const PDS_INT_WAKEUP_SRC_HBN_IRQ_OUT0: u32 = 1 << 17;
+
+/// The time the drift compensation applies from, moved forward whenever it changes, so the time
+/// counted before keeps the rate it was counted at
+#[derive(Copy, Clone)]
+struct Timebase {
+    /// The counter value the compensation applies from
+    ticks: u64,
+    /// Microseconds counted up to `ticks`
+    micros: u64,
+    /// How far the 32K clock is off its nominal rate, in ppm
+    ppm: i32,
+}
+
+impl Timebase {
+    /// Microseconds counted up to the counter value `ticks`
+    fn micros_at(&self, ticks: u64) -> u64 {
+        let since = ticks.saturating_sub(self.ticks) as u128 * 1_000_000_000;
+        self.micros + (since / tick_rate_millihertz(self.ppm) as u128) as u64
+    }
+}
+
+static TIMEBASE: Mutex<Cell<Timebase>> = Mutex::new(Cell::new(Timebase {
+    ticks: 0,
+    micros: 0,
+    ppm: 0,
+}));
+
+/// Compensates the drift of the 32K clock, in ppm and positive when it runs fast, in the RTC and
+/// the timed wakeups from PDS and hibernate. The time counted so far is kept, so the RTC doesn't
+/// jump, and the compensation applies to the time counted from now on.
+pub fn set_drift_ppm(ppm: i32) {
+    critical_section::with(|cs| {
+        let cell = TIMEBASE.borrow(cs);
+        let ticks = counter_ticks();
+        let micros = cell.get().micros_at(ticks);
+        cell.set(Timebase { ticks, micros, ppm });
+    });
+}
+
+/// The drift of the 32K clock set with [set_drift_ppm] or [calibrate], in ppm
+pub fn drift_ppm() -> i32 {
+    critical_section::with(|cs| TIMEBASE.borrow(cs).get().ppm)
+}
+
+/// Measures the drift of the 32K RC oscillator against the crystal with
+/// `clock::measure_rc32k_ppm` and compensates it, see [set_drift_ppm]. Takes about 100ms, and
+/// returns the drift in ppm.
+///
+/// # Panics
+///
+/// If the core clock runs from the RC oscillator, or the 32K clock is the crystal.
+pub fn calibrate(clocks: &Clocks) -> i32 {
+    let ppm = clock::measure_rc32k_ppm(clocks);
+    set_drift_ppm(ppm);
+    ppm
+}
+
+/// Converts ticks at the nominal 32.768kHz to ticks of the drifting 32K clock
+pub(crate) fn compensate_ticks(ticks: u64) -> u64 {
+    (ticks as i64 + ticks as i64 * drift_ppm() as i64 / 1_000_000) as u64
+}
+
+/// The actual rate of the 32K clock in mHz, with a drift of `ppm`
+fn tick_rate_millihertz(ppm: i32) -> u64 {
+    let nominal = NOMINAL_TICK_RATE as i64 * 1_000;
+    (nominal + nominal * ppm as i64 / 1_000_000) as u64
+}
+
+/// Microseconds counted since the counter started, with the drift compensated
+fn elapsed_micros() -> u64 {
+    critical_section::with(|cs| TIMEBASE.borrow(cs).get().micros_at(counter_ticks()))
+}
+
+/// The counter value, in counter units
+fn counter_ticks() -> u64 {
+    let hbn = unsafe { &*HBN::ptr() };
+    hbn.rtc_time_h
+        .modify(|r, w| unsafe { w.bits(r.bits() | 1 << 31) });
+
+    let h = hbn.rtc_time_h.read().bits();
+    let l = hbn.rtc_time_l.read().bits();
+    (h as u64) << 32 | l as u64
+}
+
+/// Calendar errors
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The date or time is invalid, or outside of the calendar from 1970 to 2105
+    InvalidInputData,
+    /// The alarm time has already passed
+    AlarmInPast,
+}
 
 pub struct Rtc {
     hbn: HBN,
@@ -22,26 +203,124 @@ impl Rtc {
         hbn.hbn_ctl
             .modify(|r, w| unsafe { w.rtc_ctl().bits(r.rtc_ctl().bits() & 0xfe) });
         // enable counter
+        hbn.hbn_ctl
+            .modify(|r, w| unsafe { w.rtc_ctl().bits(r.rtc_ctl().bits() | 1) });
+        // the calendar starts at the epoch
+        hbn.hbn_rsv1.write(|w| unsafe { w.bits(0) });
+        // the drift compensation applies from the start of the counter
+        critical_section::with(|cs| {
+            let cell = TIMEBASE.borrow(cs);
+            let ppm = cell.get().ppm;
+            cell.set(Timebase {
+                ticks: 0,
+                micros: 0,
+                ppm,
+            });
+        });
+
+        Rtc { hbn }
+    }
+
+    /// Takes over the RTC without clearing its counter, e.g. after a wakeup from hibernate, so
+    /// the calendar keeps its time. A stopped RTC is started.
+    pub fn resume(hbn: HBN) -> Rtc {
         hbn.hbn_ctl
             .modify(|r, w| unsafe { w.rtc_ctl().bits(r.rtc_ctl().bits() | 1) });
 
         Rtc { hbn }
     }
 
+    /// Sets the rate the counter actually runs at, e.g. measured with `clock::measure_rc32k`,
+    /// instead of the nominal 32.768kHz. This sets the drift compensation, see [set_drift_ppm].
+    pub fn set_tick_rate(&mut self, rate: Hertz) {
+        let nominal = NOMINAL_TICK_RATE as i64;
+        set_drift_ppm(((rate.0 as i64 - nominal) * 1_000_000 / nominal) as i32);
+    }
+
     /// Get elapsed milliseconds since the RTC was created
     pub fn get_millis(&self) -> u64 {
+        // from IOT SDK, at the nominal rate:
+        // #define BL_RTC_COUNTER_TO_MS(CNT)  (((CNT) >> 5) - ((CNT) >> 11) - ((CNT) >> 12))  // ((CNT)*(1024-16-8)/32768)
+        // see https://github.com/bouffalolab/bl_iot_sdk/blob/90acb7b46d11343d27db9518c4f86d94572c6629/components/hal_drv/bl602_hal/bl_rtc.c
+        elapsed_micros() / 1_000
+    }
+
+    /// Seconds since the Unix epoch, 1970-01-01 00:00:00 UTC
+    pub fn unix_time(&self) -> u32 {
+        self.epoch().wrapping_add(self.elapsed_seconds())
+    }
+
+    /// Sets the calendar to `seconds` since the Unix epoch. The time is kept in HBN_RSV1 as the
+    /// time the counter started at, to the second.
+    pub fn set_unix_time(&mut self, seconds: u32) {
+        let epoch = seconds.wrapping_sub(self.elapsed_seconds());
+        self.hbn.hbn_rsv1.write(|w| unsafe { w.bits(epoch) });
+    }
+
+    /// Sets the alarm to `unix_time`. It raises the `HbnOut0` interrupt, and wakes the chip from
+    /// PDS sleep, or from hibernate with `hbn::Config::wake_on_alarm()`. The alarm stays pending
+    /// until it's cleared with [Rtc::clear_alarm].
+    pub fn set_alarm(&mut self, unix_time: u32) -> Result<(), Error> {
+        let now = self.unix_time();
+        if unix_time <= now {
+            return Err(Error::AlarmInPast);
+        }
+        let rate = tick_rate_millihertz(drift_ppm());
+        let ticks = self.ticks() + (unix_time - now) as u64 * rate / 1_000;
+
         self.hbn
-            .rtc_time_h
-            .modify(|r, w| unsafe { w.bits(r.bits() | 1 << 31) });
+            .hbn_ctl
+            .modify(|r, w| unsafe { w.rtc_ctl().bits(r.rtc_ctl().bits() & !RTC_CTL_COMP_MASK) });
+        self.hbn
+            .hbn_time_l
+            .write(|w| unsafe { w.bits(ticks as u32) });
+        self.hbn
+            .hbn_time_h
+            .modify(|r, w| unsafe { w.bits(r.bits() & !TIME_H_MASK | (ticks >> 32) as u32) });
+        self.hbn
+            .hbn_ctl
+            .modify(|r, w| unsafe { w.rtc_ctl().bits(r.rtc_ctl().bits() | RTC_CTL_COMP_BIT0_39) });
 
-        let h = self.hbn.rtc_time_h.read().bits();
-        let l = self.hbn.rtc_time_l.read().bits();
-        let ts = (h as u64) << 32 | l as u64; // in counter units
+        unsafe { &*PDS::ptr() }
+            .pds_int
+            .modify(|r, w| unsafe { w.bits(r.bits() | PDS_INT_WAKEUP_SRC_HBN_IRQ_OUT0) });
 
-        // from IOT SDK:
-        // #define BL_RTC_COUNTER_TO_MS(CNT)  (((CNT) >> 5) - ((CNT) >> 11) - ((CNT) >> 12))  // ((CNT)*(1024-16-8)/32768)
-        // see https://github.com/bouffalolab/bl_iot_sdk/blob/90acb7b46d11343d27db9518c4f86d94572c6629/components/hal_drv/bl602_hal/bl_rtc.c
-        ts * (1024 - 16 - 8) / 32768
+        Ok(())
+    }
+
+    /// Returns `true` once the alarm time is reached
+    pub fn is_alarm_pending(&self) -> bool {
+        self.hbn.hbn_irq_stat.read().bits() & HBN_IRQ_RTC != 0
+    }
+
+    /// Disarms the alarm and clears its interrupt
+    pub fn clear_alarm(&mut self) {
+        self.hbn
+            .hbn_ctl
+            .modify(|r, w| unsafe { w.rtc_ctl().bits(r.rtc_ctl().bits() & !RTC_CTL_COMP_MASK) });
+        self.hbn
+            .hbn_irq_clr
+            .write(|w| unsafe { w.bits(HBN_IRQ_RTC) });
+        self.hbn.hbn_irq_clr.write(|w| unsafe { w.bits(0) });
+
+        unsafe { &*PDS::ptr() }
+            .pds_int
+            .modify(|r, w| unsafe { w.bits(r.bits() & !PDS_INT_WAKEUP_SRC_HBN_IRQ_OUT0) });
+    }
+
+    /// The calendar time the counter started at
+    fn epoch(&self) -> u32 {
+        self.hbn.hbn_rsv1.read().bits()
+    }
+
+    /// Seconds counted since the counter started
+    fn elapsed_seconds(&self) -> u32 {
+        (elapsed_micros() / 1_000_000) as u32
+    }
+
+    /// The counter value, in counter units
+    fn ticks(&self) -> u64 {
+        counter_ticks()
     }
 }
 
@@ -55,3 +334,166 @@ impl Clock for Rtc {
         Ok(embedded_time::Instant::new(self.get_millis()))
     }
 }
+
+/// Days since the epoch of a civil date, see http://howardhinnant.github.io/date_algorithms.html
+#[cfg(feature = "rtcc")]
+fn days_from_civil(year: i32, month: u32, day: u32) -> i64 {
+    // The year starts in March, so the leap day is at its end
+    let (year, month) = if month > 2 {
+        (year as i64, month as i64 - 3)
+    } else {
+        (year as i64 - 1, month as i64 + 9)
+    };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * month + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The civil date of days since the epoch, as year, month and day
+#[cfg(feature = "rtcc")]
+fn civil_from_days(days: u32) -> (i32, u32, u32) {
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    // Back from a year starting in March
+    let (year, month) = if month < 10 {
+        (era * 400 + year_of_era, month + 3)
+    } else {
+        (era * 400 + year_of_era + 1, month - 9)
+    };
+    (year as i32, month, day)
+}
+
+#[cfg(feature = "rtcc")]
+impl rtcc::DateTimeAccess for Rtc {
+    type Error = Error;
+
+    fn datetime(&mut self) -> Result<rtcc::NaiveDateTime, Error> {
+        let seconds = self.unix_time();
+        let (year, month, day) = civil_from_days(seconds / 86_400);
+        let time = seconds % 86_400;
+
+        rtcc::NaiveDate::from_ymd_opt(year, month, day)
+            .and_then(|date| date.and_hms_opt(time / 3600, time / 60 % 60, time % 60))
+            .ok_or(Error::InvalidInputData)
+    }
+
+    fn set_datetime(&mut self, datetime: &rtcc::NaiveDateTime) -> Result<(), Error> {
+        let days = days_from_civil(datetime.year(), datetime.month(), datetime.day());
+        let seconds = days * 86_400
+            + (datetime.hour() * 3600 + datetime.minute() * 60 + datetime.second()) as i64;
+        if seconds < 0 || seconds > u32::MAX as i64 {
+            return Err(Error::InvalidInputData);
+        }
+        self.set_unix_time(seconds as u32);
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "rtcc")]
+impl rtcc::Rtcc for Rtc {
+    fn seconds(&mut self) -> Result<u8, Error> {
+        Ok((self.unix_time() % 60) as u8)
+    }
+
+    fn minutes(&mut self) -> Result<u8, Error> {
+        Ok((self.unix_time() / 60 % 60) as u8)
+    }
+
+    fn hours(&mut self) -> Result<rtcc::Hours, Error> {
+        Ok(rtcc::Hours::H24((self.unix_time() / 3600 % 24) as u8))
+    }
+
+    fn time(&mut self) -> Result<rtcc::NaiveTime, Error> {
+        Ok(self.datetime()?.time())
+    }
+
+    /// The day of the week, from 1 for Sunday to 7 for Saturday
+    fn weekday(&mut self) -> Result<u8, Error> {
+        // The epoch was a Thursday
+        Ok(((self.unix_time() / 86_400 + 4) % 7 + 1) as u8)
+    }
+
+    fn day(&mut self) -> Result<u8, Error> {
+        Ok(self.datetime()?.day() as u8)
+    }
+
+    fn month(&mut self) -> Result<u8, Error> {
+        Ok(self.datetime()?.month() as u8)
+    }
+
+    fn year(&mut self) -> Result<u16, Error> {
+        Ok(self.datetime()?.year() as u16)
+    }
+
+    fn date(&mut self) -> Result<rtcc::NaiveDate, Error> {
+        Ok(self.datetime()?.date())
+    }
+
+    fn set_seconds(&mut self, seconds: u8) -> Result<(), Error> {
+        self.modify_datetime(|datetime| datetime.with_second(seconds as u32))
+    }
+
+    fn set_minutes(&mut self, minutes: u8) -> Result<(), Error> {
+        self.modify_datetime(|datetime| datetime.with_minute(minutes as u32))
+    }
+
+    fn set_hours(&mut self, hours: rtcc::Hours) -> Result<(), Error> {
+        let hour = match hours {
+            rtcc::Hours::H24(hour) if hour < 24 => hour,
+            rtcc::Hours::AM(hour) if (1..=12).contains(&hour) => hour % 12,
+            rtcc::Hours::PM(hour) if (1..=12).contains(&hour) => hour % 12 + 12,
+            _ => return Err(Error::InvalidInputData),
+        };
+        self.modify_datetime(|datetime| datetime.with_hour(hour as u32))
+    }
+
+    fn set_time(&mut self, time: &rtcc::NaiveTime) -> Result<(), Error> {
+        self.modify_datetime(|datetime| Some(datetime.date().and_time(*time)))
+    }
+
+    /// The weekday follows from the date, so only the current weekday is accepted
+    fn set_weekday(&mut self, weekday: u8) -> Result<(), Error> {
+        if weekday == rtcc::Rtcc::weekday(self)? {
+            Ok(())
+        } else {
+            Err(Error::InvalidInputData)
+        }
+    }
+
+    fn set_day(&mut self, day: u8) -> Result<(), Error> {
+        self.modify_datetime(|datetime| datetime.with_day(day as u32))
+    }
+
+    fn set_month(&mut self, month: u8) -> Result<(), Error> {
+        self.modify_datetime(|datetime| datetime.with_month(month as u32))
+    }
+
+    fn set_year(&mut self, year: u16) -> Result<(), Error> {
+        self.modify_datetime(|datetime| datetime.with_year(year as i32))
+    }
+
+    fn set_date(&mut self, date: &rtcc::NaiveDate) -> Result<(), Error> {
+        self.modify_datetime(|datetime| Some(date.and_time(datetime.time())))
+    }
+}
+
+#[cfg(feature = "rtcc")]
+impl Rtc {
+    /// Sets the calendar to the date and time `f` derives from the current one
+    fn modify_datetime(
+        &mut self,
+        f: impl FnOnce(rtcc::NaiveDateTime) -> Option<rtcc::NaiveDateTime>,
+    ) -> Result<(), Error> {
+        let datetime = f(self.datetime()?).ok_or(Error::InvalidInputData)?;
+        self.set_datetime(&datetime)
+    }
+}
@@ -2,6 +2,7 @@
 use self::private::Sealed;
 use crate::clock::Clocks;
 use crate::pac;
+use crate::power::suspend::{Resume, SavedRegisters, Suspend};
 use core::fmt;
 use core::ops::Deref;
 use embedded_hal_nb;
@@ -11,6 +12,7 @@ use nb::block;
 
 /// Serial error
 #[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[non_exhaustive]
 pub enum Error {
     /// Framing error
@@ -44,6 +46,21 @@ pub struct Config {
     pub wordlength: WordLength,
 }
 
+#[cfg(feature = "defmt")]
+impl defmt::Format for Config {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "Config {{ baudrate: {=u32}, order: {}, parity: {}, stopbits: {}, wordlength: {} }}",
+            self.baudrate.0,
+            self.order,
+            self.parity,
+            self.stopbits,
+            self.wordlength,
+        )
+    }
+}
+
 impl Config {
     /// Sets the target baudrate
     pub fn baudrate(mut self, baudrate: impl Into<Baud>) -> Self {
@@ -95,6 +112,7 @@ impl Default for Config {
 
 /// Order of the bits transmitted and received on the wire
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Order {
     /// Each byte is sent out LSB-first
     LsbFirst,
@@ -104,6 +122,7 @@ pub enum Order {
 
 /// Parity check
 #[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Parity {
     /// No parity check
     ParityNone,
@@ -115,6 +134,7 @@ pub enum Parity {
 
 /// Stop bits
 #[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum StopBits {
     /// 1 stop bit
     STOP1,
@@ -128,6 +148,7 @@ pub enum StopBits {
 
 /// Word length
 #[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum WordLength {
     Five,
     Six,
@@ -166,6 +187,8 @@ where
     UART: Deref<Target = pac::uart0::RegisterBlock>,
     PINS: Pins<UART>,
 {
+    /// Sets up the UART, dividing the baudrate from `clocks.uart_clk()`. High baudrates like 2
+    /// or 3 Mbaud need a UART clock picked for them, see `Strict::uart_baudrate()`.
     pub fn new(uart: UART, config: Config, pins: PINS, clocks: Clocks) -> Self {
         // Initialize clocks and baudrate
         let uart_clk = clocks.uart_clk();
@@ -265,6 +288,48 @@ where
     }
 }
 
+impl<UART, PINS> Suspend for Serial<UART, PINS>
+where
+    UART: Deref<Target = pac::uart0::RegisterBlock>,
+{
+    type State = SavedRegisters<9>;
+
+    fn suspend(&mut self) -> Self::State {
+        let uart = &self.uart;
+        SavedRegisters([
+            uart.uart_bit_prd.read().bits(),
+            uart.data_config.read().bits(),
+            uart.urx_rto_timer.read().bits(),
+            uart.uart_int_mask.read().bits(),
+            uart.uart_int_en.read().bits(),
+            uart.uart_fifo_config_0.read().bits(),
+            uart.uart_fifo_config_1.read().bits(),
+            uart.utx_config.read().bits(),
+            uart.urx_config.read().bits(),
+        ])
+    }
+}
+
+impl<UART, PINS> Resume for Serial<UART, PINS>
+where
+    UART: Deref<Target = pac::uart0::RegisterBlock>,
+{
+    fn resume(&mut self, state: Self::State) {
+        let uart = &self.uart;
+        let [prd, data, rto, mask, en, fifo0, fifo1, utx, urx] = state.0;
+        uart.uart_bit_prd.write(|w| unsafe { w.bits(prd) });
+        uart.data_config.write(|w| unsafe { w.bits(data) });
+        uart.urx_rto_timer.write(|w| unsafe { w.bits(rto) });
+        uart.uart_int_mask.write(|w| unsafe { w.bits(mask) });
+        uart.uart_int_en.write(|w| unsafe { w.bits(en) });
+        uart.uart_fifo_config_0.write(|w| unsafe { w.bits(fifo0) });
+        uart.uart_fifo_config_1.write(|w| unsafe { w.bits(fifo1) });
+        // The transmitter and receiver are enabled last
+        uart.utx_config.write(|w| unsafe { w.bits(utx) });
+        uart.urx_config.write(|w| unsafe { w.bits(urx) });
+    }
+}
+
 impl<UART, PINS> embedded_hal_nb::serial::ErrorType for Serial<UART, PINS> {
     type Error = Error;
 }
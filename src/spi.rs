@@ -30,9 +30,11 @@ use embedded_time::rate::Hertz;
 use crate::pac;
 
 use crate::clock::Clocks;
+use crate::power::suspend::{Resume, SavedRegisters, Suspend};
 
 /// SPI error
 #[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[non_exhaustive]
 pub enum Error {
     /// Rx overflow occurred
@@ -274,6 +276,41 @@ where
     }
 }
 
+impl<PINS> Suspend for Spi<pac::SPI, PINS> {
+    type State = SavedRegisters<8>;
+
+    fn suspend(&mut self) -> Self::State {
+        let spi = &self.spi;
+        SavedRegisters([
+            spi.spi_prd_0.read().bits(),
+            spi.spi_prd_1.read().bits(),
+            spi.spi_rxd_ignr.read().bits(),
+            spi.spi_sto_value.read().bits(),
+            spi.spi_int_sts.read().bits(),
+            spi.spi_fifo_config_0.read().bits(),
+            spi.spi_fifo_config_1.read().bits(),
+            spi.spi_config.read().bits(),
+        ])
+    }
+}
+
+impl<PINS> Resume for Spi<pac::SPI, PINS> {
+    fn resume(&mut self, state: Self::State) {
+        let spi = &self.spi;
+        let [prd0, prd1, rxd_ignr, sto_value, int_sts, fifo0, fifo1, config] = state.0;
+        spi.spi_prd_0.write(|w| unsafe { w.bits(prd0) });
+        spi.spi_prd_1.write(|w| unsafe { w.bits(prd1) });
+        spi.spi_rxd_ignr.write(|w| unsafe { w.bits(rxd_ignr) });
+        spi.spi_sto_value.write(|w| unsafe { w.bits(sto_value) });
+        // The interrupt masks and enables, the status bits are read only or cleared by writing 1
+        spi.spi_int_sts.write(|w| unsafe { w.bits(int_sts) });
+        spi.spi_fifo_config_0.write(|w| unsafe { w.bits(fifo0) });
+        spi.spi_fifo_config_1.write(|w| unsafe { w.bits(fifo1) });
+        // The master is enabled last
+        spi.spi_config.write(|w| unsafe { w.bits(config) });
+    }
+}
+
 impl<PINS> embedded_hal_nb::spi::ErrorType for Spi<pac::SPI, PINS> {
     type Error = Error;
 }
@@ -24,6 +24,180 @@
 
     ch0.enable(); // start timer
   ```
+
+  The clock source can be the system clock (FCLK), the 32K clock, the 1K clock or the crystal.
+  `set_clock_source()` picks the prescaler for a desired tick rate, while `set_clock_divider()`
+  sets the 8-bit prescaler directly. Durations are converted with the actual tick rate, which
+  `tick_rate()` returns.
+
+  ## Count down example
+  A configured channel counts down a period with `start()`, which `wait()` reports as elapsed
+  once per period. The comparator and preload setup is done by the HAL, which also implements
+  `CountDown`, `Periodic` and `Cancel` from embedded-hal 0.2 this way.
+  ```rust
+    let mut ch1 = timers
+        .channel1
+        .set_clock_source(ClockSource::Clock1Khz, 1_000u32.Hz());
+
+    ch1.start(500u32.milliseconds());
+    loop {
+        nb::block!(ch1.wait()).unwrap();
+        // Runs every 500 ms
+    }
+  ```
+
+  ## Delay example
+  A channel that isn't needed otherwise can provide delays, leaving the `mcycle` counter and the
+  machine timer free for other uses.
+  ```rust
+    use embedded_hal::delay::DelayNs;
+
+    let mut delay = timers
+        .channel0
+        .set_clock_source(ClockSource::Fclk(&clocks), 1_000_000u32.Hz());
+    delay.delay_us(250);
+  ```
+
+  ## Alarm example
+  The three comparators of a channel can serve independent deadlines on one counter.
+  ```rust
+    let Alarms { mut alarm0, mut alarm1, .. } = timers
+        .channel0
+        .set_clock_source(ClockSource::Clock1Khz, 1_000u32.Hz())
+        .into_alarms();
+
+    alarm0.set_after(100u32.milliseconds());
+    alarm1.set_callback(on_timeout);
+    alarm1.set_after(2u32.seconds());
+    enable_interrupt(Interrupt::TimerCh0);
+
+    nb::block!(alarm0.wait()).unwrap();
+  ```
+
+  Without callbacks, the matches are handled in the shared handler. Each comparator is cleared on
+  its own, so the alarms don't acknowledge each other.
+  ```rust
+    #[no_mangle]
+    fn TimerCh0() {
+        for comparator in Channel::Channel0.pending_matches().iter() {
+            Channel::Channel0.clear_match(comparator);
+            // handle the alarm on `comparator`
+        }
+        clear_interrupt(Interrupt::TimerCh0);
+    }
+  ```
+
+  ## Pulse measurement example
+  ```rust
+    let counter = timers
+        .channel1
+        .set_clock_source(ClockSource::Fclk(&clocks), 1_000_000u32.Hz())
+        .into_counter();
+    let tach = PulseMeasurement::new(parts.pin3.into_floating_input(), counter);
+    // Move `tach` into a static Mutex shared with the handler below
+    enable_interrupt(Interrupt::Gpio);
+
+    #[no_mangle]
+    fn Gpio(trap_frame: &mut TrapFrame) {
+        // Borrow `tach` from the static Mutex
+        tach.on_edge();
+    }
+
+    let rpm = tach.frequency().map(|hz| hz.0 * 60 / 2); // Two pulses per revolution
+  ```
+
+  ## 64-bit counter example
+  ```rust
+    let clock = timers
+        .channel0
+        .set_clock_source(ClockSource::Fclk(&clocks), 1_000_000u32.Hz())
+        .into_counter64();
+    enable_interrupt(Interrupt::TimerCh0);
+
+    let start = clock.ticks();
+    // ...
+    let elapsed_us = clock.ticks() - start;
+  ```
+
+  ## Scheduler example
+  Many timeouts can share one channel, with comparator 0 matching the earliest deadline.
+  ```rust
+    let counter = timers
+        .channel1
+        .set_clock_source(ClockSource::Clock1Khz, 1_000u32.Hz())
+        .into_counter();
+    let mut scheduler: Scheduler<8> = Scheduler::new(counter);
+    let blink = scheduler.schedule_periodic(500u32.milliseconds()).unwrap();
+    let timeout = scheduler.schedule_once(3u32.seconds()).unwrap();
+    // Move `scheduler` into a static Mutex shared with the handler below
+    enable_interrupt(Interrupt::TimerCh1);
+
+    #[no_mangle]
+    fn TimerCh1() {
+        scheduler.poll(|id| {
+            // `id` is either `blink` or `timeout`
+        });
+    }
+  ```
+
+  ## System tick example
+  An RTOS or a custom scheduler can drive its tick from a timer channel with one call. The hook
+  is called from the timer interrupt, which is enabled by the HAL.
+  ```rust
+    fn os_tick() {
+        // e.g. advance the kernel tick and pend a context switch
+    }
+
+    let systick = ch1.start_systick(1_000u32.Hz(), os_tick);
+    // ...
+    let uptime_ms = systick.ticks();
+  ```
+
+  ## Trigger example
+  A channel can kick a conversion or an update periodically, for a consistent sample timing.
+  ```rust
+    struct Sample;
+
+    impl Trigger for Sample {
+        fn trigger() {
+            // start the next conversion
+        }
+    }
+
+    ch1.start_trigger::<Sample>(1u32.milliseconds());
+  ```
+
+  ## fugit durations
+  With the `fugit` feature, the functions taking a duration have a `_fugit` variant accepting
+  fugit durations such as `MicrosDurationU32`, as used by RTIC and many drivers.
+  ```rust
+    use fugit::ExtU32;
+
+    ch1.start_fugit(500.millis());
+  ```
+
+  ## Async example
+  With the `async` feature, a channel implements `embedded_hal_async::delay::DelayNs` and a count
+  down can be awaited with `wait_async()`. The tasks are woken from the timer interrupt.
+  ```rust
+    ch1.start(10u32.milliseconds());
+    loop {
+        ch1.wait_async().await;
+        // Runs every 10 ms
+    }
+  ```
+
+  ## Callback example
+  Callbacks are called by the HAL from the timer interrupt, so no `TimerCh0()` or `TimerCh1()`
+  handler has to be written for them.
+  ```rust
+    fn tick(channel: Channel) {
+        // Runs every 10 ms
+    }
+
+    ch1.start_periodic(10u32.milliseconds(), tick);
+    enable_interrupt(Interrupt::TimerCh1);
+  ```
   # Units
   This library uses embedded_time::{duration::*, rate::*} for time units. You can use any supported units as long as they can be cast into Nanoseconds::<u64> for durations, or Hertz for cycles. Time can be cast into other units supported by embedded_time by explicitly typing a variable and calling .into() Note that this will round to the nearest integer in the cast units, potentially losing precision.
 
@@ -35,18 +209,36 @@
   ```
 */
 
-use crate::{clock::Clocks, pac};
+use crate::{
+    clock::Clocks,
+    gpio::{Event, InterruptPin},
+    interrupts::{enable_interrupt, Interrupt},
+    pac,
+    power::NextEvent,
+};
 use bl602_pac::TIMER;
-use core::cell::RefCell;
+use core::cell::Cell;
+#[cfg(feature = "async")]
+use core::{
+    cell::RefCell,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+use critical_section::Mutex;
+use embedded_hal::delay::DelayNs;
 use embedded_time::{duration::*, rate::*};
 use paste::paste;
 use void::Void;
 
 /// Error for [CountDown](embedded_hal::timer::CountDown)
 #[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum CountDownError {
     /// Indicates that the clock wrapped during count down
     Wrapped,
+    /// Indicates that no count down was started
+    NotStarted,
 }
 
 /// Clock sources for a timer channel.
@@ -58,7 +250,10 @@ pub enum ClockSource<'a> {
     Rc32Khz,
     /// 1K clock (32K frequency division)
     Clock1Khz,
+    /// External crystal, which is only powered on when the PLL is used
+    Xtal(&'a Clocks),
     /// 32M clock
+    #[deprecated(note = "The fourth timer clock source is the crystal, use `ClockSource::Xtal`")]
     Pll32Mhz,
 }
 
@@ -68,7 +263,8 @@ impl<'a> ClockSource<'a> {
             ClockSource::Fclk(_) => 0,
             ClockSource::Rc32Khz => 1,
             ClockSource::Clock1Khz => 2,
-            ClockSource::Pll32Mhz => 3,
+            #[allow(deprecated)]
+            ClockSource::Xtal(_) | ClockSource::Pll32Mhz => 3,
         }
     }
 
@@ -77,6 +273,11 @@ impl<'a> ClockSource<'a> {
             ClockSource::Fclk(clocks) => clocks.sysclk(),
             ClockSource::Rc32Khz => 32_000.Hz(),
             ClockSource::Clock1Khz => 1_000.Hz(),
+            ClockSource::Xtal(clocks) => match clocks.xtal_freq() {
+                Some(freq) => freq,
+                None => panic!("XTAL is not powered on"),
+            },
+            #[allow(deprecated)]
             ClockSource::Pll32Mhz => 32_000_000.Hz(),
         }
     }
@@ -105,6 +306,830 @@ impl Preload {
     }
 }
 
+/// Durations of the [fugit](https://docs.rs/fugit) crate, accepted by the `_fugit` variants of
+/// the timer functions that take a duration
+#[cfg(feature = "fugit")]
+pub trait FugitDuration {
+    /// The duration in nanoseconds
+    fn to_nanoseconds(self) -> Nanoseconds<u64>;
+}
+
+#[cfg(feature = "fugit")]
+impl<const NOM: u32, const DENOM: u32> FugitDuration for fugit::Duration<u32, NOM, DENOM> {
+    fn to_nanoseconds(self) -> Nanoseconds<u64> {
+        let nanos = self.ticks() as u64 * NOM as u64 * 1_000_000_000_u64 / DENOM as u64;
+        Nanoseconds::<u64>::new(nanos)
+    }
+}
+
+#[cfg(feature = "fugit")]
+impl<const NOM: u32, const DENOM: u32> FugitDuration for fugit::Duration<u64, NOM, DENOM> {
+    fn to_nanoseconds(self) -> Nanoseconds<u64> {
+        let nanos = self.ticks() as u128 * NOM as u128 * 1_000_000_000_u128 / DENOM as u128;
+        Nanoseconds::<u64>::new(nanos as u64)
+    }
+}
+
+/// Timer channel identifier, passed to callbacks
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Channel {
+    Channel0,
+    Channel1,
+}
+
+/// A set of comparators of a timer channel, e.g. the ones with a pending match
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Matches(u8);
+
+impl Matches {
+    /// Check if `comparator` (0, 1 or 2) is in the set
+    pub fn contains(&self, comparator: usize) -> bool {
+        comparator < 3 && self.0 & (1 << comparator) != 0
+    }
+
+    /// Check if the set is empty
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// The comparators in the set, in ascending order
+    pub fn iter(&self) -> impl Iterator<Item = usize> {
+        let bits = self.0;
+        (0..3).filter(move |comparator| bits & (1 << comparator) != 0)
+    }
+}
+
+/// A callback registered on a comparator of a channel
+#[derive(Copy, Clone)]
+struct Callback {
+    func: fn(Channel),
+    /// Disable the comparator interrupt and remove the callback after the first match
+    one_shot: bool,
+    /// Stop the counter after the first match
+    stop: bool,
+}
+
+/// Comparator callbacks, indexed by channel and comparator
+static CALLBACKS: Mutex<Cell<[[Option<Callback>; 3]; 2]>> = Mutex::new(Cell::new([[None; 3]; 2]));
+
+/// Calls the associated function `$func` of the configured channel type belonging to `$channel`
+macro_rules! with_channel {
+    ($channel: expr, $func: ident $(, $arg: expr)*) => {
+        match $channel {
+            Channel::Channel0 => ConfiguredTimerChannel0::$func($($arg),*),
+            Channel::Channel1 => ConfiguredTimerChannel1::$func($($arg),*),
+        }
+    };
+}
+
+impl Channel {
+    fn index(self) -> usize {
+        match self {
+            Channel::Channel0 => 0,
+            Channel::Channel1 => 1,
+        }
+    }
+
+    fn set_callback(self, comparator: usize, callback: Option<Callback>) {
+        critical_section::with(|cs| {
+            let cell = CALLBACKS.borrow(cs);
+            let mut callbacks = cell.get();
+            callbacks[self.index()][comparator] = callback;
+            cell.set(callbacks);
+        });
+    }
+
+    /// The comparators with a pending match. Only comparators with their interrupt enabled raise
+    /// a match. This doesn't need the channel, so it can be used in the `TimerCh0` or `TimerCh1`
+    /// handler while the comparators are owned by e.g. [Alarms].
+    pub fn pending_matches(self) -> Matches {
+        Matches(with_channel!(self, read_match_status) as u8)
+    }
+
+    /// Check if `comparator` (0, 1 or 2) has a pending match
+    pub fn is_match_pending(self, comparator: usize) -> bool {
+        self.pending_matches().contains(comparator)
+    }
+
+    /// Acknowledges the match of `comparator` (0, 1 or 2), leaving the matches of the other
+    /// comparators pending.
+    pub fn clear_match(self, comparator: usize) {
+        if comparator > 2 {
+            panic!("Invalid comparator");
+        }
+        with_channel!(self, write_match_clear, 1 << comparator);
+    }
+
+    fn interrupt(self) -> Interrupt {
+        match self {
+            Channel::Channel0 => Interrupt::TimerCh0,
+            Channel::Channel1 => Interrupt::TimerCh1,
+        }
+    }
+}
+
+/// Wakers of the tasks waiting for comparator 0, indexed by channel
+#[cfg(feature = "async")]
+static WAKERS: Mutex<RefCell<[Option<Waker>; 2]>> = Mutex::new(RefCell::new([None, None]));
+
+/// Comparator 0 matches that haven't been seen by a waiting task yet, indexed by channel
+#[cfg(feature = "async")]
+static FIRED: Mutex<Cell<[bool; 2]>> = Mutex::new(Cell::new([false; 2]));
+
+/// Comparator 0 callback waking the task waiting for the channel
+#[cfg(feature = "async")]
+fn wake_task(channel: Channel) {
+    critical_section::with(|cs| {
+        let cell = FIRED.borrow(cs);
+        let mut fired = cell.get();
+        fired[channel.index()] = true;
+        cell.set(fired);
+
+        if let Some(waker) = WAKERS.borrow(cs).borrow_mut()[channel.index()].take() {
+            waker.wake();
+        }
+    });
+}
+
+/// Future completing on the next comparator 0 match of a channel
+#[cfg(feature = "async")]
+struct MatchFuture {
+    channel: Channel,
+    one_shot: bool,
+}
+
+#[cfg(feature = "async")]
+impl MatchFuture {
+    fn new(channel: Channel, one_shot: bool) -> Self {
+        critical_section::with(|cs| {
+            channel.set_callback(
+                0,
+                Some(Callback {
+                    func: wake_task,
+                    one_shot,
+                    stop: false,
+                }),
+            );
+
+            // A one-shot wait must not complete on a match left over from an earlier wait. The
+            // caller clears the match status before arming the comparator, so a pending match
+            // was raised after arming, before the callback was registered.
+            let cell = FIRED.borrow(cs);
+            let mut fired = cell.get();
+            if one_shot {
+                fired[channel.index()] = false;
+            }
+            if with_channel!(channel, read_match_status) & 0b001 != 0 {
+                with_channel!(channel, write_match_clear, 0b001);
+                if one_shot {
+                    channel.set_callback(0, None);
+                }
+                fired[channel.index()] = true;
+            }
+            cell.set(fired);
+        });
+
+        enable_interrupt(channel.interrupt());
+
+        MatchFuture { channel, one_shot }
+    }
+}
+
+#[cfg(feature = "async")]
+impl Future for MatchFuture {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let index = self.channel.index();
+        critical_section::with(|cs| {
+            let cell = FIRED.borrow(cs);
+            let mut fired = cell.get();
+            if fired[index] {
+                fired[index] = false;
+                cell.set(fired);
+                return Poll::Ready(());
+            }
+
+            WAKERS.borrow(cs).borrow_mut()[index] = Some(cx.waker().clone());
+            Poll::Pending
+        })
+    }
+}
+
+#[cfg(feature = "async")]
+impl Drop for MatchFuture {
+    fn drop(&mut self) {
+        if self.one_shot {
+            with_channel!(self.channel, write_match_interrupt, 0, false);
+            self.channel.set_callback(0, None);
+        }
+        critical_section::with(|cs| {
+            WAKERS.borrow(cs).borrow_mut()[self.channel.index()] = None;
+        });
+    }
+}
+
+/// Handles the comparator matches of `channel` that have a callback registered.
+/// Returns `false` if any match is left for the user's `TimerCh0` or `TimerCh1` handler.
+pub(crate) fn dispatch_interrupt(channel: Channel) -> bool {
+    let callbacks = critical_section::with(|cs| CALLBACKS.borrow(cs).get()[channel.index()]);
+    if callbacks.iter().all(|callback| callback.is_none()) {
+        return false;
+    }
+
+    let status = with_channel!(channel, read_match_status);
+    let mut handled = true;
+
+    for (comparator, callback) in callbacks.iter().enumerate() {
+        let mask = 1 << comparator;
+        if status & mask == 0 {
+            continue;
+        }
+
+        match callback {
+            Some(callback) => {
+                with_channel!(channel, write_match_clear, mask);
+                if callback.one_shot {
+                    with_channel!(channel, write_match_interrupt, comparator, false);
+                    channel.set_callback(comparator, None);
+                }
+                if callback.stop {
+                    with_channel!(channel, stop);
+                }
+                (callback.func)(channel);
+            }
+            None => handled = false,
+        }
+    }
+
+    handled
+}
+
+macro_rules! impl_alarm {
+    ($name: ident, $comparator: literal) => {
+        paste! {
+            #[doc = "Comparator " $comparator " of a timer channel, used as an independent alarm"]
+            pub struct $name {
+                channel: Channel,
+                clock: Hertz,
+            }
+        }
+
+        impl $name {
+            /// Fires the alarm `timeout` from now. This clears an earlier expiry.
+            pub fn set_after(&mut self, timeout: impl Into<Nanoseconds<u64>>) {
+                let timeout: Nanoseconds<u64> = timeout.into();
+                let ticks = self.clock.0 as u64 * timeout.integer() / 1_000_000_000_u64;
+                if ticks > u32::MAX as u64 {
+                    panic!("Unreachable timeout");
+                }
+
+                let now = with_channel!(self.channel, read_ticks);
+                self.set_at_ticks(now.wrapping_add((ticks as u32).max(1)));
+            }
+
+            /// Like `set_after()`, with a fugit duration.
+            #[cfg(feature = "fugit")]
+            pub fn set_after_fugit(&mut self, timeout: impl FugitDuration) {
+                self.set_after(timeout.to_nanoseconds());
+            }
+
+            /// Fires the alarm when the counter reaches `ticks`. This clears an earlier expiry.
+            pub fn set_at_ticks(&mut self, ticks: u32) {
+                with_channel!(self.channel, write_match, $comparator, ticks);
+                // The match status is only raised for enabled comparator interrupts
+                with_channel!(self.channel, write_match_interrupt, $comparator, true);
+                self.clear();
+            }
+
+            /// Check if the alarm has fired since it was set or cleared
+            pub fn is_expired(&self) -> bool {
+                with_channel!(self.channel, read_match_status) & (1 << $comparator) != 0
+            }
+
+            /// Clears the expiry of the alarm, which acknowledges its interrupt.
+            pub fn clear(&mut self) {
+                with_channel!(self.channel, write_match_clear, 1 << $comparator);
+            }
+
+            /// Returns `Ok` once the alarm has fired, and clears it.
+            pub fn wait(&mut self) -> nb::Result<(), Void> {
+                if self.is_expired() {
+                    self.clear();
+                    Ok(())
+                } else {
+                    Err(nb::Error::WouldBlock)
+                }
+            }
+
+            /// Disarms the alarm and removes its callback.
+            pub fn cancel(&mut self) {
+                with_channel!(self.channel, write_match_interrupt, $comparator, false);
+                self.clear();
+                self.channel.set_callback($comparator, None);
+            }
+
+            /// Registers `callback` to be called once from the timer interrupt when the alarm
+            /// fires, after which the alarm is disarmed. Register the callback before setting the
+            /// alarm. The `TimerCh0` or `TimerCh1` interrupt has to be enabled with
+            /// `enable_interrupt()`.
+            pub fn set_callback(&mut self, callback: fn(Channel)) {
+                self.channel.set_callback(
+                    $comparator,
+                    Some(Callback {
+                        func: callback,
+                        one_shot: true,
+                        stop: false,
+                    }),
+                );
+            }
+
+            /// The runtime identifier of the timer channel
+            pub fn channel(&self) -> Channel {
+                self.channel
+            }
+        }
+    };
+}
+
+impl_alarm!(Alarm0, 0);
+impl_alarm!(Alarm1, 1);
+impl_alarm!(Alarm2, 2);
+
+/// The three comparators of a timer channel, sharing its free running counter
+pub struct Alarms {
+    pub alarm0: Alarm0,
+    pub alarm1: Alarm1,
+    pub alarm2: Alarm2,
+}
+
+/// The free running counter of a timer channel, used as a timestamp source
+#[derive(Copy, Clone)]
+pub struct Counter {
+    channel: Channel,
+    clock: Hertz,
+}
+
+impl Counter {
+    /// Current counter value in raw ticks.
+    pub fn ticks(&self) -> u32 {
+        with_channel!(self.channel, read_ticks)
+    }
+
+    /// The counter tick rate, from the clock source and prescaler.
+    pub fn tick_rate(&self) -> Hertz {
+        self.clock
+    }
+
+    /// Converts a number of ticks to nanoseconds.
+    pub fn to_duration(&self, ticks: u64) -> Nanoseconds<u64> {
+        Nanoseconds::<u64>::new(ticks * 1_000_000_000_u64 / self.clock.0 as u64)
+    }
+
+    /// The runtime identifier of the timer channel
+    pub fn channel(&self) -> Channel {
+        self.channel
+    }
+}
+
+/// High words of the 64-bit counters, indexed by channel
+static HIGH_WORDS: Mutex<Cell<[u32; 2]>> = Mutex::new(Cell::new([0; 2]));
+
+/// Comparator 2 callback counting the wraps of a 64-bit counter
+fn count_wrap(channel: Channel) {
+    critical_section::with(|cs| {
+        let cell = HIGH_WORDS.borrow(cs);
+        let mut high_words = cell.get();
+        high_words[channel.index()] = high_words[channel.index()].wrapping_add(1);
+        cell.set(high_words);
+    });
+}
+
+/// The free running counter of a timer channel, extended to 64 bits in software.
+///
+/// Comparator 2 matches at the end of the counter range and the HAL counts the wraps from the
+/// timer interrupt, so the `TimerCh0` or `TimerCh1` interrupt has to be enabled with
+/// `enable_interrupt()`. With a 1 MHz tick rate the counter wraps after about 71 minutes, while the
+/// 64-bit value lasts for more than 500000 years.
+#[derive(Copy, Clone)]
+pub struct Counter64 {
+    counter: Counter,
+}
+
+impl Counter64 {
+    /// Current counter value in raw ticks.
+    pub fn ticks(&self) -> u64 {
+        let channel = self.counter.channel;
+        critical_section::with(|cs| {
+            let high = HIGH_WORDS.borrow(cs).get()[channel.index()];
+            let low = self.counter.ticks();
+
+            // The wrap is counted at the match on `u32::MAX`, one tick before the counter wraps.
+            // Interrupts are disabled, so it may not have been counted yet after the wrap, or it
+            // may have been counted already while the counter still reads `u32::MAX`.
+            let wrap_pending = with_channel!(channel, read_match_status) & 0b100 != 0;
+            let high = if wrap_pending && low < u32::MAX / 2 {
+                high.wrapping_add(1)
+            } else if !wrap_pending && low == u32::MAX {
+                high.wrapping_sub(1)
+            } else {
+                high
+            };
+
+            (high as u64) << 32 | low as u64
+        })
+    }
+
+    /// Current counter value in nanoseconds.
+    pub fn now(&self) -> Nanoseconds<u64> {
+        let nanos = self.ticks() as u128 * 1_000_000_000_u128 / self.counter.clock.0 as u128;
+        Nanoseconds::<u64>::new(nanos as u64)
+    }
+
+    /// The counter tick rate, from the clock source and prescaler.
+    pub fn tick_rate(&self) -> Hertz {
+        self.counter.clock
+    }
+
+    /// The 32-bit counter, e.g. for a [PulseMeasurement].
+    pub fn counter(&self) -> Counter {
+        self.counter
+    }
+}
+
+/// Measures the pulse width, period and frequency of a signal on an input pin, e.g. a fan
+/// tachometer, by timestamping its edges with a [Counter].
+///
+/// A pin can only trigger on one edge at a time, so the trigger is swapped between the rising and
+/// the falling edge in `on_edge()`, which has to be called from the `Gpio` interrupt handler.
+///
+/// The timestamps are compared with wrapping arithmetic, so intervals up to half of the counter
+/// range are measured correctly. Results are dropped once the last edge is older than that, so a
+/// stopped signal doesn't report stale values after the counter wrapped.
+pub struct PulseMeasurement<PIN> {
+    pin: PIN,
+    counter: Counter,
+    rising_edge: Option<u32>,
+    last_edge: Option<u32>,
+    high_ticks: Option<u32>,
+    period_ticks: Option<u32>,
+    awaiting_rising_edge: bool,
+}
+
+impl<PIN: InterruptPin> PulseMeasurement<PIN> {
+    /// Starts measuring on `pin`, which has its interrupt enabled on the rising edge. The `Gpio`
+    /// interrupt has to be enabled with `enable_interrupt(Interrupt::Gpio)`.
+    pub fn new(mut pin: PIN, counter: Counter) -> Self {
+        pin.trigger_on_event(Event::PositivePulse);
+        pin.clear_interrupt_pending_bit();
+        pin.enable_interrupt();
+
+        PulseMeasurement {
+            pin,
+            counter,
+            rising_edge: None,
+            last_edge: None,
+            high_ticks: None,
+            period_ticks: None,
+            awaiting_rising_edge: true,
+        }
+    }
+
+    /// Timestamps a pending edge of the pin, if any. This has to be called from the `Gpio`
+    /// interrupt handler, and returns true if the interrupt was raised by this pin.
+    pub fn on_edge(&mut self) -> bool {
+        if !self.pin.check_interrupt() {
+            return false;
+        }
+
+        let now = self.counter.ticks();
+
+        if self.awaiting_rising_edge {
+            if let Some(previous) = self.rising_edge {
+                self.period_ticks = Some(now.wrapping_sub(previous));
+            }
+            self.rising_edge = Some(now);
+            self.pin.trigger_on_event(Event::NegativePulse);
+        } else {
+            if let Some(rising) = self.rising_edge {
+                self.high_ticks = Some(now.wrapping_sub(rising));
+            }
+            self.pin.trigger_on_event(Event::PositivePulse);
+        }
+
+        self.awaiting_rising_edge = !self.awaiting_rising_edge;
+        self.last_edge = Some(now);
+        self.pin.clear_interrupt_pending_bit();
+        true
+    }
+
+    /// The time the signal was high during the last complete pulse.
+    pub fn pulse_width(&self) -> Option<Nanoseconds<u64>> {
+        self.valid(self.high_ticks)
+            .map(|ticks| self.counter.to_duration(ticks as u64))
+    }
+
+    /// The time between the last two rising edges.
+    pub fn period(&self) -> Option<Nanoseconds<u64>> {
+        self.valid(self.period_ticks)
+            .map(|ticks| self.counter.to_duration(ticks as u64))
+    }
+
+    /// The frequency of the signal, from the last period.
+    pub fn frequency(&self) -> Option<Hertz> {
+        self.valid(self.period_ticks)
+            .filter(|ticks| *ticks != 0)
+            .map(|ticks| Hertz(self.counter.tick_rate().0 / ticks))
+    }
+
+    /// Forgets all measurements, e.g. after the signal source was switched.
+    pub fn reset(&mut self) {
+        self.pin.trigger_on_event(Event::PositivePulse);
+        self.rising_edge = None;
+        self.last_edge = None;
+        self.high_ticks = None;
+        self.period_ticks = None;
+        self.awaiting_rising_edge = true;
+    }
+
+    /// Disables the pin interrupt and returns the pin.
+    pub fn free(mut self) -> PIN {
+        self.pin.disable_interrupt();
+        self.pin
+    }
+
+    fn valid(&self, ticks: Option<u32>) -> Option<u32> {
+        let last_edge = self.last_edge?;
+        if self.counter.ticks().wrapping_sub(last_edge) > u32::MAX / 2 {
+            return None;
+        }
+        ticks
+    }
+}
+
+/// Identifies a software timer of a [Scheduler]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TimerId(usize);
+
+impl TimerId {
+    /// The slot of the timer in its scheduler, below the scheduler capacity
+    pub fn index(&self) -> usize {
+        self.0
+    }
+}
+
+#[derive(Copy, Clone)]
+struct SoftTimer {
+    deadline: u32,
+    period: Option<u32>,
+}
+
+/// Check if the counter at `now` has reached `deadline`, with deadlines up to half of the counter
+/// range in the future
+fn is_due(deadline: u32, now: u32) -> bool {
+    now.wrapping_sub(deadline) <= u32::MAX / 2
+}
+
+/// Up to `N` one-shot and periodic software timers, multiplexed over comparator 0 of the
+/// [Counter] of one timer channel.
+///
+/// Comparator 0 is always set to the earliest deadline, and the expired timers are handled in
+/// `poll()`. This is meant to be called from the `TimerCh0` or `TimerCh1` interrupt handler, but
+/// may also be called from a main loop, in which case the interrupt may stay disabled.
+///
+/// Deadlines can be at most half of the counter range in the future, and `poll()` has to be called
+/// before an expired timer is overdue by that much.
+pub struct Scheduler<const N: usize> {
+    counter: Counter,
+    timers: [Option<SoftTimer>; N],
+}
+
+impl<const N: usize> Scheduler<N> {
+    /// Takes over comparator 0 of the channel of `counter`, with no timers scheduled.
+    pub fn new(counter: Counter) -> Self {
+        let channel = counter.channel();
+        channel.set_callback(0, None);
+        with_channel!(channel, write_match_interrupt, 0, false);
+        with_channel!(channel, write_match_clear, 0b001);
+
+        Scheduler {
+            counter,
+            timers: [None; N],
+        }
+    }
+
+    /// Starts a timer expiring once, `timeout` from now.
+    /// Returns `None` if all `N` timers are in use.
+    pub fn schedule_once(&mut self, timeout: impl Into<Nanoseconds<u64>>) -> Option<TimerId> {
+        let ticks = self.to_ticks(timeout.into());
+        self.schedule(ticks, None)
+    }
+
+    /// Starts a timer expiring every `period`, the first time `period` from now.
+    /// Returns `None` if all `N` timers are in use.
+    pub fn schedule_periodic(&mut self, period: impl Into<Nanoseconds<u64>>) -> Option<TimerId> {
+        let ticks = self.to_ticks(period.into());
+        self.schedule(ticks, Some(ticks))
+    }
+
+    /// Stops a timer, which frees its slot. Cancelling an expired one-shot timer has no effect.
+    pub fn cancel(&mut self, id: TimerId) {
+        if let Some(slot) = self.timers.get_mut(id.0) {
+            *slot = None;
+        }
+        self.reprogram();
+    }
+
+    /// Check if a timer is still running, i.e. a one-shot timer that didn't expire yet or a
+    /// periodic timer that wasn't cancelled.
+    pub fn is_scheduled(&self, id: TimerId) -> bool {
+        matches!(self.timers.get(id.0), Some(Some(_)))
+    }
+
+    /// Calls `on_expired` for every timer that has expired, restarts the periodic ones and sets
+    /// comparator 0 to the next deadline. This acknowledges the comparator 0 interrupt.
+    pub fn poll(&mut self, mut on_expired: impl FnMut(TimerId)) {
+        with_channel!(self.counter.channel(), write_match_clear, 0b001);
+
+        let now = self.counter.ticks();
+        for (index, slot) in self.timers.iter_mut().enumerate() {
+            let timer = match *slot {
+                Some(timer) if is_due(timer.deadline, now) => timer,
+                _ => continue,
+            };
+
+            // Periodic timers keep their phase, even if the interrupt was handled late
+            *slot = timer.period.map(|period| SoftTimer {
+                deadline: timer.deadline.wrapping_add(period),
+                period: Some(period),
+            });
+            on_expired(TimerId(index));
+        }
+
+        self.reprogram();
+    }
+
+    /// Cancels all timers and releases comparator 0, returning the counter.
+    pub fn free(self) -> Counter {
+        let channel = self.counter.channel();
+        with_channel!(channel, write_match_interrupt, 0, false);
+        with_channel!(channel, write_match_clear, 0b001);
+        self.counter
+    }
+
+    fn schedule(&mut self, ticks: u32, period: Option<u32>) -> Option<TimerId> {
+        let index = self.timers.iter().position(|slot| slot.is_none())?;
+        self.timers[index] = Some(SoftTimer {
+            deadline: self.counter.ticks().wrapping_add(ticks),
+            period,
+        });
+        self.reprogram();
+        Some(TimerId(index))
+    }
+
+    /// The earliest deadline, the one with the least time left, where overdue timers have none
+    /// left
+    fn earliest(&self, now: u32) -> Option<u32> {
+        self.timers
+            .iter()
+            .flatten()
+            .map(|timer| timer.deadline)
+            .min_by_key(|deadline| {
+                if is_due(*deadline, now) {
+                    0
+                } else {
+                    deadline.wrapping_sub(now)
+                }
+            })
+    }
+
+    fn to_ticks(&self, duration: Nanoseconds<u64>) -> u32 {
+        let ticks = self.counter.tick_rate().0 as u64 * duration.integer() / 1_000_000_000_u64;
+        if ticks > (u32::MAX / 2) as u64 {
+            panic!("Unreachable timeout");
+        }
+        (ticks as u32).max(1)
+    }
+
+    /// Sets comparator 0 to the earliest deadline, or disables its interrupt if no timer is left.
+    fn reprogram(&mut self) {
+        let channel = self.counter.channel();
+
+        let deadline = match self.earliest(self.counter.ticks()) {
+            Some(deadline) => deadline,
+            None => {
+                with_channel!(channel, write_match_interrupt, 0, false);
+                return;
+            }
+        };
+
+        with_channel!(channel, write_match_interrupt, 0, true);
+
+        // A deadline that passed before the comparator was written would never match, so those
+        // are moved just ahead of the counter, further each time the counter overtakes it
+        let mut lead = 1_u32;
+        loop {
+            let now = self.counter.ticks();
+            let target = if is_due(deadline, now) {
+                now.wrapping_add(lead)
+            } else {
+                deadline
+            };
+            with_channel!(channel, write_match, 0, target);
+
+            let matched = with_channel!(channel, read_match_status) & 0b001 != 0;
+            if matched || !is_due(target, self.counter.ticks()) {
+                break;
+            }
+            lead = lead.saturating_mul(2);
+        }
+    }
+}
+
+impl<const N: usize> NextEvent for Scheduler<N> {
+    fn time_until_next_event(&self) -> Option<Nanoseconds<u64>> {
+        let now = self.counter.ticks();
+        let deadline = self.earliest(now)?;
+        let ticks = if is_due(deadline, now) {
+            0
+        } else {
+            deadline.wrapping_sub(now)
+        };
+        Some(Nanoseconds(
+            ticks as u64 * 1_000_000_000 / self.counter.tick_rate().0 as u64,
+        ))
+    }
+}
+
+/// Number of system ticks since `start_systick()`, indexed by channel
+static TICK_COUNTS: Mutex<Cell<[u64; 2]>> = Mutex::new(Cell::new([0; 2]));
+
+/// System tick hooks, indexed by channel
+static TICK_HOOKS: Mutex<Cell<[Option<fn()>; 2]>> = Mutex::new(Cell::new([None; 2]));
+
+/// Comparator 0 callback counting the system ticks and calling the hook
+fn system_tick(channel: Channel) {
+    let hook = critical_section::with(|cs| {
+        let cell = TICK_COUNTS.borrow(cs);
+        let mut counts = cell.get();
+        counts[channel.index()] = counts[channel.index()].wrapping_add(1);
+        cell.set(counts);
+
+        TICK_HOOKS.borrow(cs).get()[channel.index()]
+    });
+
+    if let Some(hook) = hook {
+        hook();
+    }
+}
+
+/// A periodic system tick for an RTOS or a custom scheduler, started with `start_systick()`.
+///
+/// This is a handle to read the tick count, the tick is stopped with `cancel()` on the channel.
+#[derive(Copy, Clone)]
+pub struct SysTick {
+    channel: Channel,
+    rate: Hertz,
+}
+
+impl SysTick {
+    /// Number of ticks since the tick was started
+    pub fn ticks(&self) -> u64 {
+        critical_section::with(|cs| TICK_COUNTS.borrow(cs).get()[self.channel.index()])
+    }
+
+    /// The configured tick rate
+    pub fn rate(&self) -> Hertz {
+        self.rate
+    }
+
+    /// Time since the tick was started, with the resolution of one tick
+    pub fn uptime(&self) -> Nanoseconds<u64> {
+        Nanoseconds::<u64>::new(
+            (self.ticks() as u128 * 1_000_000_000_u128 / self.rate.0 as u128) as u64,
+        )
+    }
+
+    /// The runtime identifier of the timer channel
+    pub fn channel(&self) -> Channel {
+        self.channel
+    }
+}
+
+/// A conversion or update kicked periodically from a timer channel, e.g. an ADC conversion or a
+/// DAC update, see `start_trigger()`.
+pub trait Trigger {
+    /// Starts the next conversion or update. This is called from the timer interrupt, right after
+    /// the comparator 0 match, so it should only kick the peripheral and return.
+    fn trigger();
+}
+
+/// Comparator 0 callback kicking the trigger `T`
+fn kick<T: Trigger>(_channel: Channel) {
+    T::trigger();
+}
+
 /// Timer Channel 0
 pub struct TimerChannel0 {}
 
@@ -122,14 +1147,11 @@ pub struct Timers {
 }
 
 macro_rules! impl_timer_channel {
-    ($name: ident, $conf_name: ident, $channel: literal, $channel_cs: literal) => {
+    ($name: ident, $conf_name: ident, $variant: ident, $channel: literal, $channel_cs: literal) => {
 
         /// A configured timer channel ready to use.
         pub struct $conf_name {
             clock: Hertz,
-            count_down_target: Option<Nanoseconds::<u64>>,
-            last_count_down_value: Option<Nanoseconds::<u64>>,
-            is_running: RefCell<bool>,
         }
 
         paste! {
@@ -174,19 +1196,27 @@ macro_rules! impl_timer_channel {
                 pub fn enable(&self) {
                     let timer = unsafe { &*pac::TIMER::ptr() };
                     timer.tcer.modify(|_r, w| w.[<timer $channel _en>]().set_bit());
-                    self.is_running.replace(true);
                 }
 
                 /// Disable this counter
                 pub fn disable(&self) {
-                    let timer = unsafe { &*pac::TIMER::ptr() };
-                    timer.tcer.modify(|_r, w| w.[<timer $channel _en>]().set_bit());
-                    self.is_running.replace(false);
+                    Self::stop();
                 }
 
                 /// Check if the timer is enabled / running
                 pub fn is_enabled(&self) -> bool {
-                    *self.is_running.borrow()
+                    let timer = unsafe { &*pac::TIMER::ptr() };
+                    timer.tcer.read().[<timer $channel _en>]().bit_is_set()
+                }
+
+                /// The runtime identifier of this channel
+                pub fn channel(&self) -> Channel {
+                    Channel::$variant
+                }
+
+                /// The counter tick rate, from the clock source and prescaler.
+                pub fn tick_rate(&self) -> Hertz {
+                    self.clock
                 }
 
                 /// Clear interrupt for match register 0.
@@ -261,7 +1291,7 @@ macro_rules! impl_timer_channel {
                     timer.[<tmsr $channel>].read().tmsr_0().bit()
                 }
 
-                /// Will only become true if `enable_match2_interrupt` is active
+                /// Will only become true if `enable_match1_interrupt` is active
                 pub fn is_match1(&self) -> bool {
                     let timer = unsafe { &*pac::TIMER::ptr() };
                     timer.[<tmsr $channel>].read().tmsr_1().bit()
@@ -273,6 +1303,16 @@ macro_rules! impl_timer_channel {
                     timer.[<tmsr $channel>].read().tmsr_2().bit()
                 }
 
+                /// The comparators with a pending match, see [Channel::pending_matches].
+                pub fn pending_matches(&self) -> Matches {
+                    Channel::$variant.pending_matches()
+                }
+
+                /// Acknowledges the match of `comparator` only, see [Channel::clear_match].
+                pub fn clear_match(&self, comparator: usize) {
+                    Channel::$variant.clear_match(comparator)
+                }
+
                 /// Set pre-load mode.
                 pub fn pre_load_mode(&self) {
                     let timer = unsafe { &*pac::TIMER::ptr() };
@@ -292,6 +1332,329 @@ macro_rules! impl_timer_channel {
                     let timer = unsafe { &*pac::TIMER::ptr() };
                     timer.[<tplvr $channel>].modify(|_r, w| unsafe { w.bits(time) });
                 }
+
+                /// Starts counting down `timeout`, after which `wait()` returns `Ok` once.
+                /// The counter is reloaded by comparator 0, so the count down restarts by itself
+                /// and `wait()` returns `Ok` once every `timeout`.
+                ///
+                /// This uses comparator 0 and the preload of the channel.
+                pub fn start(&mut self, timeout: impl Into<Nanoseconds::<u64>>) {
+                    let timeout: Nanoseconds::<u64> = timeout.into();
+                    let ticks = self.clock.0 as u64 * timeout.integer() / 1_000_000_000_u64;
+                    if ticks > u32::MAX as u64 {
+                        panic!("Unreachable timeout");
+                    }
+
+                    let timer = unsafe { &*pac::TIMER::ptr() };
+                    self.disable();
+                    self.pre_load_mode();
+                    timer.[<tplvr $channel>].write(|w| unsafe { w.tplvr().bits(0) });
+                    timer
+                        .[<tmr $channel _0>]
+                        .modify(|_r, w| unsafe { w.tmr().bits((ticks as u32).max(1)) });
+                    self.set_preload(Preload::PreloadMatchComparator0);
+
+                    // The match status is only raised for enabled comparator interrupts
+                    self.enable_match0_interrupt();
+                    self.clear_match0_interrupt();
+                    self.enable();
+                }
+
+                /// Like `start()`, with a fugit duration.
+                #[cfg(feature = "fugit")]
+                pub fn start_fugit(&mut self, timeout: impl FugitDuration) {
+                    self.start(timeout.to_nanoseconds());
+                }
+
+                /// Like `start_periodic()`, with a fugit duration.
+                #[cfg(feature = "fugit")]
+                pub fn start_periodic_fugit(
+                    &mut self,
+                    period: impl FugitDuration,
+                    callback: fn(Channel),
+                ) {
+                    self.start_periodic(period.to_nanoseconds(), callback);
+                }
+
+                /// Like `start_one_shot()`, with a fugit duration.
+                #[cfg(feature = "fugit")]
+                pub fn start_one_shot_fugit(
+                    &mut self,
+                    timeout: impl FugitDuration,
+                    callback: fn(Channel),
+                ) {
+                    self.start_one_shot(timeout.to_nanoseconds(), callback);
+                }
+
+                /// Like `set_preload_value()`, with a fugit duration.
+                #[cfg(feature = "fugit")]
+                pub fn set_preload_value_fugit(&self, time: impl FugitDuration) {
+                    self.set_preload_value(time.to_nanoseconds());
+                }
+
+                /// Returns `Ok` once the count down started by `start()` has elapsed, and
+                /// acknowledges it, so the next call waits for the next period.
+                pub fn wait(&mut self) -> nb::Result<(), Void> {
+                    if !self.is_enabled() {
+                        return Ok(());
+                    }
+
+                    if self.is_match0() {
+                        self.clear_match0_interrupt();
+                        Ok(())
+                    } else {
+                        Err(nb::Error::WouldBlock)
+                    }
+                }
+
+                /// Stops a count down started by `start()`, `start_periodic()` or
+                /// `start_one_shot()`.
+                pub fn cancel(&mut self) -> Result<(), CountDownError> {
+                    if !self.is_enabled() {
+                        return Err(CountDownError::NotStarted);
+                    }
+
+                    self.disable();
+                    self.disable_match0_interrupt();
+                    self.clear_match0_interrupt();
+                    Channel::$variant.set_callback(0, None);
+                    Ok(())
+                }
+
+                /// Calls `callback` from the timer interrupt once every `period`, using the auto
+                /// reload of `start()`. The HAL clears the comparator 0 interrupt before calling
+                /// it. The `TimerCh0` or `TimerCh1` interrupt has to be enabled with
+                /// `enable_interrupt()`.
+                pub fn start_periodic(
+                    &mut self,
+                    period: impl Into<Nanoseconds::<u64>>,
+                    callback: fn(Channel),
+                ) {
+                    Channel::$variant.set_callback(
+                        0,
+                        Some(Callback {
+                            func: callback,
+                            one_shot: false,
+                            stop: false,
+                        }),
+                    );
+                    self.start(period);
+                }
+
+                /// Starts a system tick at `rate`, calling `hook` from the timer interrupt on every
+                /// tick. The `TimerCh0` or `TimerCh1` interrupt is enabled, so this is all an RTOS
+                /// port needs to drive its tick. The tick is stopped with `cancel()`.
+                pub fn start_systick(&mut self, rate: Hertz, hook: fn()) -> SysTick {
+                    if rate.0 == 0 || rate.0 > self.clock.0 {
+                        panic!("Unreachable tick rate");
+                    }
+
+                    let channel = Channel::$variant;
+                    critical_section::with(|cs| {
+                        let counts = TICK_COUNTS.borrow(cs);
+                        let mut values = counts.get();
+                        values[channel.index()] = 0;
+                        counts.set(values);
+
+                        let hooks = TICK_HOOKS.borrow(cs);
+                        let mut values = hooks.get();
+                        values[channel.index()] = Some(hook);
+                        hooks.set(values);
+                    });
+
+                    self.start_periodic(
+                        Nanoseconds::<u64>::new(1_000_000_000_u64 / rate.0 as u64),
+                        system_tick,
+                    );
+                    enable_interrupt(channel.interrupt());
+
+                    SysTick { channel, rate }
+                }
+
+                /// Kicks the trigger `T` every `period`, giving a consistent sample timing for data
+                /// acquisition. The peripheral is kicked by the HAL from the timer interrupt, which
+                /// is enabled. The jitter is the interrupt latency, while the period has no drift
+                /// because of the auto reload of `start()`. This is stopped with `cancel()`.
+                pub fn start_trigger<T: Trigger>(&mut self, period: impl Into<Nanoseconds::<u64>>) {
+                    self.start_periodic(period, kick::<T>);
+                    enable_interrupt(Channel::$variant.interrupt());
+                }
+
+                /// Calls `callback` once from the timer interrupt after `timeout`, after which the
+                /// channel is stopped. The counter runs freely and is compared with comparator 0,
+                /// so it isn't reset. The `TimerCh0` or `TimerCh1` interrupt has to be enabled with
+                /// `enable_interrupt()`.
+                pub fn start_one_shot(
+                    &mut self,
+                    timeout: impl Into<Nanoseconds::<u64>>,
+                    callback: fn(Channel),
+                ) {
+                    let timeout: Nanoseconds::<u64> = timeout.into();
+                    let ticks = self.clock.0 as u64 * timeout.integer() / 1_000_000_000_u64;
+                    if ticks > u32::MAX as u64 {
+                        panic!("Unreachable timeout");
+                    }
+
+                    let timer = unsafe { &*pac::TIMER::ptr() };
+                    self.disable();
+                    self.free_running_mode();
+                    self.set_preload(Preload::NoPreload);
+                    let target = self.current_ticks().wrapping_add((ticks as u32).max(1));
+                    timer
+                        .[<tmr $channel _0>]
+                        .modify(|_r, w| unsafe { w.tmr().bits(target) });
+
+                    Channel::$variant.set_callback(
+                        0,
+                        Some(Callback {
+                            func: callback,
+                            one_shot: true,
+                            stop: true,
+                        }),
+                    );
+                    self.enable_match0_interrupt();
+                    self.clear_match0_interrupt();
+                    self.enable();
+                }
+
+                /// Sleeps until the count down started by `start()` has elapsed, like `wait()`
+                /// without polling. The `TimerCh0` or `TimerCh1` interrupt is enabled, and the HAL
+                /// keeps handling comparator 0 matches until `cancel()` is called.
+                #[cfg(feature = "async")]
+                pub async fn wait_async(&mut self) {
+                    if !self.is_enabled() {
+                        return;
+                    }
+
+                    MatchFuture::new(Channel::$variant, false).await;
+                }
+
+                fn ensure_free_running(&self) {
+                    if !self.is_enabled() || self.has_preload() {
+                        self.disable();
+                        self.free_running_mode();
+                        self.set_preload(Preload::NoPreload);
+                        self.enable();
+                    }
+                }
+
+                /// Delay in ticks, rounded up
+                fn delay_ticks(&self, ns: u32) -> u64 {
+                    (ns as u64 * self.clock.0 as u64 + 999_999_999) / 1_000_000_000_u64
+                }
+
+                fn has_preload(&self) -> bool {
+                    let timer = unsafe { &*pac::TIMER::ptr() };
+                    timer.tcmr.read().[<timer $channel _mode>]().bit_is_clear()
+                        || timer.[<tplcr $channel>].read().tplcr().bits() != 0
+                }
+
+                fn stop() {
+                    let timer = unsafe { &*pac::TIMER::ptr() };
+                    timer.tcer.modify(|_r, w| w.[<timer $channel _en>]().clear_bit());
+                }
+
+                fn read_match_status() -> u32 {
+                    let timer = unsafe { &*pac::TIMER::ptr() };
+                    timer.[<tmsr $channel>].read().bits() & 0b111
+                }
+
+                /// TICR register is write-only, no need to preserve register contents
+                fn write_match_clear(mask: u32) {
+                    let timer = unsafe { &*pac::TIMER::ptr() };
+                    timer.[<ticr $channel>].write(|w| unsafe { w.bits(mask) });
+                }
+
+                /// Turns the channel into a free running [Counter] for timestamps, and starts it.
+                pub fn into_counter(self) -> Counter {
+                    self.disable();
+                    self.free_running_mode();
+                    self.set_preload(Preload::NoPreload);
+                    self.enable();
+
+                    Counter {
+                        channel: Channel::$variant,
+                        clock: self.clock,
+                    }
+                }
+
+                /// Turns the channel into a free running [Counter64], with the wraps of the
+                /// counter counted from the timer interrupt using comparator 2, and starts it.
+                pub fn into_counter64(self) -> Counter64 {
+                    self.disable();
+                    self.free_running_mode();
+                    self.set_preload(Preload::NoPreload);
+
+                    Channel::$variant.set_callback(
+                        2,
+                        Some(Callback {
+                            func: count_wrap,
+                            one_shot: false,
+                            stop: false,
+                        }),
+                    );
+                    critical_section::with(|cs| {
+                        let cell = HIGH_WORDS.borrow(cs);
+                        let mut high_words = cell.get();
+                        high_words[Channel::$variant.index()] = 0;
+                        cell.set(high_words);
+                    });
+                    Self::write_match(2, u32::MAX);
+                    Self::write_match_interrupt(2, true);
+                    Self::write_match_clear(0b100);
+                    self.enable();
+
+                    Counter64 {
+                        counter: Counter {
+                            channel: Channel::$variant,
+                            clock: self.clock,
+                        },
+                    }
+                }
+
+                /// Splits the channel into three alarms on its comparators, which share the
+                /// counter. The counter is switched to free running mode and started.
+                pub fn into_alarms(self) -> Alarms {
+                    self.disable();
+                    self.free_running_mode();
+                    self.set_preload(Preload::NoPreload);
+                    self.enable();
+
+                    Alarms {
+                        alarm0: Alarm0 { channel: Channel::$variant, clock: self.clock },
+                        alarm1: Alarm1 { channel: Channel::$variant, clock: self.clock },
+                        alarm2: Alarm2 { channel: Channel::$variant, clock: self.clock },
+                    }
+                }
+
+                fn read_ticks() -> u32 {
+                    let timer = unsafe { &*pac::TIMER::ptr() };
+                    timer.[<tcr $channel>].read().bits()
+                }
+
+                fn write_match(comparator: usize, ticks: u32) {
+                    let timer = unsafe { &*pac::TIMER::ptr() };
+                    match comparator {
+                        0 => timer
+                            .[<tmr $channel _0>]
+                            .modify(|_r, w| unsafe { w.tmr().bits(ticks) }),
+                        1 => timer
+                            .[<tmr $channel _1>]
+                            .modify(|_r, w| unsafe { w.tmr().bits(ticks) }),
+                        _ => timer
+                            .[<tmr $channel _2>]
+                            .modify(|_r, w| unsafe { w.tmr().bits(ticks) }),
+                    }
+                }
+
+                fn write_match_interrupt(comparator: usize, enable: bool) {
+                    let timer = unsafe { &*pac::TIMER::ptr() };
+                    match comparator {
+                        0 => timer.[<tier $channel>].modify(|_r, w| w.tier_0().bit(enable)),
+                        1 => timer.[<tier $channel>].modify(|_r, w| w.tier_1().bit(enable)),
+                        _ => timer.[<tier $channel>].modify(|_r, w| w.tier_2().bit(enable)),
+                    }
+                }
             }
         }
 
@@ -302,58 +1665,101 @@ macro_rules! impl_timer_channel {
             where
                 T: Into<Self::Time>,
             {
-                self.count_down_target = Some(
-                    Nanoseconds::<u64>::new(self.current_time().0 + count.into().0)
-                );
-                self.last_count_down_value = None;
+                $conf_name::start(self, count.into())
             }
 
             fn wait(&mut self) -> Result<(), nb::Error<Void>> {
-                match self.count_down_target {
-                    Some(nanos) => {
-                        let current_time = self.current_time();
-
-                        if current_time >= nanos {
-                            Ok(())
-                        } else {
-                            match self.last_count_down_value {
-                                Some(_) => {
-                                    self.last_count_down_value = Some(current_time);
-                                    Err(nb::Error::WouldBlock)
-                                }
-                                None => {
-                                    self.last_count_down_value = Some(current_time);
-                                    Err(nb::Error::WouldBlock)
-                                }
-                            }
-                        }
-                    }
-                    None => Ok(()),
+                $conf_name::wait(self)
+            }
+        }
+
+        impl embedded_hal_zero::timer::Periodic for $conf_name {}
+
+        impl DelayNs for $conf_name {
+            /// Busy-waits on the counter of the channel. The first delay switches the channel to
+            /// free running mode and starts it, so it can't be used for count downs at the same
+            /// time. The delay is rounded up to whole ticks.
+            fn delay_ns(&mut self, ns: u32) {
+                self.ensure_free_running();
+
+                let mut remaining = self.delay_ticks(ns);
+
+                // Wait in chunks, so a wrap of the counter can't be missed
+                while remaining > 0 {
+                    let chunk = remaining.min(u32::MAX as u64 / 2) as u32;
+                    let start = self.current_ticks();
+                    while self.current_ticks().wrapping_sub(start) < chunk {}
+                    remaining -= chunk as u64;
+                }
+            }
+        }
+
+        #[cfg(feature = "async")]
+        impl embedded_hal_async::delay::DelayNs for $conf_name {
+            /// Sleeps until comparator 0 matches, which wakes the task from the timer interrupt.
+            /// Like the blocking delay this switches the channel to free running mode, and the
+            /// `TimerCh0` or `TimerCh1` interrupt is enabled.
+            async fn delay_ns(&mut self, ns: u32) {
+                self.ensure_free_running();
+
+                let mut remaining = self.delay_ticks(ns);
+
+                while remaining > 0 {
+                    let chunk = remaining.min(u32::MAX as u64 / 2) as u32;
+                    // A match left over from an earlier count down or wait would complete
+                    // the delay right away
+                    Self::write_match_interrupt(0, false);
+                    Self::write_match_clear(0b001);
+                    let target = self.current_ticks().wrapping_add(chunk);
+                    Self::write_match(0, target);
+                    Self::write_match_interrupt(0, true);
+                    MatchFuture::new(Channel::$variant, true).await;
+                    remaining -= chunk as u64;
                 }
             }
         }
 
+        impl embedded_hal_zero::timer::Cancel for $conf_name {
+            type Error = CountDownError;
+
+            fn cancel(&mut self) -> Result<(), Self::Error> {
+                $conf_name::cancel(self)
+            }
+        }
+
         paste! {
             impl $name {
 
-                /// Configures the clock source and creates a configured timer channel
+                /// Configures the clock source and creates a configured timer channel.
+                /// The divider is rounded down, so the tick rate can be higher than
+                /// `desired_timing`. Use `tick_rate()` to get the actual tick rate.
                 pub fn set_clock_source(
                     self,
                     source: ClockSource,
                     desired_timing: impl Into<Hertz>,
                 ) -> $conf_name {
                     let target_clock: Hertz = desired_timing.into();
-                    let timer = unsafe { &*pac::TIMER::ptr() };
-                    timer
-                        .tccr
-                        .modify(|_r, w| unsafe { w.[<cs_ $channel_cs>]().bits(source.tccr_value()) });
-
                     let divider = (source.hertz() / target_clock.0).0;
 
                     if !(1..=256).contains(&divider) {
                         panic!("Unreachable target clock");
                     }
 
+                    self.set_clock_divider(source, divider as u16)
+                }
+
+                /// Configures the clock source with the prescaler dividing it by `divider`, which
+                /// ranges from 1 to 256, and creates a configured timer channel.
+                pub fn set_clock_divider(self, source: ClockSource, divider: u16) -> $conf_name {
+                    if !(1..=256).contains(&divider) {
+                        panic!("Unreachable target clock");
+                    }
+
+                    let timer = unsafe { &*pac::TIMER::ptr() };
+                    timer
+                        .tccr
+                        .modify(|_r, w| unsafe { w.[<cs_ $channel_cs>]().bits(source.tccr_value()) });
+
                     timer
                         .tcdr
                         .modify(|_r, w| unsafe { w.[<tcdr $channel>]().bits((divider - 1) as u8) });
@@ -366,10 +1772,7 @@ macro_rules! impl_timer_channel {
                     });
 
                     $conf_name {
-                        clock: target_clock,
-                        count_down_target: None,
-                        last_count_down_value: None,
-                        is_running: RefCell::new(false),
+                        clock: source.hertz() / divider as u32,
                     }
                 }
             }
@@ -377,9 +1780,9 @@ macro_rules! impl_timer_channel {
     }
 }
 
-impl_timer_channel!(TimerChannel0, ConfiguredTimerChannel0, 2, 1);
+impl_timer_channel!(TimerChannel0, ConfiguredTimerChannel0, Channel0, 2, 1);
 
-impl_timer_channel!(TimerChannel1, ConfiguredTimerChannel1, 3, 2);
+impl_timer_channel!(TimerChannel1, ConfiguredTimerChannel1, Channel1, 3, 2);
 
 /// Extension trait to split TIMER peripheral into independent channels
 pub trait TimerExt {
@@ -13,6 +13,9 @@
    wd.set_mode(WatchdogMode::Interrupt);
    wd.start(10.seconds());
 
+   // Alternatively, let the driver select the clock source and divider for the timeout
+   let mut wd = timers.watchdog.with_timeout(10.seconds(), &clocks);
+
    // When using the watchdog in interrupt mode, you must also enable the IRQ interrupt
    enable_interrupt(Interrupt::Watchdog);
    loop{
@@ -30,6 +33,53 @@
    }
 
    ```
+
+   ## Register Access
+   The watchdog registers only accept a write right after the access keys. The driver does this
+   for every write, and [unlocked] gives the same protection for custom register writes.
+   ```rust
+   unlocked(|wdt| {
+       wdt.set_match(1000);
+       wdt.reset_counter();
+   });
+   ```
+
+   ## Feeder Example:
+   ```rust
+   static FEEDER: Mutex<Cell<Option<WatchdogFeeder>>> = Mutex::new(Cell::new(None));
+
+   critical_section::with(|cs| FEEDER.borrow(cs).set(Some(wd.feeder())));
+
+   // e.g. in an interrupt handler
+   if let Some(feeder) = critical_section::with(|cs| FEEDER.borrow(cs).get()) {
+       feeder.feed();
+   }
+   ```
+
+   ## Flash Erase Example:
+   ```rust
+   wd.suspend(|| flash.erase(sector));
+   ```
+
+   ## Window Example:
+   Feeds less than 100 ms apart reset the chip, as they likely come from a runaway loop.
+   ```rust
+   wd.set_window(Some(100.milliseconds().into()), WindowAction::Reset);
+   ```
+
+   ## Escalation Example:
+   The first timeout calls a callback from the watchdog interrupt, e.g. to log the hang or to save
+   state. If the watchdog isn't fed within a second window after that, the chip is reset.
+   ```rust
+   fn before_reset() {
+       // log the hang, flush buffers, ...
+   }
+
+   wd.start_escalating(2.seconds(), 500.milliseconds(), before_reset);
+   loop {
+       wd.feed();
+   }
+   ```
  # Units
  This library uses embedded_time::{duration::*, rate::*} for time units. You can use any supported units as long as they can be cast into Nanoseconds::<u64> for durations, or Hertz for cycles. Time can be cast into other units supported by embedded_time by explicitly typing a variable and calling .into() Note that this will round to the nearest integer in the cast units, potentially losing precision.
 
@@ -41,17 +91,31 @@
  ```
 */
 
-use crate::{clock::Clocks, pac, timer::TimerWatchdog};
+use crate::{
+    clock::Clocks,
+    interrupts::{enable_interrupt, pend_interrupt, Interrupt},
+    pac,
+    timer::TimerWatchdog,
+};
+use core::cell::Cell;
+use critical_section::{CriticalSection, Mutex};
 use embedded_time::{duration::*, rate::*};
 
 /// Clock sources for a Watchdog channel.
-/// There are only three timer clock sources available.
+/// There are four timer clock sources available.
 pub enum WdtClockSource<'a> {
     /// System master clock
     Fclk(&'a Clocks),
     /// 32K clock
     Rc32Khz,
+    /// 1K clock (32K frequency division)
+    Clock1Khz,
+    /// External crystal, which is only powered on when the PLL is used
+    Xtal(&'a Clocks),
     /// 32M clock
+    #[deprecated(
+        note = "The fourth timer clock source is the crystal, use `WdtClockSource::Xtal`"
+    )]
     Pll32Mhz,
 }
 
@@ -60,7 +124,9 @@ impl<'a> WdtClockSource<'a> {
         match self {
             WdtClockSource::Fclk(_) => 0,
             WdtClockSource::Rc32Khz => 1,
-            WdtClockSource::Pll32Mhz => 3,
+            WdtClockSource::Clock1Khz => 2,
+            #[allow(deprecated)]
+            WdtClockSource::Xtal(_) | WdtClockSource::Pll32Mhz => 3,
         }
     }
 
@@ -68,6 +134,12 @@ impl<'a> WdtClockSource<'a> {
         match self {
             WdtClockSource::Fclk(clocks) => clocks.sysclk(),
             WdtClockSource::Rc32Khz => 32_000.Hz(),
+            WdtClockSource::Clock1Khz => 1_000.Hz(),
+            WdtClockSource::Xtal(clocks) => match clocks.xtal_freq() {
+                Some(freq) => freq,
+                None => panic!("XTAL is not powered on"),
+            },
+            #[allow(deprecated)]
             WdtClockSource::Pll32Mhz => 32_000_000.Hz(),
         }
     }
@@ -75,6 +147,7 @@ impl<'a> WdtClockSource<'a> {
 
 /// Error for [Watchdog](embedded_hal::watchdog::blocking::Watchdog)
 #[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum WatchdogError {
     Infallible,
 }
@@ -100,30 +173,282 @@ impl WatchdogKeys {
     }
 }
 
+/// What happens when the watchdog is fed too early, see `set_window()`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum WindowAction {
+    /// Raise the watchdog interrupt, `is_window_violation()` tells it apart from a timeout
+    Interrupt,
+    /// Reset the chip right away
+    Reset,
+}
+
+/// Minimum feed interval of `set_window()`
+#[derive(Copy, Clone)]
+struct Window {
+    min_ticks: u16,
+    action: WindowAction,
+}
+
+static WINDOW: Mutex<Cell<Option<Window>>> = Mutex::new(Cell::new(None));
+static WINDOW_VIOLATION: Mutex<Cell<bool>> = Mutex::new(Cell::new(false));
+
+/// The number of cycles of `frequency` in `time`. The product is taken in u128, as it overflows
+/// u64 beyond about 115s at 160MHz.
+fn cycles_in(frequency: u32, time: Nanoseconds<u64>) -> u128 {
+    frequency as u128 * time.integer() as u128 / 1_000_000_000
+}
+
+/// Feeds the watchdog, unless it's fed before the start of the window.
+/// This runs in a critical section, so a feed from an interrupt can't split the key sequence of a
+/// feed it interrupted.
+fn feed() {
+    let timer = unsafe { &*pac::TIMER::ptr() };
+
+    critical_section::with(|cs| {
+        if let Some(window) = WINDOW.borrow(cs).get() {
+            if timer.wvr.read().wvr().bits() < window.min_ticks {
+                match window.action {
+                    WindowAction::Interrupt => {
+                        WINDOW_VIOLATION.borrow(cs).set(true);
+                        pend_interrupt(Interrupt::Watchdog);
+                    }
+                    WindowAction::Reset => reset_now(),
+                }
+                // An early feed doesn't count, so a loop feeding constantly still times out
+                return;
+            }
+        }
+
+        let wdt = Unlocked::new(cs);
+        wdt.reset_counter();
+
+        // Feeding in the second window of the two-stage mode returns to the first stage
+        let cell = ESCALATION.borrow(cs);
+        if let Some(mut escalation) = cell.get().filter(|escalation| escalation.escalated) {
+            escalation.escalated = false;
+            cell.set(Some(escalation));
+            wdt.set_reset_mode(false);
+            wdt.set_match(escalation.first_ticks);
+        }
+    });
+}
+
+/// A handle that only feeds the watchdog, from `feeder()`.
+///
+/// It's `Copy` and can be stored in a static, so the watchdog can be fed from interrupts or several
+/// tasks without moving the driver. Feeding is safe to use from interrupts.
+#[derive(Copy, Clone)]
+pub struct WatchdogFeeder {
+    _private: (),
+}
+
+impl WatchdogFeeder {
+    /// Feeds the watchdog like `ConfiguredWatchdog0::feed()`, including the window check.
+    pub fn feed(&self) {
+        feed();
+    }
+
+    /// Suspends the watchdog while `f` runs, like `ConfiguredWatchdog0::suspend()`.
+    pub fn suspend<R>(&self, f: impl FnOnce() -> R) -> R {
+        suspend(f)
+    }
+}
+
+impl embedded_hal_zero::watchdog::Watchdog for WatchdogFeeder {
+    fn feed(&mut self) {
+        feed();
+    }
+}
+
+/// Resets the chip through the watchdog, with a timeout of one tick
+fn reset_now() -> ! {
+    unlocked(|wdt| {
+        wdt.set_reset_mode(true);
+        wdt.set_match(1);
+        wdt.reset_counter();
+        wdt.set_enabled(true);
+    });
+    loop {
+        core::hint::spin_loop();
+    }
+}
+
+/// State of the two-stage mode of `start_escalating()`
+#[derive(Copy, Clone)]
+struct Escalation {
+    pre_reset: fn(),
+    first_ticks: u16,
+    second_ticks: u16,
+    escalated: bool,
+}
+
+static ESCALATION: Mutex<Cell<Option<Escalation>>> = Mutex::new(Cell::new(None));
+
+/// Handles the first timeout of the two-stage mode: the watchdog is switched to reset mode for the
+/// second window before the callback is called, so a hanging callback still ends in a reset.
+/// Returns `false` if the watchdog isn't in the two-stage mode.
+pub(crate) fn dispatch_interrupt() -> bool {
+    // A window violation is for the user's handler
+    if critical_section::with(|cs| WINDOW_VIOLATION.borrow(cs).get()) {
+        return false;
+    }
+
+    let escalation = critical_section::with(|cs| {
+        let cell = ESCALATION.borrow(cs);
+        let mut escalation = cell.get()?;
+        escalation.escalated = true;
+        cell.set(Some(escalation));
+        Some(escalation)
+    });
+
+    let escalation = match escalation {
+        Some(escalation) => escalation,
+        None => return false,
+    };
+
+    unlocked(|wdt| {
+        wdt.clear_interrupt();
+        wdt.set_match(escalation.second_ticks);
+        wdt.reset_counter();
+        wdt.set_reset_mode(true);
+    });
+
+    (escalation.pre_reset)();
+    true
+}
+
+/// Whether the watchdog counts while the chip sleeps, see `set_sleep_behavior()`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SleepBehavior {
+    /// Keep counting in PDS sleep, so a wakeup that never comes still resets the chip. This needs
+    /// the 32K or 1K clock, which keep running while the system clock is stopped.
+    KeepCounting,
+    /// Pause the watchdog while sleeping, and feed it on wakeup
+    Pause,
+}
+
+static SLEEP_BEHAVIOR: Mutex<Cell<SleepBehavior>> = Mutex::new(Cell::new(SleepBehavior::Pause));
+
+/// Disables the watchdog while `f` runs, then feeds it and enables it again if it was enabled.
+fn suspend<R>(f: impl FnOnce() -> R) -> R {
+    let timer = unsafe { &*pac::TIMER::ptr() };
+
+    let enabled = critical_section::with(|cs| {
+        let enabled = timer.wmer.read().we().bit_is_set();
+        if enabled {
+            Unlocked::new(cs).set_enabled(false);
+        }
+        enabled
+    });
+
+    let result = f();
+
+    if enabled {
+        unlocked(|wdt| {
+            wdt.reset_counter();
+            wdt.set_enabled(true);
+        });
+    }
+
+    result
+}
+
+/// Runs `f`, which puts the chip to sleep, with the watchdog handled according to the
+/// `SleepBehavior`. Code entering PDS sleep should wrap the sleep in this.
+///
+/// The watchdog is part of the core power domain, so it's reset by HBN sleep anyway.
+pub fn around_sleep<R>(f: impl FnOnce() -> R) -> R {
+    match critical_section::with(|cs| SLEEP_BEHAVIOR.borrow(cs).get()) {
+        SleepBehavior::KeepCounting => f(),
+        SleepBehavior::Pause => suspend(f),
+    }
+}
+
 /// A configured Watchdog timer ready to be enabled or `feed()`
 pub struct ConfiguredWatchdog0 {
     clock: Hertz,
 }
 
-/// This sends the access codes so that we can write values to the WDT registers.
-fn send_access_codes() {
-    let timer = unsafe { &*pac::TIMER::ptr() };
-    timer
-        .wfar
-        .write(|w| unsafe { w.wfar().bits(WatchdogKeys::Wfar.get_key()) });
-    timer
-        .wsar
-        .write(|w| unsafe { w.wsar().bits(WatchdogKeys::Wsar.get_key()) });
+/// The key-protected registers of the watchdog, unlocked for the duration of a critical section.
+///
+/// Every write sends the access keys right before it, and the critical section keeps interrupts
+/// from writing in between, so a key sequence can never be split or left incomplete.
+pub struct Unlocked<'cs> {
+    timer: &'static pac::timer::RegisterBlock,
+    _cs: CriticalSection<'cs>,
+}
+
+impl<'cs> Unlocked<'cs> {
+    /// Unlocks the registers within the critical section `cs`
+    pub fn new(cs: CriticalSection<'cs>) -> Self {
+        Unlocked {
+            timer: unsafe { &*pac::TIMER::ptr() },
+            _cs: cs,
+        }
+    }
+
+    /// Resets the counter to 0, which feeds the watchdog.
+    /// WCR register is write-only, no need to preserve register contents
+    pub fn reset_counter(&self) {
+        self.send_access_codes();
+        self.timer.wcr.write(|w| w.wcr().set_bit());
+    }
+
+    /// Sets the match register, the timeout in ticks
+    pub fn set_match(&self, ticks: u16) {
+        self.send_access_codes();
+        self.timer.wmr.write(|w| unsafe { w.wmr().bits(ticks) });
+    }
+
+    /// Sets the WE bit, keeping the mode
+    pub fn set_enabled(&self, enabled: bool) {
+        self.send_access_codes();
+        self.timer.wmer.modify(|_r, w| w.we().bit(enabled));
+    }
+
+    /// Sets the WRIE bit, keeping the enable bit
+    pub fn set_reset_mode(&self, reset: bool) {
+        self.send_access_codes();
+        self.timer.wmer.modify(|_r, w| w.wrie().bit(reset));
+    }
+
+    /// Clears the watchdog interrupt
+    pub fn clear_interrupt(&self) {
+        self.send_access_codes();
+        self.timer.wicr.write(|w| w.wiclr().set_bit());
+    }
+
+    /// Clears the watchdog reset flag (WTS)
+    pub fn clear_reset_flag(&self) {
+        self.send_access_codes();
+        self.timer.wsr.write(|w| w.wts().set_bit());
+    }
+
+    /// This sends the access codes so that we can write values to the WDT registers.
+    fn send_access_codes(&self) {
+        self.timer
+            .wfar
+            .write(|w| unsafe { w.wfar().bits(WatchdogKeys::Wfar.get_key()) });
+        self.timer
+            .wsar
+            .write(|w| unsafe { w.wsar().bits(WatchdogKeys::Wsar.get_key()) });
+    }
+}
+
+/// Runs `f` with the key-protected watchdog registers unlocked, in a critical section
+pub fn unlocked<R>(f: impl FnOnce(&Unlocked<'_>) -> R) -> R {
+    critical_section::with(|cs| f(&Unlocked::new(cs)))
 }
 
 impl ConfiguredWatchdog0 {
     /// Enable the watchdog counter
     pub fn enable(&self) {
-        let timer = unsafe { &*pac::TIMER::ptr() };
-        send_access_codes();
-        timer.wcr.write(|w| w.wcr().set_bit());
-        send_access_codes();
-        timer.wmer.modify(|_r, w| w.we().set_bit());
+        unlocked(|wdt| {
+            wdt.reset_counter();
+            wdt.set_enabled(true);
+        });
     }
 
     /// Read the WMER register's WE bit to see if the WDT is enabled or disabled.
@@ -136,29 +461,63 @@ impl ConfiguredWatchdog0 {
     }
 
     //noinspection RsSelfConvention
-    /// Set the time that the watchdog timer will be triggered unless `feed()`
+    /// Set the time that the watchdog timer will be triggered unless `feed()`.
+    /// Panics if the timeout exceeds `max_timeout()`.
     pub fn set_timeout(&self, time: impl Into<Nanoseconds<u64>>) {
-        let time: Nanoseconds<u64> = time.into();
-        let ticks = (self.clock.0 as u64 * time.integer() / 1_000_000_000_u64) as u16;
-        let timer = unsafe { &*pac::TIMER::ptr() };
-        send_access_codes();
-        timer.wmr.write(|w| unsafe { w.wmr().bits(ticks) });
+        let ticks = self.to_ticks(time.into());
+        unlocked(|wdt| wdt.set_match(ticks));
     }
 
     //noinspection RsSelfConvention
     /// Determine whether the watchdog will reset the board, or trigger an interrupt
     pub fn set_mode(&self, mode: WatchdogMode) {
-        let timer = unsafe { &*pac::TIMER::ptr() };
-        match mode {
-            WatchdogMode::Interrupt => {
-                send_access_codes();
-                timer.wmer.write(|w| w.wrie().clear_bit());
-            }
-            WatchdogMode::Reset => {
-                send_access_codes();
-                timer.wmer.write(|w| w.wrie().set_bit());
-            }
-        }
+        unlocked(|wdt| wdt.set_reset_mode(matches!(mode, WatchdogMode::Reset)));
+    }
+
+    /// Starts the watchdog in a two-stage mode. If it isn't fed within `timeout`, `pre_reset` is
+    /// called from the watchdog interrupt, which is enabled by the HAL. If it isn't fed within
+    /// `window` after that either, the chip is reset. Feeding the watchdog in the second window
+    /// returns it to the first stage.
+    ///
+    /// The `Watchdog()` handler isn't called in this mode.
+    pub fn start_escalating(
+        &mut self,
+        timeout: impl Into<Nanoseconds<u64>>,
+        window: impl Into<Nanoseconds<u64>>,
+        pre_reset: fn(),
+    ) {
+        let first_ticks = self.to_ticks(timeout.into());
+        let second_ticks = self.to_ticks(window.into());
+        critical_section::with(|cs| {
+            ESCALATION.borrow(cs).set(Some(Escalation {
+                pre_reset,
+                first_ticks,
+                second_ticks,
+                escalated: false,
+            }))
+        });
+
+        unlocked(|wdt| {
+            wdt.set_match(first_ticks);
+            wdt.set_reset_mode(false);
+        });
+        self.clear_interrupt();
+        enable_interrupt(Interrupt::Watchdog);
+        self.enable();
+    }
+
+    /// Check if the first timeout of `start_escalating()` passed, and the watchdog will reset the
+    /// chip unless it's fed
+    pub fn is_escalated(&self) -> bool {
+        critical_section::with(|cs| {
+            matches!(
+                ESCALATION.borrow(cs).get(),
+                Some(Escalation {
+                    escalated: true,
+                    ..
+                })
+            )
+        })
     }
 
     /// Check the value of the watchdog reset register (WTS) to see if a reset has occurred
@@ -169,16 +528,54 @@ impl ConfiguredWatchdog0 {
 
     /// Clear the watchdog reset register (WTS)
     pub fn clear_wts(&self) {
-        let timer = unsafe { &*pac::TIMER::ptr() };
-        send_access_codes();
-        timer.wsr.write(|w| w.wts().set_bit());
+        unlocked(|wdt| wdt.clear_reset_flag());
+    }
+
+    /// Selects whether the watchdog keeps counting during PDS sleep entered through
+    /// `around_sleep()`, or is paused. The default is to pause it.
+    ///
+    /// Panics with `KeepCounting` if the watchdog runs from a clock that stops in sleep.
+    pub fn set_sleep_behavior(&mut self, behavior: SleepBehavior) {
+        // Only the 32K clock (1) and the 1K clock derived from it (2) are always on
+        if behavior == SleepBehavior::KeepCounting && !matches!(self.get_cs_wdt(), 1 | 2) {
+            panic!("Watchdog clock stops in sleep");
+        }
+        critical_section::with(|cs| SLEEP_BEHAVIOR.borrow(cs).set(behavior));
+    }
+
+    /// Disables the watchdog while `f` runs, e.g. around a long flash erase that stalls the CPU
+    /// for longer than the timeout. Afterwards the watchdog is fed and enabled again, if it was
+    /// enabled before.
+    pub fn suspend<R>(&mut self, f: impl FnOnce() -> R) -> R {
+        suspend(f)
+    }
+
+    /// A `Copy` handle that feeds this watchdog, e.g. from an interrupt handler
+    pub fn feeder(&self) -> WatchdogFeeder {
+        WatchdogFeeder { _private: () }
+    }
+
+    /// Only accepts feeds at least `min_interval` after the previous one, emulating a windowed
+    /// watchdog. This catches runaway loops that feed the watchdog constantly. A feed arriving too
+    /// early doesn't reset the counter and triggers `action`. `None` removes the window.
+    pub fn set_window(&mut self, min_interval: Option<Nanoseconds<u64>>, action: WindowAction) {
+        let window = min_interval.map(|interval| Window {
+            min_ticks: self.to_ticks(interval),
+            action,
+        });
+        critical_section::with(|cs| WINDOW.borrow(cs).set(window));
+    }
+
+    /// Check if the watchdog interrupt was raised by a feed before the start of the window,
+    /// rather than by a timeout. This is cleared by `clear_interrupt()`.
+    pub fn is_window_violation(&self) -> bool {
+        critical_section::with(|cs| WINDOW_VIOLATION.borrow(cs).get())
     }
 
     /// clears the watchdog interrupt once it has been set by the WDT activating in Interrupt mode
     pub fn clear_interrupt(&self) {
-        let timer = unsafe { &*pac::TIMER::ptr() };
-        send_access_codes();
-        timer.wicr.write(|w| w.wiclr().set_bit());
+        critical_section::with(|cs| WINDOW_VIOLATION.borrow(cs).set(false));
+        unlocked(|wdt| wdt.clear_interrupt());
     }
 
     /// Gets the value in ticks the match register is currently set to
@@ -194,6 +591,16 @@ impl ConfiguredWatchdog0 {
         Nanoseconds::<u64>::new((ticks * 1_000_000_000_u64) / self.clock.integer() as u64)
     }
 
+    /// The tick rate of the watchdog, from the clock source and divider
+    pub fn tick_rate(&self) -> Hertz {
+        self.clock
+    }
+
+    /// The longest timeout at the current tick rate, 65535 ticks
+    pub fn max_timeout(&self) -> Nanoseconds<u64> {
+        Nanoseconds::<u64>::new(u16::MAX as u64 * 1_000_000_000_u64 / self.clock.0 as u64)
+    }
+
     /// Get the current value in ticks of the watchdog timer
     pub fn get_current_ticks(&self) -> u16 {
         let timer = unsafe { &*pac::TIMER::ptr() };
@@ -213,6 +620,14 @@ impl ConfiguredWatchdog0 {
         timer.tccr.read().cs_wdt().bits()
     }
 
+    fn to_ticks(&self, time: Nanoseconds<u64>) -> u16 {
+        let ticks = cycles_in(self.clock.0, time);
+        if ticks > u16::MAX as u128 {
+            panic!("Unreachable timeout");
+        }
+        ticks as u16
+    }
+
     /// Read the WMER register's WRIE bit to see if the WDT is in Reset or Interrupt mode.
     pub fn get_wrie(&self) -> WatchdogMode {
         let timer = unsafe { &*pac::TIMER::ptr() };
@@ -232,18 +647,17 @@ impl ConfiguredWatchdog0 {
 impl embedded_hal_zero::watchdog::Watchdog for ConfiguredWatchdog0 {
     /// This feeds the watchdog by resetting its counter value to 0.
     /// WCR register is write-only, no need to preserve register contents
+    /// With a window set, a feed before the start of the window isn't accepted and triggers the
+    /// `WindowAction` instead.
     fn feed(&mut self) {
-        let timer = unsafe { &*pac::TIMER::ptr() };
-        send_access_codes();
-        timer.wcr.write(|w| w.wcr().set_bit());
+        feed();
     }
 }
 
 impl embedded_hal_zero::watchdog::WatchdogDisable for ConfiguredWatchdog0 {
     fn disable(&mut self) {
-        let timer = unsafe { &*pac::TIMER::ptr() };
-        send_access_codes();
-        timer.wmer.write(|w| w.we().clear_bit());
+        unlocked(|wdt| wdt.set_enabled(false));
+        critical_section::with(|cs| ESCALATION.borrow(cs).set(None));
     }
 }
 
@@ -285,26 +699,86 @@ impl TimerWatchdog {
         target_clock: impl Into<Hertz>,
     ) -> ConfiguredWatchdog0 {
         let target_clock = target_clock.into();
-        let timer = unsafe { &*pac::TIMER::ptr() };
-        timer
-            .tccr
-            .modify(|_r, w| unsafe { w.cs_wdt().bits(source.tccr_value()) });
         let divider = source.hertz().0 / target_clock.0;
 
         if !(1..=256).contains(&divider) {
             panic!("unreachable target clock");
         }
 
+        self.set_clock_divider(source, divider as u16)
+    }
+
+    /// Configures the clock source with the divider, which ranges from 1 to 256, and returns a
+    /// ConfiguredWatchdog0.
+    pub fn set_clock_divider(self, source: WdtClockSource, divider: u16) -> ConfiguredWatchdog0 {
+        if !(1..=256).contains(&divider) {
+            panic!("unreachable target clock");
+        }
+
+        let timer = unsafe { &*pac::TIMER::ptr() };
+        timer
+            .tccr
+            .modify(|_r, w| unsafe { w.cs_wdt().bits(source.tccr_value()) });
         timer
             .tcdr
             .modify(|_r, w| unsafe { w.wcdr().bits((divider - 1) as u8) });
 
         //clear interrupt bit when initializing:
-        send_access_codes();
-        timer.wicr.write(|w| w.wiclr().clear_bit());
+        unlocked(|wdt| wdt.clear_interrupt());
 
         ConfiguredWatchdog0 {
-            clock: target_clock,
+            clock: source.hertz() / divider as u32,
         }
     }
+
+    /// Selects the clock source and divider for `timeout`, and returns a ConfiguredWatchdog0 with
+    /// the timeout set. Of the sources that can express the timeout in 65535 ticks, the one with
+    /// the highest tick rate is used, for the most accurate timeout. The crystal is only
+    /// considered if it's powered on. Panics if the timeout is longer than 65535 ticks of the
+    /// 1K clock divided by 256, about 4.6 hours.
+    pub fn with_timeout(
+        self,
+        timeout: impl Into<Nanoseconds<u64>>,
+        clocks: &Clocks,
+    ) -> ConfiguredWatchdog0 {
+        let timeout: Nanoseconds<u64> = timeout.into();
+
+        let mut sources = [
+            Some(WdtClockSource::Fclk(clocks)),
+            clocks.xtal_freq().map(|_| WdtClockSource::Xtal(clocks)),
+            Some(WdtClockSource::Rc32Khz),
+            Some(WdtClockSource::Clock1Khz),
+        ];
+
+        // The smallest divider for which the timeout fits in the 16-bit match register
+        let mut best: Option<(usize, u16, u32)> = None;
+        for (index, source) in sources.iter().enumerate() {
+            let source = match source {
+                Some(source) => source,
+                None => continue,
+            };
+            let frequency = source.hertz().0 as u128;
+            let cycles = cycles_in(source.hertz().0, timeout);
+            let divider = ((cycles + u16::MAX as u128 - 1) / u16::MAX as u128).max(1);
+            if divider > 256 {
+                continue;
+            }
+
+            let tick_rate = (frequency / divider) as u32;
+            match best {
+                Some((_, _, best_rate)) if best_rate >= tick_rate => {}
+                _ => best = Some((index, divider as u16, tick_rate)),
+            }
+        }
+
+        let (index, divider, _) = match best {
+            Some(best) => best,
+            None => panic!("Unreachable timeout"),
+        };
+        let source = sources[index].take().unwrap();
+
+        let watchdog = self.set_clock_divider(source, divider);
+        watchdog.set_timeout(timeout);
+        watchdog
+    }
 }